@@ -0,0 +1,11 @@
+#![no_main]
+
+use codecrafters_sqlite::fuzzing;
+use libfuzzer_sys::fuzz_target;
+
+// The first byte picks the page type, the rest is the cell
+fuzz_target!(|data: &[u8]| {
+    if let Some((page_type, cell)) = data.split_first() {
+        let _ = fuzzing::decode_cell(*page_type, cell);
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use codecrafters_sqlite::fuzzing;
+use libfuzzer_sys::fuzz_target;
+
+// A whole database file, best started from a corpus of real ones
+fuzz_target!(|data: &[u8]| {
+    fuzzing::exercise_database(data.to_vec());
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use codecrafters_sqlite::fuzzing;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = fuzzing::decode_header(data);
+});
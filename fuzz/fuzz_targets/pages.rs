@@ -0,0 +1,9 @@
+#![no_main]
+
+use codecrafters_sqlite::fuzzing;
+use libfuzzer_sys::fuzz_target;
+
+// Everything after the schema page of a known database: the roots of a table and an index
+fuzz_target!(|data: &[u8]| {
+    fuzzing::exercise_database(fuzzing::database_with_pages(data));
+});
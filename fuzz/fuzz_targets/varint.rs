@@ -0,0 +1,13 @@
+#![no_main]
+
+use codecrafters_sqlite::fuzzing;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((value, len)) = fuzzing::parse_varint(data) {
+        assert!(len <= data.len().min(9));
+
+        // every value has exactly one encoding that decodes back to it
+        assert_eq!(fuzzing::parse_varint(&fuzzing::encode_varint(value)).ok().map(|(v, _)| v), Some(value));
+    }
+});
@@ -0,0 +1,342 @@
+use crate::core::database::{Column, Database};
+use crate::core::vacuum::VacuumOptions;
+use crate::core::value::Value;
+use crate::core::vfs::Vfs;
+use crate::parser::ast::Statement as ParsedStatement;
+use crate::parser::parse::parse_sql;
+use crate::error::{Error, Result};
+use std::path::Path;
+use std::rc::Rc;
+
+// An open database, the entry point for embedding the engine
+pub struct Connection {
+    db: Database,
+}
+
+impl Connection {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            db: Database::open(path)?,
+        })
+    }
+
+    pub fn with_vfs(vfs: Rc<dyn Vfs>) -> Result<Self> {
+        Ok(Self {
+            db: Database::with_vfs(vfs)?,
+        })
+    }
+
+    // Opens a copy of a serialized database, changes to it stay in memory
+    pub fn deserialize(image: Vec<u8>) -> Result<Self> {
+        Ok(Self {
+            db: Database::deserialize(image)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        self.db.serialize()
+    }
+
+    // Rebuilds the database without free pages, optionally with a new page layout
+    pub fn vacuum(&mut self, options: &VacuumOptions) -> Result<()> {
+        self.db.vacuum(options)
+    }
+
+    // Writes a compact copy of the database to a file that must not exist yet
+    pub fn vacuum_into(&self, path: impl AsRef<Path>, options: &VacuumOptions) -> Result<()> {
+        self.db.vacuum_into(path, options)
+    }
+
+    // Registers a function SQL can call, see Database::create_function
+    pub fn create_function(
+        &mut self,
+        name: &str,
+        arguments: Option<usize>,
+        function: impl Fn(&[Value]) -> Result<Value> + 'static,
+    ) {
+        self.db.create_function(name, arguments, function);
+    }
+
+    // Parses the SQL and resolves its result columns against the schema
+    pub fn prepare(&self, sql: &str) -> Result<Statement<'_>> {
+        let parsed = parse_sql(sql)?;
+
+        if !matches!(parsed, ParsedStatement::Select(_) | ParsedStatement::Pragma(_)) {
+            return Err(Error::Misuse(format!("only SELECT and PRAGMA statements can be prepared: {sql}")));
+        }
+
+        let columns = Rc::new(self.db.describe(&parsed)?);
+
+        Ok(Statement {
+            columns,
+            parsed,
+            db: &self.db,
+        })
+    }
+}
+
+pub struct Statement<'conn> {
+    db: &'conn Database,
+    parsed: ParsedStatement,
+    columns: Rc<Vec<Column>>,
+}
+
+impl<'conn> Statement<'conn> {
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns.iter().map(|column| column.name.as_str()).collect()
+    }
+
+    // Runs the statement, every call starts again from the first row. All of its rows are
+    // read here, under one SHARED lock, so any error comes from this call and none from
+    // the rows it returns.
+    pub fn query(&mut self) -> Result<Rows> {
+        let result = self.db.query(&self.parsed)?;
+
+        Ok(Rows {
+            columns: Rc::clone(&self.columns),
+            rows: result.rows.into_iter(),
+        })
+    }
+}
+
+// The rows of a statement, read in full by the query that made them
+pub struct Rows {
+    columns: Rc<Vec<Column>>,
+    rows: std::vec::IntoIter<Vec<Value>>,
+}
+
+impl Rows {
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+}
+
+impl Iterator for Rows {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let values = self.rows.next()?;
+
+        Some(Row {
+            values,
+            columns: Rc::clone(&self.columns),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Rows {}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    columns: Rc<Vec<Column>>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    pub fn get<T: FromValue>(&self, index: usize) -> Result<T> {
+        let value = self
+            .values
+            .get(index)
+            .ok_or_else(|| {
+                Error::Misuse(format!(
+                    "column index {index} is out of range, the row has {} columns",
+                    self.values.len()
+                ))
+            })?;
+
+        T::from_value(value)
+    }
+
+    pub fn get_by_name<T: FromValue>(&self, name: &str) -> Result<T> {
+        let index = self
+            .columns
+            .iter()
+            .position(|column| column.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::NoSuchColumn(name.to_string()))?;
+
+        self.get(index)
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+}
+
+// Conversion from a column value into a Rust type, failing on NULL unless the target is an Option
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self>;
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Integer(value) => Ok(*value),
+            // -2^63 is the smallest integer and 2^63 the first real past the largest, where
+            // `as` would saturate
+            Value::Real(real) if real.fract() == 0.0 => match (i64::MIN as f64..-(i64::MIN as f64)).contains(real) {
+                true => Ok(*real as i64),
+                false => Err(Error::Mismatch(format!("real {value} does not fit in an integer"))),
+            },
+            _ => Err(Error::Mismatch(format!("cannot read a {} value as an integer", value.type_name()))),
+        }
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: &Value) -> Result<Self> {
+        let value = i64::from_value(value)?;
+
+        i32::try_from(value).map_err(|_| Error::Mismatch(format!("integer {value} does not fit in an i32")))
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(i64::from_value(value)? != 0)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Real(value) => Ok(*value),
+            Value::Integer(value) => Ok(*value as f64),
+            _ => Err(Error::Mismatch(format!("cannot read a {} value as a real", value.type_name()))),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Text(text) => Ok(text.clone()),
+            Value::Integer(_) | Value::Real(_) => Ok(value.to_string()),
+            _ => Err(Error::Mismatch(format!("cannot read a {} value as text", value.type_name()))),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Blob(bytes) => Ok(bytes.clone()),
+            Value::Text(text) => Ok(text.as_bytes().to_vec()),
+            _ => Err(Error::Mismatch(format!("cannot read a {} value as a blob", value.type_name()))),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connection, FromValue};
+    use crate::core::database::Database;
+    use crate::core::value::Value;
+    use crate::error::Error;
+
+    fn connection() -> Connection {
+        Connection {
+            db: Database::memory().unwrap(),
+        }
+    }
+
+    #[test]
+    fn statements_describe_and_return_rows() {
+        let connection = connection();
+
+        let mut statement = connection.prepare("SELECT 1 AS a, 2.5 AS b, 'x' AS c, NULL AS d, x'01' AS e").unwrap();
+
+        assert_eq!(statement.column_count(), 5);
+        assert_eq!(statement.column_names(), ["a", "b", "c", "d", "e"]);
+
+        // every query starts again from the first row
+        for _ in 0..2 {
+            let rows = statement.query().unwrap();
+
+            assert_eq!(rows.len(), 1);
+            assert_eq!(rows.columns().len(), 5);
+
+            for row in rows {
+                assert_eq!(row.get::<i64>(0).unwrap(), 1);
+                assert_eq!(row.get_by_name::<f64>("B").unwrap(), 2.5);
+                assert_eq!(row.get::<String>(2).unwrap(), "x");
+                assert_eq!(row.get::<Option<i64>>(3).unwrap(), None);
+                assert_eq!(row.get::<Vec<u8>>(4).unwrap(), [1]);
+                assert_eq!(row.values().len(), 5);
+
+                assert!(matches!(row.get::<i64>(5), Err(Error::Misuse(_))));
+                assert!(matches!(row.get_by_name::<i64>("f"), Err(Error::NoSuchColumn(_))));
+                assert!(matches!(row.get::<i64>(3), Err(Error::Mismatch(_))));
+            }
+        }
+
+        let mut statement = connection.prepare("PRAGMA page_size").unwrap();
+
+        assert_eq!(statement.column_names(), ["page_size"]);
+        let rows = statement.query().unwrap().map(|row| row.into_values()).collect::<Vec<_>>();
+
+        assert_eq!(rows, [[Value::Integer(4096)]]);
+    }
+
+    #[test]
+    fn only_statements_with_rows_are_prepared() {
+        let connection = connection();
+
+        assert!(matches!(connection.prepare("CREATE TABLE t(a)"), Err(Error::Misuse(_))));
+        assert!(matches!(connection.prepare("SELECT a FROM t"), Err(Error::NoSuchTable(_))));
+    }
+
+    #[test]
+    fn values_convert_to_rust_types() {
+        assert_eq!(i64::from_value(&Value::Real(3.0)).unwrap(), 3);
+        assert_eq!(i64::from_value(&Value::Real(-9223372036854775808.0)).unwrap(), i64::MIN);
+        assert!(i64::from_value(&Value::Real(3.5)).is_err());
+        assert!(i64::from_value(&Value::Real(1e300)).is_err());
+        assert!(i64::from_value(&Value::Real(9223372036854775808.0)).is_err());
+        assert!(i64::from_value(&Value::Text("1".to_string())).is_err());
+
+        assert_eq!(i32::from_value(&Value::Integer(-5)).unwrap(), -5);
+        assert!(i32::from_value(&Value::Integer(1 << 40)).is_err());
+
+        assert!(bool::from_value(&Value::Integer(2)).unwrap());
+        assert!(!bool::from_value(&Value::Real(0.0)).unwrap());
+
+        assert_eq!(f64::from_value(&Value::Integer(2)).unwrap(), 2.0);
+        assert_eq!(String::from_value(&Value::Real(1.5)).unwrap(), "1.5");
+        assert_eq!(Vec::<u8>::from_value(&Value::Text("ab".to_string())).unwrap(), b"ab");
+        assert!(String::from_value(&Value::Blob(vec![1])).is_err());
+
+        assert_eq!(Option::<String>::from_value(&Value::Null).unwrap(), None);
+        assert_eq!(Value::from_value(&Value::Null).unwrap(), Value::Null);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::core::btree::header_offset;
+use crate::core::cell::local_payload_size;
+use crate::core::database::Database;
+use crate::core::page::BTreePageSubType::Interior;
+use crate::core::page::PageTypes::{IndexBTree, TableBTree};
+use crate::core::page::{Page, MAX_BTREE_DEPTH};
+use crate::core::ptrmap::PtrmapLayout;
+use crate::core::schema::SchemaTypesTypes;
+use crate::error::{Error, Result};
+use std::collections::HashSet;
+use std::io::Write;
+
+// How much room one b-tree, or a group of them, takes up in the file
+#[derive(Debug, Clone, Default)]
+struct SpaceUsage {
+    // rows of a table, or entries of an index including those on interior pages
+    entries: u64,
+    leaf_pages: u64,
+    interior_pages: u64,
+    overflow_pages: u64,
+    payload: u64,
+    max_payload: u64,
+    overflowing_entries: u64,
+    children: u64,
+    unused_primary: u64,
+    unused_overflow: u64,
+    fragmented: u64,
+    depth: u64,
+}
+
+impl SpaceUsage {
+    fn add(&mut self, other: &SpaceUsage) {
+        self.entries += other.entries;
+        self.leaf_pages += other.leaf_pages;
+        self.interior_pages += other.interior_pages;
+        self.overflow_pages += other.overflow_pages;
+        self.payload += other.payload;
+        self.max_payload = self.max_payload.max(other.max_payload);
+        self.overflowing_entries += other.overflowing_entries;
+        self.children += other.children;
+        self.unused_primary += other.unused_primary;
+        self.unused_overflow += other.unused_overflow;
+        self.fragmented += other.fragmented;
+        self.depth = self.depth.max(other.depth);
+    }
+
+    fn primary_pages(&self) -> u64 {
+        self.leaf_pages + self.interior_pages
+    }
+
+    fn total_pages(&self) -> u64 {
+        self.primary_pages() + self.overflow_pages
+    }
+}
+
+// One line of the report: a label padded with dots, its value and optionally a percentage
+fn line(out: &mut dyn Write, label: &str, value: impl ToString, percent_of: Option<(u64, u64)>) -> Result<()> {
+    let value = value.to_string();
+
+    match percent_of {
+        Some((part, whole)) if whole > 0 => {
+            writeln!(out, "{label:.<50} {value:<10} {:>5.1}%", part as f64 * 100.0 / whole as f64)?
+        }
+        _ => writeln!(out, "{label:.<50} {value}")?,
+    }
+
+    Ok(())
+}
+
+fn average(total: u64, count: u64) -> String {
+    if count == 0 {
+        "0.0".to_string()
+    } else {
+        format!("{:.1}", total as f64 / count as f64)
+    }
+}
+
+impl Database {
+    // Reports how the space of the file is used, overall and for every table and index,
+    // in the spirit of sqlite3_analyzer
+    pub fn analyze(&self, out: &mut dyn Write) -> Result<()> {
+        let header = self.header();
+
+        let page_size = u64::from(header.page_size);
+
+        let page_count = u64::from(header.page_count(self.vfs().size()?));
+
+        let free_pages = u64::from(self.freelist()?.page_count());
+
+        let ptrmap_pages = if header.should_omit_pointer_map() {
+            0
+        } else {
+            let layout = PtrmapLayout::new(header);
+
+            (2..=page_count as u32).filter(|page| layout.is_map_page(*page)).count() as u64
+        };
+
+        let mut objects = vec![("sqlite_schema".to_string(), true, 1)];
+
+        for schema in self.get_schemas()? {
+            if schema.root_page > 0 {
+                objects.push((schema.name, schema.schema_type == SchemaTypesTypes::Table, schema.root_page as u32));
+            }
+        }
+
+        let mut usages = vec![];
+
+        for (name, is_table, root_page) in objects {
+            let usage = self.space_usage(root_page)?;
+
+            usages.push((name, is_table, usage));
+        }
+
+        let mut all = SpaceUsage::default();
+
+        for (_, _, usage) in &usages {
+            all.add(usage);
+        }
+
+        writeln!(out, "/** Disk-Space Utilization Report")?;
+        writeln!(out)?;
+
+        line(out, "Page size in bytes", page_size, None)?;
+        line(out, "Pages in the whole file", page_count, None)?;
+        line(out, "Bytes in the whole file", page_count * page_size, None)?;
+        line(out, "Pages that store data", all.total_pages(), Some((all.total_pages(), page_count)))?;
+        line(out, "Pages on the freelist", free_pages, Some((free_pages, page_count)))?;
+        line(out, "Pages of auto-vacuum overhead", ptrmap_pages, Some((ptrmap_pages, page_count)))?;
+        line(out, "Number of tables in the database", usages.iter().filter(|(_, is_table, _)| *is_table).count(), None)?;
+        line(out, "Number of indices", usages.iter().filter(|(_, is_table, _)| !*is_table).count(), None)?;
+        writeln!(out)?;
+
+        writeln!(out, "*** Page counts for all tables and indices separately ***")?;
+        writeln!(out)?;
+
+        let mut by_size = usages.iter().collect::<Vec<_>>();
+
+        by_size.sort_by(|a, b| b.2.total_pages().cmp(&a.2.total_pages()).then_with(|| a.0.cmp(&b.0)));
+
+        for (name, _, usage) in by_size {
+            line(out, &name.to_uppercase(), usage.total_pages(), Some((usage.total_pages(), page_count)))?;
+        }
+
+        writeln!(out)?;
+
+        self.write_usage(out, "All tables and indices", &all, page_count)?;
+
+        for (name, is_table, usage) in &usages {
+            let title = if *is_table { format!("Table {}", name.to_uppercase()) } else { format!("Index {}", name.to_uppercase()) };
+
+            self.write_usage(out, &title, usage, page_count)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_usage(&self, out: &mut dyn Write, title: &str, usage: &SpaceUsage, page_count: u64) -> Result<()> {
+        let usable_size = u64::from(self.header().get_usable_size());
+
+        let storage = usage.total_pages() * u64::from(self.header().page_size);
+
+        writeln!(out, "*** {title} ***")?;
+        writeln!(out)?;
+
+        line(out, "Percentage of total database", "", Some((usage.total_pages(), page_count)))?;
+        line(out, "Number of entries", usage.entries, None)?;
+        line(out, "Bytes of storage consumed", storage, None)?;
+        line(out, "Bytes of payload", usage.payload, Some((usage.payload, storage)))?;
+        line(out, "Average payload per entry", average(usage.payload, usage.entries), None)?;
+        line(out, "Maximum payload per entry", usage.max_payload, None)?;
+        line(out, "Entries that use overflow", usage.overflowing_entries, Some((usage.overflowing_entries, usage.entries)))?;
+
+        if usage.interior_pages > 0 {
+            line(out, "Average fanout", average(usage.children, usage.interior_pages), None)?;
+        }
+
+        line(out, "B-tree depth", usage.depth, None)?;
+        line(out, "Index pages used", usage.interior_pages, None)?;
+        line(out, "Primary pages used", usage.leaf_pages, None)?;
+        line(out, "Overflow pages used", usage.overflow_pages, None)?;
+        line(out, "Total pages used", usage.total_pages(), None)?;
+
+        let primary_bytes = usage.primary_pages() * usable_size;
+        let overflow_bytes = usage.overflow_pages * usable_size;
+
+        line(out, "Unused bytes on primary pages", usage.unused_primary, Some((usage.unused_primary, primary_bytes)))?;
+        line(out, "Unused bytes on overflow pages", usage.unused_overflow, Some((usage.unused_overflow, overflow_bytes)))?;
+        line(out, "Fragmented bytes", usage.fragmented, None)?;
+        line(
+            out,
+            "Unused bytes on all pages",
+            usage.unused_primary + usage.unused_overflow,
+            Some((usage.unused_primary + usage.unused_overflow, primary_bytes + overflow_bytes)),
+        )?;
+        writeln!(out)?;
+
+        Ok(())
+    }
+
+    // Walks a b-tree from its root, adding up its pages and the way their space is used
+    fn space_usage(&self, root_page: u32) -> Result<SpaceUsage> {
+        let mut usage = SpaceUsage::default();
+
+        let mut visited = HashSet::new();
+
+        let mut level = vec![root_page];
+
+        while !level.is_empty() {
+            usage.depth += 1;
+
+            if usage.depth as usize > MAX_BTREE_DEPTH {
+                return Err(Error::corrupt(root_page, "b-tree is too deep"));
+            }
+
+            let mut next_level = vec![];
+
+            for page_number in level {
+                if !visited.insert(page_number) {
+                    return Err(Error::corrupt(page_number, "page is referenced more than once"));
+                }
+
+                let page = self.read_page(page_number as i32)?;
+
+                self.add_page_usage(&mut usage, page_number, &page)?;
+
+                next_level.extend(page.cells.iter().filter_map(|cell| cell.left_pointer));
+                next_level.extend(page.right_most_pointer);
+            }
+
+            level = next_level;
+        }
+
+        Ok(usage)
+    }
+
+    fn add_page_usage(&self, usage: &mut SpaceUsage, page_number: u32, page: &Page) -> Result<()> {
+        let usable_size = u64::from(self.header().get_usable_size());
+
+        let is_interior = matches!(page.page_type, TableBTree(Interior) | IndexBTree(Interior));
+
+        if is_interior {
+            usage.interior_pages += 1;
+            usage.children += page.cells.len() as u64 + u64::from(page.right_most_pointer.is_some());
+        } else {
+            usage.leaf_pages += 1;
+        }
+
+        for cell in &page.cells {
+            // table interior cells hold no entry, just a key to steer searches
+            if page.page_type == TableBTree(Interior) {
+                continue;
+            }
+
+            usage.entries += 1;
+
+            let payload = cell.cell_size;
+
+            usage.payload += payload;
+            usage.max_payload = usage.max_payload.max(payload);
+
+            let local = local_payload_size(page.page_type, payload, usable_size);
+
+            if local < payload {
+                let spilled = payload - local;
+
+                let pages = spilled.div_ceil(usable_size - 4);
+
+                usage.overflowing_entries += 1;
+                usage.overflow_pages += pages;
+                usage.unused_overflow += pages * (usable_size - 4) - spilled;
+            }
+        }
+
+        // free space is the gap between the cell pointers and the cell content, the free
+        // blocks inside the content area and the fragments too small to be free blocks
+        let header_len = if is_interior { 12 } else { 8 };
+
+        let pointers_end = (header_offset(page_number) + header_len + page.cells.len() * 2) as u64;
+
+        let content_start = match page.content_area_start {
+            0 => 65536,
+            start => u64::from(start),
+        };
+
+        let mut unused = content_start.saturating_sub(pointers_end) + u64::from(page.num_of_fragmented_free_bytes);
+
+        let mut buffer = [0u8; 4];
+
+        let mut offset = u64::from(page.free_block_start);
+
+        while offset != 0 && offset + 4 <= usable_size {
+            self.vfs()
+                .read_at(&mut buffer, u64::from(page_number - 1) * u64::from(self.header().page_size) + offset)
+                .map_err(|error| error.past_end().on_page(page_number))?;
+
+            let next = u64::from(u16::from_be_bytes([buffer[0], buffer[1]]));
+
+            unused += u64::from(u16::from_be_bytes([buffer[2], buffer[3]]));
+
+            // the chain only goes forward, stopping otherwise keeps a corrupt page from looping
+            if next <= offset {
+                break;
+            }
+
+            offset = next;
+        }
+
+        usage.unused_primary += unused;
+        usage.fragmented += u64::from(page.num_of_fragmented_free_bytes);
+
+        Ok(())
+    }
+}
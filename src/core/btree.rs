@@ -0,0 +1,806 @@
+use crate::core::cell::{local_payload_size, PageCell};
+use crate::core::page::BTreePageSubType::{Interior, Leaf};
+use crate::core::page::{PageTypes, MAX_BTREE_DEPTH};
+use crate::core::page::PageTypes::{IndexBTree, TableBTree};
+use crate::core::pager::Pager;
+use crate::core::ptrmap::{PtrmapEntry, PtrmapType};
+use crate::utils::{encode_varint, parse_varint};
+use crate::error::{Error, Result};
+use std::cmp::Ordering;
+
+// A b-tree page held as a list of raw cells. Pages are laid out from scratch when
+// written, so free blocks and fragmented bytes of the original page are dropped.
+#[derive(Debug, Clone)]
+pub struct BTreePage {
+    pub page_number: u32,
+    pub page_type: PageTypes,
+    pub cells: Vec<Vec<u8>>,
+    pub right_most_pointer: Option<u32>,
+}
+
+impl BTreePage {
+    pub fn empty(page_number: u32, page_type: PageTypes) -> Self {
+        Self {
+            page_type,
+            page_number,
+            cells: vec![],
+            right_most_pointer: None,
+        }
+    }
+
+    pub fn from_bytes(page_number: u32, buffer: &[u8], usable_size: usize) -> Result<Self> {
+        Self::parse(page_number, buffer, usable_size).map_err(|error| error.on_page(page_number))
+    }
+
+    fn parse(page_number: u32, buffer: &[u8], usable_size: usize) -> Result<Self> {
+        let start = header_offset(page_number);
+
+        let page_type = PageTypes::try_from(buffer[start])?;
+
+        let num_of_cells = u16::from_be_bytes([buffer[start + 3], buffer[start + 4]]) as usize;
+
+        let mut page = Self::empty(page_number, page_type);
+
+        if matches!(page_type, TableBTree(Interior) | IndexBTree(Interior)) {
+            let pointer = u32::from_be_bytes([
+                buffer[start + 8],
+                buffer[start + 9],
+                buffer[start + 10],
+                buffer[start + 11],
+            ]);
+
+            page.right_most_pointer = Some(pointer);
+        }
+
+        let pointers_start = start + page.header_len();
+
+        if pointers_start + num_of_cells * 2 > usable_size {
+            return Err(Error::corrupt(0, format!("{num_of_cells} cell pointers do not fit in the page")));
+        }
+
+        for i in 0..num_of_cells {
+            let index = pointers_start + i * 2;
+
+            let pointer = u16::from_be_bytes([buffer[index], buffer[index + 1]]) as usize;
+
+            if pointer >= usable_size {
+                return Err(Error::corrupt(0, format!("cell pointer {pointer} is out of bounds")));
+            }
+
+            let len = cell_len(&buffer[pointer..usable_size], page_type, usable_size)?;
+
+            page.cells.push(buffer[pointer..pointer + len].to_vec());
+        }
+
+        Ok(page)
+    }
+
+    pub fn header_len(&self) -> usize {
+        match self.page_type {
+            TableBTree(Interior) | IndexBTree(Interior) => 12,
+            _ => 8,
+        }
+    }
+
+    pub fn used_space(&self) -> usize {
+        let cells: usize = self.cells.iter().map(Vec::len).sum();
+
+        header_offset(self.page_number) + self.header_len() + self.cells.len() * 2 + cells
+    }
+
+    pub fn fits(&self, cell_len: usize, usable_size: usize) -> bool {
+        self.used_space() + cell_len + 2 <= usable_size
+    }
+
+    // Lays the page out into `buffer`, keeping the database header of page 1 untouched
+    pub fn write_to(&self, buffer: &mut [u8], usable_size: usize) {
+        let start = header_offset(self.page_number);
+
+        buffer[start..].fill(0);
+
+        let mut content_start = usable_size;
+
+        let pointers_start = start + self.header_len();
+
+        for (i, cell) in self.cells.iter().enumerate() {
+            content_start -= cell.len();
+
+            buffer[content_start..content_start + cell.len()].copy_from_slice(cell);
+
+            let index = pointers_start + i * 2;
+
+            buffer[index..index + 2].copy_from_slice(&(content_start as u16).to_be_bytes());
+        }
+
+        buffer[start] = u8::from(self.page_type);
+        buffer[start + 3..start + 5].copy_from_slice(&(self.cells.len() as u16).to_be_bytes());
+
+        // a content area starting at 65536 is stored as zero
+        buffer[start + 5..start + 7].copy_from_slice(&(content_start as u16).to_be_bytes());
+
+        if let Some(pointer) = self.right_most_pointer {
+            buffer[start + 8..start + 12].copy_from_slice(&pointer.to_be_bytes());
+        }
+    }
+}
+
+// The first page starts with the 100 byte database header
+pub fn header_offset(page_number: u32) -> usize {
+    if page_number == 1 {
+        100
+    } else {
+        0
+    }
+}
+
+// Size in bytes of the cell at the start of `buffer`, including its overflow pointer
+pub fn cell_len(buffer: &[u8], page_type: PageTypes, usable_size: usize) -> Result<usize> {
+    let mut len = 0;
+
+    if matches!(page_type, TableBTree(Interior) | IndexBTree(Interior)) {
+        len += 4;
+    }
+
+    let rest = buffer.get(len..).ok_or_else(|| Error::corrupt(0, "cell is truncated"))?;
+
+    if page_type == TableBTree(Interior) {
+        let (_, _, rowid_len) = parse_varint(rest)?;
+
+        return Ok(len + rowid_len);
+    }
+
+    let (payload_size, rest, payload_size_len) = parse_varint(rest)?;
+
+    len += payload_size_len;
+
+    if page_type == TableBTree(Leaf) {
+        let (_, _, rowid_len) = parse_varint(rest)?;
+
+        len += rowid_len;
+    }
+
+    let local = local_payload_size(page_type, payload_size, usable_size as u64) as usize;
+
+    len += local;
+
+    if local < payload_size as usize {
+        len += 4;
+    }
+
+    if len > buffer.len() {
+        return Err(Error::corrupt(0, format!("cell of {len} bytes runs past the end of the page")));
+    }
+
+    Ok(len)
+}
+
+// A page number stored on a b-tree page: a child page or the first page of an overflow chain
+#[derive(Debug, Clone, Copy)]
+pub struct PageReference {
+    pub offset: usize,
+    pub page_number: u32,
+    pub entry_type: PtrmapType,
+}
+
+// Every page a b-tree page refers to, with the offset its number is stored at
+pub fn page_references(page_number: u32, buffer: &[u8], usable_size: usize) -> Result<Vec<PageReference>> {
+    let start = header_offset(page_number);
+
+    let page_type = PageTypes::try_from(buffer[start]).map_err(|error| error.on_page(page_number))?;
+
+    let is_interior = matches!(page_type, TableBTree(Interior) | IndexBTree(Interior));
+
+    let num_of_cells = u16::from_be_bytes([buffer[start + 3], buffer[start + 4]]) as usize;
+
+    let pointers_start = start + if is_interior { 12 } else { 8 };
+
+    if pointers_start + num_of_cells * 2 > usable_size {
+        return Err(Error::corrupt(page_number, format!("{num_of_cells} cell pointers do not fit in the page")));
+    }
+
+    let read_u32 = |offset: usize| u32::from_be_bytes([buffer[offset], buffer[offset + 1], buffer[offset + 2], buffer[offset + 3]]);
+
+    let mut references = vec![];
+
+    for i in 0..num_of_cells {
+        let index = pointers_start + i * 2;
+
+        let pointer = u16::from_be_bytes([buffer[index], buffer[index + 1]]) as usize;
+
+        if pointer >= usable_size {
+            return Err(Error::corrupt(page_number, format!("cell pointer {pointer} is out of bounds")));
+        }
+
+        if is_interior {
+            references.push(PageReference {
+                offset: pointer,
+                page_number: read_u32(pointer),
+                entry_type: PtrmapType::BTree,
+            });
+        }
+
+        if page_type == TableBTree(Interior) {
+            continue;
+        }
+
+        let cell = &buffer[pointer..usable_size];
+
+        let (payload_size, _, _) = parse_varint(&cell[if is_interior { 4 } else { 0 }..])?;
+
+        if local_payload_size(page_type, payload_size, usable_size as u64) < payload_size {
+            let offset = pointer + cell_len(cell, page_type, usable_size).map_err(|error| error.on_page(page_number))? - 4;
+
+            references.push(PageReference {
+                offset,
+                page_number: read_u32(offset),
+                entry_type: PtrmapType::Overflow1,
+            });
+        }
+    }
+
+    if is_interior {
+        references.push(PageReference {
+            offset: start + 8,
+            page_number: read_u32(start + 8),
+            entry_type: PtrmapType::BTree,
+        });
+    }
+
+    Ok(references)
+}
+
+// Builds the cell for a record, moving whatever does not fit on the page into a chain of
+// overflow pages. `prefix` holds the bytes that come before the payload (rowid, child pointer).
+pub fn build_payload_cell(pager: &mut Pager, page_type: PageTypes, prefix: &[u8], payload: &[u8]) -> Result<Vec<u8>> {
+    let usable_size = pager.usable_size();
+
+    let local = local_payload_size(page_type, payload.len() as u64, usable_size as u64) as usize;
+
+    let mut cell = vec![];
+
+    if matches!(page_type, IndexBTree(Interior)) {
+        cell.extend_from_slice(&prefix[..4]);
+        cell.extend(encode_varint(payload.len() as u64));
+        cell.extend_from_slice(&prefix[4..]);
+    } else {
+        cell.extend(encode_varint(payload.len() as u64));
+        cell.extend_from_slice(prefix);
+    }
+
+    cell.extend_from_slice(&payload[..local]);
+
+    if local < payload.len() {
+        let chunks = payload[local..].chunks(usable_size - 4).collect::<Vec<_>>();
+
+        let pages = (0..chunks.len())
+            .map(|_| pager.allocate())
+            .collect::<Result<Vec<_>>>()?;
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut buffer = vec![0u8; pager.page_size()];
+
+            let next = pages.get(i + 1).copied().unwrap_or(0);
+
+            buffer[0..4].copy_from_slice(&next.to_be_bytes());
+            buffer[4..4 + chunk.len()].copy_from_slice(chunk);
+
+            pager.write(pages[i], &buffer)?;
+
+            // the first page is recorded once the cell's own page is written
+            if i > 0 {
+                pager.put_ptrmap(pages[i], PtrmapEntry::new(PtrmapType::Overflow2, pages[i - 1]))?;
+            }
+        }
+
+        cell.extend_from_slice(&pages[0].to_be_bytes());
+    }
+
+    Ok(cell)
+}
+
+// The right-most path of a b-tree, from the root down to its last leaf. Only these pages
+// are kept in memory; a full page is written out and replaced by a fresh sibling, so bulk
+// loads fill pages sequentially instead of splitting them.
+struct RightEdge<'a, 'file> {
+    pager: &'a mut Pager<'file>,
+    path: Vec<BTreePage>,
+    interior_type: PageTypes,
+}
+
+impl<'a, 'file> RightEdge<'a, 'file> {
+    fn new(pager: &'a mut Pager<'file>, root_page: u32, leaf_type: PageTypes, interior_type: PageTypes) -> Result<Self> {
+        let usable_size = pager.usable_size();
+
+        let mut path = vec![];
+
+        let mut page_number = root_page;
+
+        loop {
+            let page = BTreePage::from_bytes(page_number, &pager.read(page_number)?, usable_size)?;
+
+            if page.page_type == leaf_type {
+                path.push(page);
+                break;
+            }
+
+            if page.page_type != interior_type {
+                return Err(Error::corrupt(page_number, "page does not belong to this kind of b-tree"));
+            }
+
+            page_number = page
+                .right_most_pointer
+                .ok_or_else(|| Error::corrupt(page_number, "interior page has no right-most pointer"))?;
+
+            path.push(page);
+
+            if path.len() > MAX_BTREE_DEPTH {
+                return Err(Error::corrupt(root_page, "b-tree is too deep"));
+            }
+        }
+
+        Ok(Self {
+            pager,
+            path,
+            interior_type,
+        })
+    }
+
+    fn leaf(&self) -> &BTreePage {
+        &self.path[self.path.len() - 1]
+    }
+
+    fn leaf_mut(&mut self) -> &mut BTreePage {
+        let last = self.path.len() - 1;
+
+        &mut self.path[last]
+    }
+
+    fn fits(&self, cell_len: usize) -> bool {
+        self.leaf().fits(cell_len, self.pager.usable_size())
+    }
+
+    // Writes the leaf out, starts an empty one after it and hands `divider`, the bytes of an
+    // interior cell that follow its child pointer, to the parent
+    fn split_leaf(&mut self, divider: Vec<u8>) -> Result<()> {
+        if self.path.len() == 1 {
+            self.deepen()?;
+        }
+
+        let level = self.path.len() - 1;
+
+        let new_page = self.pager.allocate()?;
+
+        let leaf_type = self.leaf().page_type;
+
+        let full = std::mem::replace(&mut self.path[level], BTreePage::empty(new_page, leaf_type));
+
+        write_page(self.pager, &full)?;
+
+        self.add_divider(level - 1, full.page_number, divider, new_page)
+    }
+
+    // Adds the cell (left_child, divider) to the interior page at `level` and makes `new_right`
+    // its right-most child. When the page is full its last cell moves up a level instead.
+    fn add_divider(&mut self, level: usize, left_child: u32, divider: Vec<u8>, new_right: u32) -> Result<()> {
+        let usable_size = self.pager.usable_size();
+
+        let mut cell = left_child.to_be_bytes().to_vec();
+
+        cell.extend(divider);
+
+        if self.path[level].fits(cell.len(), usable_size) {
+            let page = &mut self.path[level];
+
+            page.cells.push(cell);
+            page.right_most_pointer = Some(new_right);
+
+            return Ok(());
+        }
+
+        if level == 0 {
+            self.deepen()?;
+
+            return self.add_divider(1, left_child, cell.split_off(4), new_right);
+        }
+
+        let mut last_cell = self.path[level]
+            .cells
+            .pop()
+            .ok_or_else(|| Error::Misuse("cannot split an empty interior page".to_string()))?;
+
+        let last_divider = last_cell.split_off(4);
+
+        let last_child = u32::from_be_bytes([last_cell[0], last_cell[1], last_cell[2], last_cell[3]]);
+
+        self.path[level].right_most_pointer = Some(last_child);
+
+        let new_page = self.pager.allocate()?;
+
+        let sibling = BTreePage {
+            page_number: new_page,
+            page_type: self.interior_type,
+            cells: vec![cell],
+            right_most_pointer: Some(new_right),
+        };
+
+        let full = std::mem::replace(&mut self.path[level], sibling);
+
+        write_page(self.pager, &full)?;
+
+        self.add_divider(level - 1, full.page_number, last_divider, new_page)
+    }
+
+    // The root page number of a b-tree never changes, so a full root moves its content
+    // into a new child page and becomes an interior page pointing at it
+    fn deepen(&mut self) -> Result<()> {
+        let new_page = self.pager.allocate()?;
+
+        let root = &mut self.path[0];
+
+        let child = BTreePage {
+            page_number: new_page,
+            page_type: root.page_type,
+            cells: std::mem::take(&mut root.cells),
+            right_most_pointer: root.right_most_pointer,
+        };
+
+        root.page_type = self.interior_type;
+        root.right_most_pointer = Some(new_page);
+
+        self.path.insert(1, child);
+
+        Ok(())
+    }
+
+    // Writes every page still held in memory
+    fn finish(self) -> Result<()> {
+        for page in &self.path {
+            write_page(self.pager, page)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Appends rows with increasing rowids to the right edge of a table b-tree
+pub struct TableAppender<'a, 'file> {
+    edge: RightEdge<'a, 'file>,
+    last_rowid: Option<i64>,
+}
+
+impl<'a, 'file> TableAppender<'a, 'file> {
+    pub fn new(pager: &'a mut Pager<'file>, root_page: u32) -> Result<Self> {
+        let edge = RightEdge::new(pager, root_page, TableBTree(Leaf), TableBTree(Interior))?;
+
+        let last_rowid = match edge.leaf().cells.last() {
+            None => None,
+
+            Some(cell) => {
+                let (_, rest, _) = parse_varint(cell)?;
+                let (rowid, _, _) = parse_varint(rest)?;
+
+                Some(rowid as i64)
+            }
+        };
+
+        Ok(Self { edge, last_rowid })
+    }
+
+    pub fn last_rowid(&self) -> Option<i64> {
+        self.last_rowid
+    }
+
+    pub fn next_rowid(&self) -> i64 {
+        self.last_rowid.map_or(1, |rowid| rowid + 1)
+    }
+
+    pub fn append(&mut self, rowid: i64, record: &[u8]) -> Result<()> {
+        if let Some(last) = self.last_rowid {
+            if rowid <= last {
+                return Err(Error::Constraint(format!(
+                    "rowid {rowid} must be greater than the last rowid of the table ({last})"
+                )));
+            }
+        }
+
+        let cell = build_payload_cell(self.edge.pager, TableBTree(Leaf), &encode_varint(rowid as u64), record)?;
+
+        if !self.edge.fits(cell.len()) {
+            let key = self.last_rowid.ok_or_else(|| Error::Misuse("cannot split an empty leaf page".to_string()))?;
+
+            self.edge.split_leaf(encode_varint(key as u64))?;
+        }
+
+        self.edge.leaf_mut().cells.push(cell);
+
+        self.last_rowid = Some(rowid);
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        self.edge.finish()
+    }
+}
+
+// Appends records that arrive in key order to the right edge of an index b-tree. The
+// entries of an index live in its interior pages too, so when a leaf fills up its last
+// entry moves up to become the divider rather than being copied.
+pub struct IndexAppender<'a, 'file> {
+    edge: RightEdge<'a, 'file>,
+}
+
+impl<'a, 'file> IndexAppender<'a, 'file> {
+    pub fn new(pager: &'a mut Pager<'file>, root_page: u32) -> Result<Self> {
+        let edge = RightEdge::new(pager, root_page, IndexBTree(Leaf), IndexBTree(Interior))?;
+
+        Ok(Self { edge })
+    }
+
+    pub fn append(&mut self, record: &[u8]) -> Result<()> {
+        // leaf and interior index cells keep the same part of the payload on the page, so a
+        // leaf cell becomes an interior cell by putting a child pointer in front of it
+        let cell = build_payload_cell(self.edge.pager, IndexBTree(Leaf), &[], record)?;
+
+        if !self.edge.fits(cell.len()) {
+            let divider = self
+                .edge
+                .leaf_mut()
+                .cells
+                .pop()
+                .ok_or_else(|| Error::Misuse("cannot split an empty leaf page".to_string()))?;
+
+            self.edge.split_leaf(divider)?;
+        }
+
+        self.edge.leaf_mut().cells.push(cell);
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        self.edge.finish()
+    }
+}
+
+// Whether a b-tree holds an entry whose key `compare` finds equal. `compare` orders the key
+// of a cell against the one looked for.
+pub fn contains(pager: &Pager, root_page: u32, compare: &dyn Fn(&PageCell) -> Result<Ordering>) -> Result<bool> {
+    let mut page_number = root_page;
+
+    for _ in 0..=MAX_BTREE_DEPTH {
+        let page = BTreePage::from_bytes(page_number, &pager.read(page_number)?, pager.usable_size())?;
+
+        let (position, found) = search(pager, &page, compare)?;
+
+        // the keys of table interior cells only divide the rowids, they are not rows
+        if found && page.page_type != TableBTree(Interior) {
+            return Ok(true);
+        }
+
+        if matches!(page.page_type, TableBTree(Leaf) | IndexBTree(Leaf)) {
+            return Ok(false);
+        }
+
+        page_number = child(&page, position)?;
+    }
+
+    Err(Error::corrupt(root_page, "b-tree is too deep"))
+}
+
+// Inserts a cell where its key goes, which `compare` finds like for `contains`. Pages that
+// overflow are split on the way back up, a full root moving its content into a new child
+// first since the root page of a b-tree never changes.
+pub fn insert(
+    pager: &mut Pager,
+    root_page: u32,
+    cell: Vec<u8>,
+    compare: &dyn Fn(&PageCell) -> Result<Ordering>,
+) -> Result<()> {
+    let usable_size = pager.usable_size();
+
+    // the pages from the root down to the leaf, each with the position of the child taken
+    let mut path = vec![];
+
+    let mut page_number = root_page;
+
+    loop {
+        let page = BTreePage::from_bytes(page_number, &pager.read(page_number)?, usable_size)?;
+
+        let (position, _) = search(pager, &page, compare)?;
+
+        if matches!(page.page_type, TableBTree(Leaf) | IndexBTree(Leaf)) {
+            path.push((page, position));
+            break;
+        }
+
+        page_number = child(&page, position)?;
+
+        path.push((page, position));
+
+        if path.len() > MAX_BTREE_DEPTH {
+            return Err(Error::corrupt(root_page, "b-tree is too deep"));
+        }
+    }
+
+    let mut cells = vec![cell];
+
+    while let Some((mut page, position)) = path.pop() {
+        page.cells.splice(position..position, cells);
+
+        if page.used_space() <= usable_size {
+            return write_page(pager, &page);
+        }
+
+        if path.is_empty() {
+            let new_page = pager.allocate()?;
+
+            let child = BTreePage {
+                page_number: new_page,
+                page_type: page.page_type,
+                cells: std::mem::take(&mut page.cells),
+                right_most_pointer: page.right_most_pointer,
+            };
+
+            page.page_type = match page.page_type {
+                TableBTree(_) => TableBTree(Interior),
+                _ => IndexBTree(Interior),
+            };
+
+            page.right_most_pointer = Some(new_page);
+
+            path.push((page, 0));
+
+            page = child;
+        }
+
+        cells = vec![];
+
+        for (left, divider) in split(pager, &mut page)? {
+            write_page(pager, &left)?;
+
+            let mut cell = left.page_number.to_be_bytes().to_vec();
+
+            cell.extend(divider);
+
+            cells.push(cell);
+        }
+
+        write_page(pager, &page)?;
+    }
+
+    Ok(())
+}
+
+// Finds where a key goes on a page: the number of cells whose key sorts before it, and
+// whether the next cell has the same key
+fn search(pager: &Pager, page: &BTreePage, compare: &dyn Fn(&PageCell) -> Result<Ordering>) -> Result<(usize, bool)> {
+    let (mut low, mut high) = (0, page.cells.len());
+
+    let mut found = false;
+
+    while low < high {
+        let middle = (low + high) / 2;
+
+        let cell = PageCell::new(
+            &page.cells[middle],
+            page.page_type,
+            pager.usable_size() as u64,
+            &pager.header.text_encoding,
+            pager.file(),
+            pager.header.page_size,
+        )
+        .map_err(|error| error.on_page(page.page_number))?;
+
+        match compare(&cell)? {
+            Ordering::Less => low = middle + 1,
+            ordering => {
+                found = ordering == Ordering::Equal;
+                high = middle;
+            }
+        }
+    }
+
+    Ok((low, found && low < page.cells.len()))
+}
+
+// The child of an interior page to the left of its cell at `position`, or its right-most
+// child past the last cell
+fn child(page: &BTreePage, position: usize) -> Result<u32> {
+    match page.cells.get(position) {
+        Some(cell) => Ok(u32::from_be_bytes([cell[0], cell[1], cell[2], cell[3]])),
+        None => page
+            .right_most_pointer
+            .ok_or_else(|| Error::corrupt(page.page_number, "interior page has no right-most pointer")),
+    }
+}
+
+// Splits a page that overflows into pages of about the same size, the page itself keeping
+// the last part. Returns the new pages in front of it, each with the divider to put after its
+// child pointer in the parent: the last rowid of a table leaf, or else the cell between two
+// parts, which moves up.
+fn split(pager: &mut Pager, page: &mut BTreePage) -> Result<Vec<(BTreePage, Vec<u8>)>> {
+    let usable_size = pager.usable_size();
+
+    let moves_up = page.page_type != TableBTree(Leaf);
+
+    let capacity = usable_size - header_offset(page.page_number) - page.header_len();
+
+    let total: usize = page.cells.iter().map(|cell| cell.len() + 2).sum();
+
+    let target = total / total.div_ceil(capacity);
+
+    let mut cells = std::mem::take(&mut page.cells).into_iter().peekable();
+
+    let mut parts = vec![];
+    let mut part: Vec<Vec<u8>> = vec![];
+    let mut size = 0;
+
+    while let Some(cell) = cells.next() {
+        let last = cells.peek().is_none();
+
+        let full = size + cell.len() + 2 > capacity || size >= target;
+
+        // a part that moves a cell up still leaves one for the page itself
+        if !part.is_empty() && full && !(moves_up && last) {
+            if moves_up {
+                parts.push((std::mem::take(&mut part), cell));
+
+                size = 0;
+
+                continue;
+            }
+
+            let (_, rest, _) = parse_varint(&part[part.len() - 1])?;
+            let (rowid, _, _) = parse_varint(rest)?;
+
+            parts.push((std::mem::take(&mut part), encode_varint(rowid)));
+
+            size = 0;
+        }
+
+        size += cell.len() + 2;
+
+        part.push(cell);
+    }
+
+    page.cells = part;
+
+    let mut pages = vec![];
+
+    for (cells, mut divider) in parts {
+        let mut left = BTreePage::empty(pager.allocate()?, page.page_type);
+
+        left.cells = cells;
+
+        // an interior cell moving up leaves its child as the right-most one of the part before
+        if matches!(page.page_type, TableBTree(Interior) | IndexBTree(Interior)) {
+            left.right_most_pointer = Some(u32::from_be_bytes([divider[0], divider[1], divider[2], divider[3]]));
+
+            divider.drain(..4);
+        }
+
+        pages.push((left, divider));
+    }
+
+    if page.used_space() > usable_size || pages.iter().any(|(left, _)| left.used_space() > usable_size) {
+        return Err(Error::Misuse(format!("cannot split page {}", page.page_number)));
+    }
+
+    Ok(pages)
+}
+
+pub fn write_page(pager: &Pager, page: &BTreePage) -> Result<()> {
+    // page 1 is read back so the database header in front of the b-tree survives
+    let mut buffer = if page.page_number == 1 {
+        pager.read(1)?
+    } else {
+        vec![0u8; pager.page_size()]
+    };
+
+    page.write_to(&mut buffer, pager.usable_size());
+
+    pager.write(page.page_number, &buffer)?;
+
+    pager.set_child_ptrmaps(page.page_number, &buffer)
+}
@@ -2,9 +2,11 @@ use crate::core::page::{BTreePageSubType, PageTypes};
 use std::rc::Rc;
 
 use crate::core::header::TextEncoding;
+use crate::core::record::encode_raw_record;
+use crate::core::value::Value;
 use crate::utils::parse_varint;
-use anyhow::{bail, Context, Result};
-use integer_encoding::VarInt;
+use crate::error::{Error, Result};
+use crate::core::vfs::Vfs;
 
 #[derive(Clone, Debug)]
 pub enum ColumnTypes {
@@ -31,7 +33,7 @@ impl ColumnTypes {
             2 => ColumnTypes::Be16bitsInt(2),
             3 => ColumnTypes::Be24bitsInt(3),
             4 => ColumnTypes::Be32bitsInt(4),
-            5 => ColumnTypes::Be48bitsInt(5),
+            5 => ColumnTypes::Be48bitsInt(6),
             6 => ColumnTypes::Be64bitsInt(8),
             7 => ColumnTypes::Be64bitsFloat(8),
             8 => ColumnTypes::Zero,
@@ -43,7 +45,7 @@ impl ColumnTypes {
                 } else if value >= 13 && value % 2 == 1 {
                     ColumnTypes::Text((value - 12) / 2)
                 } else {
-                    bail!("Invalid Column Type, {value}")
+                    return Err(Error::corrupt(0, format!("invalid serial type {value}")))
                 }
             }
         };
@@ -51,6 +53,24 @@ impl ColumnTypes {
         Ok(col_type)
     }
 
+    pub fn serial_type(&self) -> u64 {
+        match self {
+            ColumnTypes::Null => 0,
+            ColumnTypes::Be8bitsInt(_) => 1,
+            ColumnTypes::Be16bitsInt(_) => 2,
+            ColumnTypes::Be24bitsInt(_) => 3,
+            ColumnTypes::Be32bitsInt(_) => 4,
+            ColumnTypes::Be48bitsInt(_) => 5,
+            ColumnTypes::Be64bitsInt(_) => 6,
+            ColumnTypes::Be64bitsFloat(_) => 7,
+            ColumnTypes::Zero => 8,
+            ColumnTypes::One => 9,
+            ColumnTypes::Internal(value) => *value,
+            ColumnTypes::Blob(len) => len * 2 + 12,
+            ColumnTypes::Text(len) => len * 2 + 13,
+        }
+    }
+
     pub fn get_len(&self) -> u64 {
         match self {
             ColumnTypes::Null => 0,
@@ -68,64 +88,38 @@ impl ColumnTypes {
         }
     }
 
-    pub fn print(&self, data: &[u8]) -> Result<()> {
-        match self {
-            ColumnTypes::Internal(_) => {}
-            ColumnTypes::One => {
-                print!("1")
-            }
-            ColumnTypes::Zero => {
-                print!("0")
-            }
-            ColumnTypes::Null => {
-                print!("Null")
-            }
-            ColumnTypes::Blob(_) => {
-                print!("{data:?}")
-            }
-            ColumnTypes::Text(_) => {
-                print!("{}", std::str::from_utf8(data)?)
-            }
-            ColumnTypes::Be8bitsInt(_) => {
-                print!("{}", u8::from_be_bytes([data[0]]))
-            }
-            ColumnTypes::Be16bitsInt(_) => {
-                print!("{}", u16::from_be_bytes([data[0], data[1]]))
-            }
-            ColumnTypes::Be24bitsInt(_) => {
-                print!("{}", u32::from_be_bytes([data[0], data[1], data[2], 0]))
-            }
-            ColumnTypes::Be32bitsInt(_) => {
-                print!(
-                    "{}",
-                    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
-                )
-            }
-            ColumnTypes::Be48bitsInt(_) => {
-                print!(
-                    "{}",
-                    u64::from_be_bytes([data[0], data[1], data[2], data[3], data[4], 0, 0, 0])
-                )
-            }
-            ColumnTypes::Be64bitsInt(_) => {
-                print!(
-                    "{}",
-                    u64::from_be_bytes([
-                        data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7]
-                    ])
-                )
-            }
-            ColumnTypes::Be64bitsFloat(_) => {
-                print!(
-                    "{}",
-                    f64::from_be_bytes([
-                        data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7]
-                    ])
-                )
+    pub fn decode(&self, data: &[u8]) -> Result<Value> {
+        let len = self.get_len() as usize;
+
+        if data.len() < len {
+            return Err(Error::corrupt(
+                0,
+                format!("column data is truncated: expected {len} bytes, got {}", data.len()),
+            ));
+        }
+
+        let value = match self {
+            ColumnTypes::Null | ColumnTypes::Internal(_) => Value::Null,
+            ColumnTypes::Zero => Value::Integer(0),
+            ColumnTypes::One => Value::Integer(1),
+            ColumnTypes::Blob(_) => Value::Blob(data[..len].to_vec()),
+            ColumnTypes::Text(_) => Value::Text(String::from_utf8_lossy(&data[..len]).into_owned()),
+            ColumnTypes::Be64bitsFloat(_) => Value::Real(f64::from_be_bytes([
+                data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
+            ])),
+            _ => {
+                // big-endian twos-complement integers: sign-extend from the first byte
+                let mut value: i64 = if data[0] & 0x80 != 0 { -1 } else { 0 };
+
+                for byte in &data[..len] {
+                    value = (value << 8) | i64::from(*byte);
+                }
+
+                Value::Integer(value)
             }
         };
 
-        Ok(())
+        Ok(value)
     }
 }
 
@@ -137,9 +131,13 @@ pub struct CellPayload {
 }
 
 impl CellPayload {
-    fn from_table_leaf(buffer: &Vec<u8>, _encoding: &TextEncoding) -> Result<Self> {
+    fn from_record(buffer: &[u8], _encoding: &TextEncoding) -> Result<Self> {
         let (header_size, mut bytes, header_size_var_end) =
-            parse_varint(buffer).with_context(|| "Could not parse cell size varint")?;
+            parse_varint(buffer)?;
+
+        if header_size as usize > buffer.len() {
+            return Err(Error::corrupt(0, format!("record header size {header_size} is larger than the record")));
+        }
 
         let mut next_index = header_size_var_end;
 
@@ -147,9 +145,9 @@ impl CellPayload {
 
         let mut column_types = Vec::<ColumnTypes>::with_capacity(header_size as usize);
 
-        while next_index <= header_size as usize {
+        while next_index < header_size as usize {
             let (column, column_bytes, column_size) =
-                parse_varint(bytes).with_context(|| "Could not decode Record serial Type")?;
+                parse_varint(bytes)?;
 
             bytes = column_bytes;
 
@@ -166,25 +164,50 @@ impl CellPayload {
             size: header_size as u32,
         })
     }
+
+    // The record as stored, with its values left in their original encoding
+    pub fn record(&self) -> Vec<u8> {
+        let serial_types = self.column_types.iter().map(ColumnTypes::serial_type).collect::<Vec<_>>();
+
+        encode_raw_record(&serial_types, &self.body)
+    }
+
+    pub fn values(&self) -> Result<Vec<Value>> {
+        let mut index = 0;
+
+        let mut values = Vec::with_capacity(self.column_types.len());
+
+        for column_type in &self.column_types {
+            let len = column_type.get_len() as usize;
+
+            if index + len > self.body.len() {
+                return Err(Error::corrupt(0, "record body is truncated"));
+            }
+
+            values.push(column_type.decode(&self.body[index..])?);
+
+            index += len;
+        }
+
+        Ok(values)
+    }
 }
 
 impl CellPayload {
-    pub fn new(buffer: &Vec<u8>, value: PageTypes, encoding: &TextEncoding) -> Result<Self> {
-        // println!("B-tree Type: {value:?}");
+    pub fn new(buffer: &[u8], value: PageTypes, encoding: &TextEncoding) -> Result<Self> {
         match value {
-            PageTypes::TableBTree(b_tee_type) | PageTypes::IndexBTree(b_tee_type) => match b_tee_type {
-                BTreePageSubType::Leaf => CellPayload::from_table_leaf(buffer, encoding),
+            PageTypes::TableBTree(BTreePageSubType::Leaf) | PageTypes::IndexBTree(_) => {
+                CellPayload::from_record(buffer, encoding)
+            }
 
-                _ => Ok(Self {
-                    size: 0,
-                    body: Vec::with_capacity(0),
-                    column_types: Vec::with_capacity(0),
-                }),
-            },
+            // table interior cells only hold a child pointer and a key
+            PageTypes::TableBTree(BTreePageSubType::Interior) => Ok(Self {
+                size: 0,
+                body: Vec::with_capacity(0),
+                column_types: Vec::with_capacity(0),
+            }),
 
-            _ => {
-                bail!("Invalid Btree Type")
-            }
+            _ => Err(Error::corrupt(0, "cell payload read from a page that is not a b-tree page")),
         }
     }
 }
@@ -201,74 +224,83 @@ pub struct PageCell {
 
 impl PageCell {
     pub fn new(
-        buffer: &Vec<u8>,
+        buffer: &[u8],
         btree_type: PageTypes,
         usable_page_size: u64,
         encoding: &TextEncoding,
+        file: &dyn Vfs,
+        page_size: u32,
     ) -> Result<PageCell> {
-        let (size, size_var_end) =
-            u64::decode_var(buffer).with_context(|| "Could not parse cell size varint")?;
+        let mut next_index = 0;
 
-        let (is_overflowing, payload_size, overflow_size) = PageCell::is_overflowing(btree_type, size, usable_page_size);
+        let left_pointer: Option<u32> = match btree_type {
+            PageTypes::IndexBTree(BTreePageSubType::Interior)
+            | PageTypes::TableBTree(BTreePageSubType::Interior) => {
+                let bytes = buffer
+                    .get(0..4)
+                    .ok_or_else(|| Error::corrupt(0, "cell is too short for a child pointer"))?;
 
-        let mut end_index = buffer.len();
+                next_index += 4;
 
-        if is_overflowing {
-            // end_index = (end_index - overflow_size as usize);
-            println!("Is overflowing: {is_overflowing}, Size: {size}, Buffer len: {end_index}, Payload Size: {payload_size}, Overflow Size: {overflow_size}");
+                Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }
+
+            _ => None,
+        };
+
+        // table interior cells carry no payload, just the rowid key
+        if btree_type == PageTypes::TableBTree(BTreePageSubType::Interior) {
+            let (rowid, _, _) = parse_varint(&buffer[next_index..])?;
+
+            return Ok(Self {
+                left_pointer,
+                cell_size: 0,
+                row_id: rowid,
+                overflow_pointers: 0,
+                payload: Rc::new(CellPayload::new(&[], btree_type, encoding)?),
+            });
         }
 
-        let mut next_index = std::cmp::min(payload_size as usize, size_var_end);
+        let (size, _, size_var_end) = parse_varint(&buffer[next_index..])?;
 
-        let left_pointer: Option<u32> = match btree_type {
-            PageTypes::IndexBTree(sub_type) | PageTypes::TableBTree(sub_type) => match sub_type {
-                BTreePageSubType::Leaf => None,
+        next_index += size_var_end;
 
-                BTreePageSubType::Interior => {
-                    let res = u32::from_be_bytes([
-                        buffer[next_index],
-                        buffer[next_index + 1],
-                        buffer[next_index + 2],
-                        buffer[next_index + 3],
-                    ]);
+        let mut rowid = 0;
 
-                    next_index += 4;
+        if btree_type == PageTypes::TableBTree(BTreePageSubType::Leaf) {
+            let (value, _, rowid_var_end) = parse_varint(&buffer[next_index..])?;
 
-                    Some(res)
-                }
-            },
+            rowid = value;
 
-            _ => None,
-        };
+            next_index += rowid_var_end;
+        }
 
-        let (rowid, rowid_var_end) = u64::decode_var(&buffer[next_index..end_index])
-            .with_context(|| "Could not parse cell rowid varint")?;
+        let local_size = local_payload_size(btree_type, size, usable_page_size) as usize;
 
-        next_index += std::cmp::min(rowid as usize, rowid_var_end);
+        let local_end = next_index + local_size;
 
-        let record_buffer = buffer[next_index..end_index].to_vec();
+        if local_end > buffer.len() {
+            return Err(Error::corrupt(0, "cell payload runs past the end of the page"));
+        }
 
-        let payload = CellPayload::new(&record_buffer, btree_type, encoding)?;
+        let mut record_buffer = buffer[next_index..local_end].to_vec();
 
         let mut overflow = 0;
 
-        if is_overflowing {
-            let len = end_index;
-
-            let overflow_page_pointer = u32::from_be_bytes([
-                buffer[len - 4],
-                buffer[len - 3],
-                buffer[len - 2],
-                buffer[len - 1],
-            ]);
+        if local_size < size as usize {
+            let pointer = buffer
+                .get(local_end..local_end + 4)
+                .ok_or_else(|| Error::corrupt(0, "cell is too short for an overflow pointer"))?;
 
-            println!("Buffer: {:?}", buffer[len - 20..].to_vec());
+            overflow = u32::from_be_bytes([pointer[0], pointer[1], pointer[2], pointer[3]]);
 
-            overflow = overflow_page_pointer;
+            let remaining = size as usize - local_size;
 
-            println!("Overflow page Pointer: {overflow_page_pointer}");
+            record_buffer.extend(read_overflow_chain(file, page_size, usable_page_size, overflow, remaining)?);
         }
 
+        let payload = CellPayload::new(&record_buffer, btree_type, encoding)?;
+
         let cell = Self {
             left_pointer,
             row_id: rowid,
@@ -279,35 +311,76 @@ impl PageCell {
 
         Ok(cell)
     }
+}
 
-    fn is_overflowing(page_type: PageTypes, payload_size: u64, page_size: u64) -> (bool, u64, u64) {
-        // TODO: pass usable page from db header
-        let usable_size = page_size - 64;
+// Number of payload bytes stored on the b-tree page itself, the rest spills into overflow pages
+pub fn local_payload_size(page_type: PageTypes, payload_size: u64, usable_size: u64) -> u64 {
+    let max_local = match page_type {
+        PageTypes::TableBTree(BTreePageSubType::Leaf) => usable_size - 35,
 
-        let max_payload_size = match page_type {
-            PageTypes::TableBTree(BTreePageSubType::Leaf) => {
-                usable_size - 35
-            }
+        PageTypes::IndexBTree(_) => ((usable_size - 12) * 64 / 255) - 23,
 
-            PageTypes::IndexBTree(_) => {
-                ((usable_size - 12) * 64 / 255) - 23
-            }
+        _ => return payload_size,
+    };
 
-            _ => payload_size
-        };
+    if payload_size <= max_local {
+        return payload_size;
+    }
 
-        if payload_size <= max_payload_size {
-            (false, payload_size, 0)
-        } else {
-            let min_payload_size = ((usable_size - 12) * 32 / 255) - 23;
-            let in_page_payload_size = min_payload_size + ((payload_size - min_payload_size) % (usable_size - 4));
+    let min_local = ((usable_size - 12) * 32 / 255) - 23;
 
-            if in_page_payload_size <= max_payload_size {
-                (true, in_page_payload_size, payload_size - in_page_payload_size)
-            } else {
-                (true, min_payload_size, payload_size - min_payload_size)
-            }
-        }
+    let local = min_local + ((payload_size - min_local) % (usable_size - 4));
+
+    if local <= max_local {
+        local
+    } else {
+        min_local
     }
 }
 
+// Each overflow page starts with the number of the next one, followed by payload bytes
+fn read_overflow_chain(file: &dyn Vfs, page_size: u32, usable_size: u64, first_page: u32, len: usize) -> Result<Vec<u8>> {
+    let page_count = file.size()? / page_size as u64;
+
+    // a chain can never be longer than the file, which also catches loops and
+    // payload sizes that were corrupted into something huge
+    let max_len = page_count.saturating_mul(usable_size - 4);
+
+    if len as u64 > max_len {
+        return Err(Error::corrupt(0, format!("payload of {len} bytes is larger than the database")));
+    }
+
+    let mut result = Vec::with_capacity(len);
+
+    let mut page_number = first_page;
+
+    let mut buffer = vec![0u8; page_size as usize];
+
+    let mut visited = 0;
+
+    while result.len() < len {
+        if page_number == 0 || page_number as u64 > page_count {
+            return Err(Error::corrupt(
+                page_number,
+                format!("overflow chain ended {} bytes early", len - result.len()),
+            ));
+        }
+
+        visited += 1;
+
+        if visited > page_count {
+            return Err(Error::corrupt(page_number, "overflow chain contains a loop"));
+        }
+
+        file.read_at(&mut buffer, (page_number as u64 - 1) * page_size as u64)
+            .map_err(|error| error.past_end().on_page(page_number))?;
+
+        let take = std::cmp::min(len - result.len(), usable_size as usize - 4);
+
+        result.extend_from_slice(&buffer[4..4 + take]);
+
+        page_number = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+    }
+
+    Ok(result)
+}
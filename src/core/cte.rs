@@ -0,0 +1,438 @@
+use crate::core::database::Database;
+use crate::core::select::{column_count_mismatch, SelectPlan, Source};
+use crate::core::sort::{compare_columns, ColumnOrder, RowSet};
+use crate::core::value::Value;
+use crate::error::{Error, Result};
+use crate::parser::ast::{CommonTableExpr, CompoundOperator, CreateTableStmt, SelectCore, TableRef, WithClause};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::rc::Rc;
+
+// A common table expression, in the chain of those a query can read like tables: the ones of
+// its WITH clause, each seeing itself and those before it, then the ones of the queries
+// around it
+pub(crate) struct With<'a> {
+    definition: &'a CommonTableExpr,
+    state: RefCell<State>,
+
+    // how many times queries have planned to read it, which decides whether it is materialized
+    references: Cell<usize>,
+
+    // its rows, kept by the first query that reads them when it is materialized
+    rows: RefCell<Option<Rc<Vec<Vec<Value>>>>>,
+
+    // the row the recursive SELECTs of a recursive one are run for
+    current: RefCell<Vec<Value>>,
+
+    outer: Option<Rc<With<'a>>>,
+}
+
+enum State {
+    Ready,
+
+    // its own SELECT is being planned, which cannot read it
+    Defining,
+
+    // its recursive SELECTs are being planned or run, and read the table it makes a row at a
+    // time
+    Recursing(CreateTableStmt),
+}
+
+// How a query reads a common table expression: its SELECT planned like a subquery in FROM,
+// or a recursive one
+pub(crate) enum CtePlan<'a> {
+    Select(Box<SelectPlan<'a>>),
+    Recursive(Box<Recursive<'a>>),
+}
+
+// A recursive common table expression. Its rows are queued, starting with those of the
+// SELECTs that do not read it, and each row taken from the queue adds the rows the recursive
+// SELECTs return for it.
+pub(crate) struct Recursive<'a> {
+    table: CreateTableStmt,
+    anchors: Vec<SelectPlan<'a>>,
+    steps: Vec<SelectPlan<'a>>,
+
+    // UNION rather than UNION ALL, which leaves out rows that were queued before
+    distinct: bool,
+
+    // ORDER BY, which takes rows from the queue in its order rather than in the order they
+    // were added
+    order_by: Vec<ColumnOrder>,
+
+    limit: Option<usize>,
+    offset: usize,
+}
+
+// Puts the common table expressions of a WITH clause in front of those a query already sees
+pub(crate) fn chain<'a>(
+    clause: Option<&'a WithClause>,
+    mut with: Option<Rc<With<'a>>>,
+) -> Result<Option<Rc<With<'a>>>> {
+    let Some(clause) = clause else {
+        return Ok(with);
+    };
+
+    for (i, definition) in clause.tables.iter().enumerate() {
+        if clause.tables[..i].iter().any(|other| other.name.eq_ignore_ascii_case(&definition.name)) {
+            return Err(Error::Misuse(format!("duplicate WITH table name: {}", definition.name)));
+        }
+
+        with = Some(Rc::new(With {
+            definition,
+            state: RefCell::new(State::Ready),
+            references: Cell::new(0),
+            rows: RefCell::new(None),
+            current: RefCell::new(vec![]),
+            outer: with,
+        }));
+    }
+
+    Ok(with)
+}
+
+// The common table expression a name in FROM refers to, if any
+pub(crate) fn find<'a>(mut with: Option<&Rc<With<'a>>>, name: &str) -> Option<Rc<With<'a>>> {
+    while let Some(cte) = with {
+        if cte.definition.name.eq_ignore_ascii_case(name) {
+            return Some(Rc::clone(cte));
+        }
+
+        with = cte.outer.as_ref();
+    }
+
+    None
+}
+
+impl With<'_> {
+    pub(crate) fn current_row(&self) -> Vec<Value> {
+        self.current.borrow().clone()
+    }
+}
+
+impl Database {
+    // Plans reading a common table expression. Inside its own recursive SELECTs it stands for
+    // the row the recursion is at.
+    pub(crate) fn plan_cte<'a>(&self, cte: &Rc<With<'a>>) -> Result<Source<'a>> {
+        match &*cte.state.borrow() {
+            State::Ready => {}
+            State::Defining => return Err(Error::Misuse(format!("circular reference: {}", cte.definition.name))),
+            State::Recursing(table) => return Ok(Source::Recursion(Rc::clone(cte), table.clone())),
+        }
+
+        cte.references.set(cte.references.get() + 1);
+
+        cte.state.replace(State::Defining);
+
+        let planned = self.plan_cte_select(cte);
+
+        cte.state.replace(State::Ready);
+
+        let (plan, table) = planned?;
+
+        Ok(Source::Cte(Rc::clone(cte), plan, table))
+    }
+
+    // A common table expression is recursive when one of the SELECTs after the first of its
+    // compound reads it
+    fn plan_cte_select<'a>(&self, cte: &Rc<With<'a>>) -> Result<(CtePlan<'a>, CreateTableStmt)> {
+        let definition = cte.definition;
+        let select = &definition.select;
+
+        if !select.body.compounds.iter().any(|(_, core)| reads(core, &definition.name)) {
+            let plan = self.plan_query(select, None, Some(Rc::clone(cte)))?;
+
+            let table = cte_table(definition, &plan)?;
+
+            return Ok((CtePlan::Select(Box::new(plan)), table));
+        }
+
+        let with = chain(select.with.as_ref(), Some(Rc::clone(cte)))?;
+
+        let mut anchors = vec![self.plan_core(&select.body.first, &[], None, None, with.clone())?];
+        let mut step_cores = vec![];
+        let mut distinct = false;
+
+        let count = anchors[0].columns.len();
+
+        for (operator, core) in &select.body.compounds {
+            match operator {
+                CompoundOperator::Union => distinct = true,
+                CompoundOperator::UnionAll => {}
+                _ => return Err(Error::Misuse(format!("circular reference: {}", definition.name))),
+            }
+
+            if reads(core, &definition.name) {
+                step_cores.push((operator, core));
+
+                continue;
+            }
+
+            let anchor = self.plan_core(core, &[], None, None, with.clone())?;
+
+            if anchor.columns.len() != count {
+                return Err(column_count_mismatch(*operator));
+            }
+
+            anchors.push(anchor);
+        }
+
+        let table = cte_table(definition, &anchors[0])?;
+
+        cte.state.replace(State::Recursing(table.clone()));
+
+        let mut steps = vec![];
+
+        for (operator, core) in step_cores {
+            let step = self.plan_core(core, &[], None, None, with.clone())?;
+
+            if step.columns.len() != count {
+                return Err(column_count_mismatch(*operator));
+            }
+
+            steps.push(step);
+        }
+
+        let order_by = self.plan_column_order(&select.order_by, &anchors.iter().chain(&steps).collect::<Vec<_>>())?;
+
+        let (limit, offset) = self.plan_limit(select.limit.as_ref())?;
+
+        let recursive = Recursive {
+            table: table.clone(),
+            anchors,
+            steps,
+            distinct,
+            order_by,
+            limit,
+            offset,
+        };
+
+        Ok((CtePlan::Recursive(Box::new(recursive)), table))
+    }
+
+    // Hands the rows of a common table expression to `each` until it returns false. Unless
+    // it is materialized they are made again for every query that reads it. It is when it
+    // asks to be, or by default when more than one query reads it, and always when it is
+    // recursive, and then the first query to read all its rows keeps them for the rest of the
+    // statement. Rows are made as they are read, so a query that stops reading stops a
+    // recursion that would go on forever, and what it read is not kept.
+    pub(crate) fn run_cte(
+        &self,
+        cte: &With,
+        plan: &CtePlan,
+        each: &mut dyn FnMut(Vec<Value>) -> Result<bool>,
+    ) -> Result<()> {
+        let kept = cte.rows.borrow().clone();
+
+        if let Some(rows) = kept {
+            for row in rows.iter() {
+                if !each(row.clone())? {
+                    break;
+                }
+            }
+
+            return Ok(());
+        }
+
+        let materialized = match plan {
+            CtePlan::Select(_) => cte.definition.materialized.unwrap_or(cte.references.get() > 1),
+            CtePlan::Recursive(_) => true,
+        };
+
+        let mut rows = vec![];
+        let mut complete = true;
+
+        let mut keep = |row: Vec<Value>| {
+            if materialized {
+                rows.push(row.clone());
+            }
+
+            complete = each(row)?;
+
+            Ok(complete)
+        };
+
+        match plan {
+            CtePlan::Select(plan) => self.query_each(plan, None, &mut keep)?,
+            CtePlan::Recursive(recursive) => self.run_recursive(cte, recursive, &mut keep)?,
+        }
+
+        if materialized && complete {
+            cte.rows.replace(Some(Rc::new(rows)));
+        }
+
+        Ok(())
+    }
+
+    fn run_recursive(
+        &self,
+        cte: &With,
+        recursive: &Recursive,
+        each: &mut dyn FnMut(Vec<Value>) -> Result<bool>,
+    ) -> Result<()> {
+        let limit = recursive.limit.unwrap_or(usize::MAX);
+
+        let mut queue = Queue::new(&recursive.order_by);
+        let mut queued = RowSet::default();
+
+        let mut enqueue = |queue: &mut Queue, rows: Vec<Vec<Value>>| {
+            for row in rows {
+                if !recursive.distinct || queued.insert(&row) {
+                    queue.push(row);
+                }
+            }
+        };
+
+        enqueue(&mut queue, self.run_recursive_selects(cte, recursive, &recursive.anchors)?);
+
+        let mut taken = 0;
+        let mut skipped = 0;
+
+        while let Some(row) = queue.pop() {
+            if taken >= limit {
+                break;
+            }
+
+            if skipped < recursive.offset {
+                skipped += 1;
+            } else {
+                taken += 1;
+
+                if !each(row.clone())? {
+                    break;
+                }
+            }
+
+            cte.current.replace(row);
+
+            enqueue(&mut queue, self.run_recursive_selects(cte, recursive, &recursive.steps)?);
+        }
+
+        Ok(())
+    }
+
+    // The rows some SELECTs of a recursive common table expression return, which read the
+    // row the recursion is at, and whose subqueries are planned again as they run
+    fn run_recursive_selects(
+        &self,
+        cte: &With,
+        recursive: &Recursive,
+        selects: &[SelectPlan],
+    ) -> Result<Vec<Vec<Value>>> {
+        let state = cte.state.replace(State::Recursing(recursive.table.clone()));
+
+        let rows = selects.iter().map(|select| self.run_query(select, None, None)).collect::<Result<Vec<_>>>();
+
+        cte.state.replace(state);
+
+        Ok(rows?.into_iter().flat_map(|result| result.rows).collect())
+    }
+}
+
+// The rows of a recursive common table expression still to be taken, which come out in the
+// order they were added, or with ORDER BY in its order and, of those that sort the same, in
+// the order they were added
+enum Queue<'a> {
+    Added(VecDeque<Vec<Value>>),
+    Sorted {
+        rows: BinaryHeap<Queued<'a>>,
+        added: u64,
+        order_by: &'a [ColumnOrder],
+    },
+}
+
+// A row in a sorted queue, with how many were added before it
+struct Queued<'a> {
+    row: Vec<Value>,
+    sequence: u64,
+    order_by: &'a [ColumnOrder],
+}
+
+impl<'a> Queue<'a> {
+    fn new(order_by: &'a [ColumnOrder]) -> Self {
+        match order_by {
+            [] => Queue::Added(VecDeque::new()),
+            _ => Queue::Sorted {
+                rows: BinaryHeap::new(),
+                added: 0,
+                order_by,
+            },
+        }
+    }
+
+    fn push(&mut self, row: Vec<Value>) {
+        match self {
+            Queue::Added(rows) => rows.push_back(row),
+            Queue::Sorted { rows, added, order_by } => {
+                rows.push(Queued {
+                    row,
+                    sequence: *added,
+                    order_by,
+                });
+
+                *added += 1;
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Vec<Value>> {
+        match self {
+            Queue::Added(rows) => rows.pop_front(),
+            Queue::Sorted { rows, .. } => rows.pop().map(|queued| queued.row),
+        }
+    }
+}
+
+// The heap takes the greatest first, so the row that sorts first, and was added first of
+// those that tie, is the greatest
+impl Ord for Queued<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_columns(self.order_by, &other.row, &self.row).then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Queued<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Queued<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued<'_> {}
+
+// Whether a SELECT reads a table of the name in its FROM clause
+fn reads(core: &SelectCore, name: &str) -> bool {
+    fn table_reads(table: &TableRef, name: &str) -> bool {
+        match table {
+            TableRef::Table { schema: None, name: table, .. } => table.eq_ignore_ascii_case(name),
+            TableRef::Join { left, right, .. } => table_reads(left, name) || table_reads(right, name),
+            _ => false,
+        }
+    }
+
+    match core {
+        SelectCore::Select { from: Some(from), .. } => table_reads(from, name),
+        _ => false,
+    }
+}
+
+// The table a common table expression makes, whose columns the column list names when it
+// has one
+fn cte_table(definition: &CommonTableExpr, plan: &SelectPlan) -> Result<CreateTableStmt> {
+    if !definition.columns.is_empty() && definition.columns.len() != plan.columns.len() {
+        return Err(Error::Misuse(format!(
+            "table {} has {} values for {} columns",
+            definition.name,
+            plan.columns.len(),
+            definition.columns.len()
+        )));
+    }
+
+    Ok(plan.as_table(&definition.name, &definition.columns))
+}
@@ -1,72 +1,312 @@
+use crate::core::btree::{self, build_payload_cell, write_page, BTreePage, TableAppender};
+use crate::core::cell::{CellPayload, PageCell};
+use crate::core::eval::{evaluate, Context};
+use crate::core::freelist::Freelist;
+use crate::core::functions::Registry;
 use crate::core::header::DBHeader;
-use crate::core::page::Page;
+use crate::core::index_key::IndexKey;
+use crate::core::journal;
+use crate::core::page::BTreePageSubType::Leaf;
+use crate::core::page::{Page, PageTypes};
+use crate::core::page::PageTypes::{IndexBTree, TableBTree};
+use crate::core::pager::Pager;
+use crate::core::ptrmap::PtrmapLayout;
+use crate::core::vacuum::VacuumOptions;
+use crate::core::record::encode_record;
 use crate::core::schema::{SchemaTable, SchemaTypesTypes};
+use crate::parser::csv::CsvReader;
 use crate::parser::scanner::Scanner;
 use std::collections::HashMap;
 
-use crate::core::cell::ColumnTypes;
-use crate::parser::statement::{Statement, StatementType};
-use crate::parser::token::TokenType;
-use anyhow::{bail, Context, Result};
-use std::fs::File;
-use std::io::Read;
-use std::os::unix::prelude::FileExt;
+use crate::core::value::{Affinity, Value};
+use crate::output::{OutputMode, OutputSettings};
+use crate::utils::{encode_varint, split_command_args};
+use crate::parser::ast::{ColumnDef, CreateTableStmt, Statement};
+use crate::parser::parse::Parser;
+use crate::error::{Error, Result};
+use crate::core::vfs::{FileVfs, LockLevel, MemoryVfs, Vfs};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::cell::{Cell, OnceCell};
+use std::rc::Rc;
+use std::io::{BufWriter, Write};
+
+pub type Row = HashMap<String, Value>;
+
+// Opening this path creates a private in-memory database instead of a file
+pub const MEMORY_PATH: &str = ":memory:";
+
+const DEFAULT_PAGE_SIZE: u32 = 4096;
+
+// Writes a row where its rowid goes in the table b-tree, and its entries where their keys go
+// in the indexes. When another row has the same rowid, or the same key in a UNIQUE index,
+// nothing is written and the constraint that fails is returned: the columns of the index, or
+// None for the rowid.
+fn insert_row<'a>(
+    pager: &mut Pager,
+    root_page: u32,
+    rowid: i64,
+    values: &[Value],
+    rowid_alias: Option<usize>,
+    indexes: &'a [(IndexKey, String)],
+) -> Result<Option<Option<&'a str>>> {
+    let by_rowid = |cell: &PageCell| Ok((cell.row_id as i64).cmp(&rowid));
+
+    if btree::contains(pager, root_page, &by_rowid)? {
+        return Ok(Some(None));
+    }
+
+    let entries = indexes
+        .iter()
+        .map(|(index, _)| index.entry(values, rowid, rowid_alias))
+        .collect::<Vec<_>>();
+
+    for ((index, name), entry) in indexes.iter().zip(&entries) {
+        let Some(key) = index.unique_key(entry) else {
+            continue;
+        };
+
+        if btree::contains(pager, index.root_page, &|cell| Ok(index.compare(&cell.payload.values()?, key)))? {
+            return Ok(Some(Some(name)));
+        }
+    }
+
+    let cell = build_payload_cell(pager, TableBTree(Leaf), &encode_varint(rowid as u64), &encode_record(values))?;
+
+    btree::insert(pager, root_page, cell, &by_rowid)?;
+
+    for ((index, _), entry) in indexes.iter().zip(&entries) {
+        let cell = build_payload_cell(pager, IndexBTree(Leaf), &[], &encode_record(entry))?;
+
+        btree::insert(pager, index.root_page, cell, &|cell| Ok(index.compare(&cell.payload.values()?, entry)))?;
+    }
+
+    Ok(None)
+}
+
+// The values of a table row by column name. `stored` lists the columns in the order the
+// record holds them, VIRTUAL generated columns are left NULL until they are computed.
+fn decode_row(create_statement: &CreateTableStmt, stored: &[usize], row_id: u64, payload: &CellPayload) -> Result<Row> {
+    let rowid_alias = create_statement.rowid_alias();
+
+    let mut index = 0;
+
+    let mut meta = create_statement
+        .columns
+        .iter()
+        .map(|column| (column.name.clone(), Value::Null))
+        .collect::<Row>();
+
+    for (field, &j) in stored.iter().enumerate() {
+        let column_name = &create_statement.columns[j];
+
+        // columns added by ALTER TABLE may be missing from older records
+        let value = match payload.column_types.get(field) {
+            None => Value::Null,
+
+            Some(cell_type) => {
+                let len = cell_type.get_len() as usize;
+
+                let value = cell_type.decode(payload.body.get(index..).unwrap_or_default())?;
+
+                index += len;
+
+                value
+            }
+        };
+
+        // a whole number in a REAL column is stored as an integer, and reads back as a real
+        let value = match (column_name.affinity(), value) {
+            (Affinity::Real, Value::Integer(value)) => Value::Real(value as f64),
+            (_, value) => value,
+        };
+
+        // the INTEGER PRIMARY KEY column is stored as NULL, its value is the rowid
+        if rowid_alias == Some(j) {
+            meta.insert(column_name.name.clone(), Value::Integer(row_id as i64));
+        } else {
+            meta.insert(column_name.name.clone(), value);
+        }
+    }
+
+    Ok(meta)
+}
+
+pub(crate) fn read_header(file: &dyn Vfs) -> Result<DBHeader> {
+    let mut header_buffer = vec![0u8; 100];
+
+    // anything shorter than the header cannot be a database
+    file.read_at(&mut header_buffer, 0)
+        .map_err(|_| Error::corrupt(0, "file is not a database"))?;
+
+    DBHeader::new(&header_buffer)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub declared_type: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryResult {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+pub struct Database {
+    file: Rc<dyn Vfs>,
 
-pub type Row = HashMap<String, (ColumnTypes, Vec<u8>)>;
+    // the page of sqlite_schema, read the first time the schema is, so that a file whose
+    // first page is damaged still opens for .recover
+    root_page: OnceCell<Page>,
 
-pub struct Database<'file> {
-    file: &'file File,
-    root_page: Page<'file>,
     header: DBHeader,
-    scanner: Scanner,
+    output: OutputSettings,
+
+    // the file it was opened from, next to which its journal is kept
+    path: Option<PathBuf>,
+
+    // page size set by PRAGMA page_size, taking effect on the next VACUUM
+    pub(crate) vacuum_options: Cell<VacuumOptions>,
+
+    // functions registered on this connection and PRAGMA case_sensitive_like
+    pub(crate) functions: Registry,
 }
 
-impl<'file> Database<'file> {
-    pub fn new(file: &'file mut File) -> Result<Database<'file>> {
-        let mut size_buf = vec![0u8; 2];
+impl Database {
+    // Opens the file for reading and writing, falling back to read-only access
+    // so queries still work on files we cannot write
+    pub fn open(path: impl AsRef<Path>) -> Result<Database> {
+        let path = path.as_ref();
+
+        if path.as_os_str() == MEMORY_PATH {
+            return Database::memory();
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .or_else(|_| File::open(path))
+            .map_err(|error| {
+                Error::Io(std::io::Error::new(
+                    error.kind(),
+                    format!("unable to open database \"{}\": {error}", path.display()),
+                ))
+            })?;
+
+        let file: Rc<dyn Vfs> = Rc::new(FileVfs::new(file));
+
+        // the header may be half written until a hot journal is rolled back
+        journal::roll_back_hot(path, file.as_ref())?;
+
+        let mut db = Database::with_vfs(file)?;
+
+        db.path = Some(path.to_path_buf());
+
+        Ok(db)
+    }
+
+    pub fn new(file: File) -> Result<Database> {
+        Database::with_vfs(Rc::new(FileVfs::new(file)))
+    }
+
+    // A new, empty database that only lives as long as this value
+    pub fn memory() -> Result<Database> {
+        let header = DBHeader::empty(DEFAULT_PAGE_SIZE, 0);
+
+        let mut image = vec![0u8; header.page_size as usize];
+
+        header.write(&mut image);
+
+        BTreePage::empty(1, TableBTree(Leaf)).write_to(&mut image, header.get_usable_size() as usize);
 
-        file.read_at(&mut size_buf, 16)
-            .with_context(|| "Could not read page size")?;
+        Database::deserialize(image)
+    }
+
+    // Opens a database image, as produced by `serialize`, without touching the disk
+    pub fn deserialize(image: Vec<u8>) -> Result<Database> {
+        Database::with_vfs(Rc::new(MemoryVfs::new(image)))
+    }
 
-        let page_size = u16::from_le_bytes([size_buf[0], size_buf[1]]) * 256;
+    // The database as one contiguous byte image, exactly as it would be stored on disk
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        self.with_shared_lock(|| {
+            let header = read_header(self.file.as_ref())?;
 
-        let mut page_buffer = vec![0u8; page_size as usize];
+            let file_size = self.file.size()?;
 
-        file.read_exact(&mut page_buffer)?;
+            let len = if header.is_db_size_valid() {
+                (header.db_size as u64 * header.page_size as u64).min(file_size)
+            } else {
+                file_size
+            };
 
-        let root_page = Page::new(file, page_size, 1)?;
+            let mut image = vec![0u8; len as usize];
+
+            self.file.read_at(&mut image, 0)?;
+
+            Ok(image)
+        })
+    }
+
+    // Opens a database stored behind any storage backend, like an in-memory image. Only the
+    // header is read until something needs more.
+    pub fn with_vfs(file: Rc<dyn Vfs>) -> Result<Database> {
+        let header = read_header(file.as_ref())?;
 
         let db = Self {
             file,
-            scanner: Scanner::new(),
-            header: root_page.header.clone(),
-            root_page,
+            output: OutputSettings::default(),
+            vacuum_options: Cell::new(VacuumOptions::default()),
+            functions: Registry::default(),
+            header,
+            root_page: OnceCell::new(),
+            path: None,
         };
 
         Ok(db)
     }
 
-    pub fn get_table_schemas(&self) -> Result<Vec<SchemaTable>> {
-        let mut tables = Vec::with_capacity(self.root_page.cells.len());
+    fn schema_page(&self) -> Result<&Page> {
+        if let Some(page) = self.root_page.get() {
+            return Ok(page);
+        }
 
-        for (_, payload) in &self.root_page.get_payloads()? {
-            let schema = SchemaTable::from(payload);
+        let page = Page::new(Rc::clone(&self.file), &self.header, 1)?;
 
-            if schema.schema_type == SchemaTypesTypes::Table {
-                tables.push(schema);
-            }
+        Ok(self.root_page.get_or_init(|| page))
+    }
+
+    // Every row of sqlite_schema: tables, indexes, views and triggers
+    pub fn get_schemas(&self) -> Result<Vec<SchemaTable>> {
+        let root_page = self.schema_page()?;
+
+        let mut schemas = Vec::with_capacity(root_page.cells.len());
+
+        for (_, payload) in &root_page.get_payloads()? {
+            schemas.push(SchemaTable::try_from(payload)?);
         }
 
+        Ok(schemas)
+    }
+
+    pub fn get_table_schemas(&self) -> Result<Vec<SchemaTable>> {
+        let mut tables = self.get_schemas()?;
+
+        tables.retain(|schema| schema.schema_type == SchemaTypesTypes::Table);
+
         Ok(tables)
     }
 
-    pub fn get_table_schema(&self, table_name: &String) -> Result<Option<SchemaTable>> {
+    pub fn get_table_schema(&self, table_name: &str) -> Result<Option<SchemaTable>> {
         let mut table: Option<SchemaTable> = None;
 
-        for (_, payload) in &self.root_page.get_payloads()? {
-            let schema = SchemaTable::from(payload);
+        for (_, payload) in &self.schema_page()?.get_payloads()? {
+            let schema = SchemaTable::try_from(payload)?;
 
-            if schema.schema_type == SchemaTypesTypes::Table && &schema.tbl_name == table_name {
+            if schema.schema_type == SchemaTypesTypes::Table && schema.tbl_name.eq_ignore_ascii_case(table_name) {
                 table = Some(schema);
                 break;
             }
@@ -75,192 +315,726 @@ impl<'file> Database<'file> {
         Ok(table)
     }
 
-    fn read_page(&self, page_number: i32) -> Result<Page> {
-        let table_root_page = Page::new(&self.file, self.header.page_size, page_number as u64)?;
+    pub(crate) fn read_page(&self, page_number: i32) -> Result<Page> {
+        let table_root_page = Page::new(Rc::clone(&self.file), &self.header, page_number as u64)?;
 
         Ok(table_root_page)
     }
 
-    pub fn count_records(&self, table_name: &String) -> Result<u16> {
+    pub fn freelist(&self) -> Result<Freelist> {
+        Freelist::read(self.file.as_ref(), &self.header)
+    }
+
+    // What a page is used for. Pages that are neither free nor a b-tree page are taken to
+    // be overflow pages, which have no header that would tell them apart.
+    pub fn page_type(&self, page_number: u32) -> Result<PageTypes> {
+        let page_size = u64::from(self.header.page_size);
+
+        if page_number == self.header.lock_byte_page() {
+            return Ok(PageTypes::Lock);
+        }
+
+        if !self.header.should_omit_pointer_map() && PtrmapLayout::new(&self.header).is_map_page(page_number) {
+            return Ok(PageTypes::PointerMap);
+        }
+
+        if self.freelist()?.contains(page_number) {
+            return Ok(PageTypes::FreeList);
+        }
+
+        let header_offset = if page_number == 1 { 100 } else { 0 };
+
+        let mut page_type = [0u8; 1];
+
+        self.file
+            .read_at(&mut page_type, u64::from(page_number - 1) * page_size + header_offset)
+            .map_err(|error| error.past_end().on_page(page_number))?;
+
+        Ok(PageTypes::try_from(page_type[0]).unwrap_or(PageTypes::PayloadOverflow))
+    }
+
+    pub fn count_records(&self, table_name: &str) -> Result<u64> {
         let table = self
             .get_table_schema(table_name)?
-            .with_context(|| format!("No such table: {table_name}"))?;
+            .ok_or_else(|| Error::NoSuchTable(table_name.to_string()))?;
+
+        let page = self.read_page(table.root_page)?;
 
-        let page = self.read_page(table.root_page).with_context(|| {
-            format!(
-                "Could not read Page number {}, for table: {}",
-                table.root_page, table.tbl_name
-            )
-        })?;
+        if table.table().is_some_and(|statement| statement.without_rowid) {
+            return Ok(page.get_index_payloads()?.len() as u64);
+        }
 
-        Ok(page.num_of_cells)
+        Ok(page.get_payloads()?.len() as u64)
     }
 
     pub fn get_data(&self, schema: &SchemaTable) -> Result<Vec<Row>> {
         let page = self.read_page(schema.root_page)?;
 
-        let create_statement = &schema.statement;
+        let create_statement = schema
+            .table()
+            .ok_or_else(|| Error::NoSuchTable(schema.tbl_name.clone()))?;
 
-        let mut column_vec = Vec::with_capacity(page.cells.len());
+        let stored = create_statement.stored_columns();
 
-        let payloads = page.get_payloads()?;
+        let mut rows = Vec::with_capacity(page.cells.len());
 
-        // println!("Rows len: {}", payloads.len());
+        // the rows of a WITHOUT ROWID table are the entries of an index b-tree
+        if create_statement.without_rowid {
+            for payload in page.get_index_payloads()? {
+                rows.push(decode_row(create_statement, &stored, 0, &payload)?);
+            }
+        } else {
+            for (row_id, payload) in page.get_payloads()? {
+                if payload.column_types.is_empty() {
+                    break;
+                }
 
-        for (row_id, payload) in payloads {
-            let mut index = 0;
+                rows.push(decode_row(create_statement, &stored, row_id, &payload)?);
+            }
+        }
 
-            let mut meta = Row::new();
+        self.compute_generated(create_statement, &schema.tbl_name, &mut rows)?;
 
-            if payload.column_types.len() == 0 {
-                return Ok(column_vec);
-            }
+        Ok(rows)
+    }
 
-            // println!("RowId: {row_id}");
+    // The rows with the given rowids, in that order, each found by descending the table
+    // b-tree. Rowids that are not in the table are skipped.
+    pub(crate) fn get_rows(&self, schema: &SchemaTable, row_ids: &[u64]) -> Result<Vec<Row>> {
+        let page = self.read_page(schema.root_page)?;
 
-            for j in 0..create_statement.columns.len() {
-                let column_name = &create_statement.columns[j];
+        let create_statement = schema
+            .table()
+            .ok_or_else(|| Error::NoSuchTable(schema.tbl_name.clone()))?;
 
-                let cell_type = &payload.column_types[j];
+        let stored = create_statement.stored_columns();
 
-                // if row_id == 1 {
-                //     // println!("Column: Type -> {cell_type:?}, Name -> {}, Payload -> {payload:?}", column_name.lexeme);
-                // }
+        let mut rows = Vec::with_capacity(row_ids.len());
 
-                let len = cell_type.get_len() as usize;
+        for &row_id in row_ids {
+            if let Some(payload) = page.find_row(row_id)? {
+                rows.push(decode_row(create_statement, &stored, row_id, &payload)?);
+            }
+        }
 
-                let data = &payload.body[index..index + len];
+        self.compute_generated(create_statement, &schema.tbl_name, &mut rows)?;
 
-                if column_name.lexeme == "id" {
-                    let bytes = row_id.to_be_bytes().to_vec();
-                    let data = (ColumnTypes::new(bytes.len() as u64)?, bytes);
+        Ok(rows)
+    }
 
-                    meta.insert(column_name.lexeme.to_string(), data);
-                } else {
-                    meta.insert(
-                        column_name.lexeme.to_string(),
-                        (cell_type.clone(), data.to_vec()),
-                    );
-                }
+    // Computes the VIRTUAL generated columns of rows read from a table. One may use another
+    // declared after it, so all of them are computed once for each, which leaves every one
+    // with its value after those it depends on have theirs.
+    fn compute_generated(&self, create_statement: &CreateTableStmt, table_name: &str, rows: &mut [Row]) -> Result<()> {
+        let generated = (0..create_statement.columns.len())
+            .filter_map(|column| match create_statement.generated(column) {
+                Some((expr, false)) => Some((&create_statement.columns[column], expr)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if generated.is_empty() {
+            return Ok(());
+        }
 
+        let context = Context::new(self, Some((create_statement, table_name)), None, None);
 
-                index += len;
-            }
-            // println!("Data: {meta:?}");
+        for row in rows {
+            for _ in 0..generated.len() {
+                for (column, expr) in &generated {
+                    let value = evaluate(expr, row, &context)?.apply_affinity(column.affinity());
 
-            column_vec.push(meta);
+                    row.insert(column.name.clone(), value);
+                }
+            }
         }
 
-        Ok(column_vec)
+        Ok(())
     }
 
-    pub fn execute_command(&self, command: &String) -> Result<()> {
-        let mut scanner = Scanner::from(command.clone());
+    pub(crate) fn vfs(&self) -> &dyn Vfs {
+        self.file.as_ref()
+    }
 
-        scanner.scan(command)?;
+    pub(crate) fn header(&self) -> &DBHeader {
+        &self.header
+    }
 
-        let tokens = scanner.get_tokens();
+    // Registers a function SQL can call, taking `arguments` arguments or any number when it
+    // is None. It replaces a built-in function of the same name, and one named regexp is
+    // what the REGEXP operator calls.
+    pub fn create_function(
+        &mut self,
+        name: &str,
+        arguments: Option<usize>,
+        function: impl Fn(&[Value]) -> Result<Value> + 'static,
+    ) {
+        self.functions.register(name, arguments, function);
+    }
 
-        let statement = Statement::new(tokens)?;
+    // Drops the schema read so far, which is read again when next needed
+    fn reload(&mut self) {
+        self.root_page.take();
+    }
 
-        match statement.statement_type {
-            StatementType::SELECT => self.handle_select(&statement),
+    // Reads the header again before reloading, for when the whole file was rewritten
+    pub(crate) fn reopen(&mut self) -> Result<()> {
+        self.header = read_header(self.file.as_ref())?;
 
-            _ => {
-                match command.as_str() {
-                    ".dbinfo" => {
-                        println!("{}", self.header, );
-                        println!("number of tables:    {}", self.get_table_schemas()?.len());
-                    }
+        self.reload();
 
-                    ".tables" => {
-                        let tables = self.get_table_schemas()?;
+        Ok(())
+    }
 
-                        for t in tables {
-                            print!("{} ", t.tbl_name);
-                        }
-                    }
+    // The pager borrows the storage rather than the database, so the database can be
+    // updated while a write is in progress
+    fn pager<'file>(&self, file: &'file dyn Vfs) -> Result<Pager<'file>> {
+        Pager::new(file, self.header.clone())
+    }
 
-                    _ => bail!("Missing or invalid command passed: {}", command)
-                };
+    // Gives up to `limit` free pages back to the file system, or all of them. Only databases
+    // with a pointer map can move pages around, for the others this does nothing.
+    pub fn incremental_vacuum(&mut self, limit: Option<u32>) -> Result<u32> {
+        let removed = self.give_back_free_pages(limit)?;
 
-                Ok(())
-            }
+        if removed > 0 {
+            self.reopen()?;
         }
+
+        Ok(removed)
     }
 
-    fn handle_select(&self, statement: &Statement) -> Result<()> {
-        let table = statement.tables.first().with_context(|| "No table selected")?;
+    // incremental_vacuum without reading the header again, for PRAGMA incremental_vacuum:
+    // the next statement does, since the change counter moved
+    pub(crate) fn give_back_free_pages(&self, limit: Option<u32>) -> Result<u32> {
+        if self.header.should_omit_pointer_map() {
+            return Ok(0);
+        }
 
-        let table_name = &table.lexeme;
+        let file = Rc::clone(&self.file);
 
-        let is_count = statement
-            .columns
-            .iter()
-            .any(|token| token.token_type == TokenType::COUNT);
+        let mut pager = self.pager(file.as_ref())?;
 
-        if is_count {
-            println!("{}", self.count_records(&table_name)?);
+        let removed = pager.incremental_vacuum(limit)?;
 
-            return Ok(());
+        if removed > 0 {
+            pager.commit()?;
         }
 
-        let schema = self
-            .get_table_schema(table_name)?
-            .with_context(|| format!("Could not Get Table Schema: {}", table_name))?;
+        Ok(removed)
+    }
 
-        let rows = self.get_data(&schema)?;
+    // Creates an empty table and registers it in sqlite_schema, returning its root page
+    fn create_table(&self, pager: &mut Pager, table_name: &str, sql: &str) -> Result<u32> {
+        let root_page = pager.allocate_root()?;
 
-        if rows.len() == 0 {
-            return Ok(());
+        write_page(pager, &BTreePage::empty(root_page, TableBTree(Leaf)))?;
+
+        let record = encode_record(&[
+            Value::Text("table".to_string()),
+            Value::Text(table_name.to_string()),
+            Value::Text(table_name.to_string()),
+            Value::Integer(root_page as i64),
+            Value::Text(sql.to_string()),
+        ]);
+
+        let mut schema = TableAppender::new(pager, 1)?;
+
+        let rowid = schema.next_rowid();
+
+        schema.append(rowid, &record)?;
+        schema.finish()?;
+
+        pager.header.schema_cookie += 1;
+
+        Ok(root_page)
+    }
+
+    // Loads delimited text into a table, creating it from the header row when it is missing.
+    // Rows with increasing rowids are appended to the right edge of the table b-tree, filling
+    // pages sequentially, and like in sqlite rows whose rowid or unique key is taken are left out.
+    pub fn import(&mut self, path: &str, table_name: &str, separator: char, skip: usize) -> Result<u64> {
+        let input = std::fs::read_to_string(path)
+            .map_err(|error| Error::Io(std::io::Error::new(error.kind(), format!("cannot open \"{path}\": {error}"))))?;
+
+        let mut reader = CsvReader::new(&input, separator);
+
+        for _ in 0..skip {
+            reader.next();
         }
 
-        let mut selected_columns = &statement.columns;
+        let file = Rc::clone(&self.file);
+
+        let mut pager = self.pager(file.as_ref())?;
+
+        let mut indexes = vec![];
+        let mut rowid_name = None;
+
+        let (root_page, affinities, rowid_alias) = match self.get_table_schema(table_name)? {
+            Some(schema) => {
+                let table = schema
+                    .table()
+                    .ok_or_else(|| Error::NoSuchTable(table_name.to_string()))?;
+
+                if table.without_rowid {
+                    return Err(Error::Unsupported(format!("importing into the WITHOUT ROWID table {}", table.name)));
+                }
+
+                for index in IndexKey::of_table(&self.get_schemas()?, table)? {
+                    let name = index.describe(table);
+
+                    indexes.push((index, name));
+                }
+
+                rowid_name = table.rowid_alias().map(|alias| format!("{}.{}", table.name, table.columns[alias].name));
+
+                (
+                    schema.root_page as u32,
+                    table.columns.iter().map(ColumnDef::affinity).collect::<Vec<_>>(),
+                    table.rowid_alias(),
+                )
+            }
+            None => {
+                let header = reader
+                    .next()
+                    .ok_or_else(|| Error::Misuse(format!("{path}: empty file, cannot create table {table_name}")))?;
+
+                let mut names: Vec<String> = vec![];
+
+                for (i, name) in header.iter().enumerate() {
+                    let mut name = if name.is_empty() { format!("c{}", i + 1) } else { name.clone() };
+
+                    if names.iter().any(|existing| existing.eq_ignore_ascii_case(&name)) {
+                        name = format!("{}_{}", name, i + 1);
+                    }
+
+                    names.push(name);
+                }
+
+                let definitions = names
+                    .iter()
+                    .map(|name| format!("\"{}\" TEXT", name.replace('"', "\"\"")))
+                    .collect::<Vec<_>>();
+
+                let sql = format!(
+                    "CREATE TABLE \"{}\"(\n{})",
+                    table_name.replace('"', "\"\""),
+                    definitions.join(", ")
+                );
+
+                let root_page = self.create_table(&mut pager, table_name, &sql)?;
+
+                (root_page, vec![Affinity::Text; names.len()], None)
+            }
+        };
+
+        let columns = affinities.len();
+
+        let last_rowid = TableAppender::new(&mut pager, root_page)?.last_rowid();
+
+        // rows are appended to the right edge unless some must go in between or have index
+        // entries, which are inserted where their keys go
+        let mut appending = indexes.is_empty();
+        let mut max_rowid = last_rowid;
 
-        for col_index in 0..statement.columns.len() {
-            let col = &statement.columns[col_index];
+        let mut rows = vec![];
 
-            if col.token_type == TokenType::STAR {
-                selected_columns = &schema.statement.columns;
+        loop {
+            let line = reader.line();
+
+            let Some(mut fields) = reader.next() else {
                 break;
-            } else if rows[0].get(&col.lexeme).is_none() {
-                bail!("No such Column: {}", col.lexeme)
+            };
+
+            if fields.len() == 1 && fields[0].is_empty() {
+                continue;
+            }
+
+            if fields.len() < columns {
+                eprintln!(
+                    "{path}:{line}: expected {columns} columns but found {} - filling the rest with NULL",
+                    fields.len()
+                );
+            } else if fields.len() > columns {
+                eprintln!(
+                    "{path}:{line}: expected {columns} columns but found {} - extras ignored",
+                    fields.len()
+                );
+
+                fields.truncate(columns);
+            }
+
+            let mut values = fields
+                .into_iter()
+                .zip(&affinities)
+                .map(|(field, affinity)| Value::Text(field).apply_affinity(*affinity))
+                .collect::<Vec<_>>();
+
+            values.resize(columns, Value::Null);
+
+            let mut rowid = None;
+
+            // the INTEGER PRIMARY KEY value becomes the rowid and is stored as NULL
+            if let Some(alias) = rowid_alias {
+                match &values[alias] {
+                    Value::Integer(value) => rowid = Some(*value),
+                    Value::Null => {}
+                    Value::Text(text) if text.is_empty() => {}
+                    value => return Err(Error::Mismatch(format!("{path}:{line}: rowid must be an integer, got {value}"))),
+                }
+
+                values[alias] = Value::Null;
             }
+
+            let next = max_rowid.map_or(1, |max| max + 1);
+
+            appending &= rowid.map_or(true, |rowid| rowid >= next);
+
+            max_rowid = max_rowid.max(Some(rowid.unwrap_or(next)));
+
+            rows.push((line, rowid, values));
         }
 
-        let mut limit: usize = 0;
+        let mut imported = 0;
+
+        if appending {
+            let mut appender = TableAppender::new(&mut pager, root_page)?;
 
-        if let Some(lim) = statement.limit {
-            limit = std::cmp::min(lim as usize, rows.len());
+            for (_, rowid, values) in rows {
+                let rowid = rowid.unwrap_or(appender.next_rowid());
+
+                appender.append(rowid, &encode_record(&values))?;
+
+                imported += 1;
+            }
+
+            appender.finish()?;
         } else {
-            limit = rows.len();
+            let mut max_rowid = last_rowid;
+
+            for (line, rowid, values) in rows {
+                let rowid = rowid.unwrap_or(max_rowid.map_or(1, |max| max + 1));
+
+                match insert_row(&mut pager, root_page, rowid, &values, rowid_alias, &indexes)? {
+                    None => imported += 1,
+                    Some(constraint) => {
+                        let constraint = constraint.or(rowid_name.as_deref()).unwrap_or("rowid");
+
+                        eprintln!("{path}:{line}: INSERT failed: UNIQUE constraint failed: {constraint}");
+
+                        continue;
+                    }
+                }
+
+                max_rowid = max_rowid.max(Some(rowid));
+            }
+        }
+
+        pager.commit()?;
+
+        self.header = pager.header.clone();
+
+        self.reload();
+
+        Ok(imported)
+    }
+
+    pub fn execute_command(&mut self, command: &str) -> Result<()> {
+        self.begin_read()?;
+
+        let result = self.run_command(command);
+
+        self.end_read()?;
+
+        result
+    }
+
+    // Takes a SHARED lock and drops the cached schema when another connection
+    // changed the file since we last looked at it
+    pub(crate) fn begin_read(&mut self) -> Result<()> {
+        self.roll_back_hot_journal()?;
+
+        self.file.lock(LockLevel::Shared)?;
+
+        let header = read_header(self.file.as_ref())?;
+
+        if header.file_change_counter != self.header.file_change_counter {
+            self.header = header;
+
+            self.reload();
+        }
+
+        Ok(())
+    }
+
+    // Puts back the pages a connection that crashed while changing the file left in its
+    // journal, before anything is read
+    fn roll_back_hot_journal(&self) -> Result<()> {
+        match &self.path {
+            Some(path) => journal::roll_back_hot(path, self.file.as_ref()),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub(crate) fn end_read(&self) -> Result<()> {
+        self.file.unlock(LockLevel::None)
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<()> {
+        if command.trim_start().starts_with('.') {
+            return self.execute_dot_command(command);
         }
 
-        for row_index in 0..limit {
-            let row = &rows[row_index];
+        let mut scanner = Scanner::from(command.to_string());
 
-            if !statement.evaluate_where(&row)? {
-                for col_index in 0..selected_columns.len() {
-                    let col = &selected_columns[col_index];
+        scanner.scan(command)?;
 
-                    match row.get(&col.lexeme) {
-                        None => continue,
+        let tokens = scanner.get_tokens();
+
+        let statement = Parser::new(command, tokens).parse()?;
+
+        match statement {
+            Statement::Select(_) | Statement::Pragma(_) => self.handle_select(&statement),
+
+            Statement::Vacuum(vacuum) => match vacuum.into {
+                Some(path) => self.vacuum_into(path, &self.vacuum_options.get()),
+                None => self.vacuum(&self.vacuum_options.get()),
+            },
+
+            _ => Err(Error::Unsupported(format!("statement: {}", command.trim()))),
+        }
+    }
+
+    fn execute_dot_command(&mut self, command: &str) -> Result<()> {
+        let args = split_command_args(command.trim());
+
+        let mut out = std::io::stdout().lock();
+
+        match args.first().map_or("", String::as_str) {
+            ".dbinfo" => {
+                writeln!(out, "{}", self.header)?;
+                writeln!(out, "number of tables:    {}", self.get_table_schemas()?.len())?;
+            }
+
+            ".tables" => {
+                let tables = self.get_table_schemas()?;
+
+                for t in tables {
+                    write!(out, "{} ", t.tbl_name)?;
+                }
+
+                writeln!(out)?;
+            }
 
-                        Some((col_type, data)) => {
-                            col_type.print(data).with_context(|| {
-                                format!("Could not print column Type: {col_type:?}")
-                            })?;
+            ".dump" => {
+                let mut out = BufWriter::new(out);
+
+                self.dump(&mut out, args.get(1).map(String::as_str))?;
+
+                out.flush()?;
+            }
+
+            ".analyze" => {
+                let mut out = BufWriter::new(out);
+
+                self.analyze(&mut out)?;
+
+                out.flush()?;
+            }
+
+            ".page" => {
+                let page_number = args
+                    .get(1)
+                    .and_then(|page| page.parse::<u32>().ok())
+                    .ok_or_else(|| Error::Misuse("Usage: .page PAGE".to_string()))?;
+
+                let mut out = BufWriter::new(out);
+
+                self.inspect_page(&mut out, page_number)?;
+
+                out.flush()?;
+            }
+
+            ".btree" => {
+                let name = args.get(1).ok_or_else(|| Error::Misuse("Usage: .btree TABLE".to_string()))?;
+
+                let mut out = BufWriter::new(out);
+
+                self.inspect_btree(&mut out, name)?;
+
+                out.flush()?;
+            }
+
+            ".recover" => {
+                let mut out = BufWriter::new(out);
+
+                self.recover(&mut out)?;
+
+                out.flush()?;
+            }
+
+            ".mode" => match args.get(1) {
+                None => writeln!(out, "current output mode: {}", self.output.mode)?,
+
+                Some(name) => {
+                    let mode = OutputMode::parse(name, args.get(2).map(String::as_str))?;
+
+                    self.output.set_mode(mode);
+                }
+            },
+
+            ".headers" | ".header" => match args.get(1).map(|arg| arg.to_lowercase()).as_deref() {
+                Some("on") | Some("yes") | Some("1") | Some("true") => self.output.headers = true,
+                Some("off") | Some("no") | Some("0") | Some("false") => self.output.headers = false,
+                _ => return Err(Error::Misuse("Usage: .headers on|off".to_string())),
+            },
+
+            ".separator" => {
+                let column_separator = args
+                    .get(1)
+                    .ok_or_else(|| Error::Misuse("Usage: .separator COL ?ROW?".to_string()))?;
+
+                self.output.column_separator = column_separator.clone();
+
+                if let Some(row_separator) = args.get(2) {
+                    self.output.row_separator = row_separator.clone();
+                }
+            }
+
+            ".import" => {
+                let usage = "Usage: .import [--csv|--tsv] [--skip N] FILE TABLE";
+
+                // without an explicit format the current separator is used, like sqlite3 does
+                let mut separator = match self.output.mode {
+                    OutputMode::Csv => ',',
+                    OutputMode::Tabs => '\t',
+                    _ => self.output.column_separator.chars().next().unwrap_or('|'),
+                };
+
+                let mut skip = 0;
+                let mut positional = vec![];
+                let mut rest = args.iter().skip(1);
+
+                while let Some(arg) = rest.next() {
+                    match arg.as_str() {
+                        "--csv" => separator = ',',
+                        "--tsv" => separator = '\t',
+                        "--skip" => {
+                            skip = rest
+                                .next()
+                                .and_then(|count| count.parse::<usize>().ok())
+                                .ok_or_else(|| Error::Misuse(usage.to_string()))?;
                         }
+                        _ if arg.starts_with("--") => {
+                            return Err(Error::Misuse(format!("unknown option: {arg}\n{usage}")))
+                        }
+                        _ => positional.push(arg.clone()),
                     }
+                }
 
-                    if col_index != selected_columns.len() - 1 {
-                        print!("|")
-                    }
+                if positional.len() != 2 {
+                    return Err(Error::Misuse(usage.to_string()));
                 }
 
-                print!("\n");
+                self.import(&positional[0], &positional[1], separator, skip)?;
             }
+
+            ".nullvalue" => {
+                let null_value = args
+                    .get(1)
+                    .ok_or_else(|| Error::Misuse("Usage: .nullvalue STRING".to_string()))?;
+
+                self.output.null_value = null_value.clone();
+            }
+
+            _ => {
+                return Err(Error::Misuse(format!(
+                    "unknown command or invalid arguments: \"{}\". Enter \".help\" for help",
+                    command.trim().trim_start_matches('.')
+                )))
+            }
+        };
+
+        Ok(())
+    }
+
+    // Column names and declared types of a SELECT's result, without reading any row
+    pub fn describe(&self, statement: &Statement) -> Result<Vec<Column>> {
+        match statement {
+            Statement::Pragma(pragma) => Ok(vec![Column {
+                name: pragma.name.clone(),
+                declared_type: None,
+            }]),
+
+            Statement::Select(select) => Ok(self.plan_select(select)?.columns),
+
+            _ => Err(Error::Misuse("only SELECT and PRAGMA statements return rows".to_string())),
         }
+    }
+
+    pub fn query(&self, statement: &Statement) -> Result<QueryResult> {
+        self.with_shared_lock(|| self.query_unlocked(statement))
+    }
+
+    // Runs `f` under a SHARED lock, releasing it afterwards unless the caller already held one
+    pub(crate) fn with_shared_lock<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let held = self.file.lock_level();
+
+        if held == LockLevel::None {
+            self.roll_back_hot_journal()?;
+        }
+
+        self.file.lock(LockLevel::Shared)?;
+
+        let result = f();
+
+        if held == LockLevel::None {
+            self.file.unlock(LockLevel::None)?;
+        }
+
+        result
+    }
+
+    fn query_unlocked(&self, statement: &Statement) -> Result<QueryResult> {
+        match statement {
+            Statement::Pragma(pragma) => self.pragma(pragma),
+
+            Statement::Select(select) => self.run_select(self.plan_select(select)?),
+
+            _ => Err(Error::Misuse("only SELECT and PRAGMA statements return rows".to_string())),
+        }
+    }
+
+    fn handle_select(&self, statement: &Statement) -> Result<()> {
+        let result = self.query(statement)?;
+
+        if result.rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut out = BufWriter::new(std::io::stdout().lock());
+
+        let mut formatter = self.output.formatter();
+
+        let column_names = result
+            .columns
+            .iter()
+            .map(|col| col.name.clone())
+            .collect::<Vec<_>>();
+
+        formatter.begin(&mut out, &column_names)?;
+
+        for row in &result.rows {
+            formatter.row(&mut out, row)?;
+        }
+
+        formatter.finish(&mut out)?;
+
+        out.flush()?;
 
         Ok(())
     }
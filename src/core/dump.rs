@@ -0,0 +1,104 @@
+use crate::core::database::Database;
+use crate::core::schema::{SchemaTable, SchemaTypesTypes};
+use crate::core::value::Value;
+use crate::utils::{like_match, quote_identifier};
+use crate::error::Result;
+use std::io::Write;
+
+impl Database {
+    // Writes the schema and content as an SQL script that recreates the database, in the
+    // same order as the sqlite3 shell: tables with their rows, the internal sqlite_stat1 and
+    // sqlite_sequence tables, then views, triggers and indexes. `pattern` is a LIKE pattern
+    // selecting tables by name.
+    pub fn dump(&self, out: &mut dyn Write, pattern: Option<&str>) -> Result<()> {
+        let schemas = self.get_schemas()?;
+
+        let is_selected = |schema: &SchemaTable| pattern.map_or(true, |pattern| like_match(pattern, &schema.tbl_name));
+
+        writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+        writeln!(out, "BEGIN TRANSACTION;")?;
+
+        let tables = schemas
+            .iter()
+            .filter(|schema| schema.schema_type == SchemaTypesTypes::Table && is_selected(schema))
+            .collect::<Vec<_>>();
+
+        for table in &tables {
+            if table.name.starts_with("sqlite_") || table.sql.is_empty() {
+                continue;
+            }
+
+            writeln!(out, "{};", table.sql)?;
+
+            self.dump_rows(out, table)?;
+        }
+
+        if let Some(stat) = tables.iter().find(|table| table.name == "sqlite_stat1") {
+            writeln!(out, "ANALYZE sqlite_schema;")?;
+
+            self.dump_rows(out, stat)?;
+        }
+
+        let mut is_schema_writable = false;
+
+        if let Some(sequence) = tables.iter().find(|table| table.name == "sqlite_sequence") {
+            writeln!(out, "PRAGMA writable_schema=ON;")?;
+            writeln!(out, "CREATE TABLE IF NOT EXISTS sqlite_sequence(name,seq);")?;
+            writeln!(out, "DELETE FROM sqlite_sequence;")?;
+
+            self.dump_rows(out, sequence)?;
+
+            is_schema_writable = true;
+        }
+
+        // indexes created for UNIQUE and PRIMARY KEY constraints have no sql and come back with their table
+        let mut others = schemas
+            .iter()
+            .filter(|schema| schema.schema_type != SchemaTypesTypes::Table && is_selected(schema))
+            .filter(|schema| !schema.sql.is_empty())
+            .collect::<Vec<_>>();
+
+        others.sort_by_key(|schema| match schema.schema_type {
+            SchemaTypesTypes::View => 0,
+            SchemaTypesTypes::Trigger => 1,
+            _ => 2,
+        });
+
+        for schema in others {
+            writeln!(out, "{};", schema.sql)?;
+        }
+
+        if is_schema_writable {
+            writeln!(out, "PRAGMA writable_schema=OFF;")?;
+        }
+
+        writeln!(out, "COMMIT;")?;
+
+        Ok(())
+    }
+
+    fn dump_rows(&self, out: &mut dyn Write, table: &SchemaTable) -> Result<()> {
+        let name = quote_identifier(&table.name);
+
+        let Some(statement) = table.table() else {
+            return Ok(());
+        };
+
+        // generated columns are computed again when the rows are inserted
+        let columns = (0..statement.columns.len())
+            .filter(|&column| statement.generated(column).is_none())
+            .map(|column| &statement.columns[column].name)
+            .collect::<Vec<_>>();
+
+        for row in self.get_data(table)? {
+            let values = columns
+                .iter()
+                .map(|&name| row.get(name).unwrap_or(&Value::Null).to_shell_literal(true))
+                .collect::<Vec<_>>();
+
+            writeln!(out, "INSERT INTO {} VALUES({});", name, values.join(","))?;
+        }
+
+        Ok(())
+    }
+}
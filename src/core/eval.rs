@@ -0,0 +1,848 @@
+use crate::core::cte::With;
+use crate::core::database::{Database, Row};
+use crate::core::functions;
+use crate::core::select::Scope;
+use crate::core::value::{Affinity, Value};
+use crate::error::{Error, Result};
+use crate::parser::ast::{BinaryOperator, CreateTableStmt, Expr, SelectStmt, UnaryOperator};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// What expressions are evaluated with besides the row: the database, whose functions they
+// call and whose tables their subqueries read, the scope the query was planned in, whose
+// columns give comparisons their affinity, the outer query and its row when this is a
+// subquery, and the result of each subquery that does not depend on the row, which is read
+// once per statement
+pub(crate) struct Context<'a> {
+    db: &'a Database,
+    scope: Scope<'a>,
+    outer: Option<(&'a Context<'a>, &'a Row)>,
+    subqueries: RefCell<HashMap<*const SelectStmt, Rc<Subquery>>>,
+}
+
+struct Subquery {
+    // the affinity of each result column, which comparisons with its values apply
+    affinities: Vec<Option<Affinity>>,
+
+    rows: Vec<Vec<Value>>,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(
+        db: &'a Database,
+        table: Option<(&'a CreateTableStmt, &'a str)>,
+        outer: Option<(&'a Context<'a>, &'a Row)>,
+        with: Option<Rc<With<'a>>>,
+    ) -> Self {
+        Self {
+            db,
+            scope: Scope::new(db, table, outer.map(|(context, _)| &context.scope), with),
+            outer,
+            subqueries: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // The result of a subquery run for a row, which is planned again and run again for the
+    // next row only when it refers to the row's columns. `needed` is how many of its rows
+    // are read, all of them when None.
+    fn subquery(&self, select: &SelectStmt, row: &Row, needed: Option<usize>) -> Result<Rc<Subquery>> {
+        let key = select as *const SelectStmt;
+
+        if let Some(subquery) = self.subqueries.borrow().get(&key) {
+            return Ok(Rc::clone(subquery));
+        }
+
+        let plan = self.db.plan_query(select, Some(&self.scope), self.scope.with())?;
+
+        let subquery = Rc::new(Subquery {
+            affinities: plan.affinities.clone(),
+            rows: self.db.run_query(&plan, Some((self, row)), needed)?.rows,
+        });
+
+        if !plan.correlated {
+            self.subqueries.borrow_mut().insert(key, Rc::clone(&subquery));
+        }
+
+        Ok(subquery)
+    }
+
+    // A column of this query's table from the row, or one of an outer query's from its row
+    fn column(&self, expr: &Expr, name: &str, row: &Row) -> Result<Value> {
+        match self.outer {
+            Some((outer, outer_row)) if self.scope.resolve(expr).is_err() => outer.column(expr, name, outer_row),
+            _ => column_value(row, name),
+        }
+    }
+
+    // The affinity a comparison between two expressions applies to both values
+    fn comparison_affinity(&self, left: &Expr, right: &Expr) -> Option<Affinity> {
+        comparison_affinity(affinity(left, &self.scope), affinity(right, &self.scope))
+    }
+}
+
+// Evaluates an expression against one row. NULL stands for unknown, so comparisons with NULL
+// are NULL and AND, OR, NOT, BETWEEN and IN follow three-valued logic.
+pub(crate) fn evaluate(expr: &Expr, row: &Row, context: &Context) -> Result<Value> {
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+
+        Expr::Column { name, .. } => context.column(expr, name, row),
+
+        Expr::Unary { operator, expr } => {
+            let value = evaluate(expr, row, context)?;
+
+            Ok(match operator {
+                UnaryOperator::Not => from_truth(truth(&value).map(|value| !value)),
+                UnaryOperator::Negate => negate(value),
+                UnaryOperator::Plus => value,
+                UnaryOperator::BitNot => to_integer(&value).map_or(Value::Null, |value| Value::Integer(!value)),
+            })
+        }
+
+        Expr::Binary { left, operator, right } => match operator {
+            BinaryOperator::And => {
+                let left = truth(&evaluate(left, row, context)?);
+
+                // FALSE AND anything is FALSE, without looking at the right side
+                if left == Some(false) {
+                    return Ok(from_truth(Some(false)));
+                }
+
+                let right = truth(&evaluate(right, row, context)?);
+
+                Ok(from_truth(and(left, right)))
+            }
+
+            BinaryOperator::Or => {
+                let left = truth(&evaluate(left, row, context)?);
+
+                if left == Some(true) {
+                    return Ok(from_truth(Some(true)));
+                }
+
+                let right = truth(&evaluate(right, row, context)?);
+
+                Ok(from_truth(match (left, right) {
+                    (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }))
+            }
+
+            BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::Less
+            | BinaryOperator::LessEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual
+            | BinaryOperator::Is
+            | BinaryOperator::IsNot => {
+                let affinity = context.comparison_affinity(left, right);
+
+                let left = for_comparison(evaluate(left, row, context)?, affinity);
+                let right = for_comparison(evaluate(right, row, context)?, affinity);
+
+                compare(&left, *operator, &right)
+            }
+
+            _ => {
+                let left = evaluate(left, row, context)?;
+                let right = evaluate(right, row, context)?;
+
+                binary(&left, *operator, &right)
+            }
+        },
+
+        Expr::Cast { expr, type_name } => {
+            Ok(cast(evaluate(expr, row, context)?, Affinity::from_declared_type(type_name)))
+        }
+
+        // the branches are tried in order, and only the result of the one taken is evaluated.
+        // `CASE x WHEN y` compares like `x = y`, so a NULL x matches no branch.
+        Expr::Case {
+            operand,
+            branches,
+            otherwise,
+        } => {
+            let operand = match operand {
+                Some(operand) => Some((evaluate(operand, row, context)?, operand)),
+                None => None,
+            };
+
+            for (condition, result) in branches {
+                let taken = match &operand {
+                    Some((value, operand)) => {
+                        let affinity = context.comparison_affinity(operand, condition);
+
+                        let value = for_comparison(value.clone(), affinity);
+                        let condition = for_comparison(evaluate(condition, row, context)?, affinity);
+
+                        is_true(&compare(&value, BinaryOperator::Equal, &condition)?)
+                    }
+                    None => is_true(&evaluate(condition, row, context)?),
+                };
+
+                if taken {
+                    return evaluate(result, row, context);
+                }
+            }
+
+            match otherwise {
+                Some(otherwise) => evaluate(otherwise, row, context),
+                None => Ok(Value::Null),
+            }
+        }
+
+        // these only evaluate the arguments they need, so that one that is not needed
+        // cannot fail the call
+        Expr::Function { name, args, .. } if is_named(name, &["coalesce", "ifnull"]) => {
+            for arg in args {
+                let value = evaluate(arg, row, context)?;
+
+                if !value.is_null() {
+                    return Ok(value);
+                }
+            }
+
+            Ok(Value::Null)
+        }
+
+        Expr::Function { name, args, .. } if is_named(name, &["iif"]) => {
+            for pair in args.chunks(2) {
+                match pair {
+                    [condition, value] if is_true(&evaluate(condition, row, context)?) => {
+                        return evaluate(value, row, context)
+                    }
+                    [otherwise] => return evaluate(otherwise, row, context),
+                    _ => {}
+                }
+            }
+
+            Ok(Value::Null)
+        }
+
+        Expr::Function { name, args, .. } => {
+            let args = args.iter().map(|arg| evaluate(arg, row, context)).collect::<Result<Vec<_>>>()?;
+
+            context.db.functions.call(name, &args)
+        }
+
+        // `x LIKE y ESCAPE z` calls like(y, x, z), GLOB and REGEXP call glob() and regexp()
+        Expr::Like {
+            expr,
+            operator,
+            pattern,
+            escape,
+            negated,
+        } => {
+            let Some(name) = functions::operator_function(*operator) else {
+                return Err(Error::Misuse("unable to use function MATCH in the requested context".to_string()));
+            };
+
+            let mut args = vec![evaluate(pattern, row, context)?, evaluate(expr, row, context)?];
+
+            if let Some(escape) = escape {
+                args.push(evaluate(escape, row, context)?);
+            }
+
+            let matched = context.db.functions.call(name, &args)?;
+
+            Ok(match negated {
+                true => from_truth(truth(&matched).map(|matched| !matched)),
+                false => matched,
+            })
+        }
+
+        // `x BETWEEN a AND b` is `x >= a AND x <= b`, with x evaluated once
+        Expr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => {
+            let value = evaluate(expr, row, context)?;
+
+            let low_affinity = context.comparison_affinity(expr, low);
+            let high_affinity = context.comparison_affinity(expr, high);
+
+            let low = compare(
+                &for_comparison(value.clone(), low_affinity),
+                BinaryOperator::GreaterEqual,
+                &for_comparison(evaluate(low, row, context)?, low_affinity),
+            )?;
+
+            let high = compare(
+                &for_comparison(value, high_affinity),
+                BinaryOperator::LessEqual,
+                &for_comparison(evaluate(high, row, context)?, high_affinity),
+            )?;
+
+            Ok(from_truth(and(truth(&low), truth(&high)).map(|between| between != *negated)))
+        }
+
+        Expr::InList { expr, list, negated } => {
+            // nothing is in an empty list, not even NULL
+            if list.is_empty() {
+                return Ok(from_truth(Some(*negated)));
+            }
+
+            // the list takes the affinity of the value looked for, if it has one
+            let affinity = affinity(expr, &context.scope);
+
+            let value = evaluate(expr, row, context)?;
+
+            let candidates = list.iter().map(|item| evaluate(item, row, context));
+
+            let found = contains(value, candidates, affinity)?;
+
+            Ok(from_truth(found.map(|found| found != *negated)))
+        }
+
+        // the subquery has a single column, checked when the statement was planned
+        Expr::InSelect { expr, select, negated } => {
+            let subquery = context.subquery(select, row, None)?;
+
+            if subquery.rows.is_empty() {
+                return Ok(from_truth(Some(*negated)));
+            }
+
+            let affinity = comparison_affinity(affinity(expr, &context.scope), subquery.affinities[0]);
+
+            let value = evaluate(expr, row, context)?;
+
+            let found = contains(value, subquery.rows.iter().map(|row| Ok(row[0].clone())), affinity)?;
+
+            Ok(from_truth(found.map(|found| found != *negated)))
+        }
+
+        // the first column of the first row, NULL when there is none
+        Expr::Subquery(select) => {
+            let subquery = context.subquery(select, row, Some(1))?;
+
+            Ok(subquery.rows.first().map_or(Value::Null, |row| row[0].clone()))
+        }
+
+        Expr::Exists(select) => Ok(from_truth(Some(!context.subquery(select, row, Some(1))?.rows.is_empty()))),
+
+        _ => Err(Error::Unsupported("this kind of expression".to_string())),
+    }
+}
+
+// Whether a WHERE clause keeps the row, where NULL counts as false
+pub(crate) fn is_true(value: &Value) -> bool {
+    truth(value) == Some(true)
+}
+
+// Whether a value is one of the candidates, which are evaluated until one matches. It is
+// unknown when the value is NULL, or when it matches none of them but one of them is NULL,
+// since that one might have been it.
+fn contains(
+    value: Value,
+    candidates: impl Iterator<Item = Result<Value>>,
+    affinity: Option<Affinity>,
+) -> Result<Option<bool>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    let value = for_comparison(value, affinity);
+
+    let mut unknown = false;
+
+    for candidate in candidates {
+        let candidate = for_comparison(candidate?, affinity);
+
+        if candidate.is_null() {
+            unknown = true;
+        } else if value.compare(&candidate) == Ordering::Equal {
+            return Ok(Some(true));
+        }
+    }
+
+    Ok(if unknown { None } else { Some(false) })
+}
+
+fn is_named(name: &str, names: &[&str]) -> bool {
+    names.iter().any(|candidate| candidate.eq_ignore_ascii_case(name))
+}
+
+// Rows are keyed by the declared column names, which SQL matches without regard to case
+fn column_value(row: &Row, name: &str) -> Result<Value> {
+    if let Some(value) = row.get(name) {
+        return Ok(value.clone());
+    }
+
+    row.iter()
+        .find(|(column, _)| column.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+        .ok_or_else(|| Error::NoSuchColumn(name.to_string()))
+}
+
+fn binary(left: &Value, operator: BinaryOperator, right: &Value) -> Result<Value> {
+    match operator {
+        BinaryOperator::Concat => Ok(match (left, right) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            _ => Value::Text(format!("{left}{right}")),
+        }),
+
+        BinaryOperator::Add
+        | BinaryOperator::Subtract
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::Modulo => Ok(arithmetic(to_number(left), operator, to_number(right))),
+
+        BinaryOperator::BitAnd | BinaryOperator::BitOr | BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => {
+            let (Some(left), Some(right)) = (to_integer(left), to_integer(right)) else {
+                return Ok(Value::Null);
+            };
+
+            Ok(Value::Integer(match operator {
+                BinaryOperator::BitAnd => left & right,
+                BinaryOperator::BitOr => left | right,
+                BinaryOperator::ShiftLeft => shift_left(left, right),
+                _ => shift_right(left, right),
+            }))
+        }
+
+        _ => compare(left, operator, right),
+    }
+}
+
+// Integer arithmetic stays integer until it overflows, which makes it real like in sqlite.
+// Dividing by zero is NULL rather than an error.
+fn arithmetic(left: Value, operator: BinaryOperator, right: Value) -> Value {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => Value::Null,
+
+        (Value::Integer(a), Value::Integer(b)) => {
+            let result = match operator {
+                BinaryOperator::Add => a.checked_add(b),
+                BinaryOperator::Subtract => a.checked_sub(b),
+                BinaryOperator::Multiply => a.checked_mul(b),
+                _ if b == 0 => return Value::Null,
+                BinaryOperator::Divide => a.checked_div(b),
+
+                // i64::MIN % -1 overflows, but its remainder is plainly 0
+                _ => Some(a.checked_rem(b).unwrap_or(0)),
+            };
+
+            match result {
+                Some(value) => Value::Integer(value),
+                None => real_arithmetic(a as f64, operator, b as f64),
+            }
+        }
+
+        (a, b) => real_arithmetic(to_real(&a), operator, to_real(&b)),
+    }
+}
+
+fn real_arithmetic(a: f64, operator: BinaryOperator, b: f64) -> Value {
+    let result = match operator {
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Subtract => a - b,
+        BinaryOperator::Multiply => a * b,
+        _ if b == 0.0 => return Value::Null,
+        BinaryOperator::Divide => a / b,
+
+        // sqlite takes the remainder of the integer parts
+        _ => match (a as i64, b as i64) {
+            (_, 0) => return Value::Null,
+            (a, b) => a.checked_rem(b).unwrap_or(0) as f64,
+        },
+    };
+
+    // inf - inf and the like have no value
+    if result.is_nan() {
+        return Value::Null;
+    }
+
+    Value::Real(result)
+}
+
+// A negative shift goes the other way, and shifting by 64 or more leaves only the sign
+fn shift_left(value: i64, amount: i64) -> i64 {
+    match amount {
+        ..=-1 => shift_right(value, amount.saturating_neg()),
+        64.. => 0,
+        _ => value << amount,
+    }
+}
+
+fn shift_right(value: i64, amount: i64) -> i64 {
+    match amount {
+        ..=-1 => shift_left(value, amount.saturating_neg()),
+        64.. => value >> 63,
+        _ => value >> amount,
+    }
+}
+
+fn negate(value: Value) -> Value {
+    match value {
+        Value::Integer(i64::MIN) => Value::Real(-(i64::MIN as f64)),
+        Value::Integer(value) => Value::Integer(-value),
+        Value::Real(value) => Value::Real(-value),
+        Value::Null => Value::Null,
+        value => negate(to_number(&value)),
+    }
+}
+
+// The number a value stands for in arithmetic: text and blobs are read up to the first
+// character that cannot be part of a number
+pub(crate) fn to_number(value: &Value) -> Value {
+    let text = match value {
+        Value::Text(text) => text.clone(),
+        Value::Blob(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        value => return value.clone(),
+    };
+
+    let prefix = numeric_prefix(&text);
+
+    if prefix.is_empty() {
+        return Value::Integer(0);
+    }
+
+    match prefix.parse::<i64>() {
+        Ok(value) => Value::Integer(value),
+        Err(_) => Value::Real(prefix.parse::<f64>().unwrap_or(0.0)),
+    }
+}
+
+// The integer a value stands for in bitwise operations, where reals lose their fraction
+pub(crate) fn to_integer(value: &Value) -> Option<i64> {
+    match to_number(value) {
+        Value::Integer(value) => Some(value),
+        Value::Real(value) => Some(value as i64),
+        _ => None,
+    }
+}
+
+// The real a value stands for, where NULL is 0.0
+pub(crate) fn to_real(value: &Value) -> f64 {
+    match to_number(value) {
+        Value::Integer(value) => value as f64,
+        Value::Real(value) => value,
+        _ => 0.0,
+    }
+}
+
+fn compare(left: &Value, operator: BinaryOperator, right: &Value) -> Result<Value> {
+    let ordering = left.compare(right);
+
+    let result = match operator {
+        BinaryOperator::Is => return Ok(from_truth(Some(ordering == Ordering::Equal))),
+        BinaryOperator::IsNot => return Ok(from_truth(Some(ordering != Ordering::Equal))),
+        _ if left.is_null() || right.is_null() => return Ok(Value::Null),
+        BinaryOperator::Equal => ordering == Ordering::Equal,
+        BinaryOperator::NotEqual => ordering != Ordering::Equal,
+        BinaryOperator::Less => ordering == Ordering::Less,
+        BinaryOperator::LessEqual => ordering != Ordering::Greater,
+        BinaryOperator::Greater => ordering == Ordering::Greater,
+        BinaryOperator::GreaterEqual => ordering != Ordering::Less,
+        _ => return Err(Error::Unsupported(format!("the {operator:?} operator"))),
+    };
+
+    Ok(from_truth(Some(result)))
+}
+
+// The affinity of an expression: a column has the one of its declared type, CAST the one of
+// the type it converts to and a scalar subquery the one of its column, while other
+// expressions, `+column` among them, have none
+pub(crate) fn affinity(expr: &Expr, scope: &Scope) -> Option<Affinity> {
+    match expr {
+        Expr::Column { .. } => match scope.resolve(expr) {
+            Ok((table, i)) => Some(table.columns[i].affinity()),
+            Err(_) => affinity(expr, scope.outer()?),
+        },
+        Expr::Cast { type_name, .. } => Some(Affinity::from_declared_type(type_name)),
+        Expr::Collate { expr, .. } => affinity(expr, scope),
+        Expr::Subquery(select) => scope.subquery_affinity(select),
+        _ => None,
+    }
+}
+
+// The affinity a comparison applies, by sqlite's rules: between two columns NUMERIC when
+// either one is numeric and none otherwise, between a column and another expression the
+// column's
+fn comparison_affinity(left: Option<Affinity>, right: Option<Affinity>) -> Option<Affinity> {
+    let is_numeric = |affinity| matches!(affinity, Affinity::Integer | Affinity::Real | Affinity::Numeric);
+
+    match (left, right) {
+        (Some(left), Some(right)) => (is_numeric(left) || is_numeric(right)).then_some(Affinity::Numeric),
+        (Some(affinity), None) | (None, Some(affinity)) => Some(affinity),
+        (None, None) => None,
+    }
+}
+
+// Converts an operand the way a comparison with the given affinity does: a numeric one turns
+// text that reads as a number into that number, TEXT turns numbers into text
+fn for_comparison(value: Value, affinity: Option<Affinity>) -> Value {
+    match (affinity, value) {
+        (Some(Affinity::Integer | Affinity::Real | Affinity::Numeric), value @ Value::Text(_)) => {
+            value.apply_affinity(Affinity::Numeric)
+        }
+        (Some(Affinity::Text), value @ (Value::Integer(_) | Value::Real(_))) => value.apply_affinity(Affinity::Text),
+        (_, value) => value,
+    }
+}
+
+// CAST(value AS type), which unlike the affinity of a column converts whatever it can:
+// text becomes the number it starts with, and a number becomes text or a blob
+fn cast(value: Value, affinity: Affinity) -> Value {
+    match (affinity, value) {
+        (_, Value::Null) => Value::Null,
+
+        (Affinity::Text, Value::Blob(bytes)) => Value::Text(String::from_utf8_lossy(&bytes).into_owned()),
+        (Affinity::Text, value) => value.apply_affinity(Affinity::Text),
+
+        (Affinity::Blob, Value::Blob(bytes)) => Value::Blob(bytes),
+        (Affinity::Blob, value) => Value::Blob(value.to_string().into_bytes()),
+
+        // reals lose their fraction, and text is read only up to its first non-digit
+        (Affinity::Integer, Value::Integer(value)) => Value::Integer(value),
+        (Affinity::Integer, Value::Real(value)) => Value::Integer(value as i64),
+        (Affinity::Integer, value) => Value::Integer(integer_prefix(&value.to_string())),
+
+        (Affinity::Real, value) => Value::Real(to_real(&value)),
+
+        // a real stays one, but text that reads as a whole number becomes an integer
+        (Affinity::Numeric, Value::Real(value)) => Value::Real(value),
+        (Affinity::Numeric, value) => match to_number(&value) {
+            Value::Real(value) => Value::Real(value).apply_affinity(Affinity::Numeric),
+            value => value,
+        },
+    }
+}
+
+// The integer at the start of some text, where more digits than fit clamp to the limit
+fn integer_prefix(text: &str) -> i64 {
+    let text = text.trim_start();
+
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let mut value: i64 = 0;
+
+    for digit in digits.chars().map_while(|c| c.to_digit(10)) {
+        value = value.saturating_mul(10);
+
+        value = match negative {
+            true => value.saturating_sub(i64::from(digit)),
+            false => value.saturating_add(i64::from(digit)),
+        };
+    }
+
+    value
+}
+
+// The truth of a value: numbers are true unless zero, text and blobs by the number they
+// start with, and NULL is unknown
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Null => None,
+        Value::Integer(value) => Some(*value != 0),
+        Value::Real(value) => Some(*value != 0.0),
+        Value::Text(_) | Value::Blob(_) => truth(&to_number(value)),
+    }
+}
+
+// Three-valued AND: false if either side is, unknown if either side is and neither is false
+fn and(left: Option<bool>, right: Option<bool>) -> Option<bool> {
+    match (left, right) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn from_truth(truth: Option<bool>) -> Value {
+    match truth {
+        None => Value::Null,
+        Some(value) => Value::Integer(value as i64),
+    }
+}
+
+// The number at the start of some text, the way sqlite reads '12abc' as 12 and 'abc' as 0
+fn numeric_prefix(text: &str) -> &str {
+    let text = text.trim_start();
+
+    let mut end = 0;
+    let mut seen_digit = false;
+    let mut seen_dot = false;
+    let mut seen_exponent = false;
+
+    for (i, c) in text.char_indices() {
+        let accepted = match c {
+            '+' | '-' => i == 0 || text[..i].ends_with(['e', 'E']),
+            '0'..='9' => {
+                seen_digit = true;
+                true
+            }
+            '.' => !seen_dot && !seen_exponent && {
+                seen_dot = true;
+                true
+            },
+            'e' | 'E' => seen_digit && !seen_exponent && {
+                seen_exponent = true;
+                true
+            },
+            _ => false,
+        };
+
+        if !accepted {
+            break;
+        }
+
+        // a trailing dot still makes the number a real, as in '1.'
+        if c.is_ascii_digit() || (c == '.' && seen_digit) {
+            end = i + 1;
+        }
+    }
+
+    &text[..end]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::core::database::Database;
+    use crate::core::value::Value;
+    use crate::parser::parse::parse_sql;
+
+    // t(id INTEGER PRIMARY KEY, a TEXT) with the rows (1, 'one'), (500, 'five hundred') and
+    // (2000, 'two thousand')
+    const INDEXED: &[u8] = include_bytes!("../../tests/indexed.db");
+
+    // Evaluates each expression on row 1 of t, next to the columns c with TEXT affinity, i
+    // with INTEGER affinity and s and n with none, and checks that quote() of it is what
+    // sqlite3 returns
+    fn check(cases: &[(&str, &str)]) {
+        let db = Database::deserialize(INDEXED.to_vec()).unwrap();
+
+        let columns = "id, a, CAST(1 AS TEXT) AS c, CAST('1' AS INTEGER) AS i, '1' AS s, 1 AS n";
+
+        for (expr, expected) in cases {
+            let sql = format!("SELECT quote({expr}) FROM (SELECT {columns} FROM t WHERE id = 1)");
+
+            let rows = db.query(&parse_sql(&sql).unwrap()).unwrap().rows;
+
+            assert_eq!(rows, [[Value::Text(expected.to_string())]], "{expr}");
+        }
+    }
+
+    #[test]
+    fn null_makes_conditions_unknown() {
+        check(&[
+            // IN is NULL when the value is not found but the list holds a NULL, and an empty list
+            // holds nothing, not even NULL
+            ("1 IN (1, NULL)", "1"),
+            ("2 IN (1, NULL)", "NULL"),
+            ("2 NOT IN (1, NULL)", "NULL"),
+            ("NULL IN ()", "0"),
+            ("NULL NOT IN ()", "1"),
+            ("NULL IN (1)", "NULL"),
+            ("NULL IN (NULL)", "NULL"),
+            ("1 IN (SELECT NULL UNION SELECT 1)", "1"),
+            ("2 IN (SELECT NULL UNION SELECT 1)", "NULL"),
+            ("2 NOT IN (SELECT NULL UNION SELECT 1)", "NULL"),
+            ("NULL NOT IN (SELECT id FROM t WHERE id > 5000)", "1"),
+            ("500 IN (SELECT id FROM t)", "1"),
+
+            // BETWEEN is the AND of two comparisons, so one false bound makes it false even past NULL
+            ("NULL BETWEEN 1 AND 2", "NULL"),
+            ("1 BETWEEN NULL AND 0", "0"),
+            ("1 BETWEEN NULL AND 2", "NULL"),
+            ("1 NOT BETWEEN NULL AND 0", "1"),
+            ("1 NOT BETWEEN NULL AND 2", "NULL"),
+            ("3 NOT BETWEEN 1 AND 2", "1"),
+            ("'b' BETWEEN 'a' AND 'c'", "1"),
+
+            // IS, IS NOT and their DISTINCT FROM forms treat NULL as a value and are never NULL
+            ("NULL IS NULL", "1"),
+            ("NULL IS NOT NULL", "0"),
+            ("1 IS NULL", "0"),
+            ("NULL IS 1", "0"),
+            ("1 IS 1.0", "1"),
+            ("'a' IS NOT 'a'", "0"),
+            ("NULL IS NOT 1", "1"),
+            ("1 IS DISTINCT FROM NULL", "1"),
+            ("NULL IS NOT DISTINCT FROM NULL", "1"),
+            ("1 IS NOT DISTINCT FROM 1", "1"),
+            ("a ISNULL", "0"),
+            ("a NOTNULL", "1"),
+
+            // NOT, AND and OR are NULL only when the known operands do not decide them
+            ("NOT NULL", "NULL"),
+            ("NOT 0", "1"),
+            ("NOT 'x'", "1"),
+            ("NOT 0.5", "0"),
+            ("NULL AND 0", "0"),
+            ("NULL AND 1", "NULL"),
+            ("NULL OR 1", "1"),
+            ("NULL OR 0", "NULL"),
+            ("NOT (2 IN (1, NULL))", "NULL"),
+        ]);
+    }
+
+    // A column with numeric affinity turns text it is compared with into a number, one with
+    // TEXT affinity turns numbers into text, and values with no affinity are compared as
+    // they are. IN and CASE take the affinity of the value on the left.
+    #[test]
+    fn comparisons_apply_affinity() {
+        check(&[
+            ("id = '1'", "1"),
+            ("id < '10'", "1"),
+            ("id = ' 1'", "1"),
+            ("'1' = 1", "0"),
+            ("s = 1", "0"),
+            ("n = '1'", "0"),
+            ("c = 1", "1"),
+            ("c = '1'", "1"),
+            ("c = 1.0", "0"),
+            ("c < 5", "1"),
+            ("i = '1'", "1"),
+            ("i = '1.0'", "1"),
+            ("i = 1.0", "1"),
+            ("c IN (1, 2)", "1"),
+            ("1 IN (c)", "0"),
+            ("s IN (1, 2)", "0"),
+            ("i IN ('1')", "1"),
+            ("n IN (SELECT '1')", "0"),
+            ("c IN (SELECT 1)", "1"),
+            ("i BETWEEN '0' AND '2'", "1"),
+            ("c BETWEEN 0 AND 2", "1"),
+            ("CASE c WHEN 1 THEN 'yes' ELSE 'no' END", "'yes'"),
+            ("CASE s WHEN 1 THEN 'yes' ELSE 'no' END", "'no'"),
+            ("CASE 1 WHEN c THEN 'yes' ELSE 'no' END", "'yes'"),
+            ("id = c", "1"),
+            ("a = c", "0"),
+            ("(SELECT CAST(1 AS TEXT)) = 1", "1"),
+            ("(SELECT '1') = 1", "0"),
+            ("1 = (SELECT i)", "1"),
+        ]);
+    }
+
+    // CAST converts whatever it can, reading the longest number text starts with
+    #[test]
+    fn casts_convert_like_sqlite() {
+        check(&[
+            ("CAST('12abc' AS INTEGER)", "12"),
+            ("CAST('1e3' AS INTEGER)", "1"),
+            ("CAST(' 7 ' AS REAL)", "7.0"),
+            ("CAST(x'3132' AS INTEGER)", "12"),
+            ("CAST(1.9 AS INTEGER)", "1"),
+            ("CAST(-1.5 AS TEXT)", "'-1.5'"),
+            ("CAST('abc' AS NUMERIC)", "0"),
+            ("CAST('3.0' AS NUMERIC)", "3"),
+            ("CAST('0x10' AS INTEGER)", "0"),
+            ("CAST(NULL AS TEXT)", "NULL"),
+            ("CAST(1e20 AS INTEGER)", "9223372036854775807"),
+            ("CAST('9223372036854775808' AS NUMERIC)", "9.22337203685477581e+18"),
+            ("CAST(12 AS BLOB)", "X'3132'"),
+            ("CAST(c AS INTEGER) = '1'", "1"),
+        ]);
+    }
+}
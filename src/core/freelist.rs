@@ -0,0 +1,191 @@
+use crate::core::header::DBHeader;
+use crate::core::vfs::Vfs;
+use crate::error::{Error, Result};
+use std::collections::HashSet;
+
+// A freelist trunk page: the number of the next trunk page, how many leaf pages this one
+// lists, then the leaf page numbers. Leaf pages hold nothing at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreelistTrunk {
+    pub page_number: u32,
+    pub next: u32,
+    pub leaves: Vec<u32>,
+}
+
+impl FreelistTrunk {
+    // Most leaves one trunk page can list
+    pub fn capacity(usable_size: u32) -> u32 {
+        usable_size / 4 - 2
+    }
+
+    pub fn parse(page_number: u32, buffer: &[u8], usable_size: u32) -> Result<Self> {
+        let read_u32 = |offset: usize| u32::from_be_bytes([buffer[offset], buffer[offset + 1], buffer[offset + 2], buffer[offset + 3]]);
+
+        let next = read_u32(0);
+
+        let leaf_count = read_u32(4);
+
+        if leaf_count > Self::capacity(usable_size) {
+            return Err(Error::corrupt(page_number, format!("freelist leaf count {leaf_count} is too big")));
+        }
+
+        let leaves = (0..leaf_count as usize).map(|i| read_u32(8 + i * 4)).collect();
+
+        Ok(Self {
+            page_number,
+            next,
+            leaves,
+        })
+    }
+
+    pub fn write_to(&self, buffer: &mut [u8]) {
+        buffer[0..4].copy_from_slice(&self.next.to_be_bytes());
+        buffer[4..8].copy_from_slice(&(self.leaves.len() as u32).to_be_bytes());
+
+        for (i, leaf) in self.leaves.iter().enumerate() {
+            buffer[8 + i * 4..12 + i * 4].copy_from_slice(&leaf.to_be_bytes());
+        }
+    }
+}
+
+// Pages that were freed and can be reused, as a chain of trunk pages starting at the one
+// the header points to
+#[derive(Debug, Clone, Default)]
+pub struct Freelist {
+    pub trunks: Vec<FreelistTrunk>,
+}
+
+impl Freelist {
+    pub fn read(file: &dyn Vfs, header: &DBHeader) -> Result<Self> {
+        let page_count = header.page_count(file.size()?);
+
+        Self::read_with(header, page_count, |page_number| {
+            let mut buffer = vec![0u8; header.page_size as usize];
+
+            file.read_at(&mut buffer, u64::from(page_number - 1) * u64::from(header.page_size))
+                .map_err(|error| error.past_end().on_page(page_number))?;
+
+            Ok(buffer)
+        })
+    }
+
+    // Walks the trunk chain, reading pages with `read_page`. Every page on it must exist and
+    // appear only once, and the chain must hold as many pages as the header says.
+    pub fn read_with(header: &DBHeader, page_count: u32, mut read_page: impl FnMut(u32) -> Result<Vec<u8>>) -> Result<Self> {
+        let usable_size = header.get_usable_size();
+
+        let mut seen = HashSet::new();
+
+        let mut check = |page_number: u32| {
+            if page_number == 0 || page_number > page_count {
+                return Err(Error::corrupt(page_number, "freelist page is out of range"));
+            }
+
+            if !seen.insert(page_number) {
+                return Err(Error::corrupt(page_number, "page is on the freelist twice"));
+            }
+
+            Ok(())
+        };
+
+        let mut trunks = vec![];
+
+        let mut trunk_page = header.first_free_page;
+
+        while trunk_page != 0 {
+            check(trunk_page)?;
+
+            let trunk = FreelistTrunk::parse(trunk_page, &read_page(trunk_page)?, usable_size)?;
+
+            for &leaf in &trunk.leaves {
+                check(leaf)?;
+            }
+
+            trunk_page = trunk.next;
+
+            trunks.push(trunk);
+        }
+
+        let freelist = Self { trunks };
+
+        if freelist.page_count() != header.free_page_list_size {
+            return Err(Error::corrupt(
+                0,
+                format!(
+                    "freelist has {} pages but the header says {}",
+                    freelist.page_count(),
+                    header.free_page_list_size
+                ),
+            ));
+        }
+
+        Ok(freelist)
+    }
+
+    // Trunk and leaf pages together
+    pub fn page_count(&self) -> u32 {
+        self.trunks.iter().map(|trunk| 1 + trunk.leaves.len() as u32).sum()
+    }
+
+    pub fn pages(&self) -> impl Iterator<Item = u32> + '_ {
+        self.trunks
+            .iter()
+            .flat_map(|trunk| std::iter::once(trunk.page_number).chain(trunk.leaves.iter().copied()))
+    }
+
+    pub fn is_trunk(&self, page_number: u32) -> bool {
+        self.trunks.iter().any(|trunk| trunk.page_number == page_number)
+    }
+
+    pub fn contains(&self, page_number: u32) -> bool {
+        self.pages().any(|page| page == page_number)
+    }
+
+    pub fn first_page(&self) -> u32 {
+        self.trunks.first().map_or(0, |trunk| trunk.page_number)
+    }
+
+    // Some free page numbered `max` or lower, leaves first since taking one leaves the
+    // trunk chain alone
+    pub fn page_at_most(&self, max: u32) -> Option<u32> {
+        let leaf = self.trunks.iter().flat_map(|trunk| trunk.leaves.iter().copied()).find(|page| *page <= max);
+
+        leaf.or_else(|| self.trunks.iter().map(|trunk| trunk.page_number).find(|page| *page <= max))
+    }
+
+    // Takes a page off the list. A trunk page that still lists leaves hands its place in the
+    // chain to its first leaf.
+    pub fn remove(&mut self, page_number: u32) -> bool {
+        let mut removed = false;
+
+        for i in 0..self.trunks.len() {
+            let trunk = &mut self.trunks[i];
+
+            if let Some(position) = trunk.leaves.iter().position(|leaf| *leaf == page_number) {
+                trunk.leaves.remove(position);
+
+                removed = true;
+            } else if trunk.page_number == page_number {
+                if trunk.leaves.is_empty() {
+                    self.trunks.remove(i);
+                } else {
+                    trunk.page_number = trunk.leaves.remove(0);
+                }
+
+                removed = true;
+            }
+
+            if removed {
+                break;
+            }
+        }
+
+        let next_pages = self.trunks.iter().skip(1).map(|trunk| trunk.page_number).chain([0]).collect::<Vec<_>>();
+
+        for (trunk, next) in self.trunks.iter_mut().zip(next_pages) {
+            trunk.next = next;
+        }
+
+        removed
+    }
+}
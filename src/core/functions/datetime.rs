@@ -0,0 +1,1139 @@
+use super::printf::printf;
+use super::text;
+use crate::core::value::{parse_numeric, Value};
+use crate::error::{Error, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Milliseconds from the julian day epoch to the unix epoch
+const UNIX_EPOCH_MS: i64 = 210_866_760_000_000;
+
+const DAY_MS: i64 = 86_400_000;
+
+// The last millisecond of 9999-12-31, the latest time the functions handle
+const MAX_JULIAN_MS: i64 = 464_269_060_799_999;
+
+// A point in time the way sqlite's date.c keeps it: a julian day number in milliseconds and
+// the calendar date and time of day, each worked out from the other when it is needed
+#[derive(Clone, Default)]
+struct DateTime {
+    julian_ms: i64,
+    year: i32,
+    month: i32,
+    day: i32,
+    hour: i32,
+    minute: i32,
+    second: f64,
+
+    // timezone offset in minutes
+    tz: i32,
+
+    // days past the end of the month after month arithmetic, which `floor` takes back
+    overflow: i32,
+
+    valid_julian: bool,
+    valid_date: bool,
+    valid_time: bool,
+
+    // a number that is a julian day unless a modifier says it is a unix time
+    raw: bool,
+
+    error: bool,
+    subsec: bool,
+    utc: bool,
+    local: bool,
+}
+
+impl DateTime {
+    fn now() -> Self {
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as i64);
+
+        Self {
+            julian_ms: elapsed + UNIX_EPOCH_MS,
+            valid_julian: true,
+            utc: true,
+            ..Self::default()
+        }
+    }
+
+    // A number given as the time value, a julian day when it is in range
+    fn from_number(number: f64) -> Self {
+        let mut date = Self {
+            second: number,
+            raw: true,
+            ..Self::default()
+        };
+
+        if (0.0..5373484.5).contains(&number) {
+            date.julian_ms = (number * DAY_MS as f64 + 0.5) as i64;
+            date.valid_julian = true;
+        }
+
+        date
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut date = Self::default();
+
+        if date.parse_date(text) || date.parse_time(text) {
+            return Some(date);
+        }
+
+        if text.eq_ignore_ascii_case("now") {
+            return Some(Self::now());
+        }
+
+        if let Some(number) = parse_numeric(text) {
+            return Some(Self::from_number(to_f64(&number)));
+        }
+
+        if text.eq_ignore_ascii_case("subsec") || text.eq_ignore_ascii_case("subsecond") {
+            return Some(Self { subsec: true, ..Self::now() });
+        }
+
+        None
+    }
+
+    // YYYY-MM-DD, optionally negative and optionally followed by a time
+    fn parse_date(&mut self, text: &str) -> bool {
+        let (negative, rest) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+
+        let Some(fields) = digits(rest, &[(4, 0, 14712, b'-'), (2, 1, 12, b'-'), (2, 1, 31, 0)]) else {
+            return false;
+        };
+
+        let rest = rest[10..].trim_start_matches(|c: char| c.is_ascii_whitespace() || c == 'T');
+
+        if !self.parse_time(rest) {
+            if !rest.is_empty() {
+                return false;
+            }
+
+            self.valid_time = false;
+        }
+
+        self.valid_julian = false;
+        self.valid_date = true;
+        self.year = if negative { -fields[0] } else { fields[0] };
+        self.month = fields[1];
+        self.day = fields[2];
+
+        self.compute_floor();
+
+        if self.tz != 0 {
+            self.compute_julian();
+        }
+
+        true
+    }
+
+    // HH:MM[:SS[.FFF]] with an optional timezone after it
+    fn parse_time(&mut self, text: &str) -> bool {
+        let Some(fields) = digits(text, &[(2, 0, 24, b':'), (2, 0, 59, 0)]) else {
+            return false;
+        };
+
+        let mut rest = &text[5..];
+
+        let mut second = 0.0;
+
+        if let Some(after) = rest.strip_prefix(':') {
+            let Some(whole) = digits(after, &[(2, 0, 59, 0)]) else {
+                return false;
+            };
+
+            second = whole[0] as f64;
+            rest = &after[2..];
+
+            if rest.starts_with('.') && rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                let fraction: String = rest[1..].chars().take_while(char::is_ascii_digit).collect();
+
+                rest = &rest[1 + fraction.len()..];
+
+                let (mut value, mut scale) = (0.0, 1.0);
+
+                for c in fraction.bytes() {
+                    value = value * 10.0 + f64::from(c - b'0');
+                    scale *= 10.0;
+                }
+
+                // truncated rather than rounded up into the next second
+                second += (value / scale).min(0.999);
+            }
+        }
+
+        self.valid_julian = false;
+        self.raw = false;
+        self.valid_time = true;
+        self.hour = fields[0];
+        self.minute = fields[1];
+        self.second = second;
+
+        self.parse_timezone(rest)
+    }
+
+    // Nothing, Z, or +HH:MM or -HH:MM, with spaces around
+    fn parse_timezone(&mut self, text: &str) -> bool {
+        let text = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+        self.tz = 0;
+
+        let rest = match text.as_bytes().first() {
+            None => return true,
+
+            Some(b'Z' | b'z') => {
+                self.local = false;
+                self.utc = true;
+
+                &text[1..]
+            }
+
+            Some(&sign @ (b'+' | b'-')) => {
+                let Some(fields) = digits(&text[1..], &[(2, 0, 14, b':'), (2, 0, 59, 0)]) else {
+                    return false;
+                };
+
+                let offset = fields[0] * 60 + fields[1];
+
+                self.tz = if sign == b'-' { -offset } else { offset };
+
+                &text[6..]
+            }
+
+            Some(_) => return false,
+        };
+
+        rest.trim_start_matches(|c: char| c.is_ascii_whitespace()).is_empty()
+    }
+
+    fn set_error(&mut self) {
+        *self = Self {
+            error: true,
+            ..Self::default()
+        };
+    }
+
+    fn clear_fields(&mut self) {
+        self.valid_date = false;
+        self.valid_time = false;
+        self.tz = 0;
+    }
+
+    fn compute_julian(&mut self) {
+        if self.valid_julian {
+            return;
+        }
+
+        let (mut year, mut month, day) = match self.valid_date {
+            true => (self.year, self.month, self.day),
+            false => (2000, 1, 1),
+        };
+
+        if !(-4713..=9999).contains(&year) || self.raw {
+            self.set_error();
+            return;
+        }
+
+        if month <= 2 {
+            year -= 1;
+            month += 12;
+        }
+
+        let a = year / 100;
+        let b = 2 - a + a / 4;
+        let x1 = 36525 * (year + 4716) / 100;
+        let x2 = 306001 * (month + 1) / 10000;
+
+        self.julian_ms = ((f64::from(x1 + x2 + day + b) - 1524.5) * DAY_MS as f64) as i64;
+        self.valid_julian = true;
+
+        if self.valid_time {
+            self.julian_ms += i64::from(self.hour) * 3_600_000
+                + i64::from(self.minute) * 60_000
+                + (self.second * 1000.0 + 0.5) as i64;
+
+            if self.tz != 0 {
+                self.julian_ms -= i64::from(self.tz) * 60_000;
+                self.valid_date = false;
+                self.valid_time = false;
+                self.tz = 0;
+                self.utc = true;
+                self.local = false;
+            }
+        }
+    }
+
+    fn compute_date(&mut self) {
+        if self.valid_date {
+            return;
+        }
+
+        if !self.valid_julian {
+            (self.year, self.month, self.day) = (2000, 1, 1);
+        } else if !valid_julian(self.julian_ms) {
+            self.set_error();
+            return;
+        } else {
+            let z = ((self.julian_ms + 43_200_000) / DAY_MS) as i32;
+            let a = ((f64::from(z) - 1867216.25) / 36524.25) as i32;
+            let a = z + 1 + a - a / 4;
+            let b = a + 1524;
+            let c = ((f64::from(b) - 122.1) / 365.25) as i32;
+            let d = (36525 * (c & 32767)) / 100;
+            let e = (f64::from(b - d) / 30.6001) as i32;
+            let x1 = (30.6001 * f64::from(e)) as i32;
+
+            self.day = b - d - x1;
+            self.month = if e < 14 { e - 1 } else { e - 13 };
+            self.year = if self.month > 2 { c - 4716 } else { c - 4715 };
+        }
+
+        self.valid_date = true;
+    }
+
+    fn compute_time(&mut self) {
+        if self.valid_time {
+            return;
+        }
+
+        self.compute_julian();
+
+        let day_ms = (self.julian_ms + 43_200_000) % DAY_MS;
+
+        self.second = (day_ms % 60_000) as f64 / 1000.0;
+        self.minute = ((day_ms / 60_000) % 60) as i32;
+        self.hour = (day_ms / 3_600_000) as i32;
+        self.raw = false;
+        self.valid_time = true;
+    }
+
+    fn compute_all(&mut self) {
+        self.compute_date();
+        self.compute_time();
+    }
+
+    // How many days the day of the month is past the end of the month
+    fn compute_floor(&mut self) {
+        self.overflow = if self.day <= 28 || (1 << self.month) & 0x15aa != 0 {
+            0
+        } else if self.month != 2 {
+            i32::from(self.day == 31)
+        } else if self.year % 4 != 0 || (self.year % 100 == 0 && self.year % 400 != 0) {
+            self.day - 28
+        } else {
+            self.day - 29
+        };
+    }
+
+    // Wraps the month into 1..=12, carrying into the year
+    fn normalize_month(&mut self) {
+        let years = if self.month > 0 { (self.month - 1) / 12 } else { (self.month - 12) / 12 };
+
+        self.year += years;
+        self.month -= years * 12;
+    }
+
+    fn days_after_jan01(&self) -> i32 {
+        let mut jan01 = Self {
+            valid_julian: false,
+            month: 1,
+            day: 1,
+            ..self.clone()
+        };
+
+        jan01.compute_julian();
+
+        ((self.julian_ms - jan01.julian_ms + 43_200_000) / DAY_MS) as i32
+    }
+
+    fn days_after_monday(&self) -> i32 {
+        (((self.julian_ms + 43_200_000) / DAY_MS) % 7) as i32
+    }
+
+    fn days_after_sunday(&self) -> i32 {
+        (((self.julian_ms + 129_600_000) / DAY_MS) % 7) as i32
+    }
+
+    // The Thursday of the same week, which decides the ISO 8601 year and week
+    fn iso_thursday(&self) -> Self {
+        let mut thursday = self.clone();
+
+        thursday.julian_ms += i64::from(3 - self.days_after_monday()) * DAY_MS;
+        thursday.valid_date = false;
+        thursday.compute_date();
+
+        thursday
+    }
+
+    // Converts from UTC to the local time of the process
+    fn convert_to_localtime(&mut self) -> Result<()> {
+        self.compute_julian();
+
+        // localtime_r() is only trusted from 1970 to 2037, so other years are moved into that
+        // range by a multiple of four years and moved back afterwards
+        let (seconds, year_shift) = if (UNIX_EPOCH_MS..=213_014_145_600_000).contains(&self.julian_ms) {
+            (self.julian_ms / 1000 - UNIX_EPOCH_MS / 1000, 0)
+        } else {
+            let mut shifted = self.clone();
+
+            shifted.compute_all();
+
+            let year_shift = 2000 + shifted.year % 4 - shifted.year;
+
+            shifted.year += year_shift;
+            shifted.valid_julian = false;
+            shifted.compute_julian();
+
+            (shifted.julian_ms / 1000 - UNIX_EPOCH_MS / 1000, year_shift)
+        };
+
+        let time = seconds as libc::time_t;
+
+        let mut local: libc::tm = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::localtime_r(&time, &mut local) }.is_null() {
+            return Err(Error::Misuse("local time unavailable".to_string()));
+        }
+
+        self.year = local.tm_year + 1900 - year_shift;
+        self.month = local.tm_mon + 1;
+        self.day = local.tm_mday;
+        self.hour = local.tm_hour;
+        self.minute = local.tm_min;
+        self.second = f64::from(local.tm_sec) + (self.julian_ms % 1000) as f64 * 0.001;
+        self.valid_date = true;
+        self.valid_time = true;
+        self.valid_julian = false;
+        self.raw = false;
+        self.tz = 0;
+        self.error = false;
+
+        Ok(())
+    }
+
+    // Converts from local time to UTC by guessing the UTC time and correcting the guess by
+    // how far its local time is off
+    fn convert_to_utc(&mut self) -> Result<()> {
+        self.compute_julian();
+
+        let original = self.julian_ms;
+
+        let mut guess = original;
+        let mut error = 0;
+
+        for _ in 0..4 {
+            guess -= error;
+
+            let mut local = Self {
+                julian_ms: guess,
+                valid_julian: true,
+                ..Self::default()
+            };
+
+            local.convert_to_localtime()?;
+            local.compute_julian();
+
+            error = local.julian_ms - original;
+
+            if error == 0 {
+                break;
+            }
+        }
+
+        *self = Self {
+            julian_ms: guess,
+            valid_julian: true,
+            utc: true,
+            ..Self::default()
+        };
+
+        Ok(())
+    }
+
+    // Applies one modifier, returning false when it is not one
+    fn modify(&mut self, modifier: &str, index: usize) -> Result<bool> {
+        let lower = modifier.to_ascii_lowercase();
+
+        match lower.as_str() {
+            // these say how to read a number given as the time value, so they must come first
+            "auto" | "julianday" | "unixepoch" if index > 1 => Ok(false),
+
+            "auto" => {
+                if !self.raw || self.valid_julian {
+                    self.raw = false;
+
+                    return Ok(true);
+                }
+
+                if (-210_866_760_000.0..=253_402_300_799.0).contains(&self.second) {
+                    self.read_unix_seconds();
+
+                    return Ok(true);
+                }
+
+                Ok(false)
+            }
+
+            "julianday" => {
+                if self.valid_julian && self.raw {
+                    self.raw = false;
+
+                    return Ok(true);
+                }
+
+                Ok(false)
+            }
+
+            "unixepoch" => {
+                let ms = self.second * 1000.0 + UNIX_EPOCH_MS as f64;
+
+                if self.raw && (0.0..(MAX_JULIAN_MS + 1) as f64).contains(&ms) {
+                    self.read_unix_seconds();
+
+                    return Ok(true);
+                }
+
+                Ok(false)
+            }
+
+            "ceiling" => {
+                self.compute_julian();
+                self.clear_fields();
+                self.overflow = 0;
+
+                Ok(true)
+            }
+
+            "floor" => {
+                self.compute_julian();
+                self.julian_ms -= i64::from(self.overflow) * DAY_MS;
+                self.clear_fields();
+
+                Ok(true)
+            }
+
+            "localtime" => {
+                if !self.local {
+                    self.convert_to_localtime()?;
+                }
+
+                self.utc = false;
+                self.local = true;
+
+                Ok(true)
+            }
+
+            "utc" => {
+                if !self.utc {
+                    self.convert_to_utc()?;
+                }
+
+                Ok(true)
+            }
+
+            "subsec" | "subsecond" => {
+                self.subsec = true;
+
+                Ok(true)
+            }
+
+            _ if lower.starts_with("weekday ") => Ok(self.weekday(&lower[8..])),
+
+            _ if lower.starts_with("start of ") => Ok(self.start_of(&lower[9..])),
+
+            _ if lower.starts_with(|c: char| c == '+' || c == '-' || c.is_ascii_digit()) => Ok(self.shift(&lower)),
+
+            _ => Ok(false),
+        }
+    }
+
+    fn read_unix_seconds(&mut self) {
+        let ms = self.second * 1000.0 + UNIX_EPOCH_MS as f64;
+
+        self.clear_fields();
+        self.julian_ms = (ms + 0.5) as i64;
+        self.valid_julian = true;
+        self.raw = false;
+    }
+
+    // weekday N: forward to the next day that is weekday N, Sunday being 0, unless it already is
+    fn weekday(&mut self, number: &str) -> bool {
+        let Some(weekday) = parse_numeric(number).map(|number| to_f64(&number)) else {
+            return false;
+        };
+
+        if !(0.0..7.0).contains(&weekday) || weekday.fract() != 0.0 {
+            return false;
+        }
+
+        self.compute_all();
+        self.tz = 0;
+        self.valid_julian = false;
+        self.compute_julian();
+
+        let mut current = (self.julian_ms + 129_600_000) / DAY_MS % 7;
+
+        if current > weekday as i64 {
+            current -= 7;
+        }
+
+        self.julian_ms += (weekday as i64 - current) * DAY_MS;
+        self.clear_fields();
+
+        true
+    }
+
+    // start of day, month or year: back to midnight on the first day of it
+    fn start_of(&mut self, unit: &str) -> bool {
+        if !self.valid_julian && !self.valid_date && !self.valid_time {
+            return false;
+        }
+
+        self.compute_date();
+        self.valid_time = true;
+        self.hour = 0;
+        self.minute = 0;
+        self.second = 0.0;
+        self.raw = false;
+        self.tz = 0;
+        self.valid_julian = false;
+
+        match unit {
+            "day" => true,
+
+            "month" => {
+                self.day = 1;
+
+                true
+            }
+
+            "year" => {
+                self.month = 1;
+                self.day = 1;
+
+                true
+            }
+
+            _ => false,
+        }
+    }
+
+    // ±N units, ±HH:MM[:SS.FFF] and ±YYYY-MM-DD[ HH:MM[:SS.FFF]]
+    fn shift(&mut self, modifier: &str) -> bool {
+        let bytes = modifier.as_bytes();
+
+        let first = bytes[0];
+
+        // the number runs up to a colon, a space or the dash after a year
+        let mut n = 1;
+
+        while n < bytes.len() {
+            match bytes[n] {
+                b':' => break,
+                c if c.is_ascii_whitespace() => break,
+                b'-' if (n == 5 || n == 6) && digits(&modifier[1..], &[(n as u8 - 1, 0, 14712, 0)]).is_some() => break,
+                _ => {}
+            }
+
+            n += 1;
+        }
+
+        let Some(number) = parse_numeric(&modifier[..n]).map(|number| to_f64(&number)) else {
+            return false;
+        };
+
+        let rest = &modifier[n..];
+
+        // the time to add, after the number or after a date
+        let mut clock = rest.starts_with(':').then(|| match first.is_ascii_digit() {
+            true => modifier,
+            false => &modifier[1..],
+        });
+
+        if rest.starts_with('-') {
+            if first != b'+' && first != b'-' {
+                return false;
+            }
+
+            let Some(fields) = digits(&modifier[1..], &[(n as u8 - 1, 0, 14712, b'-'), (2, 0, 12, b'-'), (2, 0, 31, 0)])
+            else {
+                return false;
+            };
+
+            let (years, months, mut days) = (fields[0], fields[1], fields[2]);
+
+            if months >= 12 || days >= 31 {
+                return false;
+            }
+
+            self.compute_all();
+            self.valid_julian = false;
+
+            if first == b'-' {
+                self.year -= years;
+                self.month -= months;
+                days = -days;
+            } else {
+                self.year += years;
+                self.month += months;
+            }
+
+            self.normalize_month();
+            self.compute_floor();
+            self.compute_julian();
+            self.valid_date = false;
+            self.valid_time = false;
+            self.julian_ms += i64::from(days) * DAY_MS;
+
+            let after = &modifier[n + 6..];
+
+            if after.is_empty() {
+                return true;
+            }
+
+            match after.strip_prefix(|c: char| c.is_ascii_whitespace()) {
+                Some(time) if digits(time, &[(2, 0, 24, b':'), (2, 0, 59, 0)]).is_some() => clock = Some(time),
+                _ => return false,
+            }
+        }
+
+        if let Some(clock) = clock {
+            let mut offset = Self::default();
+
+            if !offset.parse_time(clock) {
+                return false;
+            }
+
+            offset.compute_julian();
+            offset.julian_ms -= 43_200_000;
+            offset.julian_ms -= offset.julian_ms / DAY_MS * DAY_MS;
+
+            if first == b'-' {
+                offset.julian_ms = -offset.julian_ms;
+            }
+
+            self.compute_julian();
+            self.clear_fields();
+            self.julian_ms += offset.julian_ms;
+
+            return true;
+        }
+
+        // what is left is a unit, singular or plural
+        let unit = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+        if !(3..=10).contains(&unit.len()) {
+            return false;
+        }
+
+        let unit = unit.strip_suffix('s').unwrap_or(unit);
+
+        self.compute_julian();
+
+        self.overflow = 0;
+
+        let rounder = if number < 0.0 { -0.5 } else { 0.5 };
+
+        let Some(&(_, limit, seconds)) = UNITS.iter().find(|(name, _, _)| *name == unit) else {
+            self.clear_fields();
+            return false;
+        };
+
+        if number <= -limit || number >= limit {
+            self.clear_fields();
+            return false;
+        }
+
+        let mut number = number;
+
+        if unit == "month" || unit == "year" {
+            self.compute_all();
+
+            if unit == "month" {
+                self.month += number as i32;
+                self.normalize_month();
+            } else {
+                self.year += number as i32;
+            }
+
+            self.compute_floor();
+            self.valid_julian = false;
+
+            number -= number.trunc();
+        }
+
+        self.compute_julian();
+        self.julian_ms += (number * 1000.0 * seconds + rounder) as i64;
+        self.clear_fields();
+
+        true
+    }
+}
+
+// The units a modifier can add, with the limit on how many and their length in seconds
+const UNITS: &[(&str, f64, f64)] = &[
+    ("second", 4.6427e14, 1.0),
+    ("minute", 7.7379e12, 60.0),
+    ("hour", 1.2896e11, 3600.0),
+    ("day", 5373485.0, 86400.0),
+    ("month", 176546.0, 2592000.0),
+    ("year", 14713.0, 31536000.0),
+];
+
+fn valid_julian(julian_ms: i64) -> bool {
+    (0..=MAX_JULIAN_MS).contains(&julian_ms)
+}
+
+fn to_f64(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value) => *value as f64,
+        Value::Real(value) => *value,
+        _ => 0.0,
+    }
+}
+
+// Reads fixed-width numbers, each given as (digits, min, max, separator after it), where a
+// separator of 0 means nothing is checked after the last number
+fn digits(text: &str, fields: &[(u8, i32, i32, u8)]) -> Option<Vec<i32>> {
+    let bytes = text.as_bytes();
+
+    let mut values = Vec::with_capacity(fields.len());
+
+    let mut index = 0;
+
+    for &(width, min, max, separator) in fields {
+        let mut value = 0;
+
+        for _ in 0..width {
+            let c = *bytes.get(index).filter(|c| c.is_ascii_digit())?;
+
+            value = value * 10 + i32::from(c - b'0');
+            index += 1;
+        }
+
+        if value < min || value > max || (separator != 0 && bytes.get(index) != Some(&separator)) {
+            return None;
+        }
+
+        values.push(value);
+        index += 1;
+    }
+
+    Some(values)
+}
+
+// The time value and modifiers of a call, or None when the result is NULL
+fn date_time(args: &[Value]) -> Result<Option<DateTime>> {
+    let mut date = match args.first() {
+        None => DateTime::now(),
+        Some(Value::Integer(value)) => DateTime::from_number(*value as f64),
+        Some(Value::Real(value)) => DateTime::from_number(*value),
+
+        Some(value) => match text(value).as_deref().and_then(DateTime::parse) {
+            Some(date) => date,
+            None => return Ok(None),
+        },
+    };
+
+    for (index, modifier) in args.iter().enumerate().skip(1) {
+        match text(modifier) {
+            Some(modifier) if date.modify(&modifier, index)? => {}
+            _ => return Ok(None),
+        }
+    }
+
+    date.compute_julian();
+
+    if date.error || !valid_julian(date.julian_ms) {
+        return Ok(None);
+    }
+
+    // a day past the end of the month, like 2023-02-31, rolls over into the next month
+    if args.len() == 1 && date.valid_date && date.day > 28 {
+        date.valid_date = false;
+    }
+
+    Ok(Some(date))
+}
+
+fn formatted(format: &str, value: f64) -> String {
+    match printf(&[Value::Text(format.to_string()), Value::Real(value)]) {
+        Ok(Value::Text(text)) => text,
+        _ => String::new(),
+    }
+}
+
+fn date_text(date: &DateTime) -> String {
+    let sign = if date.year < 0 { "-" } else { "" };
+
+    format!("{sign}{:04}-{:02}-{:02}", date.year.abs() % 10000, date.month, date.day)
+}
+
+fn time_text(date: &DateTime) -> String {
+    let clock = format!("{:02}:{:02}", date.hour, date.minute);
+
+    match date.subsec {
+        true => {
+            let ms = (1000.0 * date.second + 0.5) as i32;
+
+            format!("{clock}:{:02}.{:03}", ms / 1000 % 100, ms % 1000)
+        }
+
+        false => format!("{clock}:{:02}", date.second as i32),
+    }
+}
+
+pub(super) fn date(args: &[Value]) -> Result<Value> {
+    Ok(match date_time(args)? {
+        Some(mut date) => {
+            date.compute_date();
+
+            Value::Text(date_text(&date))
+        }
+
+        None => Value::Null,
+    })
+}
+
+pub(super) fn time(args: &[Value]) -> Result<Value> {
+    Ok(match date_time(args)? {
+        Some(mut date) => {
+            date.compute_time();
+
+            Value::Text(time_text(&date))
+        }
+
+        None => Value::Null,
+    })
+}
+
+pub(super) fn datetime(args: &[Value]) -> Result<Value> {
+    Ok(match date_time(args)? {
+        Some(mut date) => {
+            date.compute_all();
+
+            Value::Text(format!("{} {}", date_text(&date), time_text(&date)))
+        }
+
+        None => Value::Null,
+    })
+}
+
+pub(super) fn julianday(args: &[Value]) -> Result<Value> {
+    Ok(match date_time(args)? {
+        Some(date) => Value::Real(date.julian_ms as f64 / DAY_MS as f64),
+        None => Value::Null,
+    })
+}
+
+// Seconds since 1970, with milliseconds as a real after `subsec`
+pub(super) fn unixepoch(args: &[Value]) -> Result<Value> {
+    Ok(match date_time(args)? {
+        Some(date) if date.subsec => Value::Real((date.julian_ms - UNIX_EPOCH_MS) as f64 / 1000.0),
+        Some(date) => Value::Integer(date.julian_ms / 1000 - UNIX_EPOCH_MS / 1000),
+        None => Value::Null,
+    })
+}
+
+// strftime(format, time value, modifiers...), NULL for a conversion it does not know
+pub(super) fn strftime(args: &[Value]) -> Result<Value> {
+    let Some(format) = args.first().and_then(text) else {
+        return Ok(Value::Null);
+    };
+
+    let Some(mut date) = date_time(&args[1..])? else {
+        return Ok(Value::Null);
+    };
+
+    date.compute_julian();
+    date.compute_all();
+
+    let mut result = String::new();
+
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        let twelve_hour = match date.hour % 12 {
+            0 => 12,
+            hour => hour,
+        };
+
+        match chars.next() {
+            Some('d') => result += &format!("{:02}", date.day),
+            Some('e') => result += &format!("{:2}", date.day),
+            Some('f') => result += &formatted("%06.3f", date.second.min(59.999)),
+            Some('F') => result += &format!("{:04}-{:02}-{:02}", date.year, date.month, date.day),
+            Some('G') => result += &format!("{:04}", date.iso_thursday().year),
+            Some('g') => result += &format!("{:02}", date.iso_thursday().year % 100),
+            Some('H') => result += &format!("{:02}", date.hour),
+            Some('k') => result += &format!("{:2}", date.hour),
+            Some('I') => result += &format!("{twelve_hour:02}"),
+            Some('l') => result += &format!("{twelve_hour:2}"),
+            Some('j') => result += &format!("{:03}", date.days_after_jan01() + 1),
+            Some('J') => result += &formatted("%.16g", date.julian_ms as f64 / DAY_MS as f64),
+            Some('m') => result += &format!("{:02}", date.month),
+            Some('M') => result += &format!("{:02}", date.minute),
+            Some('p') => result += if date.hour >= 12 { "PM" } else { "AM" },
+            Some('P') => result += if date.hour >= 12 { "pm" } else { "am" },
+            Some('R') => result += &format!("{:02}:{:02}", date.hour, date.minute),
+
+            Some('s') => match date.subsec {
+                true => result += &formatted("%.3f", (date.julian_ms - UNIX_EPOCH_MS) as f64 / 1000.0),
+                false => result += &(date.julian_ms / 1000 - UNIX_EPOCH_MS / 1000).to_string(),
+            },
+
+            Some('S') => result += &format!("{:02}", date.second as i32),
+            Some('T') => result += &format!("{:02}:{:02}:{:02}", date.hour, date.minute, date.second as i32),
+
+            Some('u') => match date.days_after_sunday() {
+                0 => result.push('7'),
+                day => result += &day.to_string(),
+            },
+
+            Some('w') => result += &date.days_after_sunday().to_string(),
+            Some('U') => result += &format!("{:02}", (date.days_after_jan01() - date.days_after_sunday() + 7) / 7),
+            Some('V') => result += &format!("{:02}", date.iso_thursday().days_after_jan01() / 7 + 1),
+            Some('W') => result += &format!("{:02}", (date.days_after_jan01() - date.days_after_monday() + 7) / 7),
+            Some('Y') => result += &format!("{:04}", date.year),
+            Some('%') => result.push('%'),
+            _ => return Ok(Value::Null),
+        }
+    }
+
+    Ok(Value::Text(result))
+}
+
+// The time from the second argument to the first as +YYYY-MM-DD HH:MM:SS.SSS, counting
+// whole years and months on the calendar before the days and time
+pub(super) fn timediff(args: &[Value]) -> Result<Value> {
+    let (Some(mut later), Some(mut earlier)) = (date_time(&args[..1])?, date_time(&args[1..])?) else {
+        return Ok(Value::Null);
+    };
+
+    later.compute_all();
+    earlier.compute_all();
+
+    let forward = later.julian_ms >= earlier.julian_ms;
+
+    let sign = if forward { '+' } else { '-' };
+
+    let mut years = if forward { later.year - earlier.year } else { earlier.year - later.year };
+
+    if years != 0 {
+        earlier.year = later.year;
+        earlier.valid_julian = false;
+        earlier.compute_julian();
+    }
+
+    let mut months = if forward { later.month - earlier.month } else { earlier.month - later.month };
+
+    if months < 0 {
+        years -= 1;
+        months += 12;
+    }
+
+    if months != 0 {
+        earlier.month = later.month;
+        earlier.valid_julian = false;
+        earlier.compute_julian();
+    }
+
+    // step back a month at a time while the whole months overshoot
+    while if forward { later.julian_ms < earlier.julian_ms } else { later.julian_ms > earlier.julian_ms } {
+        months -= 1;
+
+        if months < 0 {
+            months = 11;
+            years -= 1;
+        }
+
+        earlier.month += if forward { -1 } else { 1 };
+
+        if earlier.month < 1 {
+            earlier.month = 12;
+            earlier.year -= 1;
+        } else if earlier.month > 12 {
+            earlier.month = 1;
+            earlier.year += 1;
+        }
+
+        earlier.valid_julian = false;
+        earlier.compute_julian();
+    }
+
+    // the remainder counted from 2000-01-01 so it reads off as days and a time of day
+    let remainder = (later.julian_ms - earlier.julian_ms).abs();
+
+    let mut difference = DateTime {
+        julian_ms: remainder + 148_699_540_800_000,
+        valid_julian: true,
+        ..DateTime::default()
+    };
+
+    difference.compute_all();
+
+    Ok(Value::Text(format!(
+        "{sign}{years:04}-{months:02}-{:02} {:02}:{:02}:{}",
+        difference.day - 1,
+        difference.hour,
+        difference.minute,
+        formatted("%06.3f", difference.second)
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{date, datetime, julianday, strftime, time, timediff, unixepoch};
+    use crate::core::value::Value;
+    use crate::error::Result;
+
+    fn call(function: fn(&[Value]) -> Result<Value>, args: &[&str]) -> Value {
+        let args = args.iter().map(|arg| Value::Text(arg.to_string())).collect::<Vec<_>>();
+
+        function(&args).unwrap()
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    #[test]
+    fn parses_dates_and_times() {
+        assert_eq!(call(datetime, &["2024-01-01T10:00:00+02:00"]), text("2024-01-01 08:00:00"));
+        assert_eq!(call(julianday, &["2000-01-01 12:00"]), Value::Real(2451545.0));
+        assert_eq!(datetime(&[Value::Real(2460000.5)]).unwrap(), text("2023-02-25 00:00:00"));
+
+        // days past the end of the month run into the next one, months past 12 are errors
+        assert_eq!(call(date, &["2024-02-30"]), text("2024-03-01"));
+        assert_eq!(call(date, &["2024-13-01"]), Value::Null);
+    }
+
+    #[test]
+    fn applies_modifiers() {
+        assert_eq!(call(date, &["2024-02-28", "+1 day"]), text("2024-02-29"));
+        assert_eq!(call(date, &["2023-01-31", "+1 month"]), text("2023-03-03"));
+        assert_eq!(call(date, &["2023-01-31", "+1 month", "floor"]), text("2023-02-28"));
+        assert_eq!(call(datetime, &["2024-03-15 12:34:56.789", "start of month"]), text("2024-03-01 00:00:00"));
+        assert_eq!(call(date, &["2024-03-15", "weekday 0"]), text("2024-03-17"));
+        assert_eq!(call(time, &["12:00", "+90 minutes"]), text("13:30:00"));
+        assert_eq!(call(datetime, &["2024-01-01", "+1.5 days"]), text("2024-01-02 12:00:00"));
+        assert_eq!(datetime(&[Value::Integer(0), text("unixepoch")]).unwrap(), text("1970-01-01 00:00:00"));
+    }
+
+    #[test]
+    fn formats() {
+        let formatted = call(strftime, &["%Y-%j %W %u %H:%M:%f %%", "2024-12-31 23:59:59.5"]);
+
+        assert_eq!(formatted, text("2024-366 53 2 23:59:59.500 %"));
+        assert_eq!(call(strftime, &["%s", "2024-01-01"]), text("1704067200"));
+        assert_eq!(call(unixepoch, &["1970-01-02"]), Value::Integer(86400));
+        assert_eq!(call(timediff, &["2024-03-01", "2024-02-01"]), text("+0000-01-00 00:00:00.000"));
+    }
+}
@@ -0,0 +1,213 @@
+mod datetime;
+mod pattern;
+mod printf;
+mod scalar;
+
+pub(crate) use printf::real_with_all_digits;
+
+use crate::core::value::Value;
+use crate::parser::ast::LikeOperator;
+use crate::error::{Error, Result};
+use std::cell::Cell;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+pub(crate) use pattern::literal_prefix;
+
+// A scalar SQL function: the name it is called by, how many arguments it takes and what it
+// computes from them
+pub(crate) struct Function {
+    name: &'static str,
+    arguments: RangeInclusive<usize>,
+    call: fn(&[Value]) -> Result<Value>,
+}
+
+impl Function {
+    const fn new(name: &'static str, arguments: RangeInclusive<usize>, call: fn(&[Value]) -> Result<Value>) -> Self {
+        Self { name, arguments, call }
+    }
+}
+
+// Functions that take any number of arguments from some minimum on
+const ANY: usize = usize::MAX;
+
+// Every scalar function, looked up by name without regard to case
+const FUNCTIONS: &[Function] = &[
+    Function::new("abs", 1..=1, scalar::abs),
+    Function::new("char", 0..=ANY, scalar::char),
+    Function::new("coalesce", 2..=ANY, scalar::coalesce),
+    Function::new("date", 0..=ANY, datetime::date),
+    Function::new("datetime", 0..=ANY, datetime::datetime),
+    Function::new("format", 0..=ANY, printf::printf),
+    Function::new("glob", 2..=2, pattern::glob),
+    Function::new("hex", 1..=1, scalar::hex),
+    Function::new("ifnull", 2..=2, scalar::coalesce),
+    Function::new("iif", 2..=ANY, scalar::iif),
+    Function::new("instr", 2..=2, scalar::instr),
+    Function::new("julianday", 0..=ANY, datetime::julianday),
+    Function::new("length", 1..=1, scalar::length),
+    Function::new("like", 2..=3, pattern::like),
+    Function::new("lower", 1..=1, scalar::lower),
+    Function::new("ltrim", 1..=2, scalar::ltrim),
+    Function::new("max", 2..=ANY, scalar::max),
+    Function::new("min", 2..=ANY, scalar::min),
+    Function::new("nullif", 2..=2, scalar::nullif),
+    Function::new("printf", 0..=ANY, printf::printf),
+    Function::new("quote", 1..=1, scalar::quote),
+    Function::new("random", 0..=0, scalar::random),
+    Function::new("replace", 3..=3, scalar::replace),
+    Function::new("round", 1..=2, scalar::round),
+    Function::new("rtrim", 1..=2, scalar::rtrim),
+    Function::new("strftime", 1..=ANY, datetime::strftime),
+    Function::new("substr", 2..=3, scalar::substr),
+    Function::new("substring", 2..=3, scalar::substr),
+    Function::new("time", 0..=ANY, datetime::time),
+    Function::new("timediff", 2..=2, datetime::timediff),
+    Function::new("trim", 1..=2, scalar::trim),
+    Function::new("typeof", 1..=1, scalar::type_of),
+    Function::new("unicode", 1..=1, scalar::unicode),
+    Function::new("unixepoch", 0..=ANY, datetime::unixepoch),
+    Function::new("upper", 1..=1, scalar::upper),
+];
+
+// The functions that summarize many rows into one value
+const AGGREGATES: &[&str] = &["avg", "count", "group_concat", "max", "min", "string_agg", "sum", "total"];
+
+// The longest string or blob a function may build, sqlite's default limit
+const MAX_LENGTH: usize = 1_000_000_000;
+
+// The body of a function registered on a connection, which takes the values of its arguments
+type Callback = dyn Fn(&[Value]) -> Result<Value>;
+
+// A function registered on a connection, called in place of a built-in one of the same
+// name. REGEXP has no built-in function, it calls the one registered as regexp().
+struct Registered {
+    name: String,
+
+    // the number of arguments it takes, None for any number
+    arguments: Option<usize>,
+
+    call: Rc<Callback>,
+}
+
+// The functions a connection can call, and the settings that change how they behave
+#[derive(Default)]
+pub(crate) struct Registry {
+    registered: Vec<Registered>,
+
+    // set by PRAGMA case_sensitive_like, which swaps in another like() the way sqlite does
+    pub(crate) case_sensitive_like: Cell<bool>,
+}
+
+impl Registry {
+    // Registers a function, replacing one registered before with the same name and arguments
+    pub(crate) fn register(
+        &mut self,
+        name: &str,
+        arguments: Option<usize>,
+        call: impl Fn(&[Value]) -> Result<Value> + 'static,
+    ) {
+        self.registered
+            .retain(|function| !(function.name.eq_ignore_ascii_case(name) && function.arguments == arguments));
+
+        self.registered.push(Registered {
+            name: name.to_string(),
+            arguments,
+            call: Rc::new(call),
+        });
+    }
+
+    fn registered(&self, name: &str, argument_count: usize) -> Option<&Registered> {
+        // one taking exactly these arguments is preferred to one taking any number
+        let mut candidates = self
+            .registered
+            .iter()
+            .filter(|function| function.name.eq_ignore_ascii_case(name));
+
+        candidates
+            .clone()
+            .find(|function| function.arguments == Some(argument_count))
+            .or_else(|| candidates.find(|function| function.arguments.is_none()))
+    }
+
+    // Checks that a call refers to a function that takes the arguments it is given
+    pub(crate) fn check(&self, name: &str, argument_count: usize) -> Result<()> {
+        if self.registered(name, argument_count).is_some() {
+            return Ok(());
+        }
+
+        let is_registered = self.registered.iter().any(|function| function.name.eq_ignore_ascii_case(name));
+
+        match lookup(name, argument_count) {
+            Err(Error::NoSuchFunction(_)) if is_registered => Err(wrong_arguments(name)),
+            result => result.map(|_| ()),
+        }
+    }
+
+    pub(crate) fn call(&self, name: &str, args: &[Value]) -> Result<Value> {
+        if let Some(function) = self.registered(name, args.len()) {
+            return (function.call)(args);
+        }
+
+        self.check(name, args.len())?;
+
+        if self.case_sensitive_like.get() && name.eq_ignore_ascii_case("like") {
+            return pattern::like_case_sensitive(args);
+        }
+
+        call(name, args)
+    }
+}
+
+// Finds the built-in function a call refers to, checking the number of arguments it is given
+fn lookup(name: &str, argument_count: usize) -> Result<&'static Function> {
+    let function = FUNCTIONS
+        .iter()
+        .find(|function| function.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::NoSuchFunction(name.to_string()))?;
+
+    if !function.arguments.contains(&argument_count) {
+        return Err(wrong_arguments(name));
+    }
+
+    Ok(function)
+}
+
+fn call(name: &str, args: &[Value]) -> Result<Value> {
+    (lookup(name, args.len())?.call)(args)
+}
+
+fn wrong_arguments(name: &str) -> Error {
+    Error::Misuse(format!("wrong number of arguments to function {name}()"))
+}
+
+// The function a pattern operator calls: `x LIKE y` is like(y, x). MATCH has none, it only
+// works on virtual tables.
+pub(crate) fn operator_function(operator: LikeOperator) -> Option<&'static str> {
+    match operator {
+        LikeOperator::Like => Some("like"),
+        LikeOperator::Glob => Some("glob"),
+        LikeOperator::Regexp => Some("regexp"),
+        LikeOperator::Match => None,
+    }
+}
+
+// Whether a call summarizes rows. min() and max() only do with a single argument, with more
+// they pick among their arguments.
+pub(crate) fn is_aggregate(name: &str, argument_count: usize) -> bool {
+    let is_min_or_max = name.eq_ignore_ascii_case("min") || name.eq_ignore_ascii_case("max");
+
+    AGGREGATES.iter().any(|aggregate| aggregate.eq_ignore_ascii_case(name)) && (!is_min_or_max || argument_count == 1)
+}
+
+fn too_big() -> Error {
+    Error::Misuse("string or blob too big".to_string())
+}
+
+// The text of a value as the string functions see it, NULL for NULL
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        value => Some(value.to_string()),
+    }
+}
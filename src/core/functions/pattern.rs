@@ -0,0 +1,276 @@
+use super::text;
+use crate::core::value::Value;
+use crate::error::{Error, Result};
+
+// The longest LIKE or GLOB pattern, in bytes, sqlite's default limit
+const MAX_PATTERN_LENGTH: usize = 50_000;
+
+// The special characters of a pattern. `other` is the escape character of LIKE, or the `[`
+// that opens a character class in GLOB.
+#[derive(Clone, Copy)]
+struct Wildcards {
+    all: Option<char>,
+    one: Option<char>,
+    other: Option<char>,
+    class: bool,
+    no_case: bool,
+}
+
+// How a pattern compares with the text, where a mismatch that no wildcard before it could
+// have avoided ends the search early
+#[derive(PartialEq)]
+enum Match {
+    Yes,
+    No,
+    NoWildcard,
+}
+
+// like(pattern, text[, escape]), for `text LIKE pattern [ESCAPE escape]`, which ignores the
+// case of ASCII letters
+pub(super) fn like(args: &[Value]) -> Result<Value> {
+    like_with_case(args, true)
+}
+
+// like() after PRAGMA case_sensitive_like = ON
+pub(super) fn like_case_sensitive(args: &[Value]) -> Result<Value> {
+    like_with_case(args, false)
+}
+
+fn like_with_case(args: &[Value], no_case: bool) -> Result<Value> {
+    check_length(&args[0])?;
+
+    let mut wildcards = Wildcards {
+        all: Some('%'),
+        one: Some('_'),
+        other: None,
+        class: false,
+        no_case,
+    };
+
+    if let Some(escape) = args.get(2) {
+        let Some(escape) = text(escape) else {
+            return Ok(Value::Null);
+        };
+
+        let mut chars = escape.chars();
+
+        let (Some(escape), None) = (chars.next(), chars.next()) else {
+            return Err(Error::Misuse("ESCAPE expression must be a single character".to_string()));
+        };
+
+        // a wildcard used as the escape character only escapes
+        wildcards.all = wildcards.all.filter(|&all| all != escape);
+        wildcards.one = wildcards.one.filter(|&one| one != escape);
+        wildcards.other = Some(escape);
+    }
+
+    compare_values(&args[0], &args[1], wildcards)
+}
+
+// glob(pattern, text), for `text GLOB pattern`: `*`, `?` and `[...]` classes, matching case
+pub(super) fn glob(args: &[Value]) -> Result<Value> {
+    check_length(&args[0])?;
+
+    let wildcards = Wildcards {
+        all: Some('*'),
+        one: Some('?'),
+        other: Some('['),
+        class: true,
+        no_case: false,
+    };
+
+    compare_values(&args[0], &args[1], wildcards)
+}
+
+fn check_length(pattern: &Value) -> Result<()> {
+    match text(pattern) {
+        Some(pattern) if pattern.len() > MAX_PATTERN_LENGTH => {
+            Err(Error::Misuse("LIKE or GLOB pattern too complex".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn compare_values(pattern: &Value, value: &Value, wildcards: Wildcards) -> Result<Value> {
+    let (Some(pattern), Some(value)) = (text(pattern), text(value)) else {
+        return Ok(Value::Null);
+    };
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+
+    let matched = compare(&pattern, &value, wildcards) == Match::Yes;
+
+    Ok(Value::Integer(i64::from(matched)))
+}
+
+// The same walk as sqlite's patternCompare()
+fn compare(mut pattern: &[char], mut value: &[char], wildcards: Wildcards) -> Match {
+    let same = |a: char, b: char| a == b || (wildcards.no_case && a.is_ascii() && a.eq_ignore_ascii_case(&b));
+
+    while let Some((&c, rest)) = pattern.split_first() {
+        pattern = rest;
+
+        if Some(c) == wildcards.all {
+            // runs of wildcards collapse, each one-character wildcard taking a character
+            let mut next = None;
+
+            while let Some((&c, rest)) = pattern.split_first() {
+                if Some(c) == wildcards.one {
+                    let Some((_, remaining)) = value.split_first() else {
+                        return Match::NoWildcard;
+                    };
+
+                    value = remaining;
+                } else if Some(c) != wildcards.all {
+                    next = Some(c);
+                    break;
+                }
+
+                pattern = rest;
+            }
+
+            let Some(mut c) = next else {
+                return Match::Yes;
+            };
+
+            pattern = &pattern[1..];
+
+            if Some(c) == wildcards.other {
+                if wildcards.class {
+                    // a class right after the wildcard is tried at every position
+                    let class = &pattern_from(c, pattern);
+
+                    for start in 0..value.len() {
+                        let result = compare(class, &value[start..], wildcards);
+
+                        if result != Match::No {
+                            return result;
+                        }
+                    }
+
+                    return Match::NoWildcard;
+                }
+
+                let Some((&escaped, rest)) = pattern.split_first() else {
+                    return Match::NoWildcard;
+                };
+
+                c = escaped;
+                pattern = rest;
+            }
+
+            // try the rest of the pattern after every occurrence of the character
+            for (i, &candidate) in value.iter().enumerate() {
+                if same(c, candidate) {
+                    let result = compare(pattern, &value[i + 1..], wildcards);
+
+                    if result != Match::No {
+                        return result;
+                    }
+                }
+            }
+
+            return Match::NoWildcard;
+        }
+
+        let mut escaped = false;
+
+        let mut c = c;
+
+        if Some(c) == wildcards.other {
+            if wildcards.class {
+                let Some((&candidate, remaining)) = value.split_first() else {
+                    return Match::No;
+                };
+
+                let Some(rest) = match_class(pattern, candidate) else {
+                    return Match::No;
+                };
+
+                pattern = rest;
+                value = remaining;
+
+                continue;
+            }
+
+            let Some((&literal, rest)) = pattern.split_first() else {
+                return Match::No;
+            };
+
+            c = literal;
+            pattern = rest;
+            escaped = true;
+        }
+
+        let Some((&candidate, remaining)) = value.split_first() else {
+            return Match::No;
+        };
+
+        value = remaining;
+
+        let matches_one = Some(c) == wildcards.one && !escaped;
+
+        if !same(c, candidate) && !matches_one {
+            return Match::No;
+        }
+    }
+
+    match value.is_empty() {
+        true => Match::Yes,
+        false => Match::No,
+    }
+}
+
+fn pattern_from(first: char, rest: &[char]) -> Vec<char> {
+    std::iter::once(first).chain(rest.iter().copied()).collect()
+}
+
+// Matches a character against the class after a `[`, returning the pattern after its `]`.
+// `^` first inverts the class, a `]` first is a member and `a-z` is a range.
+fn match_class(pattern: &[char], c: char) -> Option<&[char]> {
+    let mut seen = false;
+    let mut invert = false;
+
+    let mut i = 0;
+
+    if pattern.first() == Some(&'^') {
+        invert = true;
+        i += 1;
+    }
+
+    if pattern.get(i) == Some(&']') {
+        seen = c == ']';
+        i += 1;
+    }
+
+    let mut prior = None;
+
+    loop {
+        match *pattern.get(i)? {
+            ']' => break,
+
+            '-' if prior.is_some() && !matches!(pattern.get(i + 1), None | Some(']')) => {
+                seen |= (prior?..=pattern[i + 1]).contains(&c);
+                prior = None;
+                i += 2;
+            }
+
+            member => {
+                seen |= c == member;
+                prior = Some(member);
+                i += 1;
+            }
+        }
+    }
+
+    (seen != invert).then_some(&pattern[i + 1..])
+}
+
+// The text every match of a pattern starts with, up to its first wildcard, which an index
+// on the column can find the matching rows by
+pub(crate) fn literal_prefix(pattern: &str, glob: bool) -> &str {
+    let wildcards: &[char] = if glob { &['*', '?', '['] } else { &['%', '_'] };
+
+    &pattern[..pattern.find(wildcards).unwrap_or(pattern.len())]
+}
@@ -0,0 +1,639 @@
+use super::{text, MAX_LENGTH};
+use crate::core::eval::{to_integer, to_real};
+use crate::core::value::Value;
+use crate::error::Result;
+use std::slice::Iter;
+
+// The most digits a real is written with after the point, or in all with %g, like
+// SQLITE_FP_PRECISION_LIMIT
+const MAX_PRECISION: usize = 100_000_000;
+
+// The flags, width and precision of one % conversion
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    comma: bool,
+
+    // what goes before positive numbers, `+` or ` `, whichever flag comes last
+    sign: Option<char>,
+
+    // `#`, which keeps the decimal point and trailing zeros and prefixes hex with 0x
+    alternate: bool,
+
+    // `!`, which counts characters rather than bytes and allows more digits in reals
+    alternate2: bool,
+
+    width: usize,
+    precision: Option<usize>,
+}
+
+// printf(format, ...) and format(format, ...): the C-style formatting of sqlite_mprintf(),
+// where missing arguments count as NULL and a conversion it does not know ends the output
+pub(crate) fn printf(args: &[Value]) -> Result<Value> {
+    let Some(format) = args.first().and_then(text) else {
+        return Ok(Value::Null);
+    };
+
+    let mut args = args[1..].iter();
+
+    let mut out = String::new();
+
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+
+            continue;
+        }
+
+        // a % at the very end stands for itself
+        if chars.peek().is_none() {
+            out.push('%');
+
+            break;
+        }
+
+        let mut spec = Spec::default();
+
+        while let Some(flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.sign = Some('+'),
+                ' ' => spec.sign = Some(' '),
+                '0' => spec.zero = true,
+                ',' => spec.comma = true,
+                '#' => spec.alternate = true,
+                '!' => spec.alternate2 = true,
+                _ => break,
+            }
+
+            chars.next();
+        }
+
+        // widths and precisions are C ints: an argument is cut to 32 bits, and a negative
+        // width from one justifies to the left unless it has no positive counterpart
+        if chars.next_if_eq(&'*').is_some() {
+            let width = next_integer(&mut args) as i32;
+
+            spec.left |= width < 0;
+            spec.width = if width == i32::MIN { 0 } else { width.unsigned_abs() as usize };
+        } else {
+            spec.width = number(&mut chars);
+        }
+
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = match chars.next_if_eq(&'*') {
+                Some(_) => match next_integer(&mut args) as i32 {
+                    i32::MIN => None,
+                    precision => Some(precision.unsigned_abs() as usize),
+                },
+                None => Some(number(&mut chars)),
+            };
+        }
+
+        // like everything that makes the result longer than the limit, a width that does
+        // makes it NULL rather than an error
+        if spec.width >= MAX_LENGTH {
+            return Ok(Value::Null);
+        }
+
+        // the l and ll length modifiers of C mean nothing here
+        while chars.next_if_eq(&'l').is_some() {}
+
+        // sqlite writes the digits of an integer in a buffer sized by the precision, which
+        // cannot be larger than the limit either
+        let buffer = spec.precision.map(|precision| precision + 10 + if spec.comma { precision / 3 } else { 0 });
+
+        if chars.peek().is_some_and(|c| "diuxXo".contains(*c)) && buffer.is_some_and(|buffer| buffer > MAX_LENGTH) {
+            return Ok(Value::Null);
+        }
+
+        let formatted = match chars.next() {
+            Some(conversion @ ('d' | 'i')) => {
+                let value = next_integer(&mut args);
+
+                integer(value.unsigned_abs(), value < 0, conversion, &spec)
+            }
+
+            Some(conversion @ ('u' | 'x' | 'X' | 'o')) => integer(next_integer(&mut args) as u64, false, conversion, &spec),
+
+            Some(conversion @ ('f' | 'e' | 'E' | 'g' | 'G')) => {
+                float(args.next().map_or(0.0, to_real), conversion, &spec)
+            }
+
+            // the precision limits how much of the argument is used, or of what stands for
+            // NULL, before any escaping
+            Some(conversion @ ('s' | 'z' | 'q' | 'Q' | 'w')) => {
+                let (text, escape) = match (conversion, args.next().and_then(text)) {
+                    (_, Some(text)) => (text, true),
+                    ('q' | 'w', None) => ("(NULL)".to_string(), false),
+                    ('Q', None) => ("NULL".to_string(), false),
+                    (_, None) => (String::new(), false),
+                };
+
+                let text = match spec.precision {
+                    Some(precision) => truncate(&text, precision, spec.alternate2),
+                    None => text,
+                };
+
+                match conversion {
+                    'q' if escape => text.replace('\'', "''"),
+                    'Q' if escape => format!("'{}'", text.replace('\'', "''")),
+                    'w' if escape => text.replace('"', "\"\""),
+                    _ => text,
+                }
+            }
+
+            // the first character, repeated as many times as the precision says
+            Some('c') => match args.next().and_then(text).and_then(|text| text.chars().next()) {
+                Some(c) => c.to_string().repeat(spec.precision.unwrap_or(1).max(1)),
+                None => String::new(),
+            },
+
+            Some('%') => "%".to_string(),
+
+            // %n stores a count in C, here it is ignored without taking an argument
+            Some('n') => continue,
+
+            _ => break,
+        };
+
+        let length = match spec.alternate2 {
+            true => formatted.chars().count(),
+            false => formatted.len(),
+        };
+
+        let padding = " ".repeat(spec.width.saturating_sub(length));
+
+        if spec.left {
+            out.push_str(&formatted);
+            out.push_str(&padding);
+        } else {
+            out.push_str(&padding);
+            out.push_str(&formatted);
+        }
+
+        if out.len() >= MAX_LENGTH {
+            return Ok(Value::Null);
+        }
+    }
+
+    Ok(Value::Text(out))
+}
+
+// Rounds a real to some decimal places, half away from zero like printf's %.*f
+pub(super) fn round_to_places(value: f64, places: usize) -> f64 {
+    let spec = Spec {
+        precision: Some(places),
+        ..Spec::default()
+    };
+
+    float(value, 'f', &spec).parse().unwrap_or(value)
+}
+
+fn next_integer(args: &mut Iter<Value>) -> i64 {
+    args.next().and_then(to_integer).unwrap_or(0)
+}
+
+// A width or precision written out in the format, read into a C unsigned that wraps around
+// and keeps 31 bits of it, like sqlite does
+fn number(chars: &mut std::iter::Peekable<std::str::Chars>) -> usize {
+    let mut number = 0u32;
+
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        number = number.wrapping_mul(10).wrapping_add(digit);
+
+        chars.next();
+    }
+
+    (number & 0x7fff_ffff) as usize
+}
+
+// The first bytes of some text, or the first characters with `!`
+fn truncate(text: &str, precision: usize, characters: bool) -> String {
+    if characters {
+        return text.chars().take(precision).collect();
+    }
+
+    String::from_utf8_lossy(&text.as_bytes()[..precision.min(text.len())]).into_owned()
+}
+
+fn integer(magnitude: u64, negative: bool, conversion: char, spec: &Spec) -> String {
+    let mut digits = match conversion {
+        'x' => format!("{magnitude:x}"),
+        'X' => format!("{magnitude:X}"),
+        'o' => format!("{magnitude:o}"),
+        _ => magnitude.to_string(),
+    };
+
+    let sign = match conversion {
+        'd' | 'i' if negative => "-".to_string(),
+        'd' | 'i' => spec.sign.map(String::from).unwrap_or_default(),
+        _ => String::new(),
+    };
+
+    let prefix = match conversion {
+        _ if magnitude == 0 => "",
+        'x' if spec.alternate => "0x",
+        'X' if spec.alternate => "0X",
+        'o' if spec.alternate => "0",
+        _ => "",
+    };
+
+    // zero padding fills the width with leading digits, the way a precision does
+    let mut precision = spec.precision.unwrap_or(1);
+
+    if spec.zero {
+        precision = precision.max(spec.width.saturating_sub(sign.len()));
+    }
+
+    if digits.len() < precision {
+        digits.insert_str(0, &"0".repeat(precision - digits.len()));
+    }
+
+    // the separators go between the padding zeros too
+    if spec.comma && matches!(conversion, 'd' | 'i' | 'u') {
+        digits = group_thousands(&digits);
+    }
+
+    format!("{sign}{prefix}{digits}")
+}
+
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::new();
+
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+
+        grouped.push(digit);
+    }
+
+    grouped
+}
+
+// A real with every digit sqlite works out, "%!.20g" or with 'e' "%!.20e", which reads back
+// as the same value
+pub(crate) fn real_with_all_digits(value: f64, conversion: char) -> String {
+    let spec = Spec {
+        alternate2: true,
+        precision: Some(20),
+        ..Spec::default()
+    };
+
+    float(value, conversion, &spec)
+}
+
+// %f, %e and %g. Like sqlite, reals round half away from zero and have no more than 16
+// significant digits, or with `!` as many as the 26 sqlite allows of the about 19 it works
+// out, past which come zeros.
+fn float(value: f64, conversion: char, spec: &Spec) -> String {
+    let sign = match value < 0.0 {
+        true => "-".to_string(),
+        false => spec.sign.map(String::from).unwrap_or_default(),
+    };
+
+    if value.is_infinite() {
+        return format!("{sign}Inf");
+    }
+
+    let limit = if spec.alternate2 { 26 } else { 16 };
+
+    let precision = spec.precision.unwrap_or(6).min(MAX_PRECISION);
+
+    // %g drops trailing zeros unless `#` says otherwise, and `!` makes %f and %e drop them
+    // too, though it leaves a digit after the decimal point so the number reads as a real
+    let remove_zeros = match conversion {
+        'g' | 'G' => !spec.alternate,
+        _ => spec.alternate2,
+    };
+
+    let mut digits = Digits::new(value.abs());
+
+    // zeros that are to be removed are not written in the first place
+    let places = |places: usize, needed: usize| match remove_zeros {
+        true => places.min(needed),
+        false => places,
+    };
+
+    let mut body = match conversion {
+        'f' => {
+            digits.round(digits.point as i64 + precision as i64, limit);
+
+            digits.fixed(places(precision, digits.fixed_places()), spec)
+        }
+
+        'e' | 'E' => {
+            digits.round(precision as i64 + 1, limit);
+
+            digits.exponential(places(precision, digits.exponential_places()), conversion == 'E', spec)
+        }
+
+        _ => {
+            let precision = precision.max(1);
+
+            digits.round(precision as i64, limit);
+
+            let exponent = if value == 0.0 { 0 } else { digits.point - 1 };
+
+            if exponent < -4 || exponent >= precision as i32 {
+                digits.exponential(places(precision - 1, digits.exponential_places()), conversion == 'G', spec)
+            } else {
+                let precision = (precision as i32 - 1 - exponent) as usize;
+
+                digits.fixed(places(precision, digits.fixed_places()), spec)
+            }
+        }
+    };
+
+    if remove_zeros {
+        body = remove_trailing_zeros(&body, spec.alternate2);
+    }
+
+    if spec.zero && !spec.left && spec.width > sign.len() + body.len() {
+        body.insert_str(0, &"0".repeat(spec.width - sign.len() - body.len()));
+    }
+
+    format!("{sign}{body}")
+}
+
+// Multiplies the double-double `x`, its value and the error of it, by `y` plus `yy`, with
+// Dekker's exact product of the halves of each, as sqlite does
+fn dekker_multiply(x: &mut [f64; 2], y: f64, yy: f64) {
+    // the upper 26 bits of the mantissa, whose products need no rounding
+    let high = |value: f64| f64::from_bits(value.to_bits() & 0xffff_ffff_fc00_0000);
+
+    let (hx, hy) = (high(x[0]), high(y));
+    let (tx, ty) = (x[0] - hx, y - hy);
+
+    let p = hx * hy;
+    let q = hx * ty + tx * hy;
+    let c = p + q;
+    let cc = p - c + q + tx * ty;
+    let cc = x[0] * yy + x[1] * y + cc;
+
+    x[0] = c + cc;
+    x[1] = c - x[0] + cc;
+}
+
+// Removes zeros at the end of the fraction, and the decimal point when nothing is left of
+// it unless the zero after it is kept
+fn remove_trailing_zeros(text: &str, keep_zero: bool) -> String {
+    let (mantissa, exponent) = text.split_at(text.find(['e', 'E']).unwrap_or(text.len()));
+
+    if !mantissa.contains('.') {
+        return text.to_string();
+    }
+
+    let mut mantissa = mantissa.trim_end_matches('0').to_string();
+
+    if mantissa.ends_with('.') {
+        match keep_zero {
+            true => mantissa.push('0'),
+            false => _ = mantissa.pop(),
+        }
+    }
+
+    format!("{mantissa}{exponent}")
+}
+
+// The decimal digits of a non-negative real, with the decimal point after the first `point`
+// of them. Digits past the end are zeros.
+#[derive(Debug, PartialEq)]
+struct Digits {
+    digits: Vec<u8>,
+    point: i32,
+}
+
+impl Digits {
+    // The digits sqlite works out for a real rather than its exact ones: it is scaled by
+    // powers of ten into the range of a u64 with double-double products, whose digits are
+    // the ones written. The constants are written as sqlite has them.
+    #[allow(clippy::excessive_precision)]
+    fn new(value: f64) -> Self {
+        if value == 0.0 {
+            return Self { digits: vec![0], point: 1 };
+        }
+
+        let mut exponent = 0;
+        let mut scaled = [value, 0.0];
+
+        if scaled[0] > 9.223372036854774784e+18 {
+            while scaled[0] > 9.223372036854774784e+118 {
+                exponent += 100;
+                dekker_multiply(&mut scaled, 1.0e-100, -1.99918998026028836196e-117);
+            }
+
+            while scaled[0] > 9.223372036854774784e+28 {
+                exponent += 10;
+                dekker_multiply(&mut scaled, 1.0e-10, -3.6432197315497741579e-27);
+            }
+
+            while scaled[0] > 9.223372036854774784e+18 {
+                exponent += 1;
+                dekker_multiply(&mut scaled, 1.0e-01, -5.5511151231257827021e-18);
+            }
+        } else {
+            while scaled[0] < 9.223372036854774784e-83 {
+                exponent -= 100;
+                dekker_multiply(&mut scaled, 1.0e+100, -1.5902891109759918046e+83);
+            }
+
+            while scaled[0] < 9.223372036854774784e+07 {
+                exponent -= 10;
+                dekker_multiply(&mut scaled, 1.0e+10, 0.0);
+            }
+
+            while scaled[0] < 9.22337203685477478e+17 {
+                exponent -= 1;
+                dekker_multiply(&mut scaled, 1.0e+01, 0.0);
+            }
+        }
+
+        let whole = match scaled[1] < 0.0 {
+            true => (scaled[0] as u64).wrapping_sub(-scaled[1] as u64),
+            false => (scaled[0] as u64).wrapping_add(scaled[1] as u64),
+        };
+
+        let digits = whole.to_string().bytes().map(|digit| digit - b'0').collect::<Vec<_>>();
+
+        Self {
+            point: digits.len() as i32 + exponent,
+            digits,
+        }
+    }
+
+    fn digit(&self, i: i32) -> u8 {
+        usize::try_from(i).ok().and_then(|i| self.digits.get(i)).copied().unwrap_or(0)
+    }
+
+    // The decimal places up to the last digit that is not zero, written out in full
+    fn fixed_places(&self) -> usize {
+        (self.digits.len() as i64 - self.point as i64).max(0) as usize
+    }
+
+    // The same, written with an exponent
+    fn exponential_places(&self) -> usize {
+        self.digits.len().saturating_sub(1)
+    }
+
+    // Pushes `count` digits from the one at `start`, the zeros past the end all at once as a
+    // precision can ask for millions of them
+    fn push_digits(&self, text: &mut String, start: i32, count: usize) {
+        let known = (self.digits.len() as i64 - start as i64).clamp(0, count as i64) as i32;
+
+        text.extend((start..start + known).map(|i| char::from(b'0' + self.digit(i))));
+
+        text.push_str(&"0".repeat(count - known as usize));
+    }
+
+    // Keeps `count` significant digits, no more than `limit`, rounding half away from zero
+    fn round(&mut self, count: i64, limit: usize) {
+        let Ok(count) = usize::try_from(count) else {
+            self.digits.clear();
+
+            return;
+        };
+
+        let count = count.min(limit);
+
+        let round_up = self.digits.get(count).is_some_and(|digit| *digit >= 5);
+
+        self.digits.truncate(count);
+
+        if !round_up {
+            return;
+        }
+
+        match self.digits.iter().rposition(|digit| *digit < 9) {
+            Some(i) => {
+                self.digits[i] += 1;
+
+                self.digits.truncate(i + 1);
+            }
+
+            // 999 rounds up to 1000
+            None => {
+                self.digits = vec![1];
+                self.point += 1;
+            }
+        }
+    }
+
+    fn fixed(&self, precision: usize, spec: &Spec) -> String {
+        // below 1 the integer part is a single 0
+        let mut integer: String = match self.point {
+            ..=0 => "0".to_string(),
+            point => (0..point).map(|i| char::from(b'0' + self.digit(i))).collect(),
+        };
+
+        if spec.comma {
+            integer = group_thousands(&integer);
+        }
+
+        let mut text = integer;
+
+        if precision > 0 || spec.alternate || spec.alternate2 {
+            text.push('.');
+        }
+
+        self.push_digits(&mut text, self.point, precision);
+
+        text
+    }
+
+    fn exponential(&self, precision: usize, upper: bool, spec: &Spec) -> String {
+        let mut text = char::from(b'0' + self.digit(0)).to_string();
+
+        if precision > 0 || spec.alternate || spec.alternate2 {
+            text.push('.');
+        }
+
+        self.push_digits(&mut text, 1, precision);
+
+        let exponent = if self.digits.iter().all(|digit| *digit == 0) {
+            0
+        } else {
+            self.point - 1
+        };
+
+        let sign = if exponent < 0 { '-' } else { '+' };
+
+        format!("{text}{}{sign}{:02}", if upper { 'E' } else { 'e' }, exponent.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::printf;
+    use crate::core::value::Value;
+
+    fn format(format: &str, args: &[Value]) -> String {
+        let mut all = vec![Value::Text(format.to_string())];
+
+        all.extend_from_slice(args);
+
+        match printf(&all).unwrap() {
+            Value::Text(text) => text,
+            other => panic!("printf returned {other:?}"),
+        }
+    }
+
+    #[test]
+    fn integers() {
+        let n = Value::Integer(42);
+
+        assert_eq!(format("%5d|%-5d|%05d|%+d", &[n.clone(), n.clone(), n.clone(), n]), "   42|42   |00042|+42");
+        assert_eq!(format("%,d", &[Value::Integer(1234567)]), "1,234,567");
+        assert_eq!(format("%x|%X", &[Value::Integer(255), Value::Integer(255)]), "ff|FF");
+        assert_eq!(format("%#o|%.3d", &[Value::Integer(8), Value::Integer(7)]), "010|007");
+        assert_eq!(format("%d", &[Value::Real(3.9)]), "3");
+    }
+
+    #[test]
+    fn reals() {
+        assert_eq!(format("%.2f|%e", &[Value::Real(1.23456), Value::Real(1234.5)]), "1.23|1.234500e+03");
+        let args = [Value::Real(0.0001), Value::Real(1e8), Value::Real(1.0)];
+
+        assert_eq!(format("%g|%g|%#g", &args), "0.0001|1e+08|1.00000");
+        assert_eq!(format("%!.18g|%.18g", &[Value::Real(0.1), Value::Real(0.1)]), "0.100000000000000006|0.1");
+
+        // halves round away from zero, as sqlite does on the decimal digits
+        let args = [Value::Real(0.25), Value::Real(2.5), Value::Real(9.999)];
+
+        assert_eq!(format("%.1f|%.0f|%.2e", &args), "0.3|3|1.00e+01");
+    }
+
+    #[test]
+    fn text() {
+        let args = [Value::Text("abcdef".into()), Value::Text("ab".into())];
+
+        assert_eq!(format("%.3s|%-4s", &args), "abc|ab  ");
+        let args = [Value::Text("it's".into()), Value::Text("x".into()), Value::Null];
+
+        assert_eq!(format("%q|%Q|%Q|%%", &args), "it''s|'x'|NULL|%");
+        assert_eq!(format("%c|%.2c", &[Value::Text("xyz".into()), Value::Text("a".into())]), "x|aa");
+
+        // missing arguments are empty
+        assert_eq!(format("%s %s", &[Value::Text("only".into())]), "only ");
+    }
+
+    #[test]
+    fn precision_is_clamped() {
+        assert_eq!(format("%.1000000000f", &[Value::Integer(1)]).len(), 100000002);
+        assert_eq!(format("%.100000005g", &[Value::Real(1.5)]), "1.5");
+        assert_eq!(format("%!.20g", &[Value::Real(0.1)]), "0.1000000000000000055");
+        assert_eq!(format("%!.25e", &[Value::Real(0.1)]), "1.000000000000000055e-01");
+        assert_eq!(format("%4294967299d|%.*f", &[Value::Integer(1), Value::Integer(-2), Value::Real(1.5)]), "  1|1.50");
+        assert_eq!(format("%*d|", &[Value::Integer(-3), Value::Integer(1)]), "1  |");
+
+        for (spec, arg) in [("%2147483647d", 1), ("%1000000000d", 1), ("%.999999991x", 1)] {
+            let args = [Value::Text(spec.to_string()), Value::Integer(arg)];
+
+            assert_eq!(printf(&args).unwrap(), Value::Null, "{spec}");
+        }
+    }
+}
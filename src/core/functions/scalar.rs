@@ -0,0 +1,333 @@
+use super::printf::round_to_places;
+use super::{text, too_big, MAX_LENGTH};
+use crate::core::eval::{is_true, to_integer, to_real};
+use crate::core::value::{to_hex, Value};
+use crate::error::{Error, Result};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(super) fn abs(args: &[Value]) -> Result<Value> {
+    Ok(match &args[0] {
+        Value::Null => Value::Null,
+        Value::Integer(value) => Value::Integer(
+            value
+                .checked_abs()
+                .ok_or_else(|| Error::Misuse("integer overflow".to_string()))?,
+        ),
+
+        // text and blobs are read as reals
+        value => Value::Real(to_real(value).abs()),
+    })
+}
+
+// The characters with the given code points, where NULL counts as 0 and a number that is
+// not a code point gives the replacement character
+pub(super) fn char(args: &[Value]) -> Result<Value> {
+    Ok(Value::Text(
+        args.iter()
+            .map(|arg| {
+                let code = to_integer(arg).unwrap_or(0);
+
+                u32::try_from(code)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER)
+            })
+            .collect(),
+    ))
+}
+
+// coalesce() and ifnull(): the first argument that is not NULL
+pub(super) fn coalesce(args: &[Value]) -> Result<Value> {
+    Ok(args.iter().find(|arg| !arg.is_null()).cloned().unwrap_or(Value::Null))
+}
+
+// The bytes of a blob, or of the text of anything else, in upper case hex
+pub(super) fn hex(args: &[Value]) -> Result<Value> {
+    Ok(Value::Text(match &args[0] {
+        Value::Blob(bytes) => to_hex(bytes),
+        value => to_hex(text(value).unwrap_or_default().as_bytes()),
+    }))
+}
+
+// iif(condition, value, ...): the value after the first true condition, or the last
+// argument when there is one left over, or NULL
+pub(super) fn iif(args: &[Value]) -> Result<Value> {
+    for pair in args.chunks(2) {
+        match pair {
+            [condition, value] if is_true(condition) => return Ok(value.clone()),
+            [otherwise] => return Ok(otherwise.clone()),
+            _ => {}
+        }
+    }
+
+    Ok(Value::Null)
+}
+
+// Where the second argument first appears in the first, counting characters from 1, or
+// bytes when both are blobs. 0 when it does not appear.
+pub(super) fn instr(args: &[Value]) -> Result<Value> {
+    let position = match (&args[0], &args[1]) {
+        (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
+
+        (Value::Blob(haystack), Value::Blob(needle)) => match needle.is_empty() {
+            true => Some(0),
+            false => haystack.windows(needle.len()).position(|window| window == needle.as_slice()),
+        },
+
+        (haystack, needle) => {
+            let haystack = text(haystack).unwrap_or_default();
+
+            haystack
+                .find(&text(needle).unwrap_or_default())
+                .map(|index| haystack[..index].chars().count())
+        }
+    };
+
+    Ok(Value::Integer(position.map_or(0, |position| position as i64 + 1)))
+}
+
+// Characters in text, bytes in a blob, and the length of a number written out
+pub(super) fn length(args: &[Value]) -> Result<Value> {
+    Ok(match &args[0] {
+        Value::Null => Value::Null,
+        Value::Blob(bytes) => Value::Integer(bytes.len() as i64),
+        value => Value::Integer(value.to_string().chars().count() as i64),
+    })
+}
+
+// Like sqlite without ICU, only ASCII letters change case
+pub(super) fn lower(args: &[Value]) -> Result<Value> {
+    Ok(text(&args[0]).map_or(Value::Null, |text| Value::Text(text.to_ascii_lowercase())))
+}
+
+pub(super) fn upper(args: &[Value]) -> Result<Value> {
+    Ok(text(&args[0]).map_or(Value::Null, |text| Value::Text(text.to_ascii_uppercase())))
+}
+
+pub(super) fn trim(args: &[Value]) -> Result<Value> {
+    trim_sides(args, true, true)
+}
+
+pub(super) fn ltrim(args: &[Value]) -> Result<Value> {
+    trim_sides(args, true, false)
+}
+
+pub(super) fn rtrim(args: &[Value]) -> Result<Value> {
+    trim_sides(args, false, true)
+}
+
+// Removes the characters of the second argument, spaces by default, from the ends of the
+// first
+fn trim_sides(args: &[Value], left: bool, right: bool) -> Result<Value> {
+    let Some(text) = text(&args[0]) else {
+        return Ok(Value::Null);
+    };
+
+    let characters: Vec<char> = match args.get(1) {
+        None => vec![' '],
+        Some(characters) => match super::text(characters) {
+            Some(characters) => characters.chars().collect(),
+            None => return Ok(Value::Null),
+        },
+    };
+
+    let mut trimmed = text.as_str();
+
+    if left {
+        trimmed = trimmed.trim_start_matches(characters.as_slice());
+    }
+
+    if right {
+        trimmed = trimmed.trim_end_matches(characters.as_slice());
+    }
+
+    Ok(Value::Text(trimmed.to_string()))
+}
+
+// min() and max() with several arguments pick among them, and are NULL if any of them is
+pub(super) fn min(args: &[Value]) -> Result<Value> {
+    pick(args, Ordering::Less)
+}
+
+pub(super) fn max(args: &[Value]) -> Result<Value> {
+    pick(args, Ordering::Greater)
+}
+
+fn pick(args: &[Value], wanted: Ordering) -> Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+
+    let mut picked = &args[0];
+
+    for arg in &args[1..] {
+        if arg.compare(picked) == wanted {
+            picked = arg;
+        }
+    }
+
+    Ok(picked.clone())
+}
+
+// The first argument, or NULL when both are the same
+pub(super) fn nullif(args: &[Value]) -> Result<Value> {
+    Ok(match args[0].compare(&args[1]) {
+        Ordering::Equal => Value::Null,
+        _ => args[0].clone(),
+    })
+}
+
+// The value as an SQL literal that reads back as the same value
+pub(super) fn quote(args: &[Value]) -> Result<Value> {
+    Ok(Value::Text(args[0].to_sql_literal()))
+}
+
+thread_local! {
+    // the state of a xorshift generator, seeded from the clock and the process so that
+    // every run gives different numbers
+    static RANDOM_STATE: Cell<u64> = Cell::new({
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+
+        (nanos ^ u64::from(std::process::id()).rotate_left(32)) | 1
+    });
+}
+
+// A pseudo-random integer anywhere in the 64-bit range
+pub(super) fn random(_: &[Value]) -> Result<Value> {
+    let state = RANDOM_STATE.with(|state| {
+        let mut x = state.get();
+
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+
+        state.set(x);
+
+        x
+    });
+
+    Ok(Value::Integer(state.wrapping_mul(0x2545_F491_4F6C_DD1D) as i64))
+}
+
+// Every occurrence of the second argument in the first replaced with the third
+pub(super) fn replace(args: &[Value]) -> Result<Value> {
+    let (Some(text), Some(pattern), Some(replacement)) = (text(&args[0]), text(&args[1]), text(&args[2])) else {
+        return Ok(Value::Null);
+    };
+
+    if pattern.is_empty() {
+        return Ok(Value::Text(text));
+    }
+
+    let growth = replacement.len().saturating_sub(pattern.len());
+
+    if text.len() + text.matches(&pattern).count() * growth > MAX_LENGTH {
+        return Err(too_big());
+    }
+
+    Ok(Value::Text(text.replace(&pattern, &replacement)))
+}
+
+// A real rounded half away from zero to between 0 and 30 decimal places
+pub(super) fn round(args: &[Value]) -> Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+
+    let places = args.get(1).and_then(to_integer).unwrap_or(0).clamp(0, 30) as usize;
+
+    let value = to_real(&args[0]);
+
+    // reals this large have no fraction left to round
+    if !value.is_finite() || value.abs() >= 4503599627370496.0 {
+        return Ok(Value::Real(value));
+    }
+
+    Ok(Value::Real(match places {
+        0 => (value.abs() + 0.5).trunc().copysign(value),
+        _ => round_to_places(value, places),
+    }))
+}
+
+// substr(text, start[, length]): characters of text, bytes of a blob, counting from 1. A
+// negative start counts from the end and a negative length takes the characters before
+// start.
+pub(super) fn substr(args: &[Value]) -> Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+
+    let start = to_integer(&args[1]).unwrap_or(0) as i128;
+
+    let length = args.get(2).map_or(i64::MAX, |length| to_integer(length).unwrap_or(0)) as i128;
+
+    match &args[0] {
+        Value::Blob(bytes) => {
+            let range = substr_range(bytes.len(), start, length);
+
+            Ok(Value::Blob(bytes[range].to_vec()))
+        }
+
+        value => {
+            let text = text(value).unwrap_or_default();
+
+            let range = substr_range(text.chars().count(), start, length);
+
+            Ok(Value::Text(text.chars().skip(range.start).take(range.len()).collect()))
+        }
+    }
+}
+
+// The same arithmetic as sqlite's substrFunc(), giving the range of characters to keep
+fn substr_range(size: usize, mut start: i128, mut length: i128) -> std::ops::Range<usize> {
+    let size = size as i128;
+
+    let negative_length = length < 0;
+
+    if negative_length {
+        length = -length;
+    }
+
+    if start < 0 {
+        start += size;
+
+        if start < 0 {
+            length = (length + start).max(0);
+            start = 0;
+        }
+    } else if start > 0 {
+        start -= 1;
+    } else if length > 0 {
+        // position 0 is just before the first character
+        length -= 1;
+    }
+
+    if negative_length {
+        start -= length;
+
+        if start < 0 {
+            length += start;
+            start = 0;
+        }
+    }
+
+    let start = start.min(size);
+    let end = (start + length.max(0)).min(size);
+
+    start as usize..end as usize
+}
+
+pub(super) fn type_of(args: &[Value]) -> Result<Value> {
+    Ok(Value::Text(args[0].type_name().to_string()))
+}
+
+// The code point of the first character
+pub(super) fn unicode(args: &[Value]) -> Result<Value> {
+    Ok(text(&args[0])
+        .and_then(|text| text.chars().next())
+        .map_or(Value::Null, |c| Value::Integer(c as i64)))
+}
@@ -0,0 +1,190 @@
+use crate::core::btree::{BTreePage, TableAppender};
+use crate::core::cell::{CellPayload, PageCell};
+use crate::core::database::Database;
+use crate::core::header::{DBHeader, TextEncoding};
+use crate::core::page::BTreePageSubType::{Interior, Leaf};
+use crate::core::page::PageTypes;
+use crate::core::page::PageTypes::TableBTree;
+use crate::core::pager::Pager;
+use crate::core::record;
+use crate::core::value::Value;
+use crate::core::vacuum::VacuumOptions;
+use crate::core::vfs::MemoryVfs;
+use crate::parser::parse::parse_sql;
+use crate::utils;
+use crate::error::Result;
+use std::io::sink;
+use std::sync::OnceLock;
+
+// Entry points for the fuzz targets under fuzz/ and the property tests under tests/. Each
+// one hands arbitrary bytes to the file-format decoders, which have to turn anything they
+// cannot make sense of into an error: no input may panic or read out of bounds.
+
+// Small pages keep the inputs short and every page kind within reach of the fuzzer
+pub const PAGE_SIZE: usize = 512;
+
+// More pages than this are not inspected one by one, the whole-file checks still see them
+const MAX_INSPECTED_PAGES: u32 = 64;
+
+const SCHEMA: [(&str, &str, &str, &str); 2] = [
+    ("table", "t", "t", "CREATE TABLE t(a INTEGER PRIMARY KEY, b TEXT, c)"),
+    ("index", "i", "t", "CREATE INDEX i ON t(b, c)"),
+];
+
+const QUERIES: [&str; 6] = [
+    "SELECT * FROM t",
+    "SELECT count(*) FROM t",
+    "SELECT a, c FROM t WHERE a = 7",
+    "SELECT a FROM t WHERE b = 'x'",
+    "SELECT b, c FROM t LIMIT 5",
+    "PRAGMA integrity_check",
+];
+
+pub fn parse_varint(bytes: &[u8]) -> Result<(u64, usize)> {
+    utils::parse_varint(bytes).map(|(value, _, len)| (value, len))
+}
+
+pub fn encode_varint(value: u64) -> Vec<u8> {
+    utils::encode_varint(value)
+}
+
+pub fn encode_record(values: &[Value]) -> Vec<u8> {
+    record::encode_record(values)
+}
+
+// Decodes a record the way a table leaf cell payload is decoded, values included
+pub fn decode_record(bytes: &[u8]) -> Result<Vec<Value>> {
+    CellPayload::new(bytes, TableBTree(Leaf), &TextEncoding::Utf8)?.values()
+}
+
+pub fn decode_header(bytes: &[u8]) -> Result<()> {
+    let header = DBHeader::new(bytes)?;
+
+    header.get_usable_size();
+    header.should_omit_pointer_map();
+    header.page_count(bytes.len() as u64);
+
+    Ok(())
+}
+
+// Decodes one cell of the page type given by its flag byte, any overflow pages it points
+// to are read from the cell bytes themselves
+pub fn decode_cell(page_type: u8, bytes: &[u8]) -> Result<()> {
+    let page_type = PageTypes::try_from(page_type)?;
+
+    let file = MemoryVfs::new(bytes.to_vec());
+
+    let cell = PageCell::new(bytes, page_type, PAGE_SIZE as u64, &TextEncoding::Utf8, &file, PAGE_SIZE as u32)?;
+
+    if page_type != TableBTree(Interior) {
+        cell.payload.values()?;
+    }
+
+    Ok(())
+}
+
+// A database of `PAGE_SIZE` pages whose first page holds the schema of a table and an index
+// on it, with `pages` taking the place of everything after: the table root comes first, the
+// index root second and anything else is there for the b-trees to point to
+pub fn database_with_pages(pages: &[u8]) -> Vec<u8> {
+    static FIRST_PAGE: OnceLock<Vec<u8>> = OnceLock::new();
+
+    let first_page = FIRST_PAGE.get_or_init(|| schema_page().expect("the schema page is always valid"));
+
+    let page_count = 1 + pages.len().div_ceil(PAGE_SIZE).max(2);
+
+    let mut image = vec![0u8; page_count * PAGE_SIZE];
+
+    image[..PAGE_SIZE].copy_from_slice(first_page);
+    image[PAGE_SIZE..PAGE_SIZE + pages.len()].copy_from_slice(pages);
+
+    // the size in the header has to agree with the file for it to be trusted
+    image[28..32].copy_from_slice(&(page_count as u32).to_be_bytes());
+
+    image
+}
+
+fn schema_page() -> Result<Vec<u8>> {
+    let header = DBHeader::empty(PAGE_SIZE as u32, 0);
+
+    let mut image = vec![0u8; PAGE_SIZE];
+
+    header.write(&mut image);
+
+    BTreePage::empty(1, TableBTree(Leaf)).write_to(&mut image, PAGE_SIZE);
+
+    let file = MemoryVfs::new(image);
+
+    let mut pager = Pager::new(&file, header)?;
+
+    let mut schema = TableAppender::new(&mut pager, 1)?;
+
+    for (i, (kind, name, table, sql)) in SCHEMA.iter().enumerate() {
+        let values = [
+            Value::Text(kind.to_string()),
+            Value::Text(name.to_string()),
+            Value::Text(table.to_string()),
+            Value::Integer(i as i64 + 2),
+            Value::Text(sql.to_string()),
+        ];
+
+        schema.append(i as i64 + 1, &record::encode_record(&values))?;
+    }
+
+    schema.finish()?;
+
+    pager.commit()?;
+
+    drop(pager);
+
+    let mut image = file.to_vec();
+
+    image.truncate(PAGE_SIZE);
+
+    Ok(image)
+}
+
+// Opens a database image and runs everything that reads it: the schema, queries, the
+// integrity check, the inspection and recovery commands and finally a VACUUM. Errors are
+// expected and ignored, only a panic is a failure.
+pub fn exercise_database(image: Vec<u8>) {
+    let Ok(mut db) = Database::deserialize(image) else {
+        return;
+    };
+
+    let schemas = db.get_schemas().unwrap_or_default();
+
+    for schema in &schemas {
+        let _ = db.get_data(schema);
+        let _ = db.count_records(&schema.name);
+        let _ = db.inspect_btree(&mut sink(), &schema.name);
+    }
+
+    for sql in QUERIES {
+        let _ = query(&db, sql);
+    }
+
+    let _ = db.integrity_check(100, false, None);
+    let _ = db.freelist();
+    let _ = db.analyze(&mut sink());
+    let _ = db.recover(&mut sink());
+    let _ = db.inspect_btree(&mut sink(), "sqlite_schema");
+
+    let page_count = db.header().page_count(db.vfs().size().unwrap_or(0));
+
+    for page_number in 1..=page_count.min(MAX_INSPECTED_PAGES) {
+        let _ = db.page_type(page_number);
+        let _ = db.inspect_page(&mut sink(), page_number);
+    }
+
+    let _ = db.vacuum(&VacuumOptions::default());
+}
+
+fn query(db: &Database, sql: &str) -> Result<usize> {
+    let statement = parse_sql(sql)?;
+
+    db.describe(&statement)?;
+
+    Ok(db.query(&statement)?.rows.len())
+}
+
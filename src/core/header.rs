@@ -1,6 +1,10 @@
-use anyhow::Result;
+use crate::core::vfs::PENDING_BYTE_OFFSET;
+use crate::error::{Error, Result};
 use std::fmt::{Display, Formatter};
 
+// The sqlite release whose file format we write, recorded in the header of new databases
+const SQLITE_VERSION_NUMBER: u32 = 3_045_000;
+
 #[derive(Debug, Clone)]
 pub enum TextEncoding {
     Utf8,
@@ -45,7 +49,7 @@ pub struct DBHeader {
     pub min_embedded_format: u8,
     pub leaf_payload_fraction: u8,
     pub file_change_counter: u32,
-    pub page_size: u16,
+    pub page_size: u32,
     pub first_free_page: u32,
     pub free_page_list_size: u32,
     pub schema_cookie: u32,
@@ -65,13 +69,32 @@ pub struct DBHeader {
 
 impl DBHeader {
     pub fn new(buffer: &[u8]) -> Result<Self> {
-        let page_size = u16::from_be_bytes([buffer[16], buffer[17]]);
+        if buffer.len() < 100 || !buffer.starts_with(b"SQLite format 3\0") {
+            return Err(Error::corrupt(0, "file is not a database"));
+        }
+
+        // a page size of 65536 does not fit in two bytes and is stored as 1
+        let page_size = match u16::from_be_bytes([buffer[16], buffer[17]]) {
+            1 => 65536,
+            size => u32::from(size),
+        };
+
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return Err(Error::corrupt(0, format!("invalid page size {page_size}")));
+        }
 
         let file_read_version = u8::from_be_bytes([buffer[18]]);
 
         let file_write_version = u8::from_be_bytes([buffer[19]]);
 
-        let reserved_bytes_per_page = u16::from_be_bytes([buffer[20], buffer[21]]);
+        let reserved_bytes_per_page = u16::from(buffer[20]);
+
+        if page_size - u32::from(reserved_bytes_per_page) < 480 {
+            return Err(Error::corrupt(
+                0,
+                format!("{reserved_bytes_per_page} reserved bytes leave too little room on {page_size} byte pages"),
+            ));
+        }
 
         let file_change_counter =
             u32::from_be_bytes([buffer[24], buffer[25], buffer[26], buffer[27]]);
@@ -96,14 +119,14 @@ impl DBHeader {
         let text_encoding = u32::from_be_bytes([buffer[56], buffer[57], buffer[58], buffer[59]]);
 
         let user_version_number =
-            u32::from_be_bytes([buffer[60], buffer[61], buffer[62], buffer[62]]);
+            u32::from_be_bytes([buffer[60], buffer[61], buffer[62], buffer[63]]);
 
         let incremental_vacuum =
             u32::from_be_bytes([buffer[64], buffer[65], buffer[66], buffer[67]]);
 
         let application_id = u32::from_be_bytes([buffer[68], buffer[69], buffer[70], buffer[71]]);
 
-        let change_counter = u32::from_be_bytes([buffer[92], buffer[93], buffer[93], buffer[94]]);
+        let change_counter = u32::from_be_bytes([buffer[92], buffer[93], buffer[94], buffer[95]]);
 
         let sqlite_version_number =
             u32::from_be_bytes([buffer[96], buffer[97], buffer[98], buffer[99]]);
@@ -135,20 +158,101 @@ impl DBHeader {
         })
     }
 
+    // Header of a freshly created database holding only the empty schema table on page 1
+    pub fn empty(page_size: u32, reserved_bytes_per_page: u16) -> Self {
+        Self {
+            page_size,
+            reserved_bytes_per_page,
+
+            db_size: 1,
+            file_read_version: 1,
+            file_write_version: 1,
+            file_change_counter: 1,
+            change_counter: 1,
+            first_free_page: 0,
+            free_page_list_size: 0,
+            schema_cookie: 0,
+            schema_format_number: 4,
+            suggested_cache_size: 0,
+            auto_vacuum: 0,
+            incremental_vacuum: 0,
+            user_version_number: 0,
+            application_id: 0,
+            sqlite_version_number: SQLITE_VERSION_NUMBER,
+
+            max_embedded_format: 64,
+            min_embedded_format: 32,
+            leaf_payload_fraction: 32,
+
+            text_encoding: TextEncoding::Utf8,
+        }
+    }
+
+    // Writes the header back in its on-disk layout into the first 100 bytes of the buffer
+    pub fn write(&self, buffer: &mut [u8]) {
+        let text_encoding: u32 = match self.text_encoding {
+            TextEncoding::Utf8 => 1,
+            TextEncoding::Utf16le => 2,
+            TextEncoding::Utf16be => 3,
+        };
+
+        buffer[0..16].copy_from_slice(b"SQLite format 3\0");
+        buffer[16..18].copy_from_slice(&(if self.page_size == 65536 { 1 } else { self.page_size as u16 }).to_be_bytes());
+        buffer[18] = self.file_read_version;
+        buffer[19] = self.file_write_version;
+        buffer[20] = self.reserved_bytes_per_page as u8;
+        buffer[21] = self.max_embedded_format;
+        buffer[22] = self.min_embedded_format;
+        buffer[23] = self.leaf_payload_fraction;
+        buffer[24..28].copy_from_slice(&self.file_change_counter.to_be_bytes());
+        buffer[28..32].copy_from_slice(&self.db_size.to_be_bytes());
+        buffer[32..36].copy_from_slice(&self.first_free_page.to_be_bytes());
+        buffer[36..40].copy_from_slice(&self.free_page_list_size.to_be_bytes());
+        buffer[40..44].copy_from_slice(&self.schema_cookie.to_be_bytes());
+        buffer[44..48].copy_from_slice(&self.schema_format_number.to_be_bytes());
+        buffer[48..52].copy_from_slice(&self.suggested_cache_size.to_be_bytes());
+        buffer[52..56].copy_from_slice(&self.auto_vacuum.to_be_bytes());
+        buffer[56..60].copy_from_slice(&text_encoding.to_be_bytes());
+        buffer[60..64].copy_from_slice(&self.user_version_number.to_be_bytes());
+        buffer[64..68].copy_from_slice(&self.incremental_vacuum.to_be_bytes());
+        buffer[68..72].copy_from_slice(&self.application_id.to_be_bytes());
+        buffer[72..92].fill(0);
+        buffer[92..96].copy_from_slice(&self.change_counter.to_be_bytes());
+        buffer[96..100].copy_from_slice(&self.sqlite_version_number.to_be_bytes());
+    }
+
     pub fn is_db_size_valid(&self) -> bool {
         // The in-header database size is only considered to be valid if it is non-zero
         // and the change counter exactly matches the version-valid-for number.
         self.db_size > 0 && (self.file_change_counter == self.change_counter)
     }
 
+    // Number of pages in the database, from the header when it can be trusted and from
+    // the size of the file otherwise. A header claiming pages past the end of the file is
+    // wrong, and believing it would have us walk billions of pages that are not there.
+    pub fn page_count(&self, file_size: u64) -> u32 {
+        let file_pages = (file_size / u64::from(self.page_size)).min(u64::from(u32::MAX)) as u32;
+
+        if self.is_db_size_valid() {
+            self.db_size.min(file_pages)
+        } else {
+            file_pages
+        }
+    }
+
+    // The page holding the lock bytes, which is never used for data
+    pub fn lock_byte_page(&self) -> u32 {
+        (PENDING_BYTE_OFFSET / u64::from(self.page_size)) as u32 + 1
+    }
+
     pub fn should_omit_pointer_map(&self) -> bool {
         // If the integer at offset 52 is zero then pointer-map (ptrmap) pages are omitted from the database file
         // and neither auto_vacuum nor incremental_vacuum are supported
         self.auto_vacuum == 0
     }
 
-    pub fn get_usable_size(&self) -> u16 {
-        self.page_size - self.reserved_bytes_per_page
+    pub fn get_usable_size(&self) -> u32 {
+        self.page_size - u32::from(self.reserved_bytes_per_page)
     }
 }
 
@@ -177,7 +281,7 @@ software version:    {}",
             self.file_read_version,
             self.reserved_bytes_per_page,
             self.file_change_counter,
-            self.first_free_page + self.free_page_list_size,
+            self.db_size,
             self.free_page_list_size,
             self.schema_cookie,
             self.schema_format_number,
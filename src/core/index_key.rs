@@ -0,0 +1,195 @@
+use crate::core::schema::{SchemaTable, SchemaTypesTypes};
+use crate::core::value::Value;
+use crate::error::{Error, Result};
+use crate::parser::ast::{ColumnConstraint, CreateTableStmt};
+use std::cmp::Ordering;
+
+// How an index orders the text of a column
+#[derive(Debug, Clone, Copy)]
+enum Collation {
+    Binary,
+    NoCase,
+    Rtrim,
+}
+
+impl Collation {
+    fn new(name: &str) -> Result<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "BINARY" => Ok(Collation::Binary),
+            "NOCASE" => Ok(Collation::NoCase),
+            "RTRIM" => Ok(Collation::Rtrim),
+            _ => Err(Error::Misuse(format!("no such collation sequence: {name}"))),
+        }
+    }
+
+    fn compare(self, a: &Value, b: &Value) -> Ordering {
+        match (self, a, b) {
+            (Collation::NoCase, Value::Text(a), Value::Text(b)) => a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase()),
+            (Collation::Rtrim, Value::Text(a), Value::Text(b)) => a.trim_end_matches(' ').cmp(b.trim_end_matches(' ')),
+            _ => a.compare(b),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct KeyColumn {
+    column: usize,
+    collation: Collation,
+    descending: bool,
+}
+
+// An index of a table as the rows written to the table need it: its entries hold the values
+// of some of the table's columns followed by the rowid
+#[derive(Debug)]
+pub(crate) struct IndexKey {
+    pub(crate) root_page: u32,
+    pub(crate) unique: bool,
+    columns: Vec<KeyColumn>,
+}
+
+impl IndexKey {
+    // The indexes of a table. Those on expressions and partial ones are not supported, their
+    // entries would need the expressions evaluated.
+    pub(crate) fn of_table(schemas: &[SchemaTable], table: &CreateTableStmt) -> Result<Vec<IndexKey>> {
+        schemas
+            .iter()
+            .filter(|schema| {
+                schema.schema_type == SchemaTypesTypes::Index
+                    && schema.root_page > 0
+                    && schema.tbl_name.eq_ignore_ascii_case(&table.name)
+            })
+            .map(|schema| IndexKey::new(schema, table))
+            .collect()
+    }
+
+    // The index `schema` describes, on `table`
+    pub(crate) fn new(schema: &SchemaTable, table: &CreateTableStmt) -> Result<IndexKey> {
+        let mut columns = vec![];
+
+        let unique = match schema.index() {
+            Some(definition) => {
+                if definition.where_clause.is_some() {
+                    let message = format!("writing to a table with the partial index {}", schema.name);
+
+                    return Err(Error::Unsupported(message));
+                }
+
+                for indexed in &definition.columns {
+                    let Some(column) = indexed.expr.column_name().and_then(|name| table.column_index(name)) else {
+                        let message = format!("writing to a table with the index {} on expressions", schema.name);
+
+                        return Err(Error::Unsupported(message));
+                    };
+
+                    let collation = match &indexed.collation {
+                        Some(name) => Collation::new(name)?,
+                        None => column_collation(table, column)?,
+                    };
+
+                    columns.push(KeyColumn {
+                        column,
+                        collation,
+                        descending: indexed.descending,
+                    });
+                }
+
+                definition.unique
+            }
+
+            // automatic indexes are numbered in the order of the table's constraints
+            None => {
+                let autoindex_prefix = format!("sqlite_autoindex_{}_", table.name);
+
+                let key = schema
+                    .name
+                    .strip_prefix(&autoindex_prefix)
+                    .and_then(|number| number.parse::<usize>().ok())
+                    .and_then(|number| table.unique_constraints().get(number.checked_sub(1)?).cloned())
+                    .ok_or_else(|| {
+                        Error::corrupt(1, format!("no constraint of {} makes index {}", table.name, schema.name))
+                    })?;
+
+                for column in key {
+                    columns.push(KeyColumn {
+                        column,
+                        collation: column_collation(table, column)?,
+                        descending: false,
+                    });
+                }
+
+                true
+            }
+        };
+
+        Ok(IndexKey {
+            root_page: schema.root_page as u32,
+            unique,
+            columns,
+        })
+    }
+
+    // The entry of a row: the indexed values, the INTEGER PRIMARY KEY standing for the
+    // rowid, and the rowid
+    pub(crate) fn entry(&self, values: &[Value], rowid: i64, rowid_alias: Option<usize>) -> Vec<Value> {
+        let mut entry = self
+            .columns
+            .iter()
+            .map(|key| {
+                if rowid_alias == Some(key.column) {
+                    Value::Integer(rowid)
+                } else {
+                    values.get(key.column).cloned().unwrap_or(Value::Null)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        entry.push(Value::Integer(rowid));
+
+        entry
+    }
+
+    // The entry without its rowid, which no other entry of a UNIQUE index may have. NULLs
+    // are never equal, so a key holding one does not need to be unique.
+    pub(crate) fn unique_key<'a>(&self, entry: &'a [Value]) -> Option<&'a [Value]> {
+        let key = &entry[..self.columns.len()];
+
+        (self.unique && !key.iter().any(Value::is_null)).then_some(key)
+    }
+
+    // The columns of the index, as sqlite names them when a UNIQUE constraint fails
+    pub(crate) fn describe(&self, table: &CreateTableStmt) -> String {
+        self.columns
+            .iter()
+            .map(|key| format!("{}.{}", table.name, table.columns[key.column].name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // Orders entries the way the index does. Only the values both have are compared, so a
+    // key without the rowid is equal to every entry starting with it.
+    pub(crate) fn compare(&self, a: &[Value], b: &[Value]) -> Ordering {
+        for (i, (a, b)) in a.iter().zip(b).enumerate() {
+            let ordering = match self.columns.get(i) {
+                Some(key) if key.descending => key.collation.compare(b, a),
+                Some(key) => key.collation.compare(a, b),
+                None => a.compare(b),
+            };
+
+            if ordering.is_ne() {
+                return ordering;
+            }
+        }
+
+        Ordering::Equal
+    }
+}
+
+// The collation a column is declared with, which its indexes use unless they name another
+fn column_collation(table: &CreateTableStmt, column: usize) -> Result<Collation> {
+    let declared = table.columns[column].constraints.iter().find_map(|constraint| match constraint {
+        ColumnConstraint::Collate(name) => Some(name),
+        _ => None,
+    });
+
+    declared.map_or(Ok(Collation::Binary), |name| Collation::new(name))
+}
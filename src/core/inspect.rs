@@ -0,0 +1,324 @@
+use crate::core::database::Database;
+use crate::core::freelist::FreelistTrunk;
+use crate::core::page::BTreePageSubType::Interior;
+use crate::core::page::PageTypes::{IndexBTree, TableBTree};
+use crate::core::page::{Page, PageTypes, MAX_BTREE_DEPTH};
+use crate::core::ptrmap::{PtrmapLayout, PtrmapType};
+use crate::core::schema::SchemaTable;
+use crate::error::{Error, Result};
+use std::collections::HashSet;
+use std::io::Write;
+
+// Longer values are cut short in cell listings, the hex view still has every byte
+const MAX_VALUE_LEN: usize = 40;
+
+const HEX_LINE_LEN: usize = 16;
+
+impl Database {
+    // Prints everything we know about one page: its decoded header, each cell, the free
+    // blocks and the raw bytes. Pages that fail to decode still get their hex view, since
+    // that is usually what a corrupt file needs.
+    pub fn inspect_page(&self, out: &mut dyn Write, page_number: u32) -> Result<()> {
+        let page_count = self.header().page_count(self.vfs().size()?);
+
+        if page_number == 0 || page_number > page_count {
+            return Err(Error::Misuse(format!(
+                "page {page_number} is out of range, the database has {page_count} pages"
+            )));
+        }
+
+        let page_size = self.header().page_size as usize;
+
+        let mut buffer = vec![0u8; page_size];
+
+        self.vfs()
+            .read_at(&mut buffer, u64::from(page_number - 1) * page_size as u64)
+            .map_err(|error| error.past_end().on_page(page_number))?;
+
+        let page_type = self.page_type(page_number)?;
+
+        writeln!(out, "page {page_number}: {page_type}")?;
+
+        match page_type {
+            TableBTree(_) | IndexBTree(_) => match self.read_page(page_number as i32) {
+                Ok(page) => self.write_btree_page(out, &page, &buffer)?,
+                Err(error) => writeln!(out, "cannot decode page: {error}")?,
+            },
+
+            PageTypes::FreeList if self.freelist()?.is_trunk(page_number) => {
+                let trunk = FreelistTrunk::parse(page_number, &buffer, self.header().get_usable_size())?;
+
+                writeln!(out, "next trunk page:     {}", trunk.next)?;
+                writeln!(out, "leaf pages:          {:?}", trunk.leaves)?;
+            }
+
+            PageTypes::PayloadOverflow => {
+                writeln!(out, "next overflow page:  {}", u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]))?;
+            }
+
+            PageTypes::PointerMap => self.write_ptrmap_page(out, page_number, &buffer)?,
+
+            _ => {}
+        }
+
+        writeln!(out, "hex:")?;
+
+        write_hex(out, &buffer)
+    }
+
+    // Prints the pages of a table or index b-tree as a tree, from its root page down
+    pub fn inspect_btree(&self, out: &mut dyn Write, name: &str) -> Result<()> {
+        let root_page = if ["sqlite_schema", "sqlite_master"].iter().any(|schema| schema.eq_ignore_ascii_case(name)) {
+            1
+        } else {
+            self.get_schemas()?
+                .into_iter()
+                .find(|schema: &SchemaTable| schema.root_page > 0 && schema.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| Error::NoSuchTable(name.to_string()))?
+                .root_page as u32
+        };
+
+        let mut visited = HashSet::new();
+
+        self.write_btree_node(out, root_page, "", "", "", &mut visited)
+    }
+
+    fn write_btree_page(&self, out: &mut dyn Write, page: &Page, buffer: &[u8]) -> Result<()> {
+        writeln!(out, "free block start:    {}", page.free_block_start)?;
+        writeln!(out, "cell count:          {}", page.num_of_cells)?;
+        writeln!(out, "content area start:  {}", page.content_area_start)?;
+        writeln!(out, "fragmented bytes:    {}", page.num_of_fragmented_free_bytes)?;
+
+        if let Some(pointer) = page.right_most_pointer {
+            writeln!(out, "right-most pointer:  {pointer}")?;
+        }
+
+        writeln!(out, "cells:")?;
+
+        for (i, (pointer, cell)) in page.cell_pointers.iter().zip(&page.cells).enumerate() {
+            let mut parts = vec![];
+
+            if let Some(child) = cell.left_pointer {
+                parts.push(format!("left child {child}"));
+            }
+
+            if matches!(page.page_type, TableBTree(_)) {
+                parts.push(format!("rowid {}", cell.row_id as i64));
+            }
+
+            if page.page_type != TableBTree(Interior) {
+                let values = cell
+                    .payload
+                    .values()?
+                    .iter()
+                    .map(|value| shorten(value.to_sql_literal()))
+                    .collect::<Vec<_>>();
+
+                parts.push(format!("{} bytes [{}]", cell.cell_size, values.join(", ")));
+            }
+
+            if cell.overflow_pointers != 0 {
+                parts.push(format!("overflow page {}", cell.overflow_pointers));
+            }
+
+            writeln!(out, "  {i:>4} @ {pointer:>5}: {}", parts.join(", "))?;
+        }
+
+        writeln!(out, "free blocks:")?;
+
+        let usable_size = self.header().get_usable_size() as usize;
+
+        let mut offset = page.free_block_start as usize;
+
+        // each free block starts with the offset of the next one and its own size, and the
+        // chain only goes forward, anything else means the page is corrupt
+        while offset != 0 {
+            if offset + 4 > usable_size {
+                writeln!(out, "  free block at {offset} is past the end of the page")?;
+                break;
+            }
+
+            let next = u16::from_be_bytes([buffer[offset], buffer[offset + 1]]) as usize;
+            let size = u16::from_be_bytes([buffer[offset + 2], buffer[offset + 3]]) as usize;
+
+            writeln!(out, "  {offset:>5}: {size} bytes")?;
+
+            if next != 0 && next <= offset + size {
+                writeln!(out, "  next free block {next} does not come after this one")?;
+                break;
+            }
+
+            offset = next;
+        }
+
+        Ok(())
+    }
+
+    fn write_ptrmap_page(&self, out: &mut dyn Write, page_number: u32, buffer: &[u8]) -> Result<()> {
+        let layout = PtrmapLayout::new(self.header());
+
+        let entries = self.header().get_usable_size() / 5;
+
+        writeln!(out, "entries:")?;
+
+        for page in page_number + 1..=page_number + entries {
+            if layout.map_page(page) != page_number {
+                break;
+            }
+
+            let offset = layout.entry_offset(page)?;
+
+            // entries of pages that do not exist yet are left zeroed
+            if buffer[offset] == 0 {
+                continue;
+            }
+
+            match layout.read_entry(page, buffer) {
+                Ok(entry) => {
+                    let entry_type = match entry.entry_type {
+                        PtrmapType::RootPage => "root page",
+                        PtrmapType::FreePage => "free page",
+                        PtrmapType::Overflow1 => "first overflow page",
+                        PtrmapType::Overflow2 => "overflow page",
+                        PtrmapType::BTree => "b-tree page",
+                    };
+
+                    writeln!(out, "  {page:>6}: {entry_type}, parent {}", entry.parent)?;
+                }
+
+                Err(error) => writeln!(out, "  {page:>6}: {error}")?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // One line for the page, then its children below it. `lead` goes in front of this page's
+    // line and `indent` in front of everything under it.
+    fn write_btree_node(
+        &self,
+        out: &mut dyn Write,
+        page_number: u32,
+        lead: &str,
+        indent: &str,
+        label: &str,
+        visited: &mut HashSet<u32>,
+    ) -> Result<()> {
+        if !visited.insert(page_number) {
+            writeln!(out, "{lead}{page_number}{label}: already seen, the b-tree has a loop")?;
+
+            return Ok(());
+        }
+
+        // every level below the root adds four characters to the indent
+        if lead.chars().count() / 4 > MAX_BTREE_DEPTH {
+            writeln!(out, "{lead}{page_number}{label}: b-tree is too deep")?;
+
+            return Ok(());
+        }
+
+        let page = match self.read_page(page_number as i32) {
+            Ok(page) => page,
+
+            Err(error) => {
+                writeln!(out, "{lead}{page_number}{label}: {error}")?;
+
+                return Ok(());
+            }
+        };
+
+        writeln!(out, "{lead}{page_number} {}, {} cells{label}", page.page_type, page.num_of_cells)?;
+
+        let mut children = vec![];
+
+        for cell in &page.cells {
+            if let Some(child) = cell.left_pointer {
+                let label = match page.page_type {
+                    TableBTree(_) => format!(" (rowid <= {})", cell.row_id as i64),
+                    _ => String::new(),
+                };
+
+                children.push((child, label));
+            }
+        }
+
+        if let Some(child) = page.right_most_pointer {
+            let label = match (page.page_type, page.cells.last()) {
+                (TableBTree(_), Some(cell)) => format!(" (rowid > {})", cell.row_id as i64),
+                _ => String::new(),
+            };
+
+            children.push((child, label));
+        }
+
+        let last = children.len().saturating_sub(1);
+
+        for (i, (child, label)) in children.into_iter().enumerate() {
+            let (branch, next_indent) = if i == last { ("└── ", "    ") } else { ("├── ", "│   ") };
+
+            self.write_btree_node(
+                out,
+                child,
+                &format!("{indent}{branch}"),
+                &format!("{indent}{next_indent}"),
+                &label,
+                visited,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// Keeps a cell on one line: control characters are escaped and long values cut short
+fn shorten(literal: String) -> String {
+    let literal = literal
+        .chars()
+        .map(|c| if c.is_control() { c.escape_default().to_string() } else { c.to_string() })
+        .collect::<String>();
+
+    match literal.char_indices().nth(MAX_VALUE_LEN) {
+        Some((end, _)) => format!("{}...", &literal[..end]),
+        None => literal,
+    }
+}
+
+// Sixteen bytes a line with their printable characters, runs of identical lines are
+// collapsed into a `*` like hexdump does
+fn write_hex(out: &mut dyn Write, buffer: &[u8]) -> Result<()> {
+    let mut previous: Option<&[u8]> = None;
+    let mut collapsed = false;
+
+    for (i, line) in buffer.chunks(HEX_LINE_LEN).enumerate() {
+        if previous == Some(line) {
+            if !collapsed {
+                writeln!(out, "*")?;
+
+                collapsed = true;
+            }
+
+            continue;
+        }
+
+        previous = Some(line);
+        collapsed = false;
+
+        let hex = line
+            .iter()
+            .enumerate()
+            .map(|(j, byte)| if j == HEX_LINE_LEN / 2 { format!(" {byte:02x}") } else { format!("{byte:02x}") })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let text = line
+            .iter()
+            .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+            .collect::<String>();
+
+        writeln!(out, "{:04x}: {hex}  {text}", i * HEX_LINE_LEN)?;
+    }
+
+    writeln!(out, "{:04x}", buffer.len())?;
+
+    Ok(())
+}
@@ -0,0 +1,855 @@
+use crate::core::cell::local_payload_size;
+use crate::core::database::Database;
+use crate::core::freelist::FreelistTrunk;
+use crate::core::index_key::IndexKey;
+use crate::core::page::BTreePageSubType::{Interior, Leaf};
+use crate::core::page::PageTypes;
+use crate::core::page::PageTypes::{IndexBTree, TableBTree};
+use crate::core::schema::{SchemaTable, SchemaTypesTypes};
+use crate::core::value::Value;
+use crate::core::ptrmap::{PtrmapEntry, PtrmapLayout, PtrmapType};
+use crate::core::vfs::Vfs;
+use crate::error::{Error, Result};
+use crate::utils::parse_varint;
+use std::collections::HashMap;
+
+// sqlite reports corruption found by the b-tree layer with this error code
+const SQLITE_CORRUPT: u32 = 11;
+
+const SQLITE_IOERR: u32 = 10;
+
+// What the checker is looking at, every message is prefixed with it the way sqlite does
+#[derive(Clone, Copy)]
+enum Context {
+    None,
+    Freelist,
+    Page,
+    Cell,
+    RightChild,
+}
+
+// The size and payload of a cell, as sqlite's cell parsers compute them
+struct CellInfo {
+    key: i64,
+    payload: u64,
+    local: u64,
+    size: u32,
+}
+
+// State of one pass over the b-trees and the freelist, mirroring sqlite's IntegrityCk
+struct Checker<'db> {
+    file: &'db dyn Vfs,
+    file_size: u64,
+    page_size: u32,
+    usable_size: u32,
+    page_count: u32,
+    auto_vacuum: bool,
+    ptrmap: PtrmapLayout,
+    referenced: Vec<bool>,
+    errors: Vec<String>,
+    max_errors: usize,
+    context: Context,
+    root: u32,
+    page: u32,
+    cell: usize,
+    rows: u64,
+}
+
+impl<'db> Checker<'db> {
+    fn is_full(&self) -> bool {
+        self.errors.len() >= self.max_errors
+    }
+
+    fn report(&mut self, message: String) {
+        if self.is_full() {
+            return;
+        }
+
+        let prefix = match self.context {
+            Context::None => String::new(),
+            Context::Freelist => "Freelist: ".to_string(),
+            Context::Page => format!("Tree {} page {}: ", self.root, self.page),
+            Context::Cell => format!("Tree {} page {} cell {}: ", self.root, self.page, self.cell),
+            Context::RightChild => format!("Tree {} page {} right child: ", self.root, self.page),
+        };
+
+        self.errors.push(prefix + &message);
+    }
+
+    // Reads a whole page. Pages the header counts but the file is too short to hold read
+    // as zeros, like sqlite's pager returns them. A few spare zero bytes at the end let
+    // varints at the very end of a page be decoded without bounds checks.
+    fn read_page(&self, page: u32) -> Result<Vec<u8>> {
+        let page_size = self.page_size as usize;
+
+        let mut buffer = vec![0u8; page_size + 9];
+
+        let offset = u64::from(page - 1) * u64::from(self.page_size);
+
+        let available = self.file_size.saturating_sub(offset).min(page_size as u64) as usize;
+
+        self.file.read_at(&mut buffer[..available], offset)?;
+
+        Ok(buffer)
+    }
+
+    // Marks a page as used, reporting pages that do not exist or are used twice
+    fn check_ref(&mut self, page: u32) -> bool {
+        if page == 0 || page > self.page_count {
+            self.report(format!("invalid page number {page}"));
+            return true;
+        }
+
+        if self.referenced[page as usize] {
+            self.report(format!("2nd reference to page {page}"));
+            return true;
+        }
+
+        self.referenced[page as usize] = true;
+
+        false
+    }
+
+    fn read_ptrmap(&self, page: u32) -> Option<PtrmapEntry> {
+        let map_page = self.ptrmap.map_page(page);
+
+        if map_page == 0 || map_page > self.page_count {
+            return None;
+        }
+
+        let data = self.read_page(map_page).ok()?;
+
+        self.ptrmap.read_entry(page, &data).ok()
+    }
+
+    fn check_ptrmap(&mut self, page: u32, entry_type: PtrmapType, parent: u32) {
+        match self.read_ptrmap(page) {
+            None => self.report(format!("Failed to read ptrmap key={page}")),
+
+            Some(found) if found != PtrmapEntry::new(entry_type, parent) => self.report(format!(
+                "Bad ptr map entry key={page} expected=({},{parent}) got=({},{})",
+                entry_type as u8, found.entry_type as u8, found.parent
+            )),
+
+            Some(_) => {}
+        }
+    }
+
+    // Follows an overflow chain or the freelist trunk chain starting at `page`, which
+    // should hold `expected` pages
+    fn check_list(&mut self, is_freelist: bool, mut page: u32, expected: u32) {
+        let mut remaining = expected;
+
+        let errors_at_start = self.errors.len();
+
+        while page != 0 && !self.is_full() {
+            if self.check_ref(page) {
+                break;
+            }
+
+            remaining = remaining.wrapping_sub(1);
+
+            let Ok(data) = self.read_page(page) else {
+                self.report(format!("failed to get page {page}"));
+                break;
+            };
+
+            if is_freelist {
+                if self.auto_vacuum {
+                    self.check_ptrmap(page, PtrmapType::FreePage, 0);
+                }
+
+                match FreelistTrunk::parse(page, &data, self.usable_size) {
+                    Err(_) => {
+                        self.report(format!("freelist leaf count too big on page {page}"));
+
+                        remaining = remaining.wrapping_sub(1);
+                    }
+
+                    Ok(trunk) => {
+                        for &leaf in &trunk.leaves {
+                            if self.auto_vacuum {
+                                self.check_ptrmap(leaf, PtrmapType::FreePage, 0);
+                            }
+
+                            self.check_ref(leaf);
+                        }
+
+                        remaining = remaining.wrapping_sub(trunk.leaves.len() as u32);
+                    }
+                }
+            } else if self.auto_vacuum && remaining > 0 {
+                let next = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+
+                self.check_ptrmap(next, PtrmapType::Overflow2, page);
+            }
+
+            page = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        }
+
+        if remaining != 0 && errors_at_start == self.errors.len() {
+            self.report(format!(
+                "{} is {} but should be {}",
+                if is_freelist { "size" } else { "overflow list length" },
+                expected.wrapping_sub(remaining),
+                expected
+            ));
+        }
+    }
+
+    fn parse_cell(&self, page_type: PageTypes, cell: &[u8]) -> Result<CellInfo> {
+        let child_pointer_size = if matches!(page_type, IndexBTree(Interior) | TableBTree(Interior)) { 4 } else { 0 };
+
+        if page_type == TableBTree(Interior) {
+            let (key, _, key_size) = parse_varint(&cell[4..])?;
+
+            return Ok(CellInfo {
+                key: key as i64,
+                payload: 0,
+                local: 0,
+                size: 4 + key_size as u32,
+            });
+        }
+
+        let (payload, rest, mut header_size) = parse_varint(&cell[child_pointer_size..])?;
+
+        header_size += child_pointer_size;
+
+        let mut key = payload as i64;
+
+        if page_type == TableBTree(Leaf) {
+            let (rowid, _, rowid_size) = parse_varint(rest)?;
+
+            key = rowid as i64;
+
+            header_size += rowid_size;
+        }
+
+        let local = local_payload_size(page_type, payload, u64::from(self.usable_size));
+
+        let size = if local < payload {
+            header_size as u64 + local + 4
+        } else {
+            (header_size as u64 + payload).max(4)
+        };
+
+        Ok(CellInfo {
+            key,
+            payload,
+            local,
+            size: size.min(u64::from(u32::MAX)) as u32,
+        })
+    }
+
+    // Checks that a page is a well formed b-tree page, the same way sqlite's btreeInitPage
+    // and btreeComputeFreeSpace do before a page is used
+    fn init_page(&self, data: &[u8], header: usize) -> std::result::Result<PageTypes, &'static str> {
+        let page_type = PageTypes::try_from(data[header]).map_err(|_| "btreeInitPage")?;
+
+        let cell_count = u16::from_be_bytes([data[header + 3], data[header + 4]]) as u32;
+
+        if cell_count > (self.page_size - 8) / 6 {
+            return Err("btreeInitPage");
+        }
+
+        let child_pointer_size = if matches!(page_type, IndexBTree(Interior) | TableBTree(Interior)) { 4 } else { 0 };
+
+        let usable_size = self.usable_size;
+
+        let top = content_offset(data, header);
+
+        let first_cell = header as u32 + 8 + child_pointer_size + 2 * cell_count;
+
+        let mut block = u16::from_be_bytes([data[header + 1], data[header + 2]]) as u32;
+
+        let mut free = u32::from(data[header + 7]) + top;
+
+        if block > 0 {
+            if block < top {
+                return Err("free space");
+            }
+
+            loop {
+                if block > usable_size - 4 {
+                    return Err("free space");
+                }
+
+                let next = u16::from_be_bytes([data[block as usize], data[block as usize + 1]]) as u32;
+
+                let size = u16::from_be_bytes([data[block as usize + 2], data[block as usize + 3]]) as u32;
+
+                free += size;
+
+                if next <= block + size + 3 {
+                    if next > 0 || block + size > usable_size {
+                        return Err("free space");
+                    }
+
+                    break;
+                }
+
+                block = next;
+            }
+        }
+
+        if free > usable_size || free < first_cell {
+            return Err("free space");
+        }
+
+        Ok(page_type)
+    }
+
+    // Checks one page of a b-tree and everything below it, returning the depth of the
+    // subtree. `min_key` receives the smallest rowid found, every rowid must be at most
+    // `max_key`. This follows sqlite's checkTreePage closely so that its messages come
+    // out in the same order.
+    fn check_tree_page(&mut self, page: u32, min_key: &mut i64, mut max_key: i64) -> i32 {
+        if self.is_full() || page == 0 || self.check_ref(page) {
+            return 0;
+        }
+
+        let saved = (self.context, self.page, self.cell);
+
+        let depth = self.check_tree_page_content(page, min_key, &mut max_key);
+
+        (self.context, self.page, self.cell) = saved;
+
+        depth + 1
+    }
+
+    fn check_tree_page_content(&mut self, page: u32, min_key: &mut i64, max_key: &mut i64) -> i32 {
+        self.context = Context::Page;
+        self.page = page;
+
+        let data = match self.read_page(page) {
+            Ok(data) => data,
+            Err(_) => {
+                self.report(format!("unable to get the page. error code={SQLITE_IOERR}"));
+                return -1;
+            }
+        };
+
+        let header = if page == 1 { 100 } else { 0 };
+
+        let page_type = match self.init_page(&data, header) {
+            Ok(page_type) => page_type,
+            Err("btreeInitPage") => {
+                self.report(format!("btreeInitPage() returns error code {SQLITE_CORRUPT}"));
+                return -1;
+            }
+            Err(_) => {
+                self.report("free space corruption".to_string());
+                return -1;
+            }
+        };
+
+        let is_leaf = matches!(page_type, IndexBTree(Leaf) | TableBTree(Leaf));
+
+        let is_table = matches!(page_type, TableBTree(_));
+
+        self.context = Context::Cell;
+
+        let usable_size = self.usable_size;
+
+        let content_offset = content_offset(&data, header);
+
+        let cell_count = u16::from_be_bytes([data[header + 3], data[header + 4]]) as usize;
+
+        if is_leaf || !is_table {
+            self.rows += cell_count as u64;
+        }
+
+        let cell_start = header + if is_leaf { 8 } else { 12 };
+
+        let cell_pointer = |i: usize| u16::from_be_bytes([data[cell_start + 2 * i], data[cell_start + 2 * i + 1]]) as u32;
+
+        let mut depth = -1;
+
+        let mut key_can_be_equal = true;
+
+        if !is_leaf {
+            let child = u32::from_be_bytes([data[header + 8], data[header + 9], data[header + 10], data[header + 11]]);
+
+            if self.auto_vacuum {
+                self.context = Context::RightChild;
+
+                self.check_ptrmap(child, PtrmapType::BTree, page);
+            }
+
+            let bound = *max_key;
+
+            depth = self.check_tree_page(child, max_key, bound);
+
+            key_can_be_equal = false;
+        }
+
+        // (first byte, last byte) of every cell and freeblock, to check they don't overlap
+        let mut used: Vec<(u32, u32)> = vec![];
+
+        let mut do_coverage_check = true;
+
+        for i in (0..cell_count).rev() {
+            if self.is_full() {
+                break;
+            }
+
+            self.cell = i;
+
+            let pc = cell_pointer(i);
+
+            if pc < content_offset || pc > usable_size - 4 {
+                self.report(format!("Offset {pc} out of range {content_offset}..{}", usable_size - 4));
+
+                do_coverage_check = false;
+                continue;
+            }
+
+            let info = match self.parse_cell(page_type, &data[pc as usize..]) {
+                Ok(info) if u64::from(pc) + u64::from(info.size) <= u64::from(usable_size) => info,
+                _ => {
+                    self.report("Extends off end of page".to_string());
+
+                    do_coverage_check = false;
+                    continue;
+                }
+            };
+
+            if is_table {
+                let out_of_order = if key_can_be_equal { info.key > *max_key } else { info.key >= *max_key };
+
+                if out_of_order {
+                    self.report(format!("Rowid {} out of order", info.key));
+                }
+
+                *max_key = info.key;
+
+                key_can_be_equal = false;
+            }
+
+            if info.payload > info.local {
+                let overflow_pages = (info.payload - info.local + u64::from(usable_size) - 5) / (u64::from(usable_size) - 4);
+
+                let offset = (pc + info.size - 4) as usize;
+
+                let first = u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+
+                if self.auto_vacuum {
+                    self.check_ptrmap(first, PtrmapType::Overflow1, page);
+                }
+
+                self.check_list(false, first, overflow_pages as u32);
+            }
+
+            if is_leaf {
+                used.push((pc, pc + info.size - 1));
+            } else {
+                let offset = pc as usize;
+
+                let child = u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+
+                if self.auto_vacuum {
+                    self.check_ptrmap(child, PtrmapType::BTree, page);
+                }
+
+                let bound = *max_key;
+
+                let child_depth = self.check_tree_page(child, max_key, bound);
+
+                key_can_be_equal = false;
+
+                if child_depth != depth {
+                    self.report("Child page depth differs".to_string());
+
+                    depth = child_depth;
+                }
+            }
+        }
+
+        *min_key = *max_key;
+
+        self.context = Context::None;
+
+        if do_coverage_check && !self.is_full() {
+            if !is_leaf {
+                for i in (0..cell_count).rev() {
+                    let pc = cell_pointer(i);
+
+                    let size = self.parse_cell(page_type, &data[pc as usize..]).map_or(4, |info| info.size);
+
+                    used.push((pc, pc + size - 1));
+                }
+            }
+
+            // freeblocks were validated by init_page, so the chain is in order and on the page
+            let mut block = u16::from_be_bytes([data[header + 1], data[header + 2]]) as usize;
+
+            while block > 0 {
+                let size = u16::from_be_bytes([data[block + 2], data[block + 3]]) as u32;
+
+                used.push((block as u32, block as u32 + size - 1));
+
+                block = u16::from_be_bytes([data[block], data[block + 1]]) as usize;
+            }
+
+            used.sort_unstable();
+
+            // the header, cell pointers and the gap before the content area come first
+            let mut previous_end = content_offset - 1;
+
+            let mut fragmented = 0;
+
+            // like sqlite, fragmentation is still checked when the overlap is in the last entry
+            let mut unchecked = 0;
+
+            for (i, &(start, end)) in used.iter().enumerate() {
+                if previous_end & 0xffff >= start {
+                    self.report(format!("Multiple uses for byte {start} of page {page}"));
+
+                    unchecked = used.len() - i - 1;
+                    break;
+                }
+
+                fragmented += start - (previous_end & 0xffff) - 1;
+
+                previous_end = end;
+            }
+
+            fragmented += usable_size.wrapping_sub(previous_end & 0xffff).wrapping_sub(1);
+
+            let reported = u32::from(data[header + 7]);
+
+            if unchecked == 0 && fragmented != reported {
+                self.report(format!(
+                    "Fragmentation of {fragmented} bytes reported as {reported} on page {page}"
+                ));
+            }
+        }
+
+        depth
+    }
+}
+
+// Start of the cell content area, where 0 stands for 65536
+fn content_offset(data: &[u8], header: usize) -> u32 {
+    match u16::from_be_bytes([data[header + 5], data[header + 6]]) {
+        0 => 65536,
+        offset => u32::from(offset),
+    }
+}
+
+// An index whose content can be checked against its table
+struct IndexCheck<'schema> {
+    name: &'schema str,
+    root: u32,
+    partial: bool,
+
+    // how entries are made from rows and ordered, None for indexes on expressions
+    key: Option<IndexKey>,
+}
+
+impl Database {
+    // Runs the same checks as sqlite's PRAGMA integrity_check and returns one message per
+    // problem found, or "ok". `quick` leaves out the slow comparison of indexes with their
+    // tables, like quick_check does. With `table`, only that table and its indexes are
+    // checked and pages are not accounted for.
+    pub fn integrity_check(&self, max_errors: usize, quick: bool, table: Option<&str>) -> Result<Vec<String>> {
+        let file = self.vfs();
+
+        let header = self.header();
+
+        let file_size = file.size()?;
+
+        let page_count = header.page_count(file_size);
+
+        let schemas = self.get_schemas()?;
+
+        let tables = schemas
+            .iter()
+            .filter(|schema| schema.schema_type == SchemaTypesTypes::Table)
+            .filter(|schema| table.map_or(true, |name| schema.tbl_name.eq_ignore_ascii_case(name)))
+            .collect::<Vec<_>>();
+
+        if let Some(name) = table {
+            if tables.is_empty() {
+                return Err(Error::NoSuchTable(name.to_string()));
+            }
+        }
+
+        let mut checker = Checker {
+            file,
+            file_size,
+            page_size: header.page_size,
+            usable_size: header.get_usable_size(),
+            page_count,
+            auto_vacuum: !header.should_omit_pointer_map(),
+            ptrmap: PtrmapLayout::new(header),
+            referenced: vec![false; page_count as usize + 1],
+            errors: vec![],
+            max_errors,
+            context: Context::None,
+            root: 0,
+            page: 0,
+            cell: 0,
+            rows: 0,
+        };
+
+        let mut rows = vec![];
+
+        if page_count == 0 {
+            return Ok(vec!["ok".to_string()]);
+        }
+
+        let partial = table.is_some();
+
+        // rows counted in every b-tree, by root page
+        let mut counts = HashMap::new();
+
+        let mut roots = vec![];
+
+        if !partial {
+            roots.push(1);
+        }
+
+        for table in &tables {
+            roots.push(table.root_page.max(0) as u32);
+
+            for index in table_indexes(&schemas, table) {
+                roots.push(index.root);
+            }
+        }
+
+        let lock_byte_page = header.lock_byte_page();
+
+        if lock_byte_page <= page_count {
+            checker.referenced[lock_byte_page as usize] = true;
+        }
+
+        if !partial {
+            checker.context = Context::Freelist;
+
+            checker.check_list(true, header.first_free_page, header.free_page_list_size);
+
+            checker.context = Context::None;
+
+            if checker.auto_vacuum {
+                let max_root = roots.iter().copied().max().unwrap_or(0);
+
+                if max_root != header.auto_vacuum {
+                    checker.report(format!(
+                        "max rootpage ({max_root}) disagrees with header ({})",
+                        header.auto_vacuum
+                    ));
+                }
+            } else if header.incremental_vacuum != 0 {
+                checker.report("incremental_vacuum enabled with a max rootpage of zero".to_string());
+            }
+        }
+
+        for &root in &roots {
+            if checker.is_full() {
+                break;
+            }
+
+            if root == 0 {
+                continue;
+            }
+
+            checker.rows = 0;
+
+            if checker.auto_vacuum && root > 1 && !partial {
+                checker.check_ptrmap(root, PtrmapType::RootPage, 0);
+            }
+
+            checker.root = root;
+
+            checker.check_tree_page(root, &mut 0, i64::MAX);
+
+            counts.insert(root, checker.rows);
+        }
+
+        if !partial {
+            for page in 1..=page_count {
+                if checker.is_full() {
+                    break;
+                }
+
+                let is_ptrmap = checker.auto_vacuum && checker.ptrmap.is_map_page(page);
+
+                let referenced = checker.referenced[page as usize];
+
+                if !referenced && !is_ptrmap {
+                    checker.report(format!("Page {page}: never used"));
+                }
+
+                if referenced && is_ptrmap {
+                    checker.report(format!("Page {page}: pointer map referenced"));
+                }
+            }
+        }
+
+        let mut remaining = max_errors.saturating_sub(checker.errors.len());
+
+        if !checker.errors.is_empty() {
+            rows.push(format!("*** in database main ***\n{}", checker.errors.join("\n")));
+        }
+
+        let mut report = |rows: &mut Vec<String>, message: String| {
+            if remaining > 0 {
+                rows.push(message);
+
+                remaining -= 1;
+            }
+
+            remaining == 0
+        };
+
+        for table in &tables {
+            let Some(table_count) = counts.get(&(table.root_page.max(0) as u32)) else {
+                continue;
+            };
+
+            for index in table_indexes(&schemas, table) {
+                let index_count = counts.get(&index.root);
+
+                if !index.partial
+                    && index_count.is_some_and(|count| count != table_count)
+                    && report(&mut rows, format!("wrong # of entries in index {}", index.name))
+                {
+                    return Ok(rows);
+                }
+            }
+        }
+
+        for table in &tables {
+            if self.check_table_rows(table, &schemas, quick, &mut |message| report(&mut rows, message)) {
+                return Ok(rows);
+            }
+        }
+
+        if rows.is_empty() {
+            rows.push("ok".to_string());
+        }
+
+        Ok(rows)
+    }
+
+    // Checks NOT NULL constraints and, unless `quick`, that every row has its index entries.
+    // Returns true once `report` says no more errors are wanted. Tables that cannot be read
+    // were already reported by the b-tree checks and are skipped.
+    fn check_table_rows(
+        &self,
+        table: &SchemaTable,
+        schemas: &[SchemaTable],
+        quick: bool,
+        report: &mut dyn FnMut(String) -> bool,
+    ) -> bool {
+        let Some(statement) = table.table().filter(|statement| !statement.without_rowid) else {
+            return false;
+        };
+
+        if table.root_page <= 0 {
+            return false;
+        }
+
+        let rowid_alias = statement.rowid_alias();
+
+        let Ok(payloads) = self.read_page(table.root_page).and_then(|page| page.get_payloads()) else {
+            return false;
+        };
+
+        // index entries in key order, for the indexes we know how to compute keys for. The
+        // damage of an index was reported with its b-tree, what can still be read of it is
+        // checked against the rows.
+        let mut indexes = vec![];
+
+        if !quick {
+            for index in table_indexes(schemas, table) {
+                let IndexCheck { name, root, partial: false, key: Some(key) } = index else {
+                    continue;
+                };
+
+                let Ok(page) = self.read_page(root as i32) else {
+                    continue;
+                };
+
+                let mut entries = vec![];
+
+                let _ = page.for_each_index_payload(&mut |payload| {
+                    if let Ok(values) = payload.values() {
+                        entries.push(values);
+                    }
+
+                    Ok(())
+                });
+
+                entries.sort_by(|a, b| key.compare(a, b));
+
+                indexes.push((name, key, entries));
+            }
+        }
+
+        for (row_number, (rowid, payload)) in payloads.iter().enumerate() {
+            let Ok(values) = payload.values() else {
+                continue;
+            };
+
+            for j in (0..statement.columns.len()).filter(|&j| statement.is_not_null(j)) {
+                if rowid_alias != Some(j) && values.get(j).map_or(true, Value::is_null) {
+                    let message = format!("NULL value in {}.{}", table.tbl_name, statement.columns[j].name);
+
+                    if report(message) {
+                        return true;
+                    }
+                }
+            }
+
+            for (name, key, entries) in &indexes {
+                let entry = key.entry(&values, *rowid as i64, rowid_alias);
+
+                let Ok(position) = entries.binary_search_by(|other| key.compare(other, &entry)) else {
+                    if report(format!("row {} missing from index {name}", row_number + 1)) {
+                        return true;
+                    }
+
+                    continue;
+                };
+
+                // the next entry has the same values under the index's collations
+                let is_duplicate = key.unique_key(&entry).is_some_and(|unique_key| {
+                    entries
+                        .get(position + 1)
+                        .is_some_and(|next| key.compare(next, unique_key).is_eq())
+                });
+
+                if is_duplicate && report(format!("non-unique entry in index {name}")) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+// The indexes of a table, newest first like sqlite keeps them
+fn table_indexes<'schema>(schemas: &'schema [SchemaTable], table: &SchemaTable) -> Vec<IndexCheck<'schema>> {
+    let Some(statement) = table.table() else {
+        return vec![];
+    };
+
+    let mut indexes = schemas
+        .iter()
+        .filter(|schema| schema.schema_type == SchemaTypesTypes::Index && schema.root_page > 0)
+        .filter(|schema| schema.tbl_name.eq_ignore_ascii_case(&table.tbl_name))
+        .map(|schema| IndexCheck {
+            name: &schema.name,
+            root: schema.root_page as u32,
+            partial: schema.index().is_some_and(|definition| definition.where_clause.is_some()),
+            key: IndexKey::new(schema, statement).ok(),
+        })
+        .collect::<Vec<_>>();
+
+    indexes.reverse();
+
+    indexes
+}
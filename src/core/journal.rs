@@ -0,0 +1,259 @@
+use crate::core::vfs::{LockLevel, Vfs};
+use crate::error::{Error, Result};
+use std::collections::hash_map::RandomState;
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+// A rollback journal in sqlite's format: the pages a change is about to overwrite, kept
+// next to the database until the change is synced. A connection that crashes part way
+// through leaves a hot journal behind, and the next one to read the database, this one or
+// sqlite3, writes the pages back.
+
+const MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+
+// the journal header takes a sector, and the first page follows it
+const SECTOR_SIZE: u32 = 512;
+
+pub(crate) fn journal_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+
+    name.push("-journal");
+
+    PathBuf::from(name)
+}
+
+// Keeps every page of the database in a new journal, synced before anything overwrites
+// them. `page_count` pages of `page_size` bytes are what the database is put back to.
+pub(crate) fn write(path: &Path, file: &dyn Vfs, page_size: u32, page_count: u32) -> Result<()> {
+    let journal_path = journal_path(path);
+
+    let journal = OpenOptions::new().write(true).create(true).truncate(true).open(&journal_path)?;
+
+    let mut out = BufWriter::new(&journal);
+
+    // the checksums start from a random number, so a stale record that happens to be in
+    // the file does not pass for one of this journal
+    let nonce = RandomState::new().build_hasher().finish() as u32;
+
+    let mut header = vec![0u8; SECTOR_SIZE as usize];
+
+    header[..8].copy_from_slice(&MAGIC);
+
+    for (i, field) in [page_count, nonce, page_count, SECTOR_SIZE, page_size].into_iter().enumerate() {
+        header[8 + i * 4..12 + i * 4].copy_from_slice(&field.to_be_bytes());
+    }
+
+    out.write_all(&header)?;
+
+    let mut page = vec![0u8; page_size as usize];
+
+    for page_number in 1..=page_count {
+        file.read_at(&mut page, u64::from(page_number - 1) * u64::from(page_size))
+            .map_err(|error| error.past_end().on_page(page_number))?;
+
+        out.write_all(&page_number.to_be_bytes())?;
+        out.write_all(&page)?;
+        out.write_all(&checksum(&page, nonce).to_be_bytes())?;
+    }
+
+    out.flush()?;
+
+    drop(out);
+
+    journal.sync_all()?;
+
+    // the journal must be found after a crash, which takes its directory entry too
+    if let Some(directory) = journal_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        File::open(directory)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+// Ends the change the journal was kept for, which is then the one a crash leaves behind
+pub(crate) fn delete(path: &Path) -> Result<()> {
+    std::fs::remove_file(journal_path(path))?;
+
+    Ok(())
+}
+
+// Writes the pages of a journal back and deletes it, for a change that failed before it
+// was synced
+pub(crate) fn roll_back(path: &Path, file: &dyn Vfs) -> Result<()> {
+    let journal = File::open(journal_path(path))?;
+
+    play_back(&journal, file)?;
+
+    delete(path)
+}
+
+// Rolls back a hot journal: one left behind by a connection that crashed while changing
+// the database. A connection still writing holds RESERVED until it deletes its journal,
+// and the journal of one that holds it is not hot.
+pub(crate) fn roll_back_hot(path: &Path, file: &dyn Vfs) -> Result<()> {
+    let held = file.lock_level();
+
+    if held >= LockLevel::Reserved {
+        return Ok(());
+    }
+
+    file.lock(LockLevel::Shared)?;
+
+    let result = match File::open(journal_path(path)) {
+        Ok(journal) if journal.metadata()?.len() > 0 => match file.lock(LockLevel::Reserved) {
+            Err(Error::Busy) => Ok(()),
+            Err(error) => Err(error),
+            Ok(()) => file
+                .lock(LockLevel::Exclusive)
+                .and_then(|()| play_back(&journal, file))
+                .and_then(|()| delete(path)),
+        },
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error.into()),
+    };
+
+    let unlocked = file.unlock(held);
+
+    result.and(unlocked)
+}
+
+// Writes back the pages of every segment of a journal, each a header and the pages after
+// it, up to the first record that is cut short or fails its checksum, then truncates the
+// database to the size it had
+fn play_back(journal: &File, file: &dyn Vfs) -> Result<()> {
+    let len = journal.metadata()?.len();
+
+    let mut offset = 0;
+
+    // the size of the database before the change and the size of its pages
+    let mut original = None;
+
+    'segments: while offset + 28 <= len {
+        let mut header = [0u8; 28];
+
+        journal.read_exact_at(&mut header, offset)?;
+
+        let field = |i: usize| u32::from_be_bytes(header[8 + i * 4..12 + i * 4].try_into().unwrap_or_default());
+
+        let (records, nonce, page_count, sector_size, page_size) = (field(0), field(1), field(2), field(3), field(4));
+
+        let is_size = |size: u32, min: u32| (min..=65536).contains(&size) && size.is_power_of_two();
+
+        if header[..8] != MAGIC || !is_size(sector_size, 32) || !is_size(page_size, 512) {
+            break;
+        }
+
+        let (_, page_size) = *original.get_or_insert((page_count, page_size));
+
+        let record_len = u64::from(page_size) + 8;
+
+        let mut at = offset + u64::from(sector_size);
+
+        // a journal written without syncing may not have its count of records filled in
+        let records = match records {
+            u32::MAX => (len.saturating_sub(at) / record_len) as u32,
+            0 if offset == 0 => (len.saturating_sub(at) / record_len) as u32,
+            records => records,
+        };
+
+        let mut record = vec![0u8; record_len as usize];
+
+        for _ in 0..records {
+            if at + record_len > len {
+                break 'segments;
+            }
+
+            journal.read_exact_at(&mut record, at)?;
+
+            let page_number = u32::from_be_bytes([record[0], record[1], record[2], record[3]]);
+
+            let page = &record[4..record.len() - 4];
+
+            let stored = u32::from_be_bytes(record[record.len() - 4..].try_into().unwrap_or_default());
+
+            if page_number == 0 || stored != checksum(page, nonce) {
+                break 'segments;
+            }
+
+            file.write_at(page, u64::from(page_number - 1) * u64::from(page_size))?;
+
+            at += record_len;
+        }
+
+        offset = at.div_ceil(u64::from(sector_size)) * u64::from(sector_size);
+    }
+
+    if let Some((page_count, page_size)) = original {
+        file.truncate(u64::from(page_count) * u64::from(page_size))?;
+
+        file.sync()?;
+    }
+
+    Ok(())
+}
+
+// sqlite's checksum of a journaled page, a sum of every 200th byte counted back from the end
+fn checksum(page: &[u8], nonce: u32) -> u32 {
+    let mut sum = nonce;
+
+    let mut i = page.len() as isize - 200;
+
+    while i > 0 {
+        sum = sum.wrapping_add(u32::from(page[i as usize]));
+
+        i -= 200;
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{journal_path, roll_back_hot, write};
+    use crate::core::vfs::{FileVfs, LockLevel, Vfs};
+    use std::fs::OpenOptions;
+    use std::path::PathBuf;
+
+    fn open(path: &PathBuf) -> FileVfs {
+        FileVfs::new(OpenOptions::new().read(true).write(true).open(path).unwrap())
+    }
+
+    #[test]
+    fn hot_journals_are_rolled_back() {
+        let path = std::env::temp_dir().join(format!("journal-{}.db", std::process::id()));
+
+        let original = (0..3 * 512).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        std::fs::write(&path, &original).unwrap();
+
+        let file = open(&path);
+
+        write(&path, &file, 512, 3).unwrap();
+
+        // a change cut short: a page overwritten, and the file grown
+        file.write_at(&[0xff; 700], 100).unwrap();
+        file.write_at(&[1; 512], 4 * 512).unwrap();
+
+        // to the connection still writing, which holds RESERVED, its journal is not hot
+        file.lock(LockLevel::Reserved).unwrap();
+
+        roll_back_hot(&path, &file).unwrap();
+
+        assert!(journal_path(&path).exists());
+
+        // once it lets go, as a crash would, the journal is hot
+        file.unlock(LockLevel::None).unwrap();
+
+        roll_back_hot(&path, &file).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+        assert!(!journal_path(&path).exists());
+        assert_eq!(file.lock_level(), LockLevel::None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
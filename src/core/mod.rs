@@ -1,6 +1,28 @@
+mod analyze;
+mod btree;
 pub mod cell;
+mod cte;
 pub mod database;
+mod dump;
+mod eval;
+mod functions;
+mod freelist;
+pub mod fuzzing;
 mod header;
+mod index_key;
+mod inspect;
+mod integrity;
+mod journal;
 mod page;
+mod pager;
+mod pragma;
+mod ptrmap;
+mod record;
+mod recover;
 mod schema;
+mod select;
+mod sort;
 mod utils;
+pub mod vacuum;
+pub mod vfs;
+pub mod value;
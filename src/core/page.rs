@@ -2,11 +2,14 @@ use crate::core::cell::{CellPayload, PageCell};
 use crate::core::header::DBHeader;
 use crate::core::page::BTreePageSubType::{Interior, Leaf};
 use crate::core::page::PageTypes::{IndexBTree, TableBTree};
-use anyhow::Result;
-use std::fs::File;
-use std::os::unix::prelude::FileExt;
+use crate::error::{Error, Result};
+use crate::core::vfs::Vfs;
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
+pub const MAX_BTREE_DEPTH: usize = 64;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BTreePageSubType {
     Leaf,
@@ -23,27 +26,55 @@ pub enum PageTypes {
     TableBTree(BTreePageSubType),
 }
 
-impl From<&u8> for PageTypes {
-    fn from(value: &u8) -> Self {
+impl TryFrom<u8> for PageTypes {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
         let page_type = match value {
             2 => IndexBTree(Interior),
             5 => TableBTree(Interior),
             10 => IndexBTree(Leaf),
             13 => TableBTree(Leaf),
-            _ => {
-                panic!("Invalid Page Type")
-            }
+            _ => return Err(Error::corrupt(0, format!("invalid b-tree page type {value}"))),
+        };
+
+        Ok(page_type)
+    }
+}
+
+impl From<PageTypes> for u8 {
+    fn from(value: PageTypes) -> Self {
+        match value {
+            IndexBTree(Interior) => 2,
+            TableBTree(Interior) => 5,
+            IndexBTree(Leaf) => 10,
+            TableBTree(Leaf) => 13,
+            _ => 0,
+        }
+    }
+}
+
+impl Display for PageTypes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PageTypes::Lock => "lock-byte page",
+            PageTypes::FreeList => "freelist page",
+            PageTypes::PointerMap => "pointer map page",
+            PageTypes::PayloadOverflow => "overflow page",
+            IndexBTree(Interior) => "index interior",
+            IndexBTree(Leaf) => "index leaf",
+            TableBTree(Interior) => "table interior",
+            TableBTree(Leaf) => "table leaf",
         };
 
-        page_type
+        write!(f, "{name}")
     }
 }
 
 #[derive(Debug)]
-pub struct Page<'file> {
+pub struct Page {
     pub header: DBHeader,
     pub page_type: PageTypes,
-    pub page_size: u16,
     pub free_block_start: u16,
     pub num_of_cells: u16,
     pub content_area_start: u16,
@@ -52,26 +83,38 @@ pub struct Page<'file> {
     pub cell_pointers: Vec<u16>,
     pub cells: Vec<PageCell>,
 
-    file: &'file File,
+    file: Rc<dyn Vfs>,
 }
 
-impl<'file> Page<'file> {
-    pub fn new(file: &'file File, page_size: u16, page_number: u64) -> Result<Self> {
+impl Page {
+    pub fn new(file: Rc<dyn Vfs>, header: &DBHeader, page_number: u64) -> Result<Self> {
+        Self::read(file, header, page_number).map_err(|error| error.on_page(page_number as u32))
+    }
+
+    fn read(file: Rc<dyn Vfs>, header: &DBHeader, page_number: u64) -> Result<Self> {
+        if page_number == 0 || page_number > u64::from(u32::MAX) {
+            return Err(Error::corrupt(0, "invalid page number"));
+        }
+
+        let page_size = header.page_size;
+
+        let usable_size = header.get_usable_size() as usize;
+
         let mut buffer = vec![0u8; page_size as usize];
 
-        let page_offset = ((page_number as u16 - 1) * page_size) as u64;
+        let page_offset = (page_number - 1) * page_size as u64;
 
-        file.read_exact_at(&mut buffer, page_offset)?;
+        file.read_at(&mut buffer, page_offset).map_err(Error::past_end)?;
 
         let mut start_index = 0;
 
-        let header = DBHeader::new(&buffer)?;
+        let header = header.clone();
 
         if page_number == 1 || page_offset == 0 {
             start_index = 100;
         }
 
-        let page_type = PageTypes::from(&buffer[start_index]);
+        let page_type = PageTypes::try_from(buffer[start_index])?;
 
         let free_block_start =
             u16::from_be_bytes([buffer[start_index + 1], buffer[start_index + 2]]);
@@ -90,10 +133,11 @@ impl<'file> Page<'file> {
             buffer[start_index + 11],
         ]);
 
-        let right_most_pointer = if right_most_pointer_value == 0 {
-            None
-        } else {
-            Some(right_most_pointer_value)
+        let right_most_pointer = match page_type {
+            IndexBTree(Interior) | TableBTree(Interior) if right_most_pointer_value != 0 => {
+                Some(right_most_pointer_value)
+            }
+            _ => None,
         };
 
         let mut cells: Vec<PageCell> = Vec::with_capacity(num_of_cells as usize);
@@ -106,32 +150,40 @@ impl<'file> Page<'file> {
             },
 
             _ => start_index + 8,
-        } as u16;
+        };
 
         // each cell pointer is 2 bytes
-        let cell_pointers_end_index = cell_pointers_start_index + num_of_cells * 2;
+        let cell_pointers_end_index = cell_pointers_start_index + num_of_cells as usize * 2;
 
-        for i in (cell_pointers_start_index..cell_pointers_end_index).step_by(2) {
-            let i: usize = i as usize;
+        if cell_pointers_end_index > usable_size {
+            return Err(Error::corrupt(0, format!("{num_of_cells} cell pointers do not fit in the page")));
+        }
 
+        for i in (cell_pointers_start_index..cell_pointers_end_index).step_by(2) {
             let pointer = u16::from_be_bytes([buffer[i], buffer[i + 1]]);
 
             cell_pointers.push(pointer);
 
-            if pointer != 0 {
-                let cell_vec = &buffer[(pointer as usize)..].to_vec();
+            if (pointer as usize) < cell_pointers_end_index || pointer as usize >= usable_size {
+                return Err(Error::corrupt(0, format!("cell pointer {pointer} is out of bounds")));
+            }
 
-                let cell = PageCell::new(cell_vec, page_type, page_size as u64, &header.text_encoding)?;
+            let cell = PageCell::new(
+                &buffer[(pointer as usize)..usable_size],
+                page_type,
+                usable_size as u64,
+                &header.text_encoding,
+                file.as_ref(),
+                page_size,
+            )?;
 
-                cells.push(cell);
-            }
+            cells.push(cell);
         }
 
         Ok(Self {
             file,
             cells,
             header,
-            page_size,
             page_type,
             num_of_cells,
             cell_pointers,
@@ -142,36 +194,41 @@ impl<'file> Page<'file> {
         })
     }
 
-    fn cell_content_area_offset(&self) -> u16 {
-        // the offset to the cell content area will equal the page size minus the bytes of reserved space
-        let (value, is_overflowing) = self
-            .page_size
-            .overflowing_sub(self.header.reserved_bytes_per_page);
-
-        if is_overflowing {
-            self.page_size
-        } else {
-            value
-        }
-    }
-
     pub fn get_payloads(&self) -> Result<Vec<(u64, Rc<CellPayload>)>> {
-        let mut pointers: Vec<u32> = vec![];
-
         let mut result: Vec<(u64, Rc<CellPayload>)> = vec![];
 
-        self._get_payloads(&mut pointers, &mut result)?;
+        self.for_each_payload(&mut |row_id, payload| {
+            result.push((row_id, payload));
 
-        // println!("Pointers: {pointers:?}");
+            Ok(())
+        })?;
 
         Ok(result)
     }
 
-    fn _get_payloads(&self, visited_pointers: &mut Vec<u32>, result: &mut Vec<(u64, Rc<CellPayload>)>) -> Result<()> {
+    // Hands every row of a table b-tree to `each` in rowid order, reading one page of each
+    // level at a time rather than all of them first
+    pub fn for_each_payload(&self, each: &mut dyn FnMut(u64, Rc<CellPayload>) -> Result<()>) -> Result<()> {
+        let mut pointers: Vec<u32> = vec![];
+
+        self._for_each_payload(&mut pointers, each, 0)
+    }
+
+    fn _for_each_payload(
+        &self,
+        visited_pointers: &mut Vec<u32>,
+        each: &mut dyn FnMut(u64, Rc<CellPayload>) -> Result<()>,
+        depth: usize,
+    ) -> Result<()> {
+        // real trees are a handful of levels deep, anything deeper is a corrupt chain of pages
+        if depth > MAX_BTREE_DEPTH {
+            return Err(Error::corrupt(0, "b-tree is too deep"));
+        }
+
         match self.page_type {
             TableBTree(Leaf) => {
                 for cell in &self.cells {
-                    result.push((cell.row_id, Rc::clone(&cell.payload)))
+                    each(cell.row_id, Rc::clone(&cell.payload))?;
                 }
             }
 
@@ -192,9 +249,9 @@ impl<'file> Page<'file> {
                     if !visited_pointers.contains(&pointer) {
                         visited_pointers.push(pointer);
 
-                        let mut page = Page::new(self.file, self.page_size, pointer as u64)?;
+                        let page = Page::new(Rc::clone(&self.file), &self.header, pointer as u64)?;
 
-                        page._get_payloads(visited_pointers, result)?;
+                        page._for_each_payload(visited_pointers, each, depth + 1)?;
                     }
                 }
             }
@@ -204,4 +261,189 @@ impl<'file> Page<'file> {
 
         Ok(())
     }
+
+    // Every entry of an index b-tree in key order. Interior index cells hold entries too,
+    // they sort between the subtrees on either side of them.
+    pub fn get_index_payloads(&self) -> Result<Vec<Rc<CellPayload>>> {
+        let mut result = vec![];
+
+        self.for_each_index_payload(&mut |payload| {
+            result.push(payload);
+
+            Ok(())
+        })?;
+
+        Ok(result)
+    }
+
+    // Hands every entry of an index b-tree to `each` in key order, like `for_each_payload`
+    pub fn for_each_index_payload(&self, each: &mut dyn FnMut(Rc<CellPayload>) -> Result<()>) -> Result<()> {
+        let mut visited: Vec<u32> = vec![];
+
+        self._for_each_index_payload(&mut visited, each, 0)
+    }
+
+    fn _for_each_index_payload(
+        &self,
+        visited_pointers: &mut Vec<u32>,
+        each: &mut dyn FnMut(Rc<CellPayload>) -> Result<()>,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_BTREE_DEPTH {
+            return Err(Error::corrupt(0, "b-tree is too deep"));
+        }
+
+        let mut visit = |pointer: u32, each: &mut dyn FnMut(Rc<CellPayload>) -> Result<()>| -> Result<()> {
+            if !visited_pointers.contains(&pointer) {
+                visited_pointers.push(pointer);
+
+                let page = Page::new(Rc::clone(&self.file), &self.header, pointer as u64)?;
+
+                page._for_each_index_payload(visited_pointers, each, depth + 1)?;
+            }
+
+            Ok(())
+        };
+
+        match self.page_type {
+            IndexBTree(Leaf) => {
+                for cell in &self.cells {
+                    each(Rc::clone(&cell.payload))?;
+                }
+            }
+
+            IndexBTree(Interior) => {
+                for cell in &self.cells {
+                    if let Some(pointer) = cell.left_pointer {
+                        visit(pointer, each)?;
+                    }
+
+                    each(Rc::clone(&cell.payload))?;
+                }
+
+                if let Some(pointer) = self.right_most_pointer {
+                    visit(pointer, each)?;
+                }
+            }
+
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    // The entries of an index b-tree that `position` places inside a range, in key order.
+    // Subtrees that lie wholly before or after the range are not read.
+    pub fn get_index_range(&self, position: &dyn Fn(&CellPayload) -> Result<Ordering>) -> Result<Vec<Rc<CellPayload>>> {
+        let mut visited: Vec<u32> = vec![];
+
+        let mut result = vec![];
+
+        self._get_index_range(position, &mut visited, &mut result, 0)?;
+
+        Ok(result)
+    }
+
+    fn _get_index_range(
+        &self,
+        position: &dyn Fn(&CellPayload) -> Result<Ordering>,
+        visited_pointers: &mut Vec<u32>,
+        result: &mut Vec<Rc<CellPayload>>,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_BTREE_DEPTH {
+            return Err(Error::corrupt(0, "b-tree is too deep"));
+        }
+
+        let mut visit = |pointer: u32, result: &mut Vec<Rc<CellPayload>>| -> Result<()> {
+            if !visited_pointers.contains(&pointer) {
+                visited_pointers.push(pointer);
+
+                let page = Page::new(Rc::clone(&self.file), &self.header, pointer as u64)?;
+
+                page._get_index_range(position, visited_pointers, result, depth + 1)?;
+            }
+
+            Ok(())
+        };
+
+        match self.page_type {
+            IndexBTree(Leaf) => {
+                for cell in &self.cells {
+                    if position(&cell.payload)? == Ordering::Equal {
+                        result.push(Rc::clone(&cell.payload));
+                    }
+                }
+            }
+
+            IndexBTree(Interior) => {
+                // where the divider before the current subtree is, the first subtree has
+                // nothing before it
+                let mut previous = Ordering::Less;
+
+                for cell in &self.cells {
+                    let here = position(&cell.payload)?;
+
+                    // the left subtree holds the entries between the two dividers
+                    if here != Ordering::Less && previous != Ordering::Greater {
+                        if let Some(pointer) = cell.left_pointer {
+                            visit(pointer, result)?;
+                        }
+                    }
+
+                    match here {
+                        Ordering::Equal => result.push(Rc::clone(&cell.payload)),
+                        Ordering::Greater => return Ok(()),
+                        Ordering::Less => {}
+                    }
+
+                    previous = here;
+                }
+
+                if let Some(pointer) = self.right_most_pointer {
+                    visit(pointer, result)?;
+                }
+            }
+
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    // The record of a table b-tree row, found by descending the tree towards its rowid
+    pub fn find_row(&self, row_id: u64) -> Result<Option<Rc<CellPayload>>> {
+        let mut page = None;
+
+        for _ in 0..=MAX_BTREE_DEPTH {
+            let current = page.as_ref().unwrap_or(self);
+
+            let child = match current.page_type {
+                TableBTree(Leaf) => {
+                    return Ok(current
+                        .cells
+                        .iter()
+                        .find(|cell| cell.row_id == row_id)
+                        .map(|cell| Rc::clone(&cell.payload)))
+                }
+
+                // rows up to the key of a cell are in the subtree to its left
+                TableBTree(Interior) => current
+                    .cells
+                    .iter()
+                    .find(|cell| row_id <= cell.row_id)
+                    .map_or(current.right_most_pointer, |cell| cell.left_pointer),
+
+                _ => None,
+            };
+
+            let Some(child) = child else {
+                return Ok(None);
+            };
+
+            page = Some(Page::new(Rc::clone(&self.file), &self.header, child as u64)?);
+        }
+
+        Err(Error::corrupt(0, "b-tree is too deep"))
+    }
 }
@@ -0,0 +1,418 @@
+use crate::core::btree::page_references;
+use crate::core::freelist::{Freelist, FreelistTrunk};
+use crate::core::header::DBHeader;
+use crate::core::ptrmap::{PtrmapEntry, PtrmapLayout, PtrmapType};
+use crate::error::{Error, Result};
+use crate::core::vfs::{LockLevel, Vfs};
+
+// Raw page access used by the write path. Changes to the header are kept in memory
+// and only reach page 1 on `commit`. The pager holds a RESERVED lock while it lives and
+// takes EXCLUSIVE on its first write, going back to SHARED once it is committed or dropped.
+//
+// In auto-vacuum databases the pager also keeps the pointer map up to date: every page it
+// hands out gets an entry once the page referring to it is written.
+pub struct Pager<'file> {
+    file: &'file dyn Vfs,
+    pub header: DBHeader,
+    ptrmap: Option<PtrmapLayout>,
+
+    // set when pages were removed from the end, the file is cut to size on commit
+    truncate: bool,
+}
+
+impl<'file> Pager<'file> {
+    pub fn new(file: &'file dyn Vfs, header: DBHeader) -> Result<Self> {
+        file.lock(LockLevel::Reserved)?;
+
+        let ptrmap = (!header.should_omit_pointer_map()).then(|| PtrmapLayout::new(&header));
+
+        let mut pager = Self {
+            file,
+            header,
+            ptrmap,
+            truncate: false,
+        };
+
+        // the in-header size is stale when the file was last written by an old sqlite version
+        pager.header.db_size = pager.header.page_count(file.size()?);
+
+        Ok(pager)
+    }
+
+    pub fn file(&self) -> &'file dyn Vfs {
+        self.file
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.header.page_size as usize
+    }
+
+    pub fn usable_size(&self) -> usize {
+        self.header.get_usable_size() as usize
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.header.db_size
+    }
+
+    pub fn lock_byte_page(&self) -> u32 {
+        self.header.lock_byte_page()
+    }
+
+    pub fn read(&self, page_number: u32) -> Result<Vec<u8>> {
+        if page_number == 0 || page_number > self.page_count() {
+            return Err(Error::corrupt(
+                page_number,
+                format!("page is out of range, the database has {} pages", self.page_count()),
+            ));
+        }
+
+        let mut buffer = vec![0u8; self.page_size()];
+
+        self.file
+            .read_at(&mut buffer, self.offset(page_number))
+            .map_err(|error| error.past_end().on_page(page_number))?;
+
+        Ok(buffer)
+    }
+
+    pub fn write(&self, page_number: u32, buffer: &[u8]) -> Result<()> {
+        self.file.lock(LockLevel::Exclusive)?;
+
+        self.file.write_at(buffer, self.offset(page_number))?;
+
+        Ok(())
+    }
+
+    // Returns the number of a page the caller can overwrite, reusing a page from the
+    // freelist when there is one and growing the database otherwise
+    pub fn allocate(&mut self) -> Result<u32> {
+        if self.header.first_free_page != 0 {
+            return self.allocate_free_page();
+        }
+
+        self.grow()
+    }
+
+    // Adds a page at the end of the database, stepping over the lock-byte page. Where a
+    // pointer map page is due, it is added first with no entries.
+    fn grow(&mut self) -> Result<u32> {
+        let mut page_number = self.header.db_size + 1;
+
+        if page_number == self.lock_byte_page() {
+            page_number += 1;
+        }
+
+        if self.ptrmap.is_some_and(|layout| layout.is_map_page(page_number)) {
+            self.header.db_size = page_number;
+
+            self.write(page_number, &vec![0u8; self.page_size()])?;
+
+            page_number += 1;
+
+            if page_number == self.lock_byte_page() {
+                page_number += 1;
+            }
+        }
+
+        self.header.db_size = page_number;
+
+        Ok(page_number)
+    }
+
+    // Takes the first leaf of the first trunk page, moving its last leaf into the free slot
+    // like sqlite does. A trunk without leaves is reused itself and the next trunk takes its place.
+    fn allocate_free_page(&mut self) -> Result<u32> {
+        let trunk_page = self.header.first_free_page;
+
+        let mut buffer = self.read(trunk_page)?;
+
+        let mut trunk = FreelistTrunk::parse(trunk_page, &buffer, self.usable_size() as u32)?;
+
+        let page_number = if trunk.leaves.is_empty() {
+            self.header.first_free_page = trunk.next;
+
+            trunk_page
+        } else {
+            let leaf = trunk.leaves.swap_remove(0);
+
+            if leaf == 0 || leaf > self.page_count() {
+                return Err(Error::corrupt(trunk_page, format!("freelist leaf {leaf} is out of range")));
+            }
+
+            trunk.write_to(&mut buffer);
+
+            self.write(trunk_page, &buffer)?;
+
+            leaf
+        };
+
+        self.header.free_page_list_size = self.header.free_page_list_size.saturating_sub(1);
+
+        Ok(page_number)
+    }
+
+    // Returns the page for a new table's root. Auto-vacuum databases keep every root page
+    // right after the previous ones, so vacuuming never has to move a root: the page after
+    // the largest root is taken off the freelist, or whatever it holds moves elsewhere.
+    pub fn allocate_root(&mut self) -> Result<u32> {
+        let Some(layout) = self.ptrmap else {
+            return self.allocate();
+        };
+
+        let mut root = self.header.auto_vacuum + 1;
+
+        while layout.is_map_page(root) || root == self.lock_byte_page() {
+            root += 1;
+        }
+
+        if root > self.page_count() {
+            if self.grow()? != root {
+                return Err(Error::corrupt(0, format!("largest root page {} is past the end of the database", self.header.auto_vacuum)));
+            }
+        } else {
+            let entry = self.read_ptrmap(root)?;
+
+            match entry.entry_type {
+                PtrmapType::RootPage => {
+                    return Err(Error::corrupt(root, "root page is past the largest root page in the header"))
+                }
+
+                PtrmapType::FreePage => {
+                    let mut freelist = self.freelist()?;
+
+                    freelist.remove(root);
+
+                    self.write_freelist(&freelist)?;
+                }
+
+                _ => {
+                    let page_number = self.allocate()?;
+
+                    self.relocate(root, entry, page_number)?;
+                }
+            }
+        }
+
+        self.put_ptrmap(root, PtrmapEntry::new(PtrmapType::RootPage, 0))?;
+
+        self.header.auto_vacuum = root;
+
+        Ok(root)
+    }
+
+    pub fn read_ptrmap(&self, page_number: u32) -> Result<PtrmapEntry> {
+        let layout = self.ptrmap.ok_or_else(|| Error::Misuse("the database has no pointer map".to_string()))?;
+
+        layout.read_entry(page_number, &self.read(layout.map_page(page_number))?)
+    }
+
+    // Records what a page is used for, doing nothing in databases without a pointer map
+    pub fn put_ptrmap(&self, page_number: u32, entry: PtrmapEntry) -> Result<()> {
+        let Some(layout) = self.ptrmap else {
+            return Ok(());
+        };
+
+        let map_page = layout.map_page(page_number);
+
+        let mut buffer = self.read(map_page)?;
+
+        if layout.read_entry(page_number, &buffer).ok() == Some(entry) {
+            return Ok(());
+        }
+
+        layout.write_entry(page_number, &mut buffer, entry)?;
+
+        self.write(map_page, &buffer)
+    }
+
+    // Points the entries of every child and overflow chain of a b-tree page at it
+    pub fn set_child_ptrmaps(&self, page_number: u32, buffer: &[u8]) -> Result<()> {
+        if self.ptrmap.is_none() {
+            return Ok(());
+        }
+
+        for reference in page_references(page_number, buffer, self.usable_size())? {
+            self.put_ptrmap(reference.page_number, PtrmapEntry::new(reference.entry_type, page_number))?;
+        }
+
+        Ok(())
+    }
+
+    fn freelist(&self) -> Result<Freelist> {
+        Freelist::read_with(&self.header, self.page_count(), |page_number| self.read(page_number))
+    }
+
+    // Writes every trunk page of `freelist` and points the header at it
+    fn write_freelist(&mut self, freelist: &Freelist) -> Result<()> {
+        for trunk in &freelist.trunks {
+            let mut buffer = vec![0u8; self.page_size()];
+
+            trunk.write_to(&mut buffer);
+
+            self.write(trunk.page_number, &buffer)?;
+        }
+
+        self.header.first_free_page = freelist.first_page();
+        self.header.free_page_list_size = freelist.page_count();
+
+        Ok(())
+    }
+
+    // Moves the content of page `from`, whose pointer map entry is `entry`, to the unused
+    // page `to`, then updates the page referring to it and the entries of the pages it refers to
+    fn relocate(&mut self, from: u32, entry: PtrmapEntry, to: u32) -> Result<()> {
+        let buffer = self.read(from)?;
+
+        self.write(to, &buffer)?;
+
+        match entry.entry_type {
+            PtrmapType::BTree | PtrmapType::RootPage => self.set_child_ptrmaps(to, &buffer)?,
+
+            PtrmapType::Overflow1 | PtrmapType::Overflow2 => {
+                let next = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+
+                if next != 0 {
+                    self.put_ptrmap(next, PtrmapEntry::new(PtrmapType::Overflow2, to))?;
+                }
+            }
+
+            PtrmapType::FreePage => {}
+        }
+
+        let mut parent = self.read(entry.parent)?;
+
+        let offset = match entry.entry_type {
+            PtrmapType::Overflow2 => Some(0).filter(|_| parent[0..4] == from.to_be_bytes()),
+
+            PtrmapType::BTree | PtrmapType::Overflow1 => page_references(entry.parent, &parent, self.usable_size())?
+                .into_iter()
+                .find(|reference| reference.page_number == from && reference.entry_type == entry.entry_type)
+                .map(|reference| reference.offset),
+
+            _ => None,
+        };
+
+        let offset = offset.ok_or_else(|| {
+            Error::corrupt(entry.parent, format!("pointer map says page {from} is referenced from here"))
+        })?;
+
+        parent[offset..offset + 4].copy_from_slice(&to.to_be_bytes());
+
+        self.write(entry.parent, &parent)?;
+
+        self.put_ptrmap(to, entry)
+    }
+
+    // Returns free pages to the file system, one page at a time from the end of the file:
+    // a free last page is dropped, a used one first moves into a free page further up.
+    // Stops after `limit` pages, or once the freelist is empty. Returns how many pages went.
+    pub fn incremental_vacuum(&mut self, limit: Option<u32>) -> Result<u32> {
+        let Some(layout) = self.ptrmap else {
+            return Ok(0);
+        };
+
+        let mut removed = 0;
+
+        while self.header.free_page_list_size > 0 && limit.map_or(true, |limit| removed < limit) {
+            let page_count = self.page_count();
+
+            let free_pages = self.header.free_page_list_size;
+
+            let final_count = layout.vacuumed_page_count(page_count, free_pages);
+
+            if page_count < final_count || free_pages >= page_count {
+                return Err(Error::corrupt(0, format!("{free_pages} free pages in a database of {page_count} pages")));
+            }
+
+            self.vacuum_last_page(&layout, final_count)?;
+
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    fn vacuum_last_page(&mut self, layout: &PtrmapLayout, final_count: u32) -> Result<()> {
+        let last = self.page_count();
+
+        if !layout.is_map_page(last) && last != self.lock_byte_page() {
+            let entry = self.read_ptrmap(last)?;
+
+            let mut freelist = self.freelist()?;
+
+            match entry.entry_type {
+                PtrmapType::RootPage => return Err(Error::corrupt(last, "root page is past the largest root page")),
+
+                PtrmapType::FreePage => {
+                    if !freelist.remove(last) {
+                        return Err(Error::corrupt(last, "pointer map lists a page as free that is not on the freelist"));
+                    }
+
+                    self.write_freelist(&freelist)?;
+                }
+
+                _ => {
+                    let free_page = freelist
+                        .page_at_most(final_count)
+                        .ok_or_else(|| Error::corrupt(0, format!("no free page at or before page {final_count}")))?;
+
+                    freelist.remove(free_page);
+
+                    self.write_freelist(&freelist)?;
+
+                    self.relocate(last, entry, free_page)?;
+                }
+            }
+        }
+
+        let mut new_last = last - 1;
+
+        while new_last == self.lock_byte_page() || layout.is_map_page(new_last) {
+            new_last -= 1;
+        }
+
+        self.header.db_size = new_last;
+
+        self.truncate = true;
+
+        Ok(())
+    }
+
+    // Writes the updated header to page 1, marking the in-header database size as valid.
+    // Databases in full auto-vacuum mode give back all their free pages first.
+    pub fn commit(&mut self) -> Result<()> {
+        if self.ptrmap.is_some() && self.header.incremental_vacuum == 0 {
+            self.incremental_vacuum(None)?;
+        }
+
+        self.header.file_change_counter = self.header.file_change_counter.wrapping_add(1);
+        self.header.change_counter = self.header.file_change_counter;
+
+        let mut first_page = self.read(1)?;
+
+        self.header.write(&mut first_page);
+
+        self.write(1, &first_page)?;
+
+        if self.truncate {
+            self.file.truncate(u64::from(self.page_count()) * self.page_size() as u64)?;
+        }
+
+        self.file.sync()?;
+
+        self.file.unlock(LockLevel::Shared)?;
+
+        Ok(())
+    }
+
+    fn offset(&self, page_number: u32) -> u64 {
+        (page_number as u64 - 1) * self.page_size() as u64
+    }
+}
+
+impl Drop for Pager<'_> {
+    fn drop(&mut self) {
+        let _ = self.file.unlock(LockLevel::Shared);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::core::database::{read_header, Column, Database, QueryResult};
+use crate::core::value::Value;
+use crate::error::Result;
+use crate::parser::ast::Pragma;
+
+// integrity_check stops after this many errors unless it is given another limit
+const DEFAULT_MAX_ERRORS: usize = 100;
+
+impl Database {
+    // Runs a PRAGMA, returning its result as a single column named after it. Pragmas we
+    // do not know return nothing, the way sqlite ignores them.
+    pub fn pragma(&self, pragma: &Pragma) -> Result<QueryResult> {
+        let rows = match pragma.name.as_str() {
+            "integrity_check" | "quick_check" => {
+                let quick = pragma.name == "quick_check";
+
+                // the argument is either the maximum number of errors or a table to check
+                let (max_errors, table) = match &pragma.argument {
+                    Some(Value::Integer(max)) if *max > 0 => (*max as usize, None),
+                    Some(Value::Text(table)) => (DEFAULT_MAX_ERRORS, Some(table.as_str())),
+                    _ => (DEFAULT_MAX_ERRORS, None),
+                };
+
+                self.integrity_check(max_errors, quick, table)?
+                    .into_iter()
+                    .map(|message| vec![Value::Text(message)])
+                    .collect()
+            }
+
+            "freelist_count" => {
+                let header = read_header(self.vfs())?;
+
+                vec![vec![Value::Integer(i64::from(header.free_page_list_size))]]
+            }
+
+            // a new page size only takes effect when the database is rebuilt by VACUUM, and
+            // sizes sqlite would not accept are ignored
+            "page_size" => match pragma.argument {
+                Some(Value::Integer(size)) => {
+                    if let Some(size) = u32::try_from(size).ok().filter(|size| (512..=65536).contains(size) && size.is_power_of_two()) {
+                        let mut options = self.vacuum_options.get();
+
+                        options.page_size = Some(size);
+
+                        self.vacuum_options.set(options);
+                    }
+
+                    vec![]
+                }
+
+                _ => vec![vec![Value::Integer(i64::from(self.header().page_size))]],
+            },
+
+            "incremental_vacuum" => {
+                let limit = match pragma.argument {
+                    Some(Value::Integer(limit)) if limit > 0 => Some(u32::try_from(limit).unwrap_or(u32::MAX)),
+                    _ => None,
+                };
+
+                self.give_back_free_pages(limit)?;
+
+                vec![]
+            }
+
+            // LIKE ignores the case of ASCII letters unless this is on
+            "case_sensitive_like" => {
+                if let Some(argument) = &pragma.argument {
+                    self.functions.case_sensitive_like.set(is_on(argument));
+                }
+
+                vec![]
+            }
+
+            _ => vec![],
+        };
+
+        Ok(QueryResult {
+            columns: vec![Column {
+                name: pragma.name.clone(),
+                declared_type: None,
+            }],
+            rows,
+        })
+    }
+}
+
+// A boolean pragma argument: a number, or ON, YES or TRUE
+fn is_on(argument: &Value) -> bool {
+    match argument {
+        Value::Integer(value) => *value != 0,
+        Value::Real(value) => *value != 0.0,
+        Value::Text(text) => {
+            ["on", "yes", "true"].iter().any(|word| text.eq_ignore_ascii_case(word))
+                || text.trim().parse::<i64>().is_ok_and(|value| value != 0)
+        }
+        _ => false,
+    }
+}
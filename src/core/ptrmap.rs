@@ -0,0 +1,145 @@
+use crate::core::header::DBHeader;
+use crate::error::{Error, Result};
+
+// What a page of an auto-vacuum database is used for, as recorded in its pointer map entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtrmapType {
+    RootPage = 1,
+    FreePage = 2,
+    // first page of an overflow chain, its parent is the b-tree page holding the cell
+    Overflow1 = 3,
+    // later page of an overflow chain, its parent is the previous overflow page
+    Overflow2 = 4,
+    BTree = 5,
+}
+
+impl TryFrom<u8> for PtrmapType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        let entry_type = match value {
+            1 => PtrmapType::RootPage,
+            2 => PtrmapType::FreePage,
+            3 => PtrmapType::Overflow1,
+            4 => PtrmapType::Overflow2,
+            5 => PtrmapType::BTree,
+            _ => return Err(Error::corrupt(0, format!("invalid pointer map entry type {value}"))),
+        };
+
+        Ok(entry_type)
+    }
+}
+
+// A pointer map entry: the page type and the page that refers to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtrmapEntry {
+    pub entry_type: PtrmapType,
+    pub parent: u32,
+}
+
+impl PtrmapEntry {
+    pub fn new(entry_type: PtrmapType, parent: u32) -> Self {
+        Self { entry_type, parent }
+    }
+}
+
+// Where pointer map pages are. Page 2 is the first one, it holds an entry for each of the
+// usable_size / 5 pages that follow it, then comes the next pointer map page, and so on.
+// A pointer map page that would fall on the lock-byte page moves one page further.
+#[derive(Debug, Clone, Copy)]
+pub struct PtrmapLayout {
+    usable_size: u32,
+    lock_byte_page: u32,
+}
+
+impl PtrmapLayout {
+    pub fn new(header: &DBHeader) -> Self {
+        Self {
+            usable_size: header.get_usable_size(),
+            lock_byte_page: header.lock_byte_page(),
+        }
+    }
+
+    // The pointer map page holding the entry of `page_number`, 0 for page 1 which has none
+    pub fn map_page(&self, page_number: u32) -> u32 {
+        if page_number < 2 {
+            return 0;
+        }
+
+        let pages_per_map = self.usable_size / 5 + 1;
+
+        let map_page = (page_number - 2) / pages_per_map * pages_per_map + 2;
+
+        if map_page == self.lock_byte_page {
+            map_page + 1
+        } else {
+            map_page
+        }
+    }
+
+    pub fn is_map_page(&self, page_number: u32) -> bool {
+        page_number >= 2 && self.map_page(page_number) == page_number
+    }
+
+    // Offset of the entry of `page_number` in its pointer map page
+    pub fn entry_offset(&self, page_number: u32) -> Result<usize> {
+        let map_page = self.map_page(page_number);
+
+        let offset = page_number
+            .checked_sub(map_page)
+            .and_then(|index| index.checked_sub(1))
+            .map(|index| 5 * index as usize)
+            .filter(|offset| *offset + 5 <= self.usable_size as usize)
+            .ok_or_else(|| Error::corrupt(map_page, format!("page {page_number} has no pointer map entry")))?;
+
+        Ok(offset)
+    }
+
+    pub fn read_entry(&self, page_number: u32, map_page: &[u8]) -> Result<PtrmapEntry> {
+        let offset = self.entry_offset(page_number)?;
+
+        let entry_type = PtrmapType::try_from(map_page[offset]).map_err(|error| error.on_page(self.map_page(page_number)))?;
+
+        let parent = u32::from_be_bytes([
+            map_page[offset + 1],
+            map_page[offset + 2],
+            map_page[offset + 3],
+            map_page[offset + 4],
+        ]);
+
+        Ok(PtrmapEntry { entry_type, parent })
+    }
+
+    pub fn write_entry(&self, page_number: u32, map_page: &mut [u8], entry: PtrmapEntry) -> Result<()> {
+        let offset = self.entry_offset(page_number)?;
+
+        map_page[offset] = entry.entry_type as u8;
+        map_page[offset + 1..offset + 5].copy_from_slice(&entry.parent.to_be_bytes());
+
+        Ok(())
+    }
+
+    // Size a database of `page_count` pages shrinks to once its `free_pages` free pages and
+    // the pointer map pages that are no longer needed are gone, as sqlite computes it
+    pub fn vacuumed_page_count(&self, page_count: u32, free_pages: u32) -> u32 {
+        let entries_per_map = self.usable_size / 5;
+
+        let map_pages = free_pages
+            .wrapping_sub(page_count)
+            .wrapping_add(self.map_page(page_count))
+            .wrapping_add(entries_per_map)
+            / entries_per_map;
+
+        let mut final_count = page_count.wrapping_sub(free_pages).wrapping_sub(map_pages);
+
+        if page_count > self.lock_byte_page && final_count < self.lock_byte_page {
+            final_count = final_count.wrapping_sub(1);
+        }
+
+        while self.is_map_page(final_count) || final_count == self.lock_byte_page {
+            final_count -= 1;
+        }
+
+        final_count
+    }
+}
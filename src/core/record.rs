@@ -0,0 +1,69 @@
+use crate::core::value::Value;
+use crate::utils::{encode_varint, varint_len};
+
+// Picks the smallest serial type able to hold the value, and returns it with the encoded bytes
+fn serial_type(value: &Value) -> (u64, Vec<u8>) {
+    match value {
+        Value::Null => (0, vec![]),
+
+        Value::Integer(0) => (8, vec![]),
+
+        Value::Integer(1) => (9, vec![]),
+
+        Value::Integer(value) => {
+            let bytes = value.to_be_bytes();
+
+            let (serial_type, len) = match *value {
+                -0x80..=0x7f => (1, 1),
+                -0x8000..=0x7fff => (2, 2),
+                -0x80_0000..=0x7f_ffff => (3, 3),
+                -0x8000_0000..=0x7fff_ffff => (4, 4),
+                -0x8000_0000_0000..=0x7fff_ffff_ffff => (5, 6),
+                _ => (6, 8),
+            };
+
+            (serial_type, bytes[8 - len..].to_vec())
+        }
+
+        Value::Real(value) => (7, value.to_be_bytes().to_vec()),
+
+        Value::Text(text) => (text.len() as u64 * 2 + 13, text.as_bytes().to_vec()),
+
+        Value::Blob(bytes) => (bytes.len() as u64 * 2 + 12, bytes.clone()),
+    }
+}
+
+// Encodes values in the record format: a header made of its own size followed by
+// one serial type per column, then the column bodies back to back
+pub fn encode_record(values: &[Value]) -> Vec<u8> {
+    let mut serial_types = vec![];
+    let mut body = vec![];
+
+    for value in values {
+        let (serial_type, bytes) = serial_type(value);
+
+        serial_types.push(serial_type);
+        body.extend(bytes);
+    }
+
+    encode_raw_record(&serial_types, &body)
+}
+
+// Builds a record from serial types and the column bodies they describe
+pub fn encode_raw_record(serial_types: &[u64], body: &[u8]) -> Vec<u8> {
+    let types = serial_types.iter().flat_map(|serial_type| encode_varint(*serial_type)).collect::<Vec<_>>();
+
+    // the header size counts the varint holding it, which may grow the varint itself
+    let mut header_size = types.len() + 1;
+
+    while varint_len(header_size as u64) + types.len() != header_size {
+        header_size = varint_len(header_size as u64) + types.len();
+    }
+
+    let mut record = encode_varint(header_size as u64);
+
+    record.extend(types);
+    record.extend_from_slice(body);
+
+    record
+}
@@ -0,0 +1,364 @@
+use crate::core::btree::header_offset;
+use crate::core::cell::{CellPayload, PageCell};
+use crate::core::database::Database;
+use crate::core::header::TextEncoding;
+use crate::core::page::BTreePageSubType::{Interior, Leaf};
+use crate::core::page::PageTypes::TableBTree;
+use crate::core::page::MAX_BTREE_DEPTH;
+use crate::core::schema::{SchemaTable, SchemaTypesTypes};
+use crate::core::value::Value;
+use crate::utils::quote_identifier;
+use crate::error::Result;
+use std::collections::HashSet;
+use std::io::Write;
+use std::rc::Rc;
+
+// Rows read back from a damaged file, with the page each one was found on
+struct SalvagedRow {
+    page: u32,
+    rowid: i64,
+    payload: Rc<CellPayload>,
+}
+
+impl Database {
+    // Writes an SQL script that rebuilds as much of the database as can still be read.
+    // Every page is decoded on its own and every cell of a table leaf page separately, so
+    // damage only costs the rows it touches. Rows found on pages no table leads to, or with
+    // more fields than their table has columns, go to a lost_and_found table, which is where
+    // all of them go when the schema cannot be read.
+    pub fn recover(&self, out: &mut dyn Write) -> Result<()> {
+        let page_count = self.header().page_count(self.vfs().size()?);
+
+        let mut claimed = HashSet::new();
+
+        let schemas = self
+            .salvage_tree(1, &mut claimed)
+            .into_iter()
+            .filter_map(|row| SchemaTable::try_from(&row.payload).ok())
+            .collect::<Vec<_>>();
+
+        let mut lost = vec![];
+
+        let mut tables = vec![];
+
+        for schema in &schemas {
+            if schema.schema_type != SchemaTypesTypes::Table || schema.root_page <= 0 || schema.root_page as u32 > page_count {
+                continue;
+            }
+
+            let Some(statement) = schema.table() else {
+                continue;
+            };
+
+            let root_page = schema.root_page as u32;
+
+            let (rows, extra) = self
+                .salvage_tree(root_page, &mut claimed)
+                .into_iter()
+                .partition::<Vec<_>, _>(|row| row.payload.column_types.len() <= statement.columns.len());
+
+            lost.extend(extra.into_iter().map(|row| (root_page, row)));
+
+            tables.push((schema, rows));
+        }
+
+        // whatever is left over may still hold rows: pages of dropped or unknown tables,
+        // freed pages that were not overwritten yet, subtrees cut off from their parent
+        let mut referenced = HashSet::new();
+
+        for page_number in 2..=page_count {
+            if claimed.contains(&page_number) {
+                continue;
+            }
+
+            if let Some(buffer) = self.read_raw_page(page_number) {
+                if buffer[header_offset(page_number)] == u8::from(TableBTree(Interior)) {
+                    referenced.extend(self.child_pages(page_number, &buffer));
+                }
+            }
+        }
+
+        for page_number in 2..=page_count {
+            if claimed.contains(&page_number) || referenced.contains(&page_number) {
+                continue;
+            }
+
+            lost.extend(self.salvage_tree(page_number, &mut claimed).into_iter().map(|row| (page_number, row)));
+        }
+
+        self.write_recovered(out, &schemas, &tables, &lost)
+    }
+
+    fn write_recovered(
+        &self,
+        out: &mut dyn Write,
+        schemas: &[SchemaTable],
+        tables: &[(&SchemaTable, Vec<SalvagedRow>)],
+        lost: &[(u32, SalvagedRow)],
+    ) -> Result<()> {
+        let header = self.header();
+
+        let encoding = match header.text_encoding {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf16le => "UTF-16le",
+            TextEncoding::Utf16be => "UTF-16be",
+        };
+
+        let auto_vacuum = match (header.auto_vacuum, header.incremental_vacuum) {
+            (0, _) => 0,
+            (_, 0) => 1,
+            _ => 2,
+        };
+
+        // these only take effect before the first table is created, and the page size only
+        // outside a transaction
+        writeln!(out, "PRAGMA page_size={};", header.page_size)?;
+        writeln!(out, "PRAGMA auto_vacuum={auto_vacuum};")?;
+        writeln!(out, "PRAGMA encoding='{encoding}';")?;
+        writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+        writeln!(out, "BEGIN TRANSACTION;")?;
+        writeln!(out, "PRAGMA user_version={};", header.user_version_number as i32)?;
+        writeln!(out, "PRAGMA application_id={};", header.application_id as i32)?;
+
+
+        // like .dump, the internal tables come last so AUTOINCREMENT tables exist by then
+        let mut tables = tables.iter().collect::<Vec<_>>();
+
+        tables.sort_by_key(|(schema, _)| match schema.name.as_str() {
+            "sqlite_stat1" => 1,
+            "sqlite_sequence" => 2,
+            _ => 0,
+        });
+
+        for (schema, rows) in tables {
+            let name = quote_identifier(&schema.name);
+
+            match schema.name.as_str() {
+                // created along with the first AUTOINCREMENT table, creating it ourselves
+                // needs writable_schema which the sqlite3 shell only allows for .dump output
+                "sqlite_sequence" => writeln!(out, "DELETE FROM sqlite_sequence;")?,
+
+                "sqlite_stat1" => writeln!(out, "ANALYZE sqlite_schema;")?,
+
+                table if table.starts_with("sqlite_") => continue,
+
+                _ => writeln!(out, "{};", schema.sql)?,
+            }
+
+            let Some(statement) = schema.table() else {
+                continue;
+            };
+
+            let columns = &statement.columns;
+
+            let rowid_alias = statement.rowid_alias();
+
+            for row in rows {
+                let values = row.payload.values()?;
+
+                let mut names = vec![];
+                let mut literals = vec![];
+
+                // the INTEGER PRIMARY KEY column is stored as NULL and takes the rowid
+                if rowid_alias.is_none() && !statement.without_rowid {
+                    names.push("_rowid_".to_string());
+                    literals.push(row.rowid.to_string());
+                }
+
+                for (i, value) in values.iter().enumerate() {
+                    names.push(quote_identifier(&columns[i].name));
+
+                    if rowid_alias == Some(i) {
+                        literals.push(row.rowid.to_string());
+                    } else {
+                        literals.push(value.to_sql_literal());
+                    }
+                }
+
+                writeln!(out, "INSERT OR IGNORE INTO {name}({}) VALUES({});", names.join(","), literals.join(","))?;
+            }
+        }
+
+        if !lost.is_empty() {
+            self.write_lost_and_found(out, schemas, lost)?;
+        }
+
+        // indexes created for UNIQUE and PRIMARY KEY constraints come back with their table
+        let mut others = schemas
+            .iter()
+            .filter(|schema| schema.schema_type != SchemaTypesTypes::Table && !schema.sql.is_empty())
+            .collect::<Vec<_>>();
+
+        others.sort_by_key(|schema| match schema.schema_type {
+            SchemaTypesTypes::View => 0,
+            SchemaTypesTypes::Trigger => 1,
+            _ => 2,
+        });
+
+        for schema in others {
+            writeln!(out, "{};", schema.sql)?;
+        }
+
+        writeln!(out, "COMMIT;")?;
+
+        Ok(())
+    }
+
+    // Orphan rows keep the page they were found on, the root of the b-tree that page belongs
+    // to as far as we can tell, their number of fields and their rowid
+    fn write_lost_and_found(&self, out: &mut dyn Write, schemas: &[SchemaTable], lost: &[(u32, SalvagedRow)]) -> Result<()> {
+        let taken = schemas.iter().map(|schema| schema.name.to_lowercase()).collect::<HashSet<_>>();
+
+        let name = std::iter::once("lost_and_found".to_string())
+            .chain((0..).map(|i| format!("lost_and_found_{i}")))
+            .find(|name| !taken.contains(name))
+            .unwrap_or_default();
+
+        let fields = lost.iter().map(|(_, row)| row.payload.column_types.len()).max().unwrap_or(0);
+
+        let columns = (0..fields).map(|i| format!(", c{i}")).collect::<String>();
+
+        writeln!(out, "CREATE TABLE {name}(rootpgno INTEGER, pgno INTEGER, nfield INTEGER, id INTEGER{columns});")?;
+
+        for (root_page, row) in lost {
+            let mut values = row.payload.values()?.iter().map(Value::to_sql_literal).collect::<Vec<_>>();
+
+            let nfield = values.len();
+
+            values.resize(fields, "NULL".to_string());
+
+            let values = values.iter().map(|value| format!(",{value}")).collect::<String>();
+
+            writeln!(
+                out,
+                "INSERT INTO {name} VALUES({root_page},{},{nfield},{}{values});",
+                row.page, row.rowid
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // The rows of every readable leaf page of the table b-tree under `root_page`. Pages that
+    // cannot be read or are not table pages are skipped with everything below them, and
+    // pages in `claimed` are never visited again.
+    fn salvage_tree(&self, root_page: u32, claimed: &mut HashSet<u32>) -> Vec<SalvagedRow> {
+        let mut rows = vec![];
+
+        let mut level = if claimed.contains(&root_page) { vec![] } else { vec![root_page] };
+
+        let mut depth = 0;
+
+        while !level.is_empty() && depth <= MAX_BTREE_DEPTH {
+            let mut next_level = vec![];
+
+            for page_number in level {
+                let Some(buffer) = self.read_raw_page(page_number) else {
+                    continue;
+                };
+
+                let page_type = buffer[header_offset(page_number)];
+
+                if page_type == u8::from(TableBTree(Leaf)) {
+                    claimed.insert(page_number);
+
+                    rows.extend(self.salvage_leaf(page_number, &buffer));
+                } else if page_type == u8::from(TableBTree(Interior)) {
+                    claimed.insert(page_number);
+
+                    next_level.extend(
+                        self.child_pages(page_number, &buffer)
+                            .into_iter()
+                            .filter(|child| !claimed.contains(child)),
+                    );
+                }
+            }
+
+            level = next_level;
+
+            depth += 1;
+        }
+
+        rows
+    }
+
+    fn read_raw_page(&self, page_number: u32) -> Option<Vec<u8>> {
+        let page_size = self.header().page_size as usize;
+
+        let mut buffer = vec![0u8; page_size];
+
+        self.vfs()
+            .read_at(&mut buffer, u64::from(page_number - 1) * page_size as u64)
+            .ok()?;
+
+        Some(buffer)
+    }
+
+    // Cell pointers of a page that point inside it, as many as fit after its header
+    fn cell_pointers(&self, page_number: u32, buffer: &[u8], header_len: usize) -> Vec<usize> {
+        let usable_size = self.header().get_usable_size() as usize;
+
+        let start = header_offset(page_number);
+
+        let count = u16::from_be_bytes([buffer[start + 3], buffer[start + 4]]) as usize;
+
+        let pointers_start = start + header_len;
+
+        (0..count)
+            .map(|i| pointers_start + i * 2)
+            .take_while(|index| index + 2 <= usable_size)
+            .map(|index| u16::from_be_bytes([buffer[index], buffer[index + 1]]) as usize)
+            .filter(|pointer| (pointers_start..usable_size).contains(pointer))
+            .collect()
+    }
+
+    fn child_pages(&self, page_number: u32, buffer: &[u8]) -> Vec<u32> {
+        let page_count = self.header().page_count(self.vfs().size().unwrap_or(0));
+
+        let start = header_offset(page_number);
+
+        let read_u32 = |offset: usize| {
+            buffer
+                .get(offset..offset + 4)
+                .map_or(0, |bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+
+        self.cell_pointers(page_number, buffer, 12)
+            .into_iter()
+            .map(read_u32)
+            .chain([read_u32(start + 8)])
+            .filter(|child| (2..=page_count).contains(child) && *child != page_number)
+            .collect()
+    }
+
+    // Every cell of a table leaf page that decodes, overflow chain included
+    fn salvage_leaf(&self, page_number: u32, buffer: &[u8]) -> Vec<SalvagedRow> {
+        let header = self.header();
+
+        let usable_size = header.get_usable_size() as usize;
+
+        self.cell_pointers(page_number, buffer, 8)
+            .into_iter()
+            .filter_map(|pointer| {
+                let cell = PageCell::new(
+                    &buffer[pointer..usable_size],
+                    TableBTree(Leaf),
+                    usable_size as u64,
+                    &header.text_encoding,
+                    self.vfs(),
+                    header.page_size,
+                )
+                .ok()?;
+
+                // a record whose values do not decode is as good as lost
+                cell.payload.values().ok()?;
+
+                Some(SalvagedRow {
+                    page: page_number,
+                    rowid: cell.row_id as i64,
+                    payload: cell.payload,
+                })
+            })
+            .collect()
+    }
+}
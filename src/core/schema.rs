@@ -1,6 +1,8 @@
-use crate::core::cell::{CellPayload, ColumnTypes};
-use crate::parser::scanner::Scanner;
-use crate::parser::statement::Statement;
+use crate::core::cell::CellPayload;
+use crate::core::value::Value;
+use crate::parser::ast::{CreateIndexStmt, CreateTableStmt, Statement};
+use crate::parser::parse::parse_sql;
+use crate::error::{Error, Result};
 
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
@@ -26,84 +28,67 @@ impl Display for SchemaTypesTypes {
     }
 }
 
-impl From<&str> for SchemaTypesTypes {
-    fn from(value: &str) -> Self {
-        match value.to_uppercase().as_str() {
+impl TryFrom<&str> for SchemaTypesTypes {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        let schema_type = match value.to_uppercase().as_str() {
             "VIEW" => SchemaTypesTypes::View,
             "TABLE" => SchemaTypesTypes::Table,
             "INDEX" => SchemaTypesTypes::Index,
             "TRIGGER" => SchemaTypesTypes::Trigger,
-            _ => {
-                panic!("Invalid SchemaType");
-            }
-        }
+            _ => return Err(Error::corrupt(1, format!("malformed database schema: unknown type \"{value}\""))),
+        };
+
+        Ok(schema_type)
     }
 }
 
-impl From<&Rc<CellPayload>> for SchemaTable {
-    fn from(record: &Rc<CellPayload>) -> Self {
-        let mut root_page = 0;
-
-        let mut schema_type = SchemaTypesTypes::Table;
-
-        let mut columns = Vec::with_capacity(3);
-
-        let mut start_index = 0;
+impl TryFrom<&Rc<CellPayload>> for SchemaTable {
+    type Error = Error;
 
-        if let ColumnTypes::Text(col_size) = record.column_types[0] {
-            let end_index = start_index + col_size as usize;
+    fn try_from(record: &Rc<CellPayload>) -> Result<Self> {
+        // sqlite_schema rows are (type, name, tbl_name, rootpage, sql)
+        let values = record.values()?;
 
-            let text = std::str::from_utf8(&record.body[start_index..end_index])
-                .expect("Could not parse Schema Table");
-
-            schema_type = SchemaTypesTypes::from(text);
-
-            start_index += col_size as usize;
-        }
-
-        for i in 1..record.column_types.len() {
-            let col_type = &record.column_types[i];
-
-            match col_type {
-                ColumnTypes::Be8bitsInt(size) => {
-                    let num = u8::from_be_bytes([record.body[start_index]]) as i32;
-
-                    root_page = num;
+        let text = |index: usize| match values.get(index) {
+            Some(Value::Text(text)) => text.clone(),
+            _ => String::new(),
+        };
 
-                    start_index += *size as usize;
-                }
+        let root_page = match values.get(3) {
+            Some(Value::Integer(page)) => *page as i32,
+            _ => 0,
+        };
 
-                ColumnTypes::Text(col_size) => {
-                    let end_index = start_index + *col_size as usize;
+        let sql = text(4);
 
-                    let text = String::from_utf8(record.body[start_index..end_index].to_vec())
-                        .expect("Could not parse Schema Table");
+        let name = text(1);
 
-                    columns.push(text);
+        let schema_type = SchemaTypesTypes::try_from(text(0).as_str())?;
 
-                    start_index = end_index;
-                }
-                _ => {}
+        // automatic indexes have no SQL, and views and triggers are never run, so only tables
+        // must parse
+        let statement = match parse_sql(&sql) {
+            Ok(statement @ Statement::CreateTable(_)) if schema_type == SchemaTypesTypes::Table => Some(statement),
+            Ok(_) if schema_type == SchemaTypesTypes::Table => {
+                return Err(Error::corrupt(1, format!("malformed database schema ({name})")))
             }
-        }
-
-        let mut scanner = Scanner::new();
-
-        scanner
-            .scan(&columns[2])
-            .expect("Could not scan create statement;");
-
-        let create_statement =
-            Statement::new(scanner.get_tokens()).expect("Could not parse create statement;");
+            Ok(statement) => Some(statement),
+            Err(_) if schema_type != SchemaTypesTypes::Table => None,
+            Err(error) => {
+                return Err(Error::corrupt(1, format!("malformed database schema ({name}): {error}")))
+            }
+        };
 
-        Self {
+        Ok(Self {
+            sql,
+            name,
             root_page,
+            statement,
             schema_type,
-            sql: columns[2].to_owned(),
-            name: columns[0].to_owned(),
-            statement: create_statement,
-            tbl_name: columns[1].to_owned(),
-        }
+            tbl_name: text(2),
+        })
     }
 }
 
@@ -113,10 +98,29 @@ pub struct SchemaTable {
     pub name: String,
     pub root_page: i32,
     pub tbl_name: String,
-    pub statement: Statement,
+    pub statement: Option<Statement>,
     pub schema_type: SchemaTypesTypes,
 }
 
+impl SchemaTable {
+    // The CREATE TABLE statement of a table. Every table's SQL parsed when the schema was
+    // read, so this is only None for the other kinds of entries.
+    pub fn table(&self) -> Option<&CreateTableStmt> {
+        match &self.statement {
+            Some(Statement::CreateTable(table)) => Some(table),
+            _ => None,
+        }
+    }
+
+    // The CREATE INDEX statement of an index, None for automatic indexes, which have no SQL
+    pub fn index(&self) -> Option<&CreateIndexStmt> {
+        match &self.statement {
+            Some(Statement::CreateIndex(index)) => Some(index),
+            _ => None,
+        }
+    }
+}
+
 impl Display for SchemaTable {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
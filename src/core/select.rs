@@ -0,0 +1,1383 @@
+use crate::core::cte::{self, CtePlan, With};
+use crate::core::database::{Column, Database, QueryResult, Row};
+use crate::core::eval::{affinity, evaluate, is_true, Context};
+use crate::core::cell::CellPayload;
+use crate::core::functions::{self, literal_prefix};
+use crate::core::schema::{SchemaTable, SchemaTypesTypes};
+use crate::core::sort::{compare_columns, compare_sort_keys, ColumnOrder, RowSet};
+use crate::core::value::{Affinity, Value};
+use crate::error::{Error, Result};
+use crate::parser::ast::{
+    BinaryOperator, ColumnConstraint, ColumnDef, CompoundOperator, CreateTableStmt, Expr, JoinConstraint, JoinKind,
+    LikeOperator, Limit, OrderingTerm, ResultColumn, SelectCore, SelectStmt, TableRef,
+};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// A SELECT resolved against the schema: the table it reads, what it returns for each row,
+// which rows it keeps and in what order. Names are checked here, before any row is read.
+pub(crate) struct SelectPlan<'a> {
+    // where the rows come from, and the name their columns may be qualified with, which is
+    // the alias when there is one
+    source: Source<'a>,
+    qualifier: &'a str,
+    join: Option<Join<'a>>,
+
+    // the common table expressions its subqueries can read
+    with: Option<Rc<With<'a>>>,
+
+    pub(crate) columns: Vec<Column>,
+
+    // the affinity of each result column, which comparisons with its values apply
+    pub(crate) affinities: Vec<Option<Affinity>>,
+
+    // whether it refers to columns of a query it is a subquery of, which makes its rows
+    // differ from one row of that query to the next
+    pub(crate) correlated: bool,
+
+    outputs: Vec<Output<'a>>,
+    where_clause: Option<Cow<'a, Expr>>,
+
+    // DISTINCT, which leaves out rows equal to one returned before
+    distinct: bool,
+
+    // an index the rows can be found through instead of reading the whole table
+    index_scan: Option<PrefixScan>,
+
+    order_by: Vec<SortKey<'a>>,
+    limit: Option<usize>,
+    offset: usize,
+
+    // the SELECTs after this one when it is the first of a compound, whose LIMIT is then
+    // that of the compound
+    compound: Option<Compound<'a>>,
+}
+
+// The rest of a compound SELECT, each SELECT with the operator that combines its rows with
+// those of the ones before it, and the ORDER BY of the compound
+struct Compound<'a> {
+    parts: Vec<(CompoundOperator, SelectPlan<'a>)>,
+    order_by: Vec<ColumnOrder>,
+}
+
+pub(crate) enum Source<'a> {
+    // a SELECT without FROM, which makes one row
+    Nothing,
+
+    Table(Box<SchemaTable>),
+
+    // a subquery in FROM, and the table its result columns make
+    Subquery(Box<SelectPlan<'a>>, CreateTableStmt),
+
+    // a common table expression, read like a subquery unless its rows are materialized
+    Cte(Rc<With<'a>>, CtePlan<'a>, CreateTableStmt),
+
+    // a recursive common table expression in its own recursive SELECTs, which read the row
+    // the recursion is at
+    Recursion(Rc<With<'a>>, CreateTableStmt),
+}
+
+// The row of a recursive common table expression its recursive SELECT is joined with, and
+// the ON condition of the join
+struct Join<'a> {
+    cte: Rc<With<'a>>,
+    table: CreateTableStmt,
+    qualifier: &'a str,
+    condition: Option<&'a Expr>,
+}
+
+enum Output<'a> {
+    // count(*), which turns the result into a single row
+    Count,
+
+    // a column of `*`, by its position in the table
+    Column(usize),
+
+    Expr(&'a Expr),
+}
+
+// An ORDER BY term, which sorts by one of the result columns or by an expression over the
+// table, where aliases of result columns stand for what they name
+struct SortKey<'a> {
+    key: SortExpr<'a>,
+    descending: bool,
+    nulls_first: bool,
+}
+
+enum SortExpr<'a> {
+    Output(usize),
+    Expr(Cow<'a, Expr>),
+}
+
+// A WHERE term `column LIKE 'prefix%'` or `column GLOB 'prefix*'` turned into a range of an
+// index on the column: the entries that start with the prefix. LIKE needs a NOCASE index
+// unless PRAGMA case_sensitive_like is on, GLOB a BINARY one.
+struct PrefixScan {
+    root_page: i32,
+    prefix: String,
+    no_case: bool,
+}
+
+// The table a SELECT reads and the name its columns may be qualified with, the database,
+// for the functions expressions call and the subqueries they run, and the scope of the query
+// it is a subquery of, whose columns are seen behind its own
+pub(crate) struct Scope<'a> {
+    table: Option<(&'a CreateTableStmt, &'a str)>,
+    db: &'a Database,
+    outer: Option<&'a Scope<'a>>,
+    with: Option<Rc<With<'a>>>,
+
+    // set when an expression refers to a column of an outer query
+    correlated: Cell<bool>,
+
+    // the affinity of each scalar subquery, which comparisons with it apply
+    subquery_affinities: RefCell<HashMap<*const SelectStmt, Option<Affinity>>>,
+}
+
+impl Database {
+    pub(crate) fn plan_select<'a>(&self, select: &'a SelectStmt) -> Result<SelectPlan<'a>> {
+        self.plan_query(select, None, None)
+    }
+
+    // Plans a SELECT, which is a subquery when it has the scope of an outer query, and which
+    // can read the common table expressions of its WITH clause and those the query around it
+    // can read
+    pub(crate) fn plan_query<'a>(
+        &self,
+        select: &'a SelectStmt,
+        outer: Option<&Scope>,
+        with: Option<Rc<With<'a>>>,
+    ) -> Result<SelectPlan<'a>> {
+        let with = cte::chain(select.with.as_ref(), with)?;
+
+        if select.body.compounds.is_empty() {
+            return self.plan_core(&select.body.first, &select.order_by, select.limit.as_ref(), outer, with);
+        }
+
+        // ORDER BY and LIMIT apply to the rows of the whole compound
+        let mut plan = self.plan_core(&select.body.first, &[], None, outer, with.clone())?;
+        let mut parts = vec![];
+
+        for (operator, core) in &select.body.compounds {
+            let part = self.plan_core(core, &[], None, outer, with.clone())?;
+
+            plan.correlated |= part.correlated;
+
+            parts.push((*operator, part));
+        }
+
+        // like sqlite, check each SELECT against the one before it from the last one back, so
+        // that the error names the operator furthest to the right
+        for (i, (operator, part)) in parts.iter().enumerate().rev() {
+            let prior = if i == 0 { &plan } else { &parts[i - 1].1 };
+
+            if part.columns.len() != prior.columns.len() {
+                return Err(column_count_mismatch(*operator));
+            }
+        }
+
+        let order_by = {
+            let plans = std::iter::once(&plan).chain(parts.iter().map(|(_, part)| part));
+
+            self.plan_column_order(&select.order_by, &plans.collect::<Vec<_>>())?
+        };
+
+        (plan.limit, plan.offset) = self.plan_limit(select.limit.as_ref())?;
+
+        plan.compound = Some(Compound { parts, order_by });
+
+        Ok(plan)
+    }
+
+    // Resolves an ORDER BY that can only sort by result columns, that of a compound, against
+    // its SELECTs: a term is the number of a column, or names or repeats a result column of
+    // one of them, the leftmost first
+    pub(crate) fn plan_column_order(&self, terms: &[OrderingTerm], plans: &[&SelectPlan]) -> Result<Vec<ColumnOrder>> {
+        let count = plans[0].columns.len();
+
+        terms
+            .iter()
+            .enumerate()
+            .map(|(i, term)| {
+                let column = match &term.expr {
+                    Expr::Literal(Value::Integer(number)) => match usize::try_from(*number) {
+                        Ok(number @ 1..) if number <= count => number - 1,
+                        _ => return Err(order_term_out_of_range(i, count)),
+                    },
+                    Expr::Collate { .. } => return Err(Error::Unsupported("this kind of expression".to_string())),
+                    expr => plans.iter().find_map(|plan| plan.output_of(expr)).ok_or_else(|| {
+                        Error::Misuse(format!(
+                            "{} ORDER BY term does not match any column in the result set",
+                            ordinal(i + 1)
+                        ))
+                    })?,
+                };
+
+                Ok(ColumnOrder {
+                    column,
+                    descending: term.descending,
+                    nulls_first: term.nulls_first.unwrap_or(!term.descending),
+                })
+            })
+            .collect()
+    }
+
+    // Plans one SELECT of a compound, or the only one, with the ORDER BY and LIMIT that apply
+    // to its rows
+    pub(crate) fn plan_core<'a>(
+        &self,
+        core: &'a SelectCore,
+        order_by: &'a [OrderingTerm],
+        limit: Option<&'a Limit>,
+        outer: Option<&Scope>,
+        with: Option<Rc<With<'a>>>,
+    ) -> Result<SelectPlan<'a>> {
+        let SelectCore::Select {
+            distinct,
+            columns,
+            from,
+            where_clause,
+            group_by,
+            having,
+        } = core
+        else {
+            return Err(Error::Unsupported("VALUES".to_string()));
+        };
+
+        if !group_by.is_empty() || having.is_some() {
+            return Err(Error::Unsupported("GROUP BY".to_string()));
+        }
+
+        // a recursive common table expression in its own recursive SELECTs can be joined with
+        // one other table, and as it is one row it is read like a row of an outer query
+        let mut join = None;
+
+        // a subquery in FROM that refers to an outer query ties this one to its rows too
+        let (source, qualifier, correlated) = match from {
+            None => (Source::Nothing, "", false),
+            Some(TableRef::Join {
+                left,
+                operator,
+                right,
+                constraint,
+            }) => {
+                let condition = match constraint {
+                    None => None,
+                    Some(JoinConstraint::On(condition)) => Some(condition),
+                    Some(JoinConstraint::Using(_)) => return Err(Error::Unsupported("joins".to_string())),
+                };
+
+                if operator.natural || !matches!(operator.kind, JoinKind::Inner | JoinKind::Cross) {
+                    return Err(Error::Unsupported("joins".to_string()));
+                }
+
+                let left = self.plan_from(left, outer, with.clone())?;
+                let right = self.plan_from(right, outer, with.clone())?;
+
+                let (((Source::Recursion(cte, table), qualifier, _), other)
+                | (other, (Source::Recursion(cte, table), qualifier, _))) = (left, right)
+                else {
+                    return Err(Error::Unsupported("joins".to_string()));
+                };
+
+                join = Some(Join {
+                    cte,
+                    table,
+                    qualifier,
+                    condition,
+                });
+
+                other
+            }
+            Some(item) => self.plan_from(item, outer, with.clone())?,
+        };
+
+        let join_scope = join
+            .as_ref()
+            .map(|join| Scope::new(self, Some((&join.table, join.qualifier)), outer, with.clone()));
+
+        let scope = Scope::new(
+            self,
+            source.table().map(|table| (table, qualifier)),
+            join_scope.as_ref().or(outer),
+            with.clone(),
+        );
+
+        let mut plan_columns = vec![];
+        let mut outputs = vec![];
+
+        // the result column each alias names, for ORDER BY
+        let mut aliases = vec![];
+
+        for column in columns {
+            match column {
+                ResultColumn::All | ResultColumn::AllFrom(_) if join.is_some() => {
+                    return Err(Error::Unsupported("`*` in a join".to_string()));
+                }
+
+                ResultColumn::All | ResultColumn::AllFrom(_) => {
+                    let Some((table, qualifier)) = scope.table else {
+                        return Err(Error::Misuse("no tables specified".to_string()));
+                    };
+
+                    if let ResultColumn::AllFrom(from) = column {
+                        if !from.eq_ignore_ascii_case(qualifier) {
+                            return Err(Error::NoSuchTable(from.clone()));
+                        }
+                    }
+
+                    for (i, definition) in table.columns.iter().enumerate() {
+                        plan_columns.push(Column {
+                            name: definition.name.clone(),
+                            declared_type: definition.declared_type.clone(),
+                        });
+
+                        outputs.push(Output::Column(i));
+                    }
+                }
+
+                ResultColumn::Expr { expr, alias, span } => {
+                    let is_count = matches!(
+                        expr,
+                        Expr::Function { name, args, star: true, .. }
+                            if args.is_empty() && name.eq_ignore_ascii_case("count")
+                    );
+
+                    let (output, name, declared_type) = if is_count {
+                        (Output::Count, span.clone(), None)
+                    } else if let (Expr::Column { .. }, Ok((table, i))) = (expr, scope.resolve(expr)) {
+                        // a column is named the way the table declares it
+                        let definition = &table.columns[i];
+
+                        (Output::Expr(expr), definition.name.clone(), definition.declared_type.clone())
+                    } else {
+                        scope.check(expr)?;
+
+                        (Output::Expr(expr), span.clone(), None)
+                    };
+
+                    if let Some(alias) = alias {
+                        aliases.push((alias.as_str(), outputs.len(), expr));
+                    }
+
+                    plan_columns.push(Column {
+                        name: alias.clone().unwrap_or(name),
+                        declared_type,
+                    });
+
+                    outputs.push(output);
+                }
+            }
+        }
+
+        let is_count = outputs.iter().any(|output| matches!(output, Output::Count));
+
+        if is_count && outputs.len() > 1 {
+            return Err(Error::Unsupported("count(*) next to other result columns".to_string()));
+        }
+
+        let where_clause = match where_clause {
+            Some(condition) => {
+                let condition = scope.substitute_aliases(condition, &aliases);
+
+                scope.check(&condition)?;
+
+                Some(condition)
+            }
+            None => None,
+        };
+
+        let index_scan = match (&source, &where_clause) {
+            (Source::Table(schema), Some(condition)) => self.plan_prefix_scan(schema, &scope, condition)?,
+            _ => None,
+        };
+
+        let order_by = order_by
+            .iter()
+            .enumerate()
+            .map(|(i, term)| {
+                // a lone name is an alias before it is a table column
+                let alias = match &term.expr {
+                    Expr::Column { table: None, name } => {
+                        aliases.iter().find(|(alias, ..)| alias.eq_ignore_ascii_case(name))
+                    }
+                    _ => None,
+                };
+
+                let key = match (&term.expr, alias) {
+                    (_, Some((_, output, _))) => SortExpr::Output(*output),
+
+                    // a number picks a result column, counting from 1
+                    (Expr::Literal(Value::Integer(number)), _) => match usize::try_from(*number) {
+                        Ok(number @ 1..) if number <= outputs.len() => SortExpr::Output(number - 1),
+                        _ => return Err(order_term_out_of_range(i, outputs.len())),
+                    },
+
+                    (expr, _) => {
+                        let expr = scope.substitute_aliases(expr, &aliases);
+
+                        scope.check(&expr)?;
+
+                        SortExpr::Expr(expr)
+                    }
+                };
+
+                Ok(SortKey {
+                    key,
+                    descending: term.descending,
+                    nulls_first: term.nulls_first.unwrap_or(!term.descending),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let (limit, offset) = self.plan_limit(limit)?;
+
+        let affinities = outputs
+            .iter()
+            .map(|output| match output {
+                Output::Count => None,
+                Output::Column(i) => scope.table.map(|(table, _)| table.columns[*i].affinity()),
+                Output::Expr(expr) => affinity(expr, &scope),
+            })
+            .collect();
+
+        // columns of the joined row are not those of an outer query
+        let correlated = correlated || join_scope.as_ref().unwrap_or(&scope).correlated.get();
+
+        if let Some(condition) = join.as_ref().and_then(|join| join.condition) {
+            scope.check(condition)?;
+        }
+
+        Ok(SelectPlan {
+            source,
+            qualifier,
+            join,
+            with,
+            columns: plan_columns,
+            affinities,
+            correlated,
+            outputs,
+            where_clause,
+            distinct: *distinct,
+            index_scan,
+            order_by,
+            limit,
+            offset,
+            compound: None,
+        })
+    }
+
+    // Plans a table or a subquery in FROM, and whether it refers to an outer query
+    fn plan_from<'a>(
+        &self,
+        item: &'a TableRef,
+        outer: Option<&Scope>,
+        with: Option<Rc<With<'a>>>,
+    ) -> Result<(Source<'a>, &'a str, bool)> {
+        match item {
+            TableRef::Table { schema, name, alias } => {
+                let qualifier = alias.as_ref().unwrap_or(name).as_str();
+
+                // a common table expression hides a table of the same name
+                if let Some(cte) = cte::find(with.as_ref(), name).filter(|_| schema.is_none()) {
+                    return Ok((self.plan_cte(&cte)?, qualifier, false));
+                }
+
+                let schema = self
+                    .get_table_schema(name)?
+                    .ok_or_else(|| Error::NoSuchTable(name.clone()))?;
+
+                if schema.table().is_none() {
+                    return Err(Error::NoSuchTable(name.clone()));
+                }
+
+                Ok((Source::Table(Box::new(schema)), qualifier, false))
+            }
+
+            // it sees the columns of the queries around this one, but not of this one
+            TableRef::Subquery { select, alias } => {
+                let alias = alias.as_deref().unwrap_or_default();
+
+                let plan = self.plan_query(select, outer, with)?;
+
+                let table = plan.as_table(alias, &[]);
+
+                let correlated = plan.correlated;
+
+                Ok((Source::Subquery(Box::new(plan), table), alias, correlated))
+            }
+
+            TableRef::Join { .. } => Err(Error::Unsupported("joins".to_string())),
+        }
+    }
+
+    // LIMIT and OFFSET, the number of rows to return, None for all, and to skip before them
+    pub(crate) fn plan_limit(&self, limit: Option<&Limit>) -> Result<(Option<usize>, usize)> {
+        let Some(limit) = limit else {
+            return Ok((None, 0));
+        };
+
+        let offset = match &limit.offset {
+            Some(offset) => limit_value(offset, self)?.unwrap_or(0),
+            None => 0,
+        };
+
+        Ok((limit_value(&limit.count, self)?, offset))
+    }
+
+    // Finds an index that narrows the rows down to those starting with the literal prefix of
+    // a LIKE or GLOB pattern. The WHERE clause is still checked for every row it finds.
+    fn plan_prefix_scan(&self, schema: &SchemaTable, scope: &Scope, condition: &Expr) -> Result<Option<PrefixScan>> {
+        for term in conjuncts(condition) {
+            let Expr::Like {
+                expr,
+                operator: operator @ (LikeOperator::Like | LikeOperator::Glob),
+                pattern,
+                escape: None,
+                negated: false,
+            } = term
+            else {
+                continue;
+            };
+
+            let (Expr::Column { .. }, Expr::Literal(Value::Text(pattern))) = (expr.as_ref(), pattern.as_ref()) else {
+                continue;
+            };
+
+            let (table, i) = scope.resolve(expr)?;
+
+            // the entries of the indexes of a WITHOUT ROWID table end with its primary key
+            // rather than a rowid
+            if table.without_rowid {
+                continue;
+            }
+
+            let column = &table.columns[i];
+
+            // a number stored in a column without TEXT affinity may match the pattern
+            // without sorting among the text that starts with the prefix
+            let is_text = column.affinity() == Affinity::Text;
+
+            let prefix = literal_prefix(pattern, *operator == LikeOperator::Glob);
+
+            if !is_text || prefix.is_empty() {
+                continue;
+            }
+
+            let no_case = *operator == LikeOperator::Like && !self.functions.case_sensitive_like.get();
+
+            let wanted = if no_case { "NOCASE" } else { "BINARY" };
+
+            let column_collation = column.constraints.iter().find_map(|constraint| match constraint {
+                ColumnConstraint::Collate(collation) => Some(collation.as_str()),
+                _ => None,
+            });
+
+            let index = self.get_schemas()?.into_iter().find(|index| {
+                let Some(definition) = index.index() else {
+                    return false;
+                };
+
+                let Some(first) = definition.columns.first() else {
+                    return false;
+                };
+
+                let collation = first.collation.as_deref().or(column_collation).unwrap_or("BINARY");
+
+                index.schema_type == SchemaTypesTypes::Index
+                    && index.root_page > 0
+                    && index.tbl_name.eq_ignore_ascii_case(&schema.tbl_name)
+                    && definition.where_clause.is_none()
+                    && !first.descending
+                    && first.expr.column_name().is_some_and(|name| name.eq_ignore_ascii_case(&column.name))
+                    && collation.eq_ignore_ascii_case(wanted)
+            });
+
+            if let Some(index) = index {
+                return Ok(Some(PrefixScan {
+                    root_page: index.root_page,
+                    prefix: prefix.to_string(),
+                    no_case,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub(crate) fn run_select(&self, plan: SelectPlan) -> Result<QueryResult> {
+        self.run_query(&plan, None, None)
+    }
+
+    // Runs a SELECT, which is a subquery when it has the context of an outer query and the
+    // row of it the subquery is run for. `needed` is how many rows the caller reads when it
+    // does not read them all, like EXISTS, which reads one.
+    pub(crate) fn run_query(
+        &self,
+        plan: &SelectPlan,
+        outer: Option<(&Context, &Row)>,
+        needed: Option<usize>,
+    ) -> Result<QueryResult> {
+        let needed = needed.unwrap_or(usize::MAX);
+
+        let mut rows = vec![];
+
+        match &plan.compound {
+            _ if needed == 0 => {}
+
+            None => self.query_each(plan, outer, &mut |row| {
+                rows.push(row);
+
+                Ok(rows.len() < needed)
+            })?,
+
+            // the rows of a compound are combined and sorted before any is left out
+            Some(compound) => {
+                rows = self.core_rows(plan, outer)?;
+
+                // the SELECTs of a compound combine their rows from the left
+                for (operator, part) in &compound.parts {
+                    rows = combine(*operator, rows, self.core_rows(part, outer)?);
+                }
+
+                // the sort is stable, so rows that tie stay in the order the compound returns them
+                rows.sort_by(|a, b| compare_columns(&compound.order_by, a, b));
+
+                let limit = plan.limit.unwrap_or(usize::MAX).min(needed);
+
+                rows = rows.into_iter().skip(plan.offset).take(limit).collect();
+            }
+        }
+
+        Ok(QueryResult {
+            columns: plan.columns.clone(),
+            rows,
+        })
+    }
+
+    // Runs a SELECT and hands its rows to `each` until it returns false. A single SELECT hands
+    // on each row as soon as it has it and stops reading when no more are wanted, by the
+    // caller or by its LIMIT, which is what ends a recursive common table expression that
+    // would go on forever.
+    pub(crate) fn query_each(
+        &self,
+        plan: &SelectPlan,
+        outer: Option<(&Context, &Row)>,
+        each: &mut dyn FnMut(Vec<Value>) -> Result<bool>,
+    ) -> Result<()> {
+        if plan.compound.is_some() {
+            for row in self.run_query(plan, outer, None)?.rows {
+                if !each(row)? {
+                    break;
+                }
+            }
+
+            return Ok(());
+        }
+
+        let limit = plan.limit.unwrap_or(usize::MAX);
+
+        if limit == 0 {
+            return Ok(());
+        }
+
+        let mut skipped = 0;
+        let mut taken = 0;
+
+        self.run_core(plan, outer, &mut |row| {
+            if skipped < plan.offset {
+                skipped += 1;
+
+                return Ok(true);
+            }
+
+            taken += 1;
+
+            Ok(each(row)? && taken < limit)
+        })
+    }
+
+    // All the rows of one SELECT of a compound
+    fn core_rows(&self, plan: &SelectPlan, outer: Option<(&Context, &Row)>) -> Result<Vec<Vec<Value>>> {
+        let mut rows = vec![];
+
+        self.run_core(plan, outer, &mut |row| {
+            rows.push(row);
+
+            Ok(true)
+        })?;
+
+        Ok(rows)
+    }
+
+    // The rows of one SELECT of a compound, or of the only one, sorted by its ORDER BY but
+    // before its LIMIT, which applies to the whole compound. Unsorted rows go to `each` as
+    // they are read, and once it returns false no more are, so that the WHERE clause is not
+    // evaluated for rows no one reads.
+    fn run_core(
+        &self,
+        plan: &SelectPlan,
+        outer: Option<(&Context, &Row)>,
+        each: &mut dyn FnMut(Vec<Value>) -> Result<bool>,
+    ) -> Result<()> {
+        let is_count = matches!(plan.outputs.as_slice(), [Output::Count]);
+
+        // without a WHERE clause the rows only need counting, not decoding
+        if let Source::Table(schema) = &plan.source {
+            if is_count && plan.where_clause.is_none() {
+                each(vec![Value::Integer(self.count_records(&schema.tbl_name)? as i64)])?;
+
+                return Ok(());
+            }
+        }
+
+        let table = plan.source.table().map(|table| (table, plan.qualifier));
+
+        let joined = plan.join.as_ref().map(|join| {
+            let context = Context::new(self, Some((&join.table, join.qualifier)), outer, plan.with.clone());
+
+            (context, to_row(&join.table, join.cte.current_row()))
+        });
+
+        let context = Context::new(
+            self,
+            table,
+            joined.as_ref().map(|(context, row)| (context, row)).or(outer),
+            plan.with.clone(),
+        );
+
+        let join_condition = plan.join.as_ref().and_then(|join| join.condition);
+
+        let mut matched = 0;
+
+        // each row kept with the values it sorts by
+        let mut sorted = vec![];
+
+        let mut distinct = RowSet::default();
+
+        let is_sorted = !plan.order_by.is_empty();
+
+        self.scan(plan, outer, &mut |row| {
+            if let Some(condition) = join_condition {
+                if !is_true(&evaluate(condition, &row, &context)?) {
+                    return Ok(true);
+                }
+            }
+
+            if let Some(condition) = &plan.where_clause {
+                if !is_true(&evaluate(condition, &row, &context)?) {
+                    return Ok(true);
+                }
+            }
+
+            matched += 1;
+
+            if is_count {
+                return Ok(true);
+            }
+
+            let values = plan.project(&row, &context)?;
+
+            // the first of equal rows is the one returned
+            if plan.distinct && !distinct.insert(&values) {
+                return Ok(true);
+            }
+
+            if !is_sorted {
+                return each(values);
+            }
+
+            let keys = plan
+                .order_by
+                .iter()
+                .map(|key| match &key.key {
+                    SortExpr::Output(i) => Ok(values[*i].clone()),
+                    SortExpr::Expr(expr) => evaluate(expr, &row, &context),
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            sorted.push((keys, values));
+
+            Ok(true)
+        })?;
+
+        if is_count {
+            each(vec![Value::Integer(matched)])?;
+        }
+
+        // the sort is stable, so rows that tie stay in table order
+        sorted.sort_by(|(a, _), (b, _)| {
+            let keys = plan.order_by.iter().map(|key| (key.descending, key.nulls_first));
+
+            compare_sort_keys(keys, a, b)
+        });
+
+        for (_, values) in sorted {
+            if !each(values)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Hands the rows of the table a SELECT reads to `each` until it returns false. Subqueries
+    // and common table expressions make theirs as they are read.
+    fn scan(
+        &self,
+        plan: &SelectPlan,
+        outer: Option<(&Context, &Row)>,
+        each: &mut dyn FnMut(Row) -> Result<bool>,
+    ) -> Result<()> {
+        let rows = match (&plan.source, &plan.index_scan) {
+            (Source::Table(schema), Some(scan)) => self.get_rows(schema, &scan.row_ids(self)?)?,
+            (Source::Table(schema), None) => self.get_data(schema)?,
+            (Source::Subquery(subquery, table), _) => {
+                return self.query_each(subquery, outer, &mut |values| each(to_row(table, values)));
+            }
+            (Source::Cte(cte, cte_plan, table), _) => {
+                return self.run_cte(cte, cte_plan, &mut |values| each(to_row(table, values)));
+            }
+            (Source::Recursion(cte, table), _) => vec![to_row(table, cte.current_row())],
+            (Source::Nothing, _) => vec![Row::new()],
+        };
+
+        for row in rows {
+            if !each(row)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SelectPlan<'_> {
+    // The table a subquery in FROM makes, with a column for each result column under its
+    // name, or the one a column list gives it, and with its affinity. A name that repeats gets
+    // a number, `a:1`, like in sqlite.
+    pub(crate) fn as_table(&self, name: &str, column_names: &[String]) -> CreateTableStmt {
+        let mut columns: Vec<ColumnDef> = vec![];
+
+        for (i, affinity) in self.affinities.iter().enumerate() {
+            let column_name = column_names.get(i).unwrap_or(&self.columns[i].name);
+
+            let base = match column_name.rsplit_once(':') {
+                Some((base, number)) if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) => base,
+                _ => column_name,
+            };
+
+            let mut name = column_name.clone();
+
+            for number in 1.. {
+                if !columns.iter().any(|column| column.name.eq_ignore_ascii_case(&name)) {
+                    break;
+                }
+
+                name = format!("{base}:{number}");
+            }
+
+            let declared_type = match affinity {
+                Some(Affinity::Integer) => Some("INTEGER"),
+                Some(Affinity::Text) => Some("TEXT"),
+                Some(Affinity::Real) => Some("REAL"),
+                Some(Affinity::Numeric) => Some("NUMERIC"),
+                Some(Affinity::Blob) | None => None,
+            };
+
+            columns.push(ColumnDef {
+                name,
+                declared_type: declared_type.map(str::to_string),
+                constraints: vec![],
+            });
+        }
+
+        CreateTableStmt {
+            temporary: false,
+            if_not_exists: false,
+            schema: None,
+            name: name.to_string(),
+            columns,
+            constraints: vec![],
+            without_rowid: false,
+            strict: false,
+            select: None,
+        }
+    }
+
+    // The result column an ORDER BY term of a compound names or repeats. Expressions are
+    // the same when they are written the same.
+    fn output_of(&self, expr: &Expr) -> Option<usize> {
+        if let Expr::Column { table: None, name } = expr {
+            if let Some(i) = self.columns.iter().position(|column| column.name.eq_ignore_ascii_case(name)) {
+                return Some(i);
+            }
+        }
+
+        let written = format!("{expr:?}");
+
+        self.outputs
+            .iter()
+            .position(|output| matches!(output, Output::Expr(output) if format!("{output:?}") == written))
+    }
+
+    fn project(&self, row: &Row, context: &Context) -> Result<Vec<Value>> {
+        let table = self.source.table();
+
+        self.outputs
+            .iter()
+            .map(|output| match output {
+                Output::Count => Ok(Value::Null),
+                Output::Column(i) => Ok(table
+                    .and_then(|table| row.get(&table.columns[*i].name))
+                    .cloned()
+                    .unwrap_or(Value::Null)),
+                Output::Expr(expr) => evaluate(expr, row, context),
+            })
+            .collect()
+    }
+}
+
+impl Source<'_> {
+    fn table(&self) -> Option<&CreateTableStmt> {
+        match self {
+            Source::Nothing => None,
+            Source::Table(schema) => schema.table(),
+            Source::Subquery(_, table) | Source::Cte(_, _, table) | Source::Recursion(_, table) => Some(table),
+        }
+    }
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn new(
+        db: &'a Database,
+        table: Option<(&'a CreateTableStmt, &'a str)>,
+        outer: Option<&'a Scope<'a>>,
+        with: Option<Rc<With<'a>>>,
+    ) -> Self {
+        Self {
+            table,
+            db,
+            outer,
+            with,
+            correlated: Cell::new(false),
+            subquery_affinities: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn outer(&self) -> Option<&'a Scope<'a>> {
+        self.outer
+    }
+
+    pub(crate) fn with(&self) -> Option<Rc<With<'a>>> {
+        self.with.clone()
+    }
+
+    // The table and the position of the column an expression refers to in this query, not
+    // counting the queries around it
+    pub(crate) fn resolve(&self, expr: &Expr) -> Result<(&'a CreateTableStmt, usize)> {
+        let Expr::Column { table: prefix, name } = expr else {
+            return Err(Error::Unsupported("expressions in place of a column".to_string()));
+        };
+
+        let qualified_name = || match prefix {
+            Some(prefix) => format!("{prefix}.{name}"),
+            None => name.clone(),
+        };
+
+        let Some((table, qualifier)) = self.table else {
+            return Err(Error::NoSuchColumn(qualified_name()));
+        };
+
+        if prefix.as_ref().is_some_and(|prefix| !prefix.eq_ignore_ascii_case(qualifier)) {
+            return Err(Error::NoSuchColumn(qualified_name()));
+        }
+
+        let i = table
+            .column_index(name)
+            .ok_or_else(|| Error::NoSuchColumn(qualified_name()))?;
+
+        Ok((table, i))
+    }
+
+    // Every column an expression mentions must exist and every function it calls must take
+    // the arguments it is given, so that mistakes are reported even when the table is empty
+    fn check(&self, expr: &Expr) -> Result<()> {
+        match expr {
+            Expr::Literal(_) => Ok(()),
+            Expr::Column { .. } => match (self.resolve(expr), self.outer) {
+                (Ok(_), _) => Ok(()),
+
+                // a column of an outer query, which ties this one to its rows
+                (Err(_), Some(outer)) if outer.check(expr).is_ok() => {
+                    self.correlated.set(true);
+
+                    Ok(())
+                }
+
+                (Err(error), _) => Err(error),
+            },
+            Expr::Unary { expr, .. } => self.check(expr),
+            Expr::Binary { left, right, .. } => {
+                self.check(left)?;
+                self.check(right)
+            }
+            Expr::Function { name, args, .. } => {
+                if functions::is_aggregate(name, args.len()) {
+                    return Err(Error::Unsupported(format!("the aggregate function {name}() here")));
+                }
+
+                self.db.functions.check(name, args.len())?;
+
+                args.iter().try_for_each(|arg| self.check(arg))
+            }
+            Expr::Like {
+                expr,
+                operator,
+                pattern,
+                escape,
+                ..
+            } => {
+                if let Some(name) = functions::operator_function(*operator) {
+                    self.db.functions.check(name, 2 + usize::from(escape.is_some()))?;
+                }
+
+                self.check(expr)?;
+                self.check(pattern)?;
+
+                escape.iter().try_for_each(|escape| self.check(escape))
+            }
+            Expr::Cast { expr, .. } => self.check(expr),
+            Expr::Case {
+                operand,
+                branches,
+                otherwise,
+            } => {
+                operand.iter().try_for_each(|operand| self.check(operand))?;
+
+                for (condition, result) in branches {
+                    self.check(condition)?;
+                    self.check(result)?;
+                }
+
+                otherwise.iter().try_for_each(|otherwise| self.check(otherwise))
+            }
+            Expr::Between { expr, low, high, .. } => {
+                self.check(expr)?;
+                self.check(low)?;
+                self.check(high)
+            }
+            Expr::InList { expr, list, .. } => {
+                self.check(expr)?;
+
+                list.iter().try_for_each(|item| self.check(item))
+            }
+            Expr::InSelect { expr, select, .. } => {
+                self.check(expr)?;
+
+                self.plan_subquery(select, true).map(|_| ())
+            }
+            Expr::Subquery(select) => self.plan_subquery(select, true).map(|_| ()),
+            Expr::Exists(select) => self.plan_subquery(select, false).map(|_| ()),
+            _ => Err(Error::Unsupported("this kind of expression".to_string())),
+        }
+    }
+
+    // Plans a subquery in this scope, which for a value has to return a single column
+    fn plan_subquery<'s>(&'s self, select: &'s SelectStmt, single_column: bool) -> Result<SelectPlan<'s>> {
+        let plan = self.db.plan_query(select, Some(self), self.with.clone())?;
+
+        match plan.columns.len() {
+            1 => Ok(plan),
+            _ if !single_column => Ok(plan),
+            count => Err(Error::Misuse(format!("sub-select returns {count} columns - expected 1"))),
+        }
+    }
+
+    // The affinity of a scalar subquery, which is the one of its column
+    pub(crate) fn subquery_affinity(&self, select: &SelectStmt) -> Option<Affinity> {
+        let key = select as *const SelectStmt;
+
+        if let Some(affinity) = self.subquery_affinities.borrow().get(&key) {
+            return *affinity;
+        }
+
+        let affinity = self.plan_subquery(select, true).ok()?.affinities[0];
+
+        self.subquery_affinities.borrow_mut().insert(key, affinity);
+
+        affinity
+    }
+
+    fn has_column(&self, name: &str) -> bool {
+        self.table.is_some_and(|(table, _)| table.column_index(name).is_some())
+    }
+
+    // In WHERE and ORDER BY, a name that is not a column of the table may be the alias of a
+    // result column, and stands for its expression
+    fn substitute_aliases<'e>(&self, expr: &'e Expr, aliases: &[(&str, usize, &Expr)]) -> Cow<'e, Expr> {
+        let substitute = |expr: &Expr| Box::new(self.substitute_aliases(expr, aliases).into_owned());
+
+        match expr {
+            Expr::Column { table: None, name } if !self.has_column(name) => {
+                match aliases.iter().find(|(alias, ..)| alias.eq_ignore_ascii_case(name)) {
+                    Some((.., aliased)) => Cow::Owned((*aliased).clone()),
+                    None => Cow::Borrowed(expr),
+                }
+            }
+
+            Expr::Unary { operator, expr } => Cow::Owned(Expr::Unary {
+                operator: *operator,
+                expr: substitute(expr),
+            }),
+
+            Expr::Binary { left, operator, right } => Cow::Owned(Expr::Binary {
+                left: substitute(left),
+                operator: *operator,
+                right: substitute(right),
+            }),
+
+            Expr::Function {
+                name,
+                args,
+                distinct,
+                star,
+            } => Cow::Owned(Expr::Function {
+                name: name.clone(),
+                args: args.iter().map(|arg| *substitute(arg)).collect(),
+                distinct: *distinct,
+                star: *star,
+            }),
+
+            Expr::Like {
+                expr,
+                operator,
+                pattern,
+                escape,
+                negated,
+            } => Cow::Owned(Expr::Like {
+                expr: substitute(expr),
+                operator: *operator,
+                pattern: substitute(pattern),
+                escape: escape.as_deref().map(substitute),
+                negated: *negated,
+            }),
+
+            Expr::Cast { expr, type_name } => Cow::Owned(Expr::Cast {
+                expr: substitute(expr),
+                type_name: type_name.clone(),
+            }),
+
+            Expr::Case {
+                operand,
+                branches,
+                otherwise,
+            } => Cow::Owned(Expr::Case {
+                operand: operand.as_deref().map(substitute),
+                branches: branches
+                    .iter()
+                    .map(|(condition, result)| (*substitute(condition), *substitute(result)))
+                    .collect(),
+                otherwise: otherwise.as_deref().map(substitute),
+            }),
+
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => Cow::Owned(Expr::Between {
+                expr: substitute(expr),
+                low: substitute(low),
+                high: substitute(high),
+                negated: *negated,
+            }),
+
+            Expr::InList { expr, list, negated } => Cow::Owned(Expr::InList {
+                expr: substitute(expr),
+                list: list.iter().map(|item| *substitute(item)).collect(),
+                negated: *negated,
+            }),
+
+            // the subquery has names of its own, only the value looked for may be an alias
+            Expr::InSelect { expr, select, negated } => Cow::Owned(Expr::InSelect {
+                expr: substitute(expr),
+                select: select.clone(),
+                negated: *negated,
+            }),
+
+            _ => Cow::Borrowed(expr),
+        }
+    }
+}
+
+impl PrefixScan {
+    // The rowids of the index entries in the range: first the text that starts with the
+    // prefix, then, like sqlite, the blobs between the prefix in upper and lower case, which
+    // LIKE reads as text. The blobs sort after all text, so each is a range of its own.
+    fn row_ids(&self, db: &Database) -> Result<Vec<u64>> {
+        let root = db.read_page(self.root_page)?;
+
+        let mut entries = root.get_index_range(&|entry| Ok(self.text_position(&first_key(entry)?)))?;
+
+        entries.extend(root.get_index_range(&|entry| Ok(self.blob_position(&first_key(entry)?)))?);
+
+        // the rowid is the last value of an index entry
+        entries
+            .iter()
+            .map(|entry| match entry.values()?.last() {
+                Some(Value::Integer(row_id)) => Ok(*row_id as u64),
+                _ => Err(Error::corrupt(self.root_page as u32, "index entry without a rowid")),
+            })
+            .collect()
+    }
+
+    // Where a key lies relative to the text that starts with the prefix. Text sorts after
+    // NULL and numbers and before blobs.
+    fn text_position(&self, key: &Value) -> Ordering {
+        let Value::Text(key) = key else {
+            return match key {
+                Value::Blob(_) => Ordering::Greater,
+                _ => Ordering::Less,
+            };
+        };
+
+        let (key, prefix) = match self.no_case {
+            true => (key.to_ascii_lowercase(), self.prefix.to_ascii_lowercase()),
+            false => (key.clone(), self.prefix.clone()),
+        };
+
+        match key.starts_with(&prefix) {
+            true => Ordering::Equal,
+            false => key.cmp(&prefix),
+        }
+    }
+
+    // Where a key lies relative to the blobs from the prefix, in upper case when case is
+    // ignored, up to the lower case prefix with its last byte incremented
+    fn blob_position(&self, key: &Value) -> Ordering {
+        let Value::Blob(key) = key else {
+            return Ordering::Less;
+        };
+
+        let (low, mut high) = match self.no_case {
+            true => (self.prefix.to_ascii_uppercase().into_bytes(), self.prefix.to_ascii_lowercase().into_bytes()),
+            false => (self.prefix.clone().into_bytes(), self.prefix.clone().into_bytes()),
+        };
+
+        if let Some(last) = high.last_mut() {
+            *last += 1;
+        }
+
+        if key.as_slice() < low.as_slice() {
+            Ordering::Less
+        } else if key.as_slice() >= high.as_slice() {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
+// The first value of an index entry, the one the entries are sorted by first
+fn first_key(entry: &CellPayload) -> Result<Value> {
+    match entry.column_types.first() {
+        Some(column_type) => column_type.decode(&entry.body),
+        None => Ok(Value::Null),
+    }
+}
+
+// The terms of a condition joined by AND, each of which must hold for a row to be kept
+fn conjuncts(condition: &Expr) -> Vec<&Expr> {
+    match condition {
+        Expr::Binary {
+            left,
+            operator: BinaryOperator::And,
+            right,
+        } => {
+            let mut terms = conjuncts(left);
+
+            terms.extend(conjuncts(right));
+
+            terms
+        }
+        _ => vec![condition],
+    }
+}
+
+// 1st, 2nd, 3rd, 4th, ..., 11th, 12th, 13th, ..., 21st
+fn order_term_out_of_range(i: usize, count: usize) -> Error {
+    Error::Misuse(format!(
+        "{} ORDER BY term out of range - should be between 1 and {count}",
+        ordinal(i + 1)
+    ))
+}
+
+// sqlite names the operator where the SELECTs around it disagree
+pub(crate) fn column_count_mismatch(operator: CompoundOperator) -> Error {
+    let operator = match operator {
+        CompoundOperator::Union => "UNION",
+        CompoundOperator::UnionAll => "UNION ALL",
+        CompoundOperator::Intersect => "INTERSECT",
+        CompoundOperator::Except => "EXCEPT",
+    };
+
+    Error::Misuse(format!(
+        "SELECTs to the left and right of {operator} do not have the same number of result columns"
+    ))
+}
+
+// Combines the rows of a compound so far with those of its next SELECT. All but UNION ALL
+// leave out duplicates and return the rows sorted on every column, and where rows are equal
+// UNION keeps the last and INTERSECT and EXCEPT the one on the left, like sqlite.
+fn combine(operator: CompoundOperator, left: Vec<Vec<Value>>, right: Vec<Vec<Value>>) -> Vec<Vec<Value>> {
+    match operator {
+        CompoundOperator::UnionAll => left.into_iter().chain(right).collect(),
+        CompoundOperator::Union => left.into_iter().chain(right).collect::<RowSet>().into_rows(),
+        CompoundOperator::Intersect => {
+            let right = right.into_iter().collect::<RowSet>();
+
+            let left = left.into_iter().collect::<RowSet>().into_rows();
+
+            left.into_iter().filter(|row| right.contains(row)).collect()
+        }
+        CompoundOperator::Except => {
+            let mut rows = left.into_iter().collect::<RowSet>();
+
+            for row in right {
+                rows.remove(&row);
+            }
+
+            rows.into_rows()
+        }
+    }
+}
+
+fn ordinal(number: usize) -> String {
+    let suffix = match (number % 10, number % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+
+    format!("{number}{suffix}")
+}
+
+// The values of a row by the names of the table's columns
+fn to_row(table: &CreateTableStmt, values: Vec<Value>) -> Row {
+    table.columns.iter().map(|column| column.name.clone()).zip(values).collect()
+}
+
+// LIMIT and OFFSET take a constant integer, a negative LIMIT means no limit
+fn limit_value(expr: &Expr, db: &Database) -> Result<Option<usize>> {
+    match evaluate(expr, &Row::new(), &Context::new(db, None, None, None))? {
+        Value::Integer(value) => Ok(usize::try_from(value).ok()),
+        Value::Real(value) if value.fract() == 0.0 => Ok(usize::try_from(value as i64).ok()),
+        _ => Err(Error::Mismatch(String::new())),
+    }
+}
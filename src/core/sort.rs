@@ -0,0 +1,212 @@
+use crate::core::value::Value;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+// An ORDER BY term that sorts by a result column, the only kind a compound SELECT or a
+// recursive common table expression takes
+pub(crate) struct ColumnOrder {
+    pub(crate) column: usize,
+    pub(crate) descending: bool,
+    pub(crate) nulls_first: bool,
+}
+
+// Orders rows by their sort keys, each ascending or descending and with NULLs first or last
+pub(crate) fn compare_sort_keys(
+    keys: impl Iterator<Item = (bool, bool)>,
+    a: &[Value],
+    b: &[Value],
+) -> Ordering {
+    for ((descending, nulls_first), (a, b)) in keys.zip(a.iter().zip(b)) {
+        let ordering = match (a.is_null(), b.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) if nulls_first => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, true) if nulls_first => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) if descending => b.compare(a),
+            (false, false) => a.compare(b),
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+// Orders rows by some of their columns
+pub(crate) fn compare_columns(order_by: &[ColumnOrder], a: &[Value], b: &[Value]) -> Ordering {
+    for term in order_by {
+        let ordering = compare_sort_keys(
+            std::iter::once((term.descending, term.nulls_first)),
+            &a[term.column..=term.column],
+            &b[term.column..=term.column],
+        );
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+// A row as the key of an ordered set, where rows whose values sqlite compares equal are
+// the same, which is how DISTINCT and UNION tell duplicates apart
+struct RowKey(Vec<Value>);
+
+impl Ord for RowKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_sort_keys(std::iter::repeat((false, true)), &self.0, &other.0)
+    }
+}
+
+impl PartialOrd for RowKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RowKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RowKey {}
+
+// Rows without duplicates, which come out sorted on every column the way sqlite's
+// temporary index for DISTINCT, UNION, INTERSECT and EXCEPT returns them
+#[derive(Default)]
+pub(crate) struct RowSet(BTreeSet<RowKey>);
+
+impl RowSet {
+    // Adds a row unless an equal one is there already, and tells whether it was added
+    pub(crate) fn insert(&mut self, row: &[Value]) -> bool {
+        self.0.insert(RowKey(row.to_vec()))
+    }
+
+    // Adds a row in place of an equal one, so that of rows like 1 and 1.0 the last is kept
+    pub(crate) fn replace(&mut self, row: Vec<Value>) {
+        self.0.replace(RowKey(row));
+    }
+
+    pub(crate) fn remove(&mut self, row: &[Value]) {
+        self.0.remove(&RowKey(row.to_vec()));
+    }
+
+    pub(crate) fn contains(&self, row: &[Value]) -> bool {
+        self.0.contains(&RowKey(row.to_vec()))
+    }
+
+    pub(crate) fn into_rows(self) -> Vec<Vec<Value>> {
+        self.0.into_iter().map(|RowKey(row)| row).collect()
+    }
+}
+
+impl FromIterator<Vec<Value>> for RowSet {
+    fn from_iter<I: IntoIterator<Item = Vec<Value>>>(rows: I) -> Self {
+        let mut set = RowSet::default();
+
+        for row in rows {
+            set.replace(row);
+        }
+
+        set
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::core::database::Database;
+    use crate::parser::parse::parse_sql;
+
+    // t(id INTEGER PRIMARY KEY, a TEXT) with the rows (1, 'one'), (500, 'five hundred') and
+    // (2000, 'two thousand')
+    const INDEXED: &[u8] = include_bytes!("../../tests/indexed.db");
+
+    // Runs each query on t and checks its rows, each as the SQL literals of its values joined
+    // by commas, against what sqlite3 -quote writes
+    fn check(cases: &[(&str, &[&str])]) {
+        let db = Database::deserialize(INDEXED.to_vec()).unwrap();
+
+        for (sql, expected) in cases {
+            let rows = db.query(&parse_sql(sql).unwrap()).unwrap().rows;
+
+            let rows = rows
+                .iter()
+                .map(|row| row.iter().map(|value| value.to_sql_literal()).collect::<Vec<_>>().join(","))
+                .collect::<Vec<_>>();
+
+            assert_eq!(rows, *expected, "{sql}");
+        }
+    }
+
+    // UNION, INTERSECT and EXCEPT leave out rows sqlite compares equal, 1 and 1.0 but not 1
+    // and '1', keep NULLs as one value, and return what is left sorted
+    #[test]
+    fn compounds_leave_out_duplicates() {
+        check(&[
+            ("SELECT 1 UNION SELECT 1.0", &["1.0"]),
+            ("SELECT 1.0 UNION SELECT 1", &["1"]),
+            ("SELECT 1 UNION ALL SELECT 1.0", &["1", "1.0"]),
+            ("SELECT 2 UNION SELECT 1 UNION SELECT '1' UNION SELECT x'31' UNION SELECT NULL",
+                &["NULL", "1", "2", "'1'", "X'31'"]),
+            ("SELECT NULL UNION SELECT NULL", &["NULL"]),
+            ("SELECT 1, NULL UNION SELECT 1, NULL", &["1,NULL"]),
+            ("SELECT 'a' UNION SELECT 'A'", &["'A'", "'a'"]),
+            ("SELECT 1 UNION SELECT '1'", &["1", "'1'"]),
+            ("SELECT id FROM t UNION SELECT 500", &["1", "500", "2000"]),
+            ("SELECT id FROM t INTERSECT SELECT 500.0", &["500"]),
+            ("SELECT id FROM t INTERSECT SELECT '500'", &[]),
+            ("SELECT id FROM t EXCEPT SELECT 500", &["1", "2000"]),
+            ("SELECT 1 EXCEPT SELECT NULL", &["1"]),
+            ("SELECT NULL INTERSECT SELECT NULL", &["NULL"]),
+            ("SELECT NULL EXCEPT SELECT NULL", &[]),
+            ("SELECT 2 UNION SELECT 1 EXCEPT SELECT 2", &["1"]),
+            ("SELECT 1 UNION ALL SELECT 1 INTERSECT SELECT 1", &["1"]),
+            ("SELECT a FROM t EXCEPT SELECT 'one'", &["'five hundred'", "'two thousand'"]),
+            ("SELECT 'x' UNION SELECT x'78'", &["'x'", "X'78'"]),
+            ("SELECT DISTINCT 0.0 FROM t UNION SELECT 0", &["0"]),
+            ("SELECT DISTINCT -0.0 FROM t UNION SELECT 0.0", &["0.0"]),
+            ("SELECT 3 UNION SELECT 1 UNION SELECT 2 ORDER BY 1 DESC", &["3", "2", "1"]),
+            ("SELECT 1 UNION SELECT 2 LIMIT 1 OFFSET 1", &["2"]),
+        ]);
+    }
+
+    // DISTINCT leaves out the same duplicates but keeps the rows in the order they come
+    #[test]
+    fn distinct_leaves_out_duplicates() {
+        check(&[
+            ("SELECT DISTINCT id > 1 FROM t", &["0", "1"]),
+            ("SELECT DISTINCT id > 1, a < 'p' FROM t", &["0,1", "1,1", "1,0"]),
+            ("SELECT DISTINCT NULL FROM t", &["NULL"]),
+            ("SELECT DISTINCT id / 1000 FROM t", &["0", "2"]),
+            ("SELECT DISTINCT id / 1000.0 > 0.4 FROM t", &["0", "1"]),
+            ("SELECT DISTINCT CAST(id > 1 AS REAL) FROM t", &["0.0", "1.0"]),
+            ("SELECT DISTINCT CASE id WHEN 1 THEN 1 WHEN 500 THEN 1.0 ELSE '1' END FROM t", &["1", "'1'"]),
+            ("SELECT DISTINCT CASE id WHEN 500 THEN 1.0 END, 'x' FROM t", &["NULL,'x'", "1.0,'x'"]),
+        ]);
+    }
+
+    #[test]
+    fn compounds_need_the_same_number_of_columns() {
+        let db = Database::deserialize(INDEXED.to_vec()).unwrap();
+
+        let cases = [
+            ("SELECT 1 UNION SELECT 1, 2", "UNION"),
+            ("SELECT 1 INTERSECT SELECT 1, 2 EXCEPT SELECT 1", "EXCEPT"),
+            ("SELECT 1, 2 UNION ALL SELECT 1 UNION SELECT 1", "UNION ALL"),
+        ];
+
+        for (sql, operator) in cases {
+            let error = db.query(&parse_sql(sql).unwrap()).err().unwrap().to_string();
+
+            let expected = format!("SELECTs to the left and right of {operator} do not have the same number");
+
+            assert!(error.contains(&expected), "{sql}: {error}");
+        }
+    }
+}
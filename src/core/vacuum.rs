@@ -0,0 +1,269 @@
+use crate::core::btree::{write_page, BTreePage, IndexAppender, TableAppender};
+use crate::core::database::{read_header, Database};
+use crate::core::header::DBHeader;
+use crate::core::journal;
+use crate::core::page::BTreePageSubType::Leaf;
+use crate::core::page::PageTypes::{IndexBTree, TableBTree};
+use crate::core::pager::Pager;
+use crate::core::record::encode_record;
+use crate::core::schema::SchemaTable;
+use crate::core::value::Value;
+use crate::core::vfs::{FileVfs, LockLevel, MemoryVfs, Vfs};
+use crate::error::{Error, Result};
+use std::fs::OpenOptions;
+use std::path::Path;
+
+// Layout changes applied while a database is rebuilt, fields left as None keep the
+// current value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VacuumOptions {
+    pub page_size: Option<u32>,
+    pub reserved_bytes: Option<u8>,
+}
+
+impl VacuumOptions {
+    // The header the rebuilt database starts from: an empty database with the settings of
+    // `header` that are not about where things are stored
+    fn header(&self, header: &DBHeader) -> Result<DBHeader> {
+        let page_size = self.page_size.unwrap_or(header.page_size);
+
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return Err(Error::Misuse(format!("invalid page size {page_size}")));
+        }
+
+        let reserved_bytes = self.reserved_bytes.map_or(header.reserved_bytes_per_page, u16::from);
+
+        // sqlite needs at least 480 usable bytes on every page
+        if page_size < u32::from(reserved_bytes) + 480 {
+            return Err(Error::Misuse(format!(
+                "{reserved_bytes} reserved bytes leave too little room in pages of {page_size} bytes"
+            )));
+        }
+
+        let mut rebuilt = DBHeader::empty(page_size, reserved_bytes);
+
+        rebuilt.file_change_counter = header.file_change_counter;
+        rebuilt.change_counter = header.file_change_counter;
+        rebuilt.schema_cookie = header.schema_cookie.wrapping_add(1);
+        rebuilt.schema_format_number = header.schema_format_number;
+        rebuilt.suggested_cache_size = header.suggested_cache_size;
+        rebuilt.text_encoding = header.text_encoding.clone();
+        rebuilt.user_version_number = header.user_version_number;
+        rebuilt.application_id = header.application_id;
+        rebuilt.incremental_vacuum = header.incremental_vacuum;
+
+        // page 1 is the only root so far, roots of the copied b-trees follow it
+        if !header.should_omit_pointer_map() {
+            rebuilt.auto_vacuum = 1;
+        }
+
+        Ok(rebuilt)
+    }
+}
+
+impl Database {
+    // Rebuilds the database in place: every b-tree is copied into fresh pages in key order,
+    // so the file ends up with no free pages and no half-empty or scattered b-tree pages
+    pub fn vacuum(&mut self, options: &VacuumOptions) -> Result<()> {
+        let file = self.vfs();
+
+        let held = file.lock_level();
+
+        // nobody may write while the copy is made, nor read while it replaces the file
+        file.lock(LockLevel::Reserved)?;
+
+        let result = match self.path() {
+            Some(path) => self.vacuum_file(path, options),
+            None => self.vacuum_image(options),
+        };
+
+        file.unlock(held.min(LockLevel::Shared))?;
+
+        result?;
+
+        self.reopen()
+    }
+
+    // A database on disk is rebuilt in a file next to it, which is copied over it once
+    // synced. Until the copy is synced too the pages it replaces are kept in a journal, so
+    // that a crash leaves the old database or the new one and never a mix of both.
+    fn vacuum_file(&self, path: &Path, options: &VacuumOptions) -> Result<()> {
+        let mut copy_path = path.as_os_str().to_os_string();
+
+        copy_path.push("-vacuum");
+
+        let copy = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&copy_path)?;
+
+        let copy = FileVfs::new(copy);
+
+        let result = self.rebuild(options, &copy).and_then(|()| {
+            let file = self.vfs();
+
+            let header = read_header(file)?;
+
+            journal::write(path, file, header.page_size, header.page_count(file.size()?))?;
+
+            let copied = file.lock(LockLevel::Exclusive).and_then(|()| copy_file(&copy, file));
+
+            match copied {
+                Ok(()) => journal::delete(path),
+                Err(error) => {
+                    let _ = journal::roll_back(path, file);
+
+                    Err(error)
+                }
+            }
+        });
+
+        drop(copy);
+
+        let _ = std::fs::remove_file(&copy_path);
+
+        result
+    }
+
+    // One without a file, like one in memory, has no crash to survive and is rebuilt in
+    // memory
+    fn vacuum_image(&self, options: &VacuumOptions) -> Result<()> {
+        let copy = MemoryVfs::new(vec![]);
+
+        self.rebuild(options, &copy)?;
+
+        self.vfs().lock(LockLevel::Exclusive)?;
+
+        copy_file(&copy, self.vfs())
+    }
+
+    // Writes a vacuumed copy of the database to a new file, leaving this one alone
+    pub fn vacuum_into(&self, path: impl AsRef<Path>, options: &VacuumOptions) -> Result<()> {
+        let path = path.as_ref();
+
+        let mut output = OpenOptions::new();
+
+        output.read(true).write(true).create(true);
+
+        let output = output.open(path).map_err(|error| {
+            Error::Io(std::io::Error::new(
+                error.kind(),
+                format!("unable to open \"{}\": {error}", path.display()),
+            ))
+        })?;
+
+        // like sqlite, an existing database is never overwritten
+        if output.metadata()?.len() > 0 {
+            return Err(Error::Misuse("output file already exists".to_string()));
+        }
+
+        let output = FileVfs::new(output);
+
+        let result = self
+            .with_shared_lock(|| self.rebuild(options, &output))
+            .and_then(|()| output.unlock(LockLevel::None));
+
+        // a copy cut short is no database
+        if result.is_err() {
+            drop(output);
+
+            let _ = std::fs::remove_file(path);
+        }
+
+        result
+    }
+
+    // Copies the schema and every table and index into a new database in `file`, which is
+    // empty, and syncs it. Schema rows keep their rowids and order, only their root pages
+    // change. Rows are copied as they are read, a page of each b-tree level at a time.
+    fn rebuild(&self, options: &VacuumOptions, file: &dyn Vfs) -> Result<()> {
+        let header = options.header(&read_header(self.vfs())?)?;
+
+        let mut first_page = vec![0u8; header.page_size as usize];
+
+        header.write(&mut first_page);
+
+        BTreePage::empty(1, TableBTree(Leaf)).write_to(&mut first_page, header.get_usable_size() as usize);
+
+        file.write_at(&first_page, 0)?;
+
+        let mut pager = Pager::new(file, header)?;
+
+        let mut btrees = vec![];
+        let mut schema_rows = vec![];
+
+        for (rowid, payload) in self.read_page(1)?.get_payloads()? {
+            let schema = SchemaTable::try_from(&payload)?;
+
+            let mut values = payload.values()?;
+
+            if schema.root_page > 0 {
+                let page_type = self.page_type(schema.root_page as u32)?;
+
+                let leaf_type = match page_type {
+                    TableBTree(_) => TableBTree(Leaf),
+                    IndexBTree(_) => IndexBTree(Leaf),
+                    _ => return Err(Error::corrupt(schema.root_page as u32, format!("root page of {} is not a b-tree page", schema.name))),
+                };
+
+                let root_page = pager.allocate_root()?;
+
+                write_page(&pager, &BTreePage::empty(root_page, leaf_type))?;
+
+                values[3] = Value::Integer(i64::from(root_page));
+
+                btrees.push((schema.root_page, root_page, leaf_type));
+            }
+
+            schema_rows.push((rowid as i64, encode_record(&values)));
+        }
+
+        let mut schema = TableAppender::new(&mut pager, 1)?;
+
+        for (rowid, record) in &schema_rows {
+            schema.append(*rowid, record)?;
+        }
+
+        schema.finish()?;
+
+        for (old_root, new_root, leaf_type) in btrees {
+            let page = self.read_page(old_root)?;
+
+            if leaf_type == TableBTree(Leaf) {
+                let mut table = TableAppender::new(&mut pager, new_root)?;
+
+                page.for_each_payload(&mut |rowid, payload| table.append(rowid as i64, &payload.record()))?;
+
+                table.finish()?;
+            } else {
+                let mut index = IndexAppender::new(&mut pager, new_root)?;
+
+                page.for_each_index_payload(&mut |payload| index.append(&payload.record()))?;
+
+                index.finish()?;
+            }
+        }
+
+        pager.commit()
+    }
+}
+
+// Makes `to` a copy of `from` and syncs it
+fn copy_file(from: &dyn Vfs, to: &dyn Vfs) -> Result<()> {
+    let size = from.size()?;
+
+    let mut buffer = vec![0u8; 1 << 16];
+
+    let mut offset = 0;
+
+    while offset < size {
+        let len = buffer.len().min((size - offset) as usize);
+
+        from.read_at(&mut buffer[..len], offset)?;
+
+        to.write_at(&buffer[..len], offset)?;
+
+        offset += len as u64;
+    }
+
+    to.truncate(size)?;
+
+    to.sync()
+}
@@ -0,0 +1,365 @@
+use crate::core::functions::real_with_all_digits;
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Integer(_) => "integer",
+            Value::Real(_) => "real",
+            Value::Text(_) => "text",
+            Value::Blob(_) => "blob",
+        }
+    }
+
+    // Renders the value as an SQL literal, the way quote() writes it
+    pub fn to_sql_literal(&self) -> String {
+        match self {
+            Value::Null => "NULL".to_string(),
+            Value::Integer(value) => value.to_string(),
+            Value::Real(value) => format_real_exact(*value),
+            Value::Text(text) => format!("'{}'", text.replace('\'', "''")),
+            Value::Blob(bytes) => format!("X'{}'", to_hex(bytes)),
+        }
+    }
+
+    // Renders the value the way the sqlite3 shell writes one in `.mode quote`, or with
+    // `insert` in `.mode insert` and `.dump`: reals with all their digits, blobs in lowercase
+    // hex, and for inserts whole reals as integers with ".0" and text holding control
+    // characters through unistr(), so that every row stays on one line
+    pub fn to_shell_literal(&self, insert: bool) -> String {
+        match self {
+            Value::Real(value) if insert && is_whole(*value) => format!("{}.0", *value as i64),
+
+            Value::Real(value) if value.is_infinite() && !insert => {
+                if *value > 0.0 { "Inf" } else { "-Inf" }.to_string()
+            }
+
+            Value::Real(value) if value.is_finite() => format_real_digits(*value),
+
+            Value::Text(text) if insert && text.chars().any(|c| c < ' ') => {
+                let mut escaped = String::with_capacity(text.len() + 16);
+
+                for c in text.chars() {
+                    match c {
+                        '\'' => escaped.push_str("''"),
+                        '\\' => escaped.push_str("\\\\"),
+                        c if c < ' ' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => escaped.push(c),
+                    }
+                }
+
+                format!("unistr('{escaped}')")
+            }
+
+            Value::Blob(bytes) => format!("X'{}'", to_hex(bytes).to_ascii_lowercase()),
+            _ => self.to_sql_literal(),
+        }
+    }
+
+    // Orders values the way sqlite sorts them: NULL, then numbers, then text, then blobs
+    pub fn compare(&self, other: &Value) -> Ordering {
+        fn class(value: &Value) -> u8 {
+            match value {
+                Value::Null => 0,
+                Value::Integer(_) | Value::Real(_) => 1,
+                Value::Text(_) => 2,
+                Value::Blob(_) => 3,
+            }
+        }
+
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Real(b)) => compare_integer_real(*a, *b),
+            (Value::Real(a), Value::Integer(b)) => compare_integer_real(*b, *a).reverse(),
+            (Value::Real(a), Value::Real(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            _ => class(self).cmp(&class(other)),
+        }
+    }
+}
+
+// Compares an integer with a real exactly, as sqlite does, rather than as two reals, which
+// would make 2^53 + 1 equal to 2^53. The real's whole part decides unless it is the integer,
+// and then its fraction does.
+fn compare_integer_real(integer: i64, real: f64) -> Ordering {
+    if real.is_nan() {
+        return Ordering::Equal;
+    }
+
+    // the reals past either end of i64, 2^63 being the first above it
+    if real < -9223372036854775808.0 {
+        return Ordering::Greater;
+    }
+
+    if real >= 9223372036854775808.0 {
+        return Ordering::Less;
+    }
+
+    match integer.cmp(&(real as i64)) {
+        Ordering::Equal => (integer as f64).partial_cmp(&real).unwrap_or(Ordering::Equal),
+        ordering => ordering,
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Real(value) => write!(f, "{}", format_real(*value)),
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Blob(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+        }
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// Formats a float the way sqlite3 does for display ("%!.15g"): 15 significant digits,
+// always keeping a decimal point so the value reads back as a REAL
+pub fn format_real(value: f64) -> String {
+    format_real_with_precision(value, 15)
+}
+
+// Formats a float with every digit sqlite works out for it, like the sqlite3 shell does for
+// SQL and JSON to read back
+pub fn format_real_digits(value: f64) -> String {
+    real_with_all_digits(value, 'g')
+}
+
+// A real holding a whole number an i64 can hold too
+fn is_whole(value: f64) -> bool {
+    (i64::MIN as f64..-(i64::MIN as f64)).contains(&value) && value.fract() == 0.0
+}
+
+// Formats a float with enough digits to read back the exact same value, the way quote() does:
+// 15 significant digits like for display when they are enough, else every digit sqlite works
+// out, in exponent form.
+pub fn format_real_exact(value: f64) -> String {
+    if value.is_nan() {
+        return "NULL".to_string();
+    }
+
+    if value.is_infinite() {
+        return if value > 0.0 { "9.0e+999" } else { "-9.0e+999" }.to_string();
+    }
+
+    let formatted = format_real_with_precision(value, 15);
+
+    if formatted.parse::<f64>().is_ok_and(|parsed| parsed == value) {
+        return formatted;
+    }
+
+    real_with_all_digits(value, 'e')
+}
+
+fn format_real_with_precision(value: f64, precision: usize) -> String {
+    if value.is_nan() {
+        return String::new();
+    }
+
+    if value.is_infinite() {
+        return if value > 0.0 { "Inf" } else { "-Inf" }.to_string();
+    }
+
+    if value == 0.0 {
+        return "0.0".to_string();
+    }
+
+    let scientific = format!("{:.*e}", precision - 1, value);
+
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+
+    let exponent = exponent.parse::<i32>().unwrap_or(0);
+
+    if exponent < -4 || exponent >= precision as i32 {
+        let mut mantissa = trim_fraction(mantissa);
+
+        if !mantissa.contains('.') {
+            mantissa.push_str(".0");
+        }
+
+        let sign = if exponent < 0 { '-' } else { '+' };
+
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    } else {
+        let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+
+        let mut fixed = trim_fraction(&format!("{:.*}", decimals, value));
+
+        if !fixed.contains('.') {
+            fixed.push_str(".0");
+        }
+
+        fixed
+    }
+}
+
+fn trim_fraction(number: &str) -> String {
+    if !number.contains('.') {
+        return number.to_string();
+    }
+
+    let trimmed = number.trim_end_matches('0');
+
+    match trimmed.strip_suffix('.') {
+        Some(integer_part) => format!("{}.0", integer_part),
+        None => trimmed.to_string(),
+    }
+}
+
+// Type affinity of a column, derived from its declared type with SQLite's substring rules
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Affinity {
+    Integer,
+    Text,
+    Blob,
+    Real,
+    Numeric,
+}
+
+impl Affinity {
+    pub fn from_declared_type(declared_type: &str) -> Self {
+        let declared_type = declared_type.to_uppercase();
+
+        if declared_type.contains("INT") {
+            Affinity::Integer
+        } else if ["CHAR", "CLOB", "TEXT"].iter().any(|name| declared_type.contains(name)) {
+            Affinity::Text
+        } else if declared_type.is_empty() || declared_type.contains("BLOB") {
+            Affinity::Blob
+        } else if ["REAL", "FLOA", "DOUB"].iter().any(|name| declared_type.contains(name)) {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+}
+
+impl Value {
+    // Converts a value before it is stored in a column with the given affinity
+    pub fn apply_affinity(self, affinity: Affinity) -> Value {
+        match (affinity, self) {
+            (Affinity::Text, Value::Integer(value)) => Value::Text(value.to_string()),
+
+            (Affinity::Text, Value::Real(value)) => Value::Text(format_real(value)),
+
+            (Affinity::Integer | Affinity::Numeric, Value::Text(text)) => match parse_numeric(&text) {
+                Some(Value::Real(value)) if is_integral(value) => Value::Integer(value as i64),
+                Some(value) => value,
+                None => Value::Text(text),
+            },
+
+            (Affinity::Integer | Affinity::Numeric, Value::Real(value)) if is_integral(value) => {
+                Value::Integer(value as i64)
+            }
+
+            (Affinity::Real, Value::Text(text)) => match parse_numeric(&text) {
+                Some(Value::Integer(value)) => Value::Real(value as f64),
+                Some(value) => value,
+                None => Value::Text(text),
+            },
+
+            (Affinity::Real, Value::Integer(value)) => Value::Real(value as f64),
+
+            (_, value) => value,
+        }
+    }
+}
+
+fn is_integral(value: f64) -> bool {
+    value.fract() == 0.0 && (-9.223372036854775e18..=9.223372036854775e18).contains(&value)
+}
+
+// Parses text that is a well-formed integer or real literal, ignoring surrounding spaces
+pub fn parse_numeric(text: &str) -> Option<Value> {
+    let text = text.trim();
+
+    let is_number = !text.is_empty()
+        && text
+            .trim_start_matches(['+', '-'])
+            .starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && !text.contains(|c: char| c.is_alphabetic() && c != 'e' && c != 'E');
+
+    if !is_number {
+        return None;
+    }
+
+    if let Ok(value) = text.parse::<i64>() {
+        return Some(Value::Integer(value));
+    }
+
+    text.parse::<f64>().ok().filter(|value| value.is_finite()).map(Value::Real)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn integers_compare_with_reals_exactly() {
+        let cases = [
+            (Value::Integer(9007199254740993), Value::Real(9007199254740992.0), Ordering::Greater),
+            (Value::Integer(9007199254740992), Value::Real(9007199254740992.0), Ordering::Equal),
+            (Value::Integer(2), Value::Real(2.5), Ordering::Less),
+            (Value::Integer(-2), Value::Real(-2.5), Ordering::Greater),
+            (Value::Integer(i64::MAX), Value::Real(9223372036854775808.0), Ordering::Less),
+            (Value::Integer(i64::MIN), Value::Real(-9223372036854775808.0), Ordering::Equal),
+            (Value::Integer(i64::MIN), Value::Real(-1e19), Ordering::Greater),
+        ];
+
+        for (integer, real, ordering) in cases {
+            assert_eq!(integer.compare(&real), ordering, "{integer:?} against {real:?}");
+            assert_eq!(real.compare(&integer), ordering.reverse(), "{real:?} against {integer:?}");
+        }
+    }
+
+    #[test]
+    fn classes_sort_null_numbers_text_blobs() {
+        let sorted = [Value::Null, Value::Real(-1.5), Value::Integer(3), Value::Text("a".into()), Value::Blob(vec![0])];
+
+        for pair in sorted.windows(2) {
+            assert_eq!(pair[0].compare(&pair[1]), Ordering::Less, "{:?} before {:?}", pair[0], pair[1]);
+        }
+    }
+
+    // quote() writes 15 significant digits when they read back as the same real, else every
+    // digit sqlite3 works out, in exponent form
+    #[test]
+    fn reals_quote_like_sqlite() {
+        let cases = [
+            (0.1, "0.1"),
+            (1e-7, "1.0e-07"),
+            (100.0, "100.0"),
+            (1.0 / 3.0, "3.333333333333333148e-01"),
+            (0.1 + 0.2, "3.000000000000000445e-01"),
+            (9223372036854775808.0, "9.22337203685477581e+18"),
+            (123456789012345678.0, "1.2345678901234568e+17"),
+            (f64::MAX, "1.797693134862315692e+308"),
+            (f64::INFINITY, "9.0e+999"),
+        ];
+
+        for (real, expected) in cases {
+            assert_eq!(Value::Real(real).to_sql_literal(), expected, "{real:?}");
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::error::Result;
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::prelude::FileExt;
+
+// The page holding the byte at offset 2^30 is reserved for file locking and never used
+pub const PENDING_BYTE_OFFSET: u64 = 0x4000_0000;
+
+const RESERVED_BYTE_OFFSET: u64 = PENDING_BYTE_OFFSET + 1;
+
+const SHARED_FIRST_OFFSET: u64 = PENDING_BYTE_OFFSET + 2;
+
+const SHARED_SIZE: u64 = 510;
+
+// The lock levels of sqlite's locking protocol, each one includes the ones before it.
+// Readers hold SHARED, a writer takes RESERVED while it prepares changes and
+// EXCLUSIVE while they reach the file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockLevel {
+    #[default]
+    None,
+    Shared,
+    Reserved,
+    Exclusive,
+}
+
+// Storage underneath a database: a flat array of bytes that pages are read from and
+// written to. Reads past the end fail with `UnexpectedEof`, which callers reading pages
+// turn into a truncated database with `Error::past_end`.
+pub trait Vfs {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> Result<()>;
+
+    fn write_at(&self, buffer: &[u8], offset: u64) -> Result<()>;
+
+    fn sync(&self) -> Result<()>;
+
+    fn truncate(&self, size: u64) -> Result<()>;
+
+    fn size(&self) -> Result<u64>;
+
+    // Raises the lock to `level`, doing nothing when it is already held. Fails with
+    // `WouldBlock` when another connection holds a conflicting lock.
+    fn lock(&self, level: LockLevel) -> Result<()>;
+
+    // Lowers the lock to `level`, which is either SHARED or NONE
+    fn unlock(&self, level: LockLevel) -> Result<()>;
+
+    fn lock_level(&self) -> LockLevel;
+}
+
+impl std::fmt::Debug for dyn Vfs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Vfs {{ size: {:?}, lock: {:?} }}", self.size().ok(), self.lock_level())
+    }
+}
+
+// A database file on disk. Locks are POSIX advisory locks on the same bytes sqlite3
+// uses, so both can safely work on the same file at once.
+pub struct FileVfs {
+    file: File,
+    level: Cell<LockLevel>,
+}
+
+impl FileVfs {
+    pub fn new(file: File) -> Self {
+        Self {
+            file,
+            level: Cell::new(LockLevel::None),
+        }
+    }
+
+    fn set_lock(&self, lock_type: libc::c_int, start: u64, len: u64) -> Result<()> {
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+
+        lock.l_type = lock_type as libc::c_short;
+        lock.l_whence = libc::SEEK_SET as libc::c_short;
+        lock.l_start = start as libc::off_t;
+        lock.l_len = len as libc::off_t;
+
+        // SAFETY: the descriptor is owned by `self.file` and `lock` is a valid flock
+        let result = unsafe { libc::fcntl(self.file.as_raw_fd(), libc::F_SETLK, &lock) };
+
+        if result == -1 {
+            let error = IoError::last_os_error();
+
+            return match error.raw_os_error() {
+                Some(libc::EAGAIN) | Some(libc::EACCES) => {
+                    Err(IoError::new(ErrorKind::WouldBlock, "database is locked").into())
+                }
+                _ => Err(error.into()),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+impl Vfs for FileVfs {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> Result<()> {
+        self.file.read_exact_at(buffer, offset)?;
+
+        Ok(())
+    }
+
+    fn write_at(&self, buffer: &[u8], offset: u64) -> Result<()> {
+        self.file.write_all_at(buffer, offset)?;
+
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<()> {
+        self.file.sync_data()?;
+
+        Ok(())
+    }
+
+    fn truncate(&self, size: u64) -> Result<()> {
+        self.file.set_len(size)?;
+
+        Ok(())
+    }
+
+    fn size(&self) -> Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    fn lock(&self, level: LockLevel) -> Result<()> {
+        let current = self.level.get();
+
+        if level <= current {
+            return Ok(());
+        }
+
+        if current == LockLevel::None {
+            // new readers take the pending byte first, so they queue behind a waiting writer
+            self.set_lock(libc::F_RDLCK, PENDING_BYTE_OFFSET, 1)?;
+
+            let shared = self.set_lock(libc::F_RDLCK, SHARED_FIRST_OFFSET, SHARED_SIZE);
+
+            self.set_lock(libc::F_UNLCK, PENDING_BYTE_OFFSET, 1)?;
+
+            shared?;
+
+            self.level.set(LockLevel::Shared);
+        }
+
+        if level >= LockLevel::Reserved && self.level.get() < LockLevel::Reserved {
+            self.set_lock(libc::F_WRLCK, RESERVED_BYTE_OFFSET, 1)?;
+
+            self.level.set(LockLevel::Reserved);
+        }
+
+        if level == LockLevel::Exclusive {
+            self.set_lock(libc::F_WRLCK, PENDING_BYTE_OFFSET, 1)?;
+
+            if let Err(error) = self.set_lock(libc::F_WRLCK, SHARED_FIRST_OFFSET, SHARED_SIZE) {
+                self.set_lock(libc::F_UNLCK, PENDING_BYTE_OFFSET, 1)?;
+
+                return Err(error);
+            }
+
+            self.level.set(LockLevel::Exclusive);
+        }
+
+        Ok(())
+    }
+
+    fn unlock(&self, level: LockLevel) -> Result<()> {
+        let current = self.level.get();
+
+        if level >= current {
+            return Ok(());
+        }
+
+        if level == LockLevel::Shared {
+            if current == LockLevel::Exclusive {
+                self.set_lock(libc::F_RDLCK, SHARED_FIRST_OFFSET, SHARED_SIZE)?;
+            }
+
+            self.set_lock(libc::F_UNLCK, PENDING_BYTE_OFFSET, 2)?;
+        } else {
+            self.set_lock(libc::F_UNLCK, PENDING_BYTE_OFFSET, 2 + SHARED_SIZE)?;
+        }
+
+        self.level.set(level);
+
+        Ok(())
+    }
+
+    fn lock_level(&self) -> LockLevel {
+        self.level.get()
+    }
+}
+
+// A database held in memory. There is only ever one connection to it, so every lock is granted.
+#[derive(Default)]
+pub struct MemoryVfs {
+    data: RefCell<Vec<u8>>,
+    level: Cell<LockLevel>,
+}
+
+impl MemoryVfs {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data: RefCell::new(data),
+            level: Cell::new(LockLevel::None),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.data.borrow().clone()
+    }
+}
+
+impl Vfs for MemoryVfs {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> Result<()> {
+        let data = self.data.borrow();
+
+        let source = usize::try_from(offset)
+            .ok()
+            .and_then(|start| data.get(start..start.checked_add(buffer.len())?))
+            .ok_or_else(|| IoError::from(ErrorKind::UnexpectedEof))?;
+
+        buffer.copy_from_slice(source);
+
+        Ok(())
+    }
+
+    fn write_at(&self, buffer: &[u8], offset: u64) -> Result<()> {
+        let mut data = self.data.borrow_mut();
+
+        let start = usize::try_from(offset).map_err(|_| IoError::from(ErrorKind::OutOfMemory))?;
+        let end = start + buffer.len();
+
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+
+        data[start..end].copy_from_slice(buffer);
+
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn truncate(&self, size: u64) -> Result<()> {
+        let size = usize::try_from(size).map_err(|_| IoError::from(ErrorKind::OutOfMemory))?;
+
+        self.data.borrow_mut().resize(size, 0);
+
+        Ok(())
+    }
+
+    fn size(&self) -> Result<u64> {
+        Ok(self.data.borrow().len() as u64)
+    }
+
+    fn lock(&self, level: LockLevel) -> Result<()> {
+        self.level.set(std::cmp::max(level, self.level.get()));
+
+        Ok(())
+    }
+
+    fn unlock(&self, level: LockLevel) -> Result<()> {
+        self.level.set(std::cmp::min(level, self.level.get()));
+
+        Ok(())
+    }
+
+    fn lock_level(&self) -> LockLevel {
+        self.level.get()
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+
+    // The file does not follow the database format. `page` is 0 when the
+    // problem is not tied to a specific page, like a bad file header.
+    Corrupt { page: u32, reason: String },
+
+    Syntax { line: u64, column: u64, msg: String },
+
+    NoSuchTable(String),
+
+    NoSuchColumn(String),
+
+    NoSuchFunction(String),
+
+    Constraint(String),
+
+    // Another connection holds a lock that conflicts with the one we need
+    Busy,
+
+    // A value does not have the type an operation needs
+    Mismatch(String),
+
+    Unsupported(String),
+
+    // Wrong arguments to a dot-command, an SQL function or the library API, or a query that
+    // cannot be prepared, with the message sqlite gives for it
+    Misuse(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn corrupt(page: u32, reason: impl Into<String>) -> Self {
+        Error::Corrupt {
+            page,
+            reason: reason.into(),
+        }
+    }
+
+    // Decoders that only see part of a page report page 0, the caller that
+    // knows which page was being read fills it in
+    pub fn on_page(self, page_number: u32) -> Self {
+        match self {
+            Error::Corrupt { page: 0, reason } => Error::Corrupt {
+                page: page_number,
+                reason,
+            },
+            error => error,
+        }
+    }
+
+    // A read of the database that ends past the end of the file, which is shorter than its
+    // header or a page pointer says: corruption rather than an I/O failure
+    pub fn past_end(self) -> Self {
+        match self {
+            Error::Io(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Error::corrupt(0, "file is truncated")
+            }
+            error => error,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "disk I/O error: {}", error),
+            Error::Corrupt { page: 0, reason } => write!(f, "database disk image is malformed: {}", reason),
+            Error::Corrupt { page, reason } => {
+                write!(f, "database disk image is malformed: page {}: {}", page, reason)
+            }
+            Error::Syntax { line, column, msg } => write!(f, "near line {}:{}: {}", line, column, msg),
+            Error::NoSuchTable(name) => write!(f, "no such table: {}", name),
+            Error::NoSuchColumn(name) => write!(f, "no such column: {}", name),
+            Error::NoSuchFunction(name) => write!(f, "no such function: {}", name),
+            Error::Constraint(msg) => write!(f, "constraint failed: {}", msg),
+            Error::Busy => write!(f, "database is locked"),
+            Error::Mismatch(msg) if msg.is_empty() => write!(f, "datatype mismatch"),
+            Error::Mismatch(msg) => write!(f, "datatype mismatch: {}", msg),
+            Error::Unsupported(msg) => write!(f, "not supported: {}", msg),
+            Error::Misuse(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+// The message of an I/O error is part of the one Display writes, so it is not also given
+// as the source, which would have it printed twice
+impl std::error::Error for Error {}
+
+// A short read is only corruption when it is a read of the database, see `past_end`
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::WouldBlock => Error::Busy,
+            _ => Error::Io(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use std::io::{Error as IoError, ErrorKind};
+
+    #[test]
+    fn messages_are_written_once() {
+        assert_eq!(Error::Mismatch(String::new()).to_string(), "datatype mismatch");
+        assert_eq!(Error::Mismatch("rowid".to_string()).to_string(), "datatype mismatch: rowid");
+
+        let error = Error::from(IoError::new(ErrorKind::NotFound, "unable to open database"));
+
+        assert_eq!(error.to_string(), "disk I/O error: unable to open database");
+        assert!(std::error::Error::source(&error).is_none());
+    }
+
+    #[test]
+    fn only_reads_of_pages_are_truncated() {
+        let error = Error::from(IoError::from(ErrorKind::UnexpectedEof));
+
+        assert!(matches!(error, Error::Io(_)));
+        assert!(matches!(error.past_end().on_page(3), Error::Corrupt { page: 3, .. }));
+        assert!(matches!(Error::from(IoError::from(ErrorKind::WouldBlock)), Error::Busy));
+    }
+}
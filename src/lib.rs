@@ -0,0 +1,15 @@
+mod connection;
+mod core;
+mod error;
+mod output;
+mod parser;
+mod utils;
+
+pub use crate::connection::{Connection, FromValue, Row, Rows, Statement};
+pub use crate::core::database::{Column, Database};
+#[doc(hidden)]
+pub use crate::core::fuzzing;
+pub use crate::core::vacuum::VacuumOptions;
+pub use crate::core::value::Value;
+pub use crate::core::vfs::{FileVfs, LockLevel, MemoryVfs, Vfs};
+pub use crate::error::{Error, Result};
@@ -1,11 +1,7 @@
 #![allow(warnings)]
 
-mod core;
-mod parser;
-mod utils;
-
-use core::database::Database;
-use std::fs::File;
+use codecrafters_sqlite::Database;
+use std::io::Read;
 
 use anyhow::{bail, Result};
 
@@ -13,23 +9,58 @@ fn main() -> Result<()> {
     // Parse arguments
     let args = std::env::args().collect::<Vec<_>>();
 
-    match args.len() {
-        0 | 1 => bail!("Missing <database path> and <command>"),
-        2 => bail!("Missing <command>"),
-        3 => {}
-        l => bail!("Expected 2 arguments but got {}", l),
+    if args.len() < 2 {
+        bail!("Missing <database path> and <command>");
     }
 
     // Parse command and act accordingly
     let db_file_name = &args[1];
 
-    let command = &args[2];
+    let mut db = Database::open(db_file_name)?;
+
+    // every extra argument is a separate command, like `sqlite3 db ".mode csv" "select ..."`;
+    // without any, commands are read from stdin
+    let commands = if args.len() > 2 {
+        args[2..].to_vec()
+    } else {
+        let mut input = String::new();
 
-    let mut file = File::open(&db_file_name)?;
+        std::io::stdin().read_to_string(&mut input)?;
 
-    let db = Database::new(&mut file)?;
+        split_script(&input)
+    };
 
-    db.execute_command(command)?;
+    for command in &commands {
+        db.execute_command(command)?;
+    }
 
     Ok(())
 }
+
+// Splits a script into commands: dot-commands span a single line,
+// SQL statements run until a line ending with a semicolon
+fn split_script(input: &str) -> Vec<String> {
+    let mut commands = vec![];
+    let mut statement = String::new();
+
+    for line in input.lines() {
+        if statement.is_empty() && line.trim_start().starts_with('.') {
+            commands.push(line.trim().to_string());
+            continue;
+        }
+
+        statement.push_str(line);
+        statement.push('\n');
+
+        if line.trim_end().ends_with(';') {
+            commands.push(statement.trim().to_string());
+            statement.clear();
+        }
+    }
+
+    if !statement.trim().is_empty() {
+        commands.push(statement.trim().to_string());
+    }
+
+    commands
+}
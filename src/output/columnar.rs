@@ -0,0 +1,351 @@
+use crate::core::value::Value;
+use crate::output::{OutputSettings, RowFormatter};
+use crate::error::Result;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnarStyle {
+    Column,
+    Box,
+    Markdown,
+    Table,
+}
+
+// Characters used to draw one horizontal rule: left edge, fill, column junction, right edge
+struct Rule(&'static str, &'static str, &'static str, &'static str);
+
+// Column widths depend on every value, so rows are buffered and printed in `finish`
+pub struct ColumnarFormatter {
+    settings: OutputSettings,
+    style: ColumnarStyle,
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl ColumnarFormatter {
+    pub fn new(settings: OutputSettings, style: ColumnarStyle) -> Self {
+        Self {
+            style,
+            settings,
+            rows: vec![],
+            columns: vec![],
+        }
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths = self
+            .columns
+            .iter()
+            .map(|name| display_width(name))
+            .collect::<Vec<_>>();
+
+        for row in &self.rows {
+            for (index, cell) in row.iter().enumerate() {
+                let width = cell.lines().map(display_width).max().unwrap_or(0);
+
+                widths[index] = widths[index].max(width);
+            }
+        }
+
+        widths
+    }
+
+    fn write_rule(&self, out: &mut dyn Write, widths: &[usize], rule: Rule) -> Result<()> {
+        let Rule(left, fill, junction, right) = rule;
+
+        let segments = widths
+            .iter()
+            .map(|width| fill.repeat(width + 2))
+            .collect::<Vec<_>>();
+
+        writeln!(out, "{}{}{}", left, segments.join(junction), right)?;
+
+        Ok(())
+    }
+
+    fn write_cells(&self, out: &mut dyn Write, widths: &[usize], cells: &[String], centered: bool) -> Result<()> {
+        let border = match self.style {
+            ColumnarStyle::Box => "│",
+            _ => "|",
+        };
+
+        // multi-line values are printed one physical line at a time
+        let lines = cells
+            .iter()
+            .map(|cell| cell.split('\n').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let height = lines.iter().map(Vec::len).max().unwrap_or(1);
+
+        for line_index in 0..height {
+            let parts = widths
+                .iter()
+                .enumerate()
+                .map(|(index, width)| {
+                    let text = lines
+                        .get(index)
+                        .and_then(|cell_lines| cell_lines.get(line_index))
+                        .copied()
+                        .unwrap_or("");
+
+                    if centered {
+                        center(text, *width)
+                    } else {
+                        pad(text, *width)
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            match self.style {
+                ColumnarStyle::Column => writeln!(out, "{}", parts.join("  "))?,
+
+                _ => {
+                    let separator = format!(" {} ", border);
+
+                    writeln!(out, "{} {} {}", border, parts.join(&separator), border)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Text as sqlite3 shows it in a cell: tabs expanded to the next multiple of 8 columns and
+// other control characters spelled out as ^X, line breaks being the only ones kept
+fn for_display(text: &str) -> String {
+    let mut shown = String::with_capacity(text.len());
+
+    // the column of the line so far, which the next tab stop is counted from
+    let mut column = 0;
+
+    for c in text.chars() {
+        match c {
+            '\n' => {
+                shown.push(c);
+
+                column = 0;
+            }
+
+            '\t' => {
+                let spaces = 8 - column % 8;
+
+                shown.push_str(&" ".repeat(spaces));
+
+                column += spaces;
+            }
+
+            c if c < ' ' => {
+                shown.push('^');
+                shown.push(char::from(c as u8 ^ 0x40));
+
+                column += 2;
+            }
+
+            c => {
+                shown.push(c);
+
+                column += 1;
+            }
+        }
+    }
+
+    shown
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().count()
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(text));
+
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+fn center(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(text));
+
+    let left = padding / 2;
+
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(padding - left))
+}
+
+impl RowFormatter for ColumnarFormatter {
+    fn begin(&mut self, _out: &mut dyn Write, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+
+        Ok(())
+    }
+
+    fn row(&mut self, _out: &mut dyn Write, row: &[Value]) -> Result<()> {
+        let cells = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => for_display(&self.settings.null_value),
+                _ => for_display(&value.to_string()),
+            })
+            .collect();
+
+        self.rows.push(cells);
+
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        let widths = self.widths();
+
+        // once a row takes more than one line, every row is set apart from the next
+        let multi_line = self.rows.iter().flatten().any(|cell| cell.contains('\n'));
+
+        match self.style {
+            ColumnarStyle::Column => {
+                if self.settings.headers {
+                    self.write_cells(out, &widths, &self.columns, false)?;
+
+                    let dashes = widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>();
+
+                    writeln!(out, "{}", dashes.join("  "))?;
+                }
+
+                for (i, row) in self.rows.iter().enumerate() {
+                    if multi_line && i > 0 {
+                        writeln!(out)?;
+                    }
+
+                    self.write_cells(out, &widths, row, false)?;
+                }
+            }
+
+            ColumnarStyle::Box => {
+                self.write_rule(out, &widths, Rule("┌", "─", "┬", "┐"))?;
+                self.write_cells(out, &widths, &self.columns, true)?;
+                self.write_rule(out, &widths, Rule("├", "─", "┼", "┤"))?;
+
+                for (i, row) in self.rows.iter().enumerate() {
+                    if multi_line && i > 0 {
+                        self.write_rule(out, &widths, Rule("├", "─", "┼", "┤"))?;
+                    }
+
+                    self.write_cells(out, &widths, row, false)?;
+                }
+
+                self.write_rule(out, &widths, Rule("└", "─", "┴", "┘"))?;
+            }
+
+            ColumnarStyle::Markdown => {
+                self.write_cells(out, &widths, &self.columns, true)?;
+                self.write_rule(out, &widths, Rule("|", "-", "|", "|"))?;
+
+                for row in &self.rows {
+                    self.write_cells(out, &widths, row, false)?;
+                }
+            }
+
+            ColumnarStyle::Table => {
+                self.write_rule(out, &widths, Rule("+", "-", "+", "+"))?;
+                self.write_cells(out, &widths, &self.columns, true)?;
+                self.write_rule(out, &widths, Rule("+", "-", "+", "+"))?;
+
+                for (i, row) in self.rows.iter().enumerate() {
+                    if multi_line && i > 0 {
+                        self.write_rule(out, &widths, Rule("+", "-", "+", "+"))?;
+                    }
+
+                    self.write_cells(out, &widths, row, false)?;
+                }
+
+                self.write_rule(out, &widths, Rule("+", "-", "+", "+"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::value::Value;
+    use crate::output::tests::{format, sample};
+    use crate::output::OutputMode;
+
+    // tabs are expanded to the next multiple of 8 columns
+    #[test]
+    fn column_sets_rows_apart_once_one_takes_several_lines() {
+        let expected = concat!(
+            "n   b    r    big      t             long name\n",
+            "--  ---  ---  -------  ------------  ---------\n",
+            "    abc  1.5  2.0e+20  a,\"b\" <&>'x\\  two      \n",
+            "                                     lines    \n",
+            "\n",
+            "-7       0.1  1.0e-07                é        \n",
+        );
+
+        assert_eq!(sample(OutputMode::Column), expected);
+    }
+
+    #[test]
+    fn box_rules_between_rows_once_one_takes_several_lines() {
+        let expected = concat!(
+            "┌────┬─────┬─────┬─────────┬──────────────┬───────────┐\n",
+            "│ n  │  b  │  r  │   big   │      t       │ long name │\n",
+            "├────┼─────┼─────┼─────────┼──────────────┼───────────┤\n",
+            "│    │ abc │ 1.5 │ 2.0e+20 │ a,\"b\" <&>'x\\ │ two       │\n",
+            "│    │     │     │         │              │ lines     │\n",
+            "├────┼─────┼─────┼─────────┼──────────────┼───────────┤\n",
+            "│ -7 │     │ 0.1 │ 1.0e-07 │              │ é         │\n",
+            "└────┴─────┴─────┴─────────┴──────────────┴───────────┘\n",
+        );
+
+        assert_eq!(sample(OutputMode::Box), expected);
+    }
+
+    #[test]
+    fn markdown_never_rules_between_rows() {
+        let expected = concat!(
+            "| n  |  b  |  r  |   big   |      t       | long name |\n",
+            "|----|-----|-----|---------|--------------|-----------|\n",
+            "|    | abc | 1.5 | 2.0e+20 | a,\"b\" <&>'x\\ | two       |\n",
+            "|    |     |     |         |              | lines     |\n",
+            "| -7 |     | 0.1 | 1.0e-07 |              | é         |\n",
+        );
+
+        assert_eq!(sample(OutputMode::Markdown), expected);
+    }
+
+    #[test]
+    fn table_rules_between_rows_once_one_takes_several_lines() {
+        let expected = concat!(
+            "+----+-----+-----+---------+--------------+-----------+\n",
+            "| n  |  b  |  r  |   big   |      t       | long name |\n",
+            "+----+-----+-----+---------+--------------+-----------+\n",
+            "|    | abc | 1.5 | 2.0e+20 | a,\"b\" <&>'x\\ | two       |\n",
+            "|    |     |     |         |              | lines     |\n",
+            "+----+-----+-----+---------+--------------+-----------+\n",
+            "| -7 |     | 0.1 | 1.0e-07 |              | é         |\n",
+            "+----+-----+-----+---------+--------------+-----------+\n",
+        );
+
+        assert_eq!(sample(OutputMode::Table), expected);
+    }
+
+    // control characters other than line breaks are spelled out, and count as two columns
+    #[test]
+    fn control_characters_are_shown_as_carets() {
+        let rows = [vec![Value::Text("\u{1}a\rb".to_string()), Value::Text("\tz".to_string())]];
+
+        let expected = concat!(
+            "┌────────┬───────────┐\n",
+            "│   x    │     y     │\n",
+            "├────────┼───────────┤\n",
+            "│ ^Aa^Mb │         z │\n",
+            "└────────┴───────────┘\n",
+        );
+
+        assert_eq!(format(OutputMode::Box, &["x", "y"], &rows), expected);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::core::value::Value;
+use crate::output::{OutputSettings, RowFormatter};
+use crate::error::Result;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelimitedStyle {
+    List,
+    Csv,
+    Quote,
+}
+
+pub struct DelimitedFormatter {
+    settings: OutputSettings,
+    style: DelimitedStyle,
+}
+
+impl DelimitedFormatter {
+    pub fn new(settings: OutputSettings, style: DelimitedStyle) -> Self {
+        Self { settings, style }
+    }
+
+    fn render(&self, value: &Value) -> String {
+        match self.style {
+            DelimitedStyle::List => match value {
+                Value::Null => self.settings.null_value.clone(),
+                _ => value.to_string(),
+            },
+
+            DelimitedStyle::Csv => match value {
+                Value::Null => self.settings.null_value.clone(),
+                Value::Text(_) | Value::Blob(_) => self.csv_quote(&value.to_string()),
+                _ => value.to_string(),
+            },
+
+            DelimitedStyle::Quote => value.to_shell_literal(false),
+        }
+    }
+
+    fn render_header(&self, name: &str) -> String {
+        match self.style {
+            DelimitedStyle::List => name.to_string(),
+            DelimitedStyle::Csv => self.csv_quote(name),
+            DelimitedStyle::Quote => Value::Text(name.to_string()).to_sql_literal(),
+        }
+    }
+
+    fn csv_quote(&self, text: &str) -> String {
+        // same rule as the sqlite3 shell: quote empty strings and anything containing
+        // the separator, quotes, whitespace, control characters or non-ascii bytes
+        let needs_quotes = text.is_empty()
+            || text.contains(self.settings.column_separator.as_str())
+            || text.bytes().any(|b| b <= b' ' || b == b'"' || b >= 0x7f);
+
+        if needs_quotes {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn write_line(&self, out: &mut dyn Write, fields: Vec<String>) -> Result<()> {
+        let separator = match self.style {
+            DelimitedStyle::Quote => ",",
+            _ => self.settings.column_separator.as_str(),
+        };
+
+        let row_separator = match self.style {
+            DelimitedStyle::Quote => "\n",
+            _ => self.settings.row_separator.as_str(),
+        };
+
+        write!(out, "{}{}", fields.join(separator), row_separator)?;
+
+        Ok(())
+    }
+}
+
+impl RowFormatter for DelimitedFormatter {
+    fn begin(&mut self, out: &mut dyn Write, columns: &[String]) -> Result<()> {
+        if self.settings.headers {
+            let fields = columns.iter().map(|name| self.render_header(name)).collect();
+
+            self.write_line(out, fields)?;
+        }
+
+        Ok(())
+    }
+
+    fn row(&mut self, out: &mut dyn Write, row: &[Value]) -> Result<()> {
+        let fields = row.iter().map(|value| self.render(value)).collect();
+
+        self.write_line(out, fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::tests::sample;
+    use crate::output::OutputMode;
+
+    // NULL is the null value, empty by default, and nothing is quoted
+    #[test]
+    fn list_writes_values_as_they_are() {
+        let expected = concat!(
+            "n|b|r|big|t|long name\n",
+            "|abc|1.5|2.0e+20|a,\"b\" <&>'x\\|two\n",
+            "lines\t\n",
+            "-7||0.1|1.0e-07||é\n",
+        );
+
+        assert_eq!(sample(OutputMode::List), expected);
+    }
+
+    // empty text and blobs are quoted so they read back as empty rather than NULL
+    #[test]
+    fn csv_quotes_text_and_blobs_that_need_it() {
+        let expected = concat!(
+            "n,b,r,big,t,\"long name\"\r\n",
+            ",abc,1.5,2.0e+20,\"a,\"\"b\"\" <&>'x\\\",\"two\n",
+            "lines\t\"\r\n",
+            "-7,\"\",0.1,1.0e-07,\"\",\"é\"\r\n",
+        );
+
+        assert_eq!(sample(OutputMode::Csv), expected);
+    }
+
+    #[test]
+    fn tabs_writes_values_as_they_are() {
+        let expected = concat!(
+            "n\tb\tr\tbig\tt\tlong name\n",
+            "\tabc\t1.5\t2.0e+20\ta,\"b\" <&>'x\\\ttwo\n",
+            "lines\t\n",
+            "-7\t\t0.1\t1.0e-07\t\té\n",
+        );
+
+        assert_eq!(sample(OutputMode::Tabs), expected);
+    }
+
+    // reals with all their digits, blobs in lowercase hex and text as it is, line breaks too
+    #[test]
+    fn quote_writes_sql_literals() {
+        let expected = concat!(
+            "'n','b','r','big','t','long name'\n",
+            "NULL,X'616263',1.5,2.0e+20,'a,\"b\" <&>''x\\','two\n",
+            "lines\t'\n",
+            "-7,X'',0.1000000000000000055,9.99999999999999955e-08,'','é'\n",
+        );
+
+        assert_eq!(sample(OutputMode::Quote), expected);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::core::value::Value;
+use crate::output::{OutputSettings, RowFormatter};
+use crate::error::Result;
+use std::io::Write;
+
+pub struct HtmlFormatter {
+    settings: OutputSettings,
+}
+
+impl HtmlFormatter {
+    pub fn new(settings: OutputSettings) -> Self {
+        Self { settings }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn write_html_row(out: &mut dyn Write, cell_tag: &str, cells: Vec<String>) -> Result<()> {
+    write!(out, "<TR>")?;
+
+    for cell in cells {
+        writeln!(out, "<{cell_tag}>{}</{cell_tag}>", escape_html(&cell))?;
+    }
+
+    writeln!(out, "</TR>")?;
+
+    Ok(())
+}
+
+impl RowFormatter for HtmlFormatter {
+    fn begin(&mut self, out: &mut dyn Write, columns: &[String]) -> Result<()> {
+        if self.settings.headers {
+            write_html_row(out, "TH", columns.to_vec())?;
+        }
+
+        Ok(())
+    }
+
+    fn row(&mut self, out: &mut dyn Write, row: &[Value]) -> Result<()> {
+        let cells = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => self.settings.null_value.clone(),
+                _ => value.to_string(),
+            })
+            .collect();
+
+        write_html_row(out, "TD", cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::tests::sample;
+    use crate::output::OutputMode;
+
+    #[test]
+    fn html_escapes_markup_and_quotes() {
+        let expected = concat!(
+            "<TR><TH>n</TH>\n",
+            "<TH>b</TH>\n",
+            "<TH>r</TH>\n",
+            "<TH>big</TH>\n",
+            "<TH>t</TH>\n",
+            "<TH>long name</TH>\n",
+            "</TR>\n",
+            "<TR><TD></TD>\n",
+            "<TD>abc</TD>\n",
+            "<TD>1.5</TD>\n",
+            "<TD>2.0e+20</TD>\n",
+            "<TD>a,&quot;b&quot; &lt;&amp;&gt;&#39;x\\</TD>\n",
+            "<TD>two\n",
+            "lines\t</TD>\n",
+            "</TR>\n",
+            "<TR><TD>-7</TD>\n",
+            "<TD></TD>\n",
+            "<TD>0.1</TD>\n",
+            "<TD>1.0e-07</TD>\n",
+            "<TD></TD>\n",
+            "<TD>é</TD>\n",
+            "</TR>\n",
+        );
+
+        assert_eq!(sample(OutputMode::Html), expected);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::core::value::Value;
+use crate::output::{OutputSettings, RowFormatter};
+use crate::utils::quote_identifier;
+use crate::error::Result;
+use std::io::Write;
+
+pub struct InsertFormatter {
+    settings: OutputSettings,
+    table: String,
+    columns: Vec<String>,
+}
+
+impl InsertFormatter {
+    pub fn new(settings: OutputSettings, table: String) -> Self {
+        Self {
+            table,
+            settings,
+            columns: vec![],
+        }
+    }
+}
+
+impl RowFormatter for InsertFormatter {
+    fn begin(&mut self, _out: &mut dyn Write, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+
+        Ok(())
+    }
+
+    fn row(&mut self, out: &mut dyn Write, row: &[Value]) -> Result<()> {
+        let values = row.iter().map(|value| value.to_shell_literal(true)).collect::<Vec<_>>();
+
+        // the column list is only spelled out when headers are turned on
+        let column_list = if self.settings.headers {
+            let names = self.columns.iter().map(|name| quote_identifier(name)).collect::<Vec<_>>();
+
+            format!("({})", names.join(","))
+        } else {
+            String::new()
+        };
+
+        writeln!(
+            out,
+            "INSERT INTO {}{} VALUES({});",
+            quote_identifier(&self.table),
+            column_list,
+            values.join(",")
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::tests::sample;
+    use crate::output::OutputMode;
+
+    #[test]
+    fn insert_writes_control_characters_through_unistr() {
+        let expected = concat!(
+            "INSERT INTO \"table\"(n,b,r,big,t,\"long name\") VALUES(NULL,X'616263',",
+            "1.5,2.0e+20,'a,\"b\" <&>''x\\',unistr('two\\u000alines\\u0009'));\n",
+            "INSERT INTO \"table\"(n,b,r,big,t,\"long name\") VALUES(-7,X'',",
+            "0.1000000000000000055,9.99999999999999955e-08,'','é');\n",
+        );
+
+        assert_eq!(sample(OutputMode::Insert("table".to_string())), expected);
+    }
+}
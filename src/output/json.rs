@@ -0,0 +1,104 @@
+use crate::core::value::{format_real_digits, Value};
+use crate::output::RowFormatter;
+use crate::error::Result;
+use std::io::Write;
+
+pub struct JsonFormatter {
+    columns: Vec<String>,
+    rows_written: usize,
+}
+
+impl JsonFormatter {
+    pub fn new() -> Self {
+        Self {
+            columns: vec![],
+            rows_written: 0,
+        }
+    }
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+
+    result.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\u{8}' => result.push_str("\\b"),
+            '\u{c}' => result.push_str("\\f"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+
+    result
+}
+
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Integer(value) => value.to_string(),
+        Value::Real(value) if value.is_finite() => format_real_digits(*value),
+        Value::Real(_) => value.to_sql_literal(),
+        Value::Text(text) => json_string(text),
+        Value::Blob(bytes) => json_string(&String::from_utf8_lossy(bytes)),
+    }
+}
+
+impl RowFormatter for JsonFormatter {
+    fn begin(&mut self, _out: &mut dyn Write, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+
+        Ok(())
+    }
+
+    fn row(&mut self, out: &mut dyn Write, row: &[Value]) -> Result<()> {
+        let fields = self
+            .columns
+            .iter()
+            .zip(row)
+            .map(|(name, value)| format!("{}:{}", json_string(name), json_value(value)))
+            .collect::<Vec<_>>();
+
+        let prefix = if self.rows_written == 0 { "[" } else { ",\n" };
+
+        write!(out, "{}{{{}}}", prefix, fields.join(","))?;
+
+        self.rows_written += 1;
+
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> Result<()> {
+        if self.rows_written > 0 {
+            writeln!(out, "]")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::tests::sample;
+    use crate::output::OutputMode;
+
+    #[test]
+    fn json_escapes_text_and_writes_reals_with_all_their_digits() {
+        let expected = concat!(
+            "[{\"n\":null,\"b\":\"abc\",\"r\":1.5,\"big\":2.0e+20,\"t\":\"a,",
+            "\\\"b\\\" <&>'x\\\\\",\"long name\":\"two\\nlines\\t\"},\n",
+            "{\"n\":-7,\"b\":\"\",\"r\":0.1000000000000000055,\"big\":9.99999999999999955e-08,",
+            "\"t\":\"\",\"long name\":\"é\"}]\n",
+        );
+
+        assert_eq!(sample(OutputMode::Json), expected);
+    }
+}
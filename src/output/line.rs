@@ -0,0 +1,94 @@
+use crate::core::value::Value;
+use crate::output::{OutputSettings, RowFormatter};
+use crate::error::Result;
+use std::io::Write;
+
+pub struct LineFormatter {
+    settings: OutputSettings,
+    columns: Vec<String>,
+    rows_written: usize,
+}
+
+impl LineFormatter {
+    pub fn new(settings: OutputSettings) -> Self {
+        Self {
+            settings,
+            columns: vec![],
+            rows_written: 0,
+        }
+    }
+}
+
+impl RowFormatter for LineFormatter {
+    fn begin(&mut self, _out: &mut dyn Write, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+
+        Ok(())
+    }
+
+    fn row(&mut self, out: &mut dyn Write, row: &[Value]) -> Result<()> {
+        // names are right-aligned to the longest, but to at least 5 characters like in sqlite
+        let width = self
+            .columns
+            .iter()
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(5);
+
+        // records are separated by an empty line
+        if self.rows_written > 0 {
+            writeln!(out)?;
+        }
+
+        for (name, value) in self.columns.iter().zip(row) {
+            let text = match value {
+                Value::Null => self.settings.null_value.clone(),
+                _ => value.to_string(),
+            };
+
+            writeln!(out, "{:>width$} = {}", name, text, width = width)?;
+        }
+
+        self.rows_written += 1;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::value::Value;
+    use crate::output::tests::{format, sample};
+    use crate::output::OutputMode;
+
+    #[test]
+    fn line_aligns_names_to_the_longest() {
+        let expected = concat!(
+            "        n = \n",
+            "        b = abc\n",
+            "        r = 1.5\n",
+            "      big = 2.0e+20\n",
+            "        t = a,\"b\" <&>'x\\\n",
+            "long name = two\n",
+            "lines\t\n",
+            "\n",
+            "        n = -7\n",
+            "        b = \n",
+            "        r = 0.1\n",
+            "      big = 1.0e-07\n",
+            "        t = \n",
+            "long name = é\n",
+        );
+
+        assert_eq!(sample(OutputMode::Line), expected);
+    }
+
+    // short names still take 5 characters, like in sqlite3
+    #[test]
+    fn line_aligns_names_to_at_least_5_characters() {
+        let rows = [vec![Value::Integer(1), Value::Integer(2)]];
+
+        assert_eq!(format(OutputMode::Line, &["a", "bc"], &rows), "    a = 1\n   bc = 2\n");
+    }
+}
@@ -0,0 +1,222 @@
+mod columnar;
+mod delimited;
+mod html;
+mod insert;
+mod json;
+mod line;
+
+use crate::core::value::Value;
+use crate::output::columnar::{ColumnarFormatter, ColumnarStyle};
+use crate::output::delimited::{DelimitedFormatter, DelimitedStyle};
+use crate::output::html::HtmlFormatter;
+use crate::output::insert::InsertFormatter;
+use crate::output::json::JsonFormatter;
+use crate::output::line::LineFormatter;
+use crate::error::{Error, Result};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputMode {
+    List,
+    Csv,
+    Tabs,
+    Json,
+    Line,
+    Column,
+    Box,
+    Markdown,
+    Table,
+    Insert(String),
+    Quote,
+    Html,
+}
+
+impl OutputMode {
+    pub fn parse(name: &str, table: Option<&str>) -> Result<Self> {
+        let mode = match name.to_lowercase().as_str() {
+            "list" => OutputMode::List,
+            "csv" => OutputMode::Csv,
+            "tabs" => OutputMode::Tabs,
+            "json" => OutputMode::Json,
+            "line" | "lines" => OutputMode::Line,
+            "column" | "columns" => OutputMode::Column,
+            "box" => OutputMode::Box,
+            "markdown" => OutputMode::Markdown,
+            "table" => OutputMode::Table,
+            "insert" => OutputMode::Insert(table.unwrap_or("table").to_string()),
+            "quote" => OutputMode::Quote,
+            "html" => OutputMode::Html,
+            _ => {
+                return Err(Error::Misuse(
+                    "mode should be one of: box column csv html insert json line list markdown quote table tabs"
+                        .to_string(),
+                ))
+            }
+        };
+
+        Ok(mode)
+    }
+}
+
+impl Display for OutputMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OutputMode::List => "list",
+            OutputMode::Csv => "csv",
+            OutputMode::Tabs => "tabs",
+            OutputMode::Json => "json",
+            OutputMode::Line => "line",
+            OutputMode::Column => "column",
+            OutputMode::Box => "box",
+            OutputMode::Markdown => "markdown",
+            OutputMode::Table => "table",
+            OutputMode::Insert(_) => "insert",
+            OutputMode::Quote => "quote",
+            OutputMode::Html => "html",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputSettings {
+    pub mode: OutputMode,
+    pub headers: bool,
+    pub column_separator: String,
+    pub row_separator: String,
+    pub null_value: String,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            headers: false,
+            mode: OutputMode::List,
+            null_value: String::new(),
+            row_separator: "\n".to_string(),
+            column_separator: "|".to_string(),
+        }
+    }
+}
+
+impl OutputSettings {
+    pub fn set_mode(&mut self, mode: OutputMode) {
+        // list, csv and tabs are the same formatter with different separators
+        match mode {
+            OutputMode::List => {
+                self.column_separator = "|".to_string();
+                self.row_separator = "\n".to_string();
+            }
+
+            OutputMode::Csv => {
+                self.column_separator = ",".to_string();
+                self.row_separator = "\r\n".to_string();
+            }
+
+            OutputMode::Tabs => {
+                self.column_separator = "\t".to_string();
+                self.row_separator = "\n".to_string();
+            }
+
+            _ => {}
+        }
+
+        self.mode = mode;
+    }
+
+    pub fn formatter(&self) -> Box<dyn RowFormatter> {
+        match &self.mode {
+            OutputMode::List | OutputMode::Tabs => {
+                Box::new(DelimitedFormatter::new(self.clone(), DelimitedStyle::List))
+            }
+            OutputMode::Csv => Box::new(DelimitedFormatter::new(self.clone(), DelimitedStyle::Csv)),
+            OutputMode::Quote => Box::new(DelimitedFormatter::new(self.clone(), DelimitedStyle::Quote)),
+            OutputMode::Json => Box::new(JsonFormatter::new()),
+            OutputMode::Line => Box::new(LineFormatter::new(self.clone())),
+            OutputMode::Column => Box::new(ColumnarFormatter::new(self.clone(), ColumnarStyle::Column)),
+            OutputMode::Box => Box::new(ColumnarFormatter::new(self.clone(), ColumnarStyle::Box)),
+            OutputMode::Markdown => {
+                Box::new(ColumnarFormatter::new(self.clone(), ColumnarStyle::Markdown))
+            }
+            OutputMode::Table => Box::new(ColumnarFormatter::new(self.clone(), ColumnarStyle::Table)),
+            OutputMode::Insert(table) => Box::new(InsertFormatter::new(self.clone(), table.clone())),
+            OutputMode::Html => Box::new(HtmlFormatter::new(self.clone())),
+        }
+    }
+}
+
+// A formatter receives the column names once, then every row in order, and is
+// finished once the result set is exhausted. Formatters that need to see every row
+// before printing (column widths, closing brackets) do their writing in `finish`.
+pub trait RowFormatter {
+    fn begin(&mut self, out: &mut dyn Write, columns: &[String]) -> Result<()>;
+
+    fn row(&mut self, out: &mut dyn Write, row: &[Value]) -> Result<()>;
+
+    fn finish(&mut self, _out: &mut dyn Write) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OutputMode, OutputSettings};
+    use crate::core::value::Value;
+
+    // What `mode` writes, headers included, for `rows` of `columns`
+    pub(crate) fn format(mode: OutputMode, columns: &[&str], rows: &[Vec<Value>]) -> String {
+        let mut settings = OutputSettings {
+            headers: true,
+            ..OutputSettings::default()
+        };
+
+        settings.set_mode(mode);
+
+        let mut formatter = settings.formatter();
+
+        let mut out = vec![];
+
+        let columns = columns.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        formatter.begin(&mut out, &columns).unwrap();
+
+        for row in rows {
+            formatter.row(&mut out, row).unwrap();
+        }
+
+        formatter.finish(&mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    // Every kind of value, and text that needs quoting or escaping in most modes, as sqlite3
+    // returns it for
+    //     SELECT NULL AS n, x'616263' AS b, 1.5 AS r, 2e20 AS big, 'a,"b" <&>''x\' AS t,
+    //         'two'||char(10)||'lines'||char(9) AS "long name"
+    //     UNION ALL SELECT -7, x'', 0.1, 1e-7, '', 'é'
+    // The output each mode is expected to write is what `sqlite3 -header` writes for it.
+    pub(crate) fn sample(mode: OutputMode) -> String {
+        let rows = [
+            vec![
+                Value::Null,
+                Value::Blob(b"abc".to_vec()),
+                Value::Real(1.5),
+                Value::Real(2e20),
+                Value::Text("a,\"b\" <&>'x\\".to_string()),
+                Value::Text("two\nlines\t".to_string()),
+            ],
+            vec![
+                Value::Integer(-7),
+                Value::Blob(vec![]),
+                Value::Real(0.1),
+                Value::Real(1e-7),
+                Value::Text(String::new()),
+                Value::Text("é".to_string()),
+            ],
+        ];
+
+        format(mode, &["n", "b", "r", "big", "t", "long name"], &rows)
+    }
+}
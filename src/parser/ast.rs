@@ -0,0 +1,677 @@
+use crate::core::value::{Affinity, Value};
+
+// The statements the parser understands, one variant per kind of SQL statement
+#[derive(Debug, Clone)]
+pub enum Statement {
+    Select(SelectStmt),
+    Insert(InsertStmt),
+    Update(UpdateStmt),
+    Delete(DeleteStmt),
+    CreateTable(CreateTableStmt),
+    CreateIndex(CreateIndexStmt),
+    CreateView(CreateViewStmt),
+    CreateTrigger(CreateTriggerStmt),
+    Drop(DropStmt),
+    AlterTable(AlterTableStmt),
+    Pragma(Pragma),
+    Vacuum(Vacuum),
+}
+
+// `[WITH ...] select-core [compound-operator select-core ...] [ORDER BY ...] [LIMIT ...]`
+#[derive(Debug, Clone)]
+pub struct SelectStmt {
+    pub with: Option<WithClause>,
+    pub body: SelectBody,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WithClause {
+    pub recursive: bool,
+    pub tables: Vec<CommonTableExpr>,
+}
+
+// `name [(column, ...)] AS [[NOT] MATERIALIZED] (select)`
+#[derive(Debug, Clone)]
+pub struct CommonTableExpr {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub materialized: Option<bool>,
+    pub select: Box<SelectStmt>,
+}
+
+// The first SELECT of a compound, followed by the ones combined with it from left to right
+#[derive(Debug, Clone)]
+pub struct SelectBody {
+    pub first: SelectCore,
+    pub compounds: Vec<(CompoundOperator, SelectCore)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompoundOperator {
+    Union,
+    UnionAll,
+    Intersect,
+    Except,
+}
+
+// a VALUES list is rare enough that it does not matter that it takes the room of a SELECT
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum SelectCore {
+    Select {
+        distinct: bool,
+        columns: Vec<ResultColumn>,
+        from: Option<TableRef>,
+        where_clause: Option<Expr>,
+        group_by: Vec<Expr>,
+        having: Option<Expr>,
+    },
+
+    Values(Vec<Vec<Expr>>),
+}
+
+#[derive(Debug, Clone)]
+pub enum ResultColumn {
+    // `*`
+    All,
+
+    // `table.*`
+    AllFrom(String),
+
+    // `span` is the expression as written, which sqlite uses as the column name when there
+    // is no alias
+    Expr { expr: Expr, alias: Option<String>, span: String },
+}
+
+// Everything after FROM: a single table, a subquery or joins of them
+#[derive(Debug, Clone)]
+pub enum TableRef {
+    Table {
+        schema: Option<String>,
+        name: String,
+        alias: Option<String>,
+    },
+
+    Subquery {
+        select: Box<SelectStmt>,
+        alias: Option<String>,
+    },
+
+    Join {
+        left: Box<TableRef>,
+        operator: JoinOperator,
+        right: Box<TableRef>,
+        constraint: Option<JoinConstraint>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinOperator {
+    pub natural: bool,
+    pub kind: JoinKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
+    Cross,
+}
+
+#[derive(Debug, Clone)]
+pub enum JoinConstraint {
+    On(Expr),
+    Using(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub descending: bool,
+
+    // None sorts NULLs the default way, first when ascending and last when descending
+    pub nulls_first: Option<bool>,
+}
+
+// `LIMIT count [OFFSET skip]`, also written `LIMIT skip, count`
+#[derive(Debug, Clone)]
+pub struct Limit {
+    pub count: Expr,
+    pub offset: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Literal(Value),
+
+    // `[table.]name`
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+
+    Unary {
+        operator: UnaryOperator,
+        expr: Box<Expr>,
+    },
+
+    Binary {
+        left: Box<Expr>,
+        operator: BinaryOperator,
+        right: Box<Expr>,
+    },
+
+    // `name(args)`, `name(DISTINCT args)` or `name(*)`, which has no arguments and `star` set
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        distinct: bool,
+        star: bool,
+    },
+
+    Cast {
+        expr: Box<Expr>,
+        type_name: String,
+    },
+
+    Collate {
+        expr: Box<Expr>,
+        collation: String,
+    },
+
+    // `expr [NOT] LIKE|GLOB|REGEXP|MATCH pattern [ESCAPE escape]`
+    Like {
+        expr: Box<Expr>,
+        operator: LikeOperator,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+        negated: bool,
+    },
+
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+
+    InSelect {
+        expr: Box<Expr>,
+        select: Box<SelectStmt>,
+        negated: bool,
+    },
+
+    // `CASE [operand] WHEN ... THEN ... [ELSE ...] END`
+    Case {
+        operand: Option<Box<Expr>>,
+        branches: Vec<(Expr, Expr)>,
+        otherwise: Option<Box<Expr>>,
+    },
+
+    Exists(Box<SelectStmt>),
+
+    // a parenthesized SELECT used as a value
+    Subquery(Box<SelectStmt>),
+
+    // a row value, `(a, b)`, compared or assigned as a whole
+    Row(Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    Plus,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+
+    // `IS`, `IS NOT DISTINCT FROM` and the postfix ISNULL
+    Is,
+
+    // `IS NOT`, `IS DISTINCT FROM` and the postfix NOTNULL or NOT NULL
+    IsNot,
+
+    Concat,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    BitAnd,
+    BitOr,
+    ShiftLeft,
+    ShiftRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LikeOperator {
+    Like,
+    Glob,
+    Regexp,
+    Match,
+}
+
+// `INSERT|REPLACE INTO table [(columns)] VALUES ...|select|DEFAULT VALUES`
+#[derive(Debug, Clone)]
+pub struct InsertStmt {
+    pub with: Option<WithClause>,
+    pub conflict: Option<ConflictResolution>,
+    pub schema: Option<String>,
+    pub table: String,
+    pub alias: Option<String>,
+    pub columns: Vec<String>,
+
+    // None for DEFAULT VALUES
+    pub source: Option<Box<SelectStmt>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    Rollback,
+    Abort,
+    Fail,
+    Ignore,
+    Replace,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateStmt {
+    pub with: Option<WithClause>,
+    pub conflict: Option<ConflictResolution>,
+    pub schema: Option<String>,
+    pub table: String,
+    pub alias: Option<String>,
+    pub assignments: Vec<(Vec<String>, Expr)>,
+    pub from: Option<TableRef>,
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteStmt {
+    pub with: Option<WithClause>,
+    pub schema: Option<String>,
+    pub table: String,
+    pub alias: Option<String>,
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateTableStmt {
+    pub temporary: bool,
+    pub if_not_exists: bool,
+    pub schema: Option<String>,
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    pub constraints: Vec<TableConstraint>,
+    pub without_rowid: bool,
+    pub strict: bool,
+
+    // CREATE TABLE ... AS SELECT has a query instead of column definitions
+    pub select: Option<Box<SelectStmt>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnDef {
+    pub name: String,
+
+    // the type name as written, None when the column has none
+    pub declared_type: Option<String>,
+
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ColumnConstraint {
+    PrimaryKey { descending: bool, autoincrement: bool },
+    NotNull,
+    Null,
+    Unique,
+    Check(Expr),
+    Default(Expr),
+    Collate(String),
+    References(ForeignKeyClause),
+    Generated { expr: Expr, stored: bool },
+}
+
+#[derive(Debug, Clone)]
+pub enum TableConstraint {
+    PrimaryKey(Vec<IndexedColumn>),
+    Unique(Vec<IndexedColumn>),
+    Check(Expr),
+    ForeignKey { columns: Vec<String>, clause: ForeignKeyClause },
+}
+
+// `REFERENCES table [(columns)]`, the ON DELETE, ON UPDATE and DEFERRABLE parts are
+// accepted and left out
+#[derive(Debug, Clone)]
+pub struct ForeignKeyClause {
+    pub table: String,
+    pub columns: Vec<String>,
+}
+
+// One key of an index or of a PRIMARY KEY or UNIQUE constraint
+#[derive(Debug, Clone)]
+pub struct IndexedColumn {
+    pub expr: Expr,
+    pub collation: Option<String>,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateIndexStmt {
+    pub unique: bool,
+    pub if_not_exists: bool,
+    pub schema: Option<String>,
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<IndexedColumn>,
+
+    // partial indexes only hold the rows matching their WHERE clause
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateViewStmt {
+    pub temporary: bool,
+    pub if_not_exists: bool,
+    pub schema: Option<String>,
+    pub name: String,
+    pub columns: Vec<String>,
+    pub select: Box<SelectStmt>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateTriggerStmt {
+    pub temporary: bool,
+    pub if_not_exists: bool,
+    pub schema: Option<String>,
+    pub name: String,
+
+    // BEFORE, AFTER or INSTEAD OF, then the event as written
+    pub timing: Option<String>,
+    pub event: String,
+
+    pub table: String,
+    pub when: Option<Expr>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectType {
+    Table,
+    Index,
+    View,
+    Trigger,
+}
+
+#[derive(Debug, Clone)]
+pub struct DropStmt {
+    pub object_type: ObjectType,
+    pub if_exists: bool,
+    pub schema: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlterTableStmt {
+    pub schema: Option<String>,
+    pub table: String,
+    pub action: AlterTableAction,
+}
+
+#[derive(Debug, Clone)]
+pub enum AlterTableAction {
+    RenameTable(String),
+    RenameColumn { from: String, to: String },
+    AddColumn(ColumnDef),
+    DropColumn(String),
+}
+
+// `PRAGMA [schema.]name [= value | (value)]`
+#[derive(Debug, Clone)]
+pub struct Pragma {
+    pub name: String,
+    pub argument: Option<Value>,
+}
+
+// `VACUUM [schema] [INTO 'file']`
+#[derive(Debug, Clone)]
+pub struct Vacuum {
+    pub schema: Option<String>,
+    pub into: Option<String>,
+}
+
+impl CreateTableStmt {
+    // Index of the INTEGER PRIMARY KEY column, whose value is the rowid. A table level
+    // PRIMARY KEY(col) on a single INTEGER column counts too, but not in WITHOUT ROWID
+    // tables, where the primary key is the table b-tree itself.
+    pub fn rowid_alias(&self) -> Option<usize> {
+        if self.without_rowid {
+            return None;
+        }
+
+        let is_integer = |i: usize| {
+            self.columns[i]
+                .declared_type
+                .as_deref()
+                .is_some_and(|declared_type| declared_type.eq_ignore_ascii_case("INTEGER"))
+        };
+
+        let column_key = self.columns.iter().position(|column| {
+            column
+                .constraints
+                .iter()
+                .any(|constraint| matches!(constraint, ColumnConstraint::PrimaryKey { .. }))
+        });
+
+        if let Some(i) = column_key {
+            return Some(i).filter(|&i| is_integer(i));
+        }
+
+        self.constraints.iter().find_map(|constraint| match constraint {
+            TableConstraint::PrimaryKey(key) => match self.key_columns(key).as_deref() {
+                Some(&[i]) if is_integer(i) => Some(i),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    pub fn is_not_null(&self, column: usize) -> bool {
+        self.columns[column]
+            .constraints
+            .iter()
+            .any(|constraint| matches!(constraint, ColumnConstraint::NotNull))
+    }
+
+    // Column lists of the PRIMARY KEY and UNIQUE constraints that have an automatic index,
+    // in the order sqlite numbers them: the first one is sqlite_autoindex_<table>_1
+    pub fn unique_constraints(&self) -> Vec<Vec<usize>> {
+        let rowid_alias = self.rowid_alias();
+
+        let mut keys = vec![];
+
+        for (i, column) in self.columns.iter().enumerate() {
+            for constraint in &column.constraints {
+                match constraint {
+                    ColumnConstraint::PrimaryKey { .. } if rowid_alias != Some(i) && !self.without_rowid => keys.push(vec![i]),
+                    ColumnConstraint::Unique => keys.push(vec![i]),
+                    _ => {}
+                }
+            }
+        }
+
+        for constraint in &self.constraints {
+            let key = match constraint {
+                TableConstraint::PrimaryKey(key) if rowid_alias.is_none() && !self.without_rowid => key,
+                TableConstraint::Unique(key) => key,
+                _ => continue,
+            };
+
+            if let Some(columns) = self.key_columns(key) {
+                keys.push(columns);
+            }
+        }
+
+        let mut unique = vec![];
+
+        for key in keys {
+            if !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+
+        unique
+    }
+
+    // The expression of a generated column, and whether its value is stored
+    pub fn generated(&self, column: usize) -> Option<(&Expr, bool)> {
+        self.columns[column].constraints.iter().find_map(|constraint| match constraint {
+            ColumnConstraint::Generated { expr, stored } => Some((expr, *stored)),
+            _ => None,
+        })
+    }
+
+    // The columns in the order records hold their values. VIRTUAL generated columns are
+    // computed when read and have none, and a WITHOUT ROWID table stores its primary key
+    // first since its records are the entries of the b-tree ordered by it.
+    pub fn stored_columns(&self) -> Vec<usize> {
+        let mut columns = vec![];
+
+        if self.without_rowid {
+            let column_key = self.columns.iter().position(|column| {
+                column
+                    .constraints
+                    .iter()
+                    .any(|constraint| matches!(constraint, ColumnConstraint::PrimaryKey { .. }))
+            });
+
+            let table_key = self.constraints.iter().find_map(|constraint| match constraint {
+                TableConstraint::PrimaryKey(key) => self.key_columns(key),
+                _ => None,
+            });
+
+            for column in column_key.map(|column| vec![column]).or(table_key).unwrap_or_default() {
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+
+        for column in 0..self.columns.len() {
+            let is_virtual = self.generated(column).is_some_and(|(_, stored)| !stored);
+
+            if !is_virtual && !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+
+        columns
+    }
+
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.name.eq_ignore_ascii_case(name))
+    }
+
+    // Positions of the columns a constraint is on, None if one of them is not a column
+    fn key_columns(&self, key: &[IndexedColumn]) -> Option<Vec<usize>> {
+        key.iter()
+            .map(|column| column.expr.column_name().and_then(|name| self.column_index(name)))
+            .collect()
+    }
+}
+
+impl ColumnDef {
+    // The affinity values stored in the column get, from its declared type
+    pub fn affinity(&self) -> Affinity {
+        Affinity::from_declared_type(self.declared_type.as_deref().unwrap_or_default())
+    }
+}
+
+impl CreateIndexStmt {
+    // Names of the indexed columns, None when the index is on expressions
+    pub fn column_names(&self) -> Option<Vec<String>> {
+        self.columns
+            .iter()
+            .map(|column| column.expr.column_name().map(str::to_string))
+            .collect()
+    }
+}
+
+impl Expr {
+    // The column an expression names. A string literal counts, sqlite takes `'name'` for a
+    // column in index and key definitions.
+    pub fn column_name(&self) -> Option<&str> {
+        match self {
+            Expr::Column { table: None, name } => Some(name),
+            Expr::Literal(Value::Text(name)) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CreateTableStmt, Statement};
+    use crate::parser::parse::parse_sql;
+
+    fn table(sql: &str) -> CreateTableStmt {
+        match parse_sql(sql).unwrap() {
+            Statement::CreateTable(table) => table,
+            other => panic!("{sql} is not a CREATE TABLE: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rowid_alias() {
+        assert_eq!(table("CREATE TABLE t (a, id INTEGER PRIMARY KEY)").rowid_alias(), Some(1));
+        assert_eq!(table("CREATE TABLE t (a, id integer, PRIMARY KEY (id))").rowid_alias(), Some(1));
+        assert_eq!(table("CREATE TABLE t (id INT PRIMARY KEY)").rowid_alias(), None);
+        assert_eq!(table("CREATE TABLE t (a, b INTEGER, PRIMARY KEY (a, b))").rowid_alias(), None);
+        assert_eq!(table("CREATE TABLE t (id INTEGER PRIMARY KEY) WITHOUT ROWID").rowid_alias(), None);
+    }
+
+    #[test]
+    fn unique_constraints_in_autoindex_order() {
+        let sql = "CREATE TABLE t (a UNIQUE, b PRIMARY KEY, c, UNIQUE (b, 'a'), UNIQUE (a))";
+
+        assert_eq!(table(sql).unique_constraints(), [vec![0], vec![1], vec![1, 0]]);
+
+        // the key of a WITHOUT ROWID table is the table itself, not an index
+        let sql = "CREATE TABLE t (a PRIMARY KEY, b UNIQUE) WITHOUT ROWID";
+
+        assert_eq!(table(sql).unique_constraints(), [vec![1]]);
+    }
+
+    #[test]
+    fn stored_columns() {
+        let sql = "CREATE TABLE t (a, b AS (a + 1), c AS (a * 2) STORED, d)";
+
+        assert_eq!(table(sql).stored_columns(), [0, 2, 3]);
+
+        let sql = "CREATE TABLE t (a, b, c, PRIMARY KEY (c, a)) WITHOUT ROWID";
+
+        assert_eq!(table(sql).stored_columns(), [2, 0, 1]);
+    }
+}
@@ -0,0 +1,108 @@
+// Reads delimited text the way the sqlite3 shell's `.import` does: fields may be wrapped
+// in double quotes, in which case they can contain separators, newlines and "" escapes.
+pub struct CsvReader<'input> {
+    input: &'input str,
+    separator: char,
+    position: usize,
+    line: usize,
+}
+
+impl<'input> CsvReader<'input> {
+    pub fn new(input: &'input str, separator: char) -> Self {
+        Self {
+            input,
+            separator,
+            line: 1,
+            position: 0,
+        }
+    }
+
+    // Line number the next record starts on, for error messages
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    fn read_field(&mut self, chars: &mut std::iter::Peekable<std::str::CharIndices>) -> (String, bool) {
+        let mut field = String::new();
+
+        if chars.peek().map(|(_, c)| *c) == Some('"') {
+            chars.next();
+
+            while let Some((_, c)) = chars.next() {
+                if c == '"' {
+                    if chars.peek().map(|(_, c)| *c) == Some('"') {
+                        chars.next();
+                        field.push('"');
+                        continue;
+                    }
+
+                    break;
+                }
+
+                if c == '\n' {
+                    self.line += 1;
+                }
+
+                field.push(c);
+            }
+        }
+
+        // anything after a closing quote, or an unquoted field, runs until the next separator
+        while let Some((_, c)) = chars.peek().copied() {
+            if c == self.separator {
+                chars.next();
+                return (field, false);
+            }
+
+            if c == '\n' || c == '\r' {
+                return (field, true);
+            }
+
+            field.push(c);
+            chars.next();
+        }
+
+        (field, true)
+    }
+}
+
+impl<'input> Iterator for CsvReader<'input> {
+    type Item = Vec<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.input.len() {
+            return None;
+        }
+
+        let rest = &self.input[self.position..];
+
+        let mut chars = rest.char_indices().peekable();
+
+        let mut record = vec![];
+
+        loop {
+            let (field, end_of_record) = self.read_field(&mut chars);
+
+            record.push(field);
+
+            if end_of_record {
+                break;
+            }
+        }
+
+        // consume the record terminator: \n, \r\n or a lone \r
+        if chars.peek().map(|(_, c)| *c) == Some('\r') {
+            chars.next();
+        }
+
+        if chars.peek().map(|(_, c)| *c) == Some('\n') {
+            chars.next();
+        }
+
+        self.line += 1;
+
+        self.position += chars.peek().map_or(rest.len(), |(index, _)| *index);
+
+        Some(record)
+    }
+}
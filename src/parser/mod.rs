@@ -1,3 +1,5 @@
+pub mod ast;
+pub mod csv;
+pub mod parse;
 pub mod scanner;
-pub mod statement;
 pub mod token;
@@ -0,0 +1,2241 @@
+use crate::core::value::Value;
+use crate::error::{Error, Result};
+use crate::parser::ast::*;
+use crate::parser::scanner::Scanner;
+use crate::parser::token::{Token, TokenType};
+use crate::utils::unquote_identifier;
+
+pub fn syntax_error(token: &Token) -> Error {
+    let msg = match token.token_type {
+        TokenType::EOF => "incomplete input".to_string(),
+        _ => format!("near \"{}\": syntax error", token.lexeme),
+    };
+
+    Error::Syntax {
+        msg,
+        line: token.line,
+        column: token.column,
+    }
+}
+
+// Scans and parses a single SQL statement
+pub fn parse_sql(sql: &str) -> Result<Statement> {
+    let mut scanner = Scanner::new();
+
+    scanner.scan(sql)?;
+
+    Parser::new(sql, scanner.get_tokens()).parse()
+}
+
+// A recursive-descent parser over the tokens of one statement. Every grammar rule is a
+// method that consumes the tokens it matches, and errors point at the first token that
+// does not fit, with the line and column the scanner recorded for it.
+pub struct Parser<'a> {
+    // the text the tokens were scanned from, which names result columns without an alias
+    source: &'a str,
+
+    tokens: &'a [Token],
+    current: usize,
+
+    // how deeply the expression being parsed nests, limited so that input like ((((...
+    // cannot overflow the stack
+    depth: usize,
+}
+
+// The deepest expression sqlite accepts
+const MAX_EXPR_DEPTH: usize = 1000;
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a str, tokens: &'a [Token]) -> Self {
+        Self {
+            source,
+            tokens,
+            current: 0,
+            depth: 0,
+        }
+    }
+
+    // Parses one statement, which may be followed by semicolons and nothing else
+    pub fn parse(mut self) -> Result<Statement> {
+        // the scanner always ends the tokens with EOF, which the helpers below rely on
+        match self.tokens.last() {
+            Some(token) if token.token_type == TokenType::EOF => {}
+            _ => {
+                return Err(Error::Syntax {
+                    line: self.tokens.last().map_or(1, |token| token.line),
+                    column: self.tokens.last().map_or(0, |token| token.column),
+                    msg: "incomplete input".to_string(),
+                })
+            }
+        }
+
+        let statement = self.statement()?;
+
+        while self.matches(TokenType::SEMICOLON) {}
+
+        if !self.check(TokenType::EOF) {
+            return Err(self.error());
+        }
+
+        Ok(statement)
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        let with = self.with_clause()?;
+
+        let token = self.peek();
+
+        let statement = match token.token_type {
+            TokenType::SELECT | TokenType::VALUES => Statement::Select(self.select_after_with(with)?),
+            TokenType::INSERT | TokenType::REPLACE => Statement::Insert(self.insert(with)?),
+            TokenType::UPDATE => Statement::Update(self.update(with)?),
+            TokenType::DELETE => Statement::Delete(self.delete(with)?),
+            _ if with.is_some() => return Err(self.error()),
+            TokenType::CREATE => self.create()?,
+            TokenType::DROP => Statement::Drop(self.drop()?),
+            TokenType::ALTER => Statement::AlterTable(self.alter_table()?),
+            TokenType::PRAGMA => Statement::Pragma(self.pragma()?),
+            TokenType::VACUUM => Statement::Vacuum(self.vacuum()?),
+            _ => return Err(syntax_error(token)),
+        };
+
+        Ok(statement)
+    }
+
+    // `WITH [RECURSIVE] name [(columns)] AS [[NOT] MATERIALIZED] (select), ...`
+    fn with_clause(&mut self) -> Result<Option<WithClause>> {
+        if !self.matches(TokenType::WITH) {
+            return Ok(None);
+        }
+
+        let recursive = self.matches(TokenType::RECURSIVE);
+
+        let mut tables = vec![];
+
+        loop {
+            let name = self.name()?;
+
+            let columns = if self.check(TokenType::LeftParen) {
+                self.name_list()?
+            } else {
+                vec![]
+            };
+
+            self.expect(TokenType::AS)?;
+
+            let materialized = if self.matches_word("MATERIALIZED") {
+                Some(true)
+            } else if self.check(TokenType::NOT) && self.peek_at(1).is_word("MATERIALIZED") {
+                self.current += 2;
+
+                Some(false)
+            } else {
+                None
+            };
+
+            self.expect(TokenType::LeftParen)?;
+
+            let select = Box::new(self.select()?);
+
+            self.expect(TokenType::RightParen)?;
+
+            tables.push(CommonTableExpr {
+                name,
+                columns,
+                materialized,
+                select,
+            });
+
+            if !self.matches(TokenType::COMMA) {
+                break;
+            }
+        }
+
+        Ok(Some(WithClause { recursive, tables }))
+    }
+
+    fn select(&mut self) -> Result<SelectStmt> {
+        let with = self.with_clause()?;
+
+        self.select_after_with(with)
+    }
+
+    fn select_after_with(&mut self, with: Option<WithClause>) -> Result<SelectStmt> {
+        let first = self.select_core()?;
+
+        let mut compounds = vec![];
+
+        loop {
+            let operator = match self.peek().token_type {
+                TokenType::UNION if self.peek_at(1).token_type == TokenType::ALL => {
+                    self.current += 1;
+
+                    CompoundOperator::UnionAll
+                }
+                TokenType::UNION => CompoundOperator::Union,
+                TokenType::INTERSECT => CompoundOperator::Intersect,
+                TokenType::EXCEPT => CompoundOperator::Except,
+                _ => break,
+            };
+
+            self.current += 1;
+
+            compounds.push((operator, self.select_core()?));
+        }
+
+        let mut order_by = vec![];
+
+        if self.matches(TokenType::ORDER) {
+            self.expect(TokenType::BY)?;
+
+            loop {
+                order_by.push(self.ordering_term()?);
+
+                if !self.matches(TokenType::COMMA) {
+                    break;
+                }
+            }
+        }
+
+        let mut limit = None;
+
+        if self.matches(TokenType::LIMIT) {
+            let mut count = self.expr()?;
+
+            let mut offset = None;
+
+            if self.matches(TokenType::OFFSET) {
+                offset = Some(self.expr()?);
+            } else if self.matches(TokenType::COMMA) {
+                // `LIMIT skip, count` puts the offset first
+                offset = Some(std::mem::replace(&mut count, self.expr()?));
+            }
+
+            limit = Some(Limit { count, offset });
+        }
+
+        // the SELECTs of a compound take no ORDER BY or LIMIT of their own
+        let operator = match self.peek().token_type {
+            TokenType::UNION if self.peek_at(1).token_type == TokenType::ALL => Some("UNION ALL"),
+            TokenType::UNION => Some("UNION"),
+            TokenType::INTERSECT => Some("INTERSECT"),
+            TokenType::EXCEPT => Some("EXCEPT"),
+            _ => None,
+        };
+
+        if let Some(operator) = operator.filter(|_| !order_by.is_empty() || limit.is_some()) {
+            let clause = if order_by.is_empty() { "LIMIT" } else { "ORDER BY" };
+
+            return Err(Error::Syntax {
+                msg: format!("{clause} clause should come after {operator} not before"),
+                line: self.peek().line,
+                column: self.peek().column,
+            });
+        }
+
+        Ok(SelectStmt {
+            with,
+            body: SelectBody { first, compounds },
+            order_by,
+            limit,
+        })
+    }
+
+    fn select_core(&mut self) -> Result<SelectCore> {
+        if self.matches(TokenType::VALUES) {
+            let mut rows = vec![];
+
+            loop {
+                self.expect(TokenType::LeftParen)?;
+
+                rows.push(self.expr_list()?);
+
+                self.expect(TokenType::RightParen)?;
+
+                if !self.matches(TokenType::COMMA) {
+                    break;
+                }
+            }
+
+            return Ok(SelectCore::Values(rows));
+        }
+
+        self.expect(TokenType::SELECT)?;
+
+        let distinct = self.matches(TokenType::DISTINCT);
+
+        if !distinct {
+            self.matches(TokenType::ALL);
+        }
+
+        let mut columns = vec![];
+
+        loop {
+            columns.push(self.result_column()?);
+
+            if !self.matches(TokenType::COMMA) {
+                break;
+            }
+        }
+
+        let from = if self.matches(TokenType::FROM) {
+            Some(self.table_ref()?)
+        } else {
+            None
+        };
+
+        let where_clause = if self.matches(TokenType::WHERE) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        let mut group_by = vec![];
+
+        if self.matches(TokenType::GROUP) {
+            self.expect(TokenType::BY)?;
+
+            group_by = self.expr_list()?;
+        }
+
+        let having = if self.matches(TokenType::HAVING) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(SelectCore::Select {
+            distinct,
+            columns,
+            from,
+            where_clause,
+            group_by,
+            having,
+        })
+    }
+
+    fn result_column(&mut self) -> Result<ResultColumn> {
+        if self.matches(TokenType::STAR) {
+            return Ok(ResultColumn::All);
+        }
+
+        let is_table_star = self.is_name(self.peek())
+            && self.peek_at(1).token_type == TokenType::DOT
+            && self.peek_at(2).token_type == TokenType::STAR;
+
+        if is_table_star {
+            let table = self.name()?;
+
+            self.current += 2;
+
+            return Ok(ResultColumn::AllFrom(table));
+        }
+
+        let start = self.peek().offset;
+
+        let expr = self.expr()?;
+
+        let span = self.span_from(start);
+
+        let alias = self.alias()?;
+
+        Ok(ResultColumn::Expr { expr, alias, span })
+    }
+
+    // `AS name`, or a name right after the aliased item. Join keywords are left for the join
+    // that follows, so `FROM a LEFT JOIN b` does not call `a` "left".
+    fn alias(&mut self) -> Result<Option<String>> {
+        if self.matches(TokenType::AS) {
+            return self.name().map(Some);
+        }
+
+        let token = self.peek();
+
+        let is_alias = match token.token_type {
+            TokenType::IDENTIFIER | TokenType::STRING => true,
+            token_type => token_type.is_fallback_keyword() && !token_type.is_join_keyword(),
+        };
+
+        if !is_alias {
+            return Ok(None);
+        }
+
+        self.name().map(Some)
+    }
+
+    fn ordering_term(&mut self) -> Result<OrderingTerm> {
+        let expr = self.expr()?;
+
+        let descending = if self.matches(TokenType::DESC) {
+            true
+        } else {
+            self.matches(TokenType::ASC);
+
+            false
+        };
+
+        let mut nulls_first = None;
+
+        if self.matches_word("NULLS") {
+            if self.matches_word("FIRST") {
+                nulls_first = Some(true);
+            } else if self.matches_word("LAST") {
+                nulls_first = Some(false);
+            } else {
+                return Err(self.error());
+            }
+        }
+
+        Ok(OrderingTerm {
+            expr,
+            descending,
+            nulls_first,
+        })
+    }
+
+    // The FROM clause: tables and subqueries joined from left to right
+    fn table_ref(&mut self) -> Result<TableRef> {
+        let mut left = self.table_or_subquery()?;
+
+        while let Some(operator) = self.join_operator()? {
+            let right = self.table_or_subquery()?;
+
+            let constraint = if self.matches(TokenType::ON) {
+                Some(JoinConstraint::On(self.expr()?))
+            } else if self.matches(TokenType::USING) {
+                Some(JoinConstraint::Using(self.name_list()?))
+            } else {
+                None
+            };
+
+            left = TableRef::Join {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+                constraint,
+            };
+        }
+
+        Ok(left)
+    }
+
+    // `,` or `[NATURAL] [LEFT|RIGHT|FULL [OUTER] | INNER | CROSS] JOIN`
+    fn join_operator(&mut self) -> Result<Option<JoinOperator>> {
+        if self.matches(TokenType::COMMA) {
+            return Ok(Some(JoinOperator {
+                natural: false,
+                kind: JoinKind::Inner,
+            }));
+        }
+
+        let natural = self.matches(TokenType::NATURAL);
+
+        let kind = match self.peek().token_type {
+            TokenType::LEFT => JoinKind::Left,
+            TokenType::RIGHT => JoinKind::Right,
+            TokenType::FULL => JoinKind::Full,
+            TokenType::INNER => JoinKind::Inner,
+            TokenType::CROSS => JoinKind::Cross,
+            TokenType::JOIN => JoinKind::Inner,
+            _ if natural => return Err(self.error()),
+            _ => return Ok(None),
+        };
+
+        if !self.check(TokenType::JOIN) {
+            self.current += 1;
+
+            if matches!(kind, JoinKind::Left | JoinKind::Right | JoinKind::Full) {
+                self.matches(TokenType::OUTER);
+            }
+        }
+
+        self.expect(TokenType::JOIN)?;
+
+        Ok(Some(JoinOperator { natural, kind }))
+    }
+
+    fn table_or_subquery(&mut self) -> Result<TableRef> {
+        if self.matches(TokenType::LeftParen) {
+            if matches!(self.peek().token_type, TokenType::SELECT | TokenType::VALUES | TokenType::WITH) {
+                let select = Box::new(self.select()?);
+
+                self.expect(TokenType::RightParen)?;
+
+                let alias = self.alias()?;
+
+                return Ok(TableRef::Subquery { select, alias });
+            }
+
+            let table = self.nested(|parser| parser.table_ref())?;
+
+            self.expect(TokenType::RightParen)?;
+
+            return Ok(table);
+        }
+
+        let (schema, name) = self.qualified_name()?;
+
+        // a table may be called by a join keyword, `FROM t left WHERE ...`, as long as no
+        // join follows
+        let next = self.peek_at(1).token_type;
+
+        let alias = if self.peek().token_type.is_join_keyword()
+            && !next.is_join_keyword()
+            && !matches!(next, TokenType::JOIN | TokenType::EOF)
+        {
+            Some(self.name()?)
+        } else {
+            self.alias()?
+        };
+
+        Ok(TableRef::Table { schema, name, alias })
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>> {
+        let mut exprs = vec![self.expr()?];
+
+        while self.matches(TokenType::COMMA) {
+            exprs.push(self.expr()?);
+        }
+
+        Ok(exprs)
+    }
+
+    // Expressions, one method per precedence level from the loosest binding to the tightest:
+    // OR, AND, NOT, equality and the postfix operators, comparison, bit operators, addition,
+    // multiplication, concatenation, COLLATE, then the unary operators.
+    pub fn expr(&mut self) -> Result<Expr> {
+        self.nested(|parser| parser.or_expr())
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        self.left_associative(Self::and_expr, |token_type| match token_type {
+            TokenType::OR => Some(BinaryOperator::Or),
+            _ => None,
+        })
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        self.left_associative(Self::not_expr, |token_type| match token_type {
+            TokenType::AND => Some(BinaryOperator::And),
+            _ => None,
+        })
+    }
+
+    fn not_expr(&mut self) -> Result<Expr> {
+        if self.matches(TokenType::NOT) {
+            let expr = self.nested(|parser| parser.not_expr())?;
+
+            return Ok(Expr::Unary {
+                operator: UnaryOperator::Not,
+                expr: Box::new(expr),
+            });
+        }
+
+        self.equality()
+    }
+
+    fn equality(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+
+        let mut left = self.comparison()?;
+
+        loop {
+            let token = self.peek();
+
+            // NOT only continues the expression in front of the operators it negates
+            let negated = token.token_type == TokenType::NOT
+                && matches!(
+                    self.peek_at(1).token_type,
+                    TokenType::BETWEEN
+                        | TokenType::IN
+                        | TokenType::LIKE
+                        | TokenType::GLOB
+                        | TokenType::REGEXP
+                        | TokenType::MATCH
+                        | TokenType::NULL
+                );
+
+            if negated {
+                self.current += 1;
+            }
+
+            let token_type = self.peek().token_type;
+
+            left = match token_type {
+                TokenType::EQUAL | TokenType::BangEqual if !negated => {
+                    self.current += 1;
+
+                    let operator = match token_type {
+                        TokenType::EQUAL => BinaryOperator::Equal,
+                        _ => BinaryOperator::NotEqual,
+                    };
+
+                    binary(left, operator, self.comparison()?)
+                }
+
+                // IS [NOT] [DISTINCT FROM], where IS DISTINCT FROM is IS NOT
+                TokenType::IS if !negated => {
+                    self.current += 1;
+
+                    let mut is_not = self.matches(TokenType::NOT);
+
+                    if self.matches(TokenType::DISTINCT) {
+                        self.expect(TokenType::FROM)?;
+
+                        is_not = !is_not;
+                    }
+
+                    let operator = if is_not { BinaryOperator::IsNot } else { BinaryOperator::Is };
+
+                    binary(left, operator, self.comparison()?)
+                }
+
+                TokenType::ISNULL | TokenType::NOTNULL if !negated => {
+                    self.current += 1;
+
+                    let operator = match token_type {
+                        TokenType::ISNULL => BinaryOperator::Is,
+                        _ => BinaryOperator::IsNot,
+                    };
+
+                    binary(left, operator, Expr::Literal(Value::Null))
+                }
+
+                TokenType::NULL if negated => {
+                    self.current += 1;
+
+                    binary(left, BinaryOperator::IsNot, Expr::Literal(Value::Null))
+                }
+
+                TokenType::BETWEEN => {
+                    self.current += 1;
+
+                    let low = self.comparison()?;
+
+                    self.expect(TokenType::AND)?;
+
+                    let high = self.comparison()?;
+
+                    Expr::Between {
+                        expr: Box::new(left),
+                        low: Box::new(low),
+                        high: Box::new(high),
+                        negated,
+                    }
+                }
+
+                TokenType::IN => {
+                    self.current += 1;
+
+                    self.in_expr(left, negated)?
+                }
+
+                TokenType::LIKE | TokenType::GLOB | TokenType::REGEXP | TokenType::MATCH => {
+                    self.current += 1;
+
+                    let operator = match token_type {
+                        TokenType::LIKE => LikeOperator::Like,
+                        TokenType::GLOB => LikeOperator::Glob,
+                        TokenType::REGEXP => LikeOperator::Regexp,
+                        _ => LikeOperator::Match,
+                    };
+
+                    let pattern = self.comparison()?;
+
+                    let escape = if self.matches(TokenType::ESCAPE) {
+                        Some(Box::new(self.comparison()?))
+                    } else {
+                        None
+                    };
+
+                    Expr::Like {
+                        expr: Box::new(left),
+                        operator,
+                        pattern: Box::new(pattern),
+                        escape,
+                        negated,
+                    }
+                }
+
+                _ => break,
+            };
+
+            self.deepen()?;
+        }
+
+        self.depth = depth;
+
+        Ok(left)
+    }
+
+    // `IN (select)` or `IN (expr, ...)`, the list may be empty
+    fn in_expr(&mut self, left: Expr, negated: bool) -> Result<Expr> {
+        self.expect(TokenType::LeftParen)?;
+
+        if matches!(self.peek().token_type, TokenType::SELECT | TokenType::VALUES | TokenType::WITH) {
+            let select = Box::new(self.select()?);
+
+            self.expect(TokenType::RightParen)?;
+
+            return Ok(Expr::InSelect {
+                expr: Box::new(left),
+                select,
+                negated,
+            });
+        }
+
+        let list = if self.check(TokenType::RightParen) {
+            vec![]
+        } else {
+            self.expr_list()?
+        };
+
+        self.expect(TokenType::RightParen)?;
+
+        Ok(Expr::InList {
+            expr: Box::new(left),
+            list,
+            negated,
+        })
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        self.left_associative(Self::bitwise, |token_type| match token_type {
+            TokenType::LESS => Some(BinaryOperator::Less),
+            TokenType::LessEqual => Some(BinaryOperator::LessEqual),
+            TokenType::GREATER => Some(BinaryOperator::Greater),
+            TokenType::GreaterEqual => Some(BinaryOperator::GreaterEqual),
+            _ => None,
+        })
+    }
+
+    fn bitwise(&mut self) -> Result<Expr> {
+        self.left_associative(Self::additive, |token_type| match token_type {
+            TokenType::AMPERSAND => Some(BinaryOperator::BitAnd),
+            TokenType::PIPE => Some(BinaryOperator::BitOr),
+            TokenType::LeftShift => Some(BinaryOperator::ShiftLeft),
+            TokenType::RightShift => Some(BinaryOperator::ShiftRight),
+            _ => None,
+        })
+    }
+
+    fn additive(&mut self) -> Result<Expr> {
+        self.left_associative(Self::multiplicative, |token_type| match token_type {
+            TokenType::PLUS => Some(BinaryOperator::Add),
+            TokenType::MINUS => Some(BinaryOperator::Subtract),
+            _ => None,
+        })
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr> {
+        self.left_associative(Self::concat, |token_type| match token_type {
+            TokenType::STAR => Some(BinaryOperator::Multiply),
+            TokenType::SLASH => Some(BinaryOperator::Divide),
+            TokenType::PERCENT => Some(BinaryOperator::Modulo),
+            _ => None,
+        })
+    }
+
+    fn concat(&mut self) -> Result<Expr> {
+        self.left_associative(Self::collate, |token_type| match token_type {
+            TokenType::CONCAT => Some(BinaryOperator::Concat),
+            _ => None,
+        })
+    }
+
+    // `operand (operator operand)*`, grouped from the left. Every operator adds a level to
+    // the tree, so a long chain counts against the depth limit like nesting does.
+    fn left_associative(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr>,
+        operator: fn(TokenType) -> Option<BinaryOperator>,
+    ) -> Result<Expr> {
+        let depth = self.depth;
+
+        let mut left = operand(self)?;
+
+        while let Some(operator) = operator(self.peek().token_type) {
+            self.deepen()?;
+
+            self.current += 1;
+
+            left = binary(left, operator, operand(self)?);
+        }
+
+        self.depth = depth;
+
+        Ok(left)
+    }
+
+    fn collate(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+
+        let mut expr = self.unary()?;
+
+        while self.matches(TokenType::COLLATE) {
+            self.deepen()?;
+
+            let collation = self.name()?;
+
+            expr = Expr::Collate {
+                expr: Box::new(expr),
+                collation,
+            };
+        }
+
+        self.depth = depth;
+
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let operator = match self.peek().token_type {
+            // a negative number is one literal, so -9223372036854775808 stays an integer
+            TokenType::MINUS if self.peek_at(1).token_type == TokenType::NUMBER => {
+                self.current += 1;
+
+                let token = self.advance();
+
+                return Ok(Expr::Literal(parse_number(token, true)?));
+            }
+
+            TokenType::MINUS => UnaryOperator::Negate,
+            TokenType::PLUS => UnaryOperator::Plus,
+            TokenType::TILDE => UnaryOperator::BitNot,
+
+            // NOT binds loosely, `1 = NOT 0 = 0` negates `0 = 0`
+            TokenType::NOT => {
+                self.current += 1;
+
+                let expr = self.nested(|parser| parser.not_expr())?;
+
+                return Ok(Expr::Unary {
+                    operator: UnaryOperator::Not,
+                    expr: Box::new(expr),
+                });
+            }
+
+            _ => return self.primary(),
+        };
+
+        self.current += 1;
+
+        let expr = self.nested(|parser| parser.unary())?;
+
+        Ok(Expr::Unary {
+            operator,
+            expr: Box::new(expr),
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let token = self.peek();
+
+        match token.token_type {
+            TokenType::NUMBER => {
+                self.current += 1;
+
+                Ok(Expr::Literal(parse_number(token, false)?))
+            }
+
+            TokenType::STRING => {
+                self.current += 1;
+
+                Ok(Expr::Literal(Value::Text(unquote_identifier(&token.lexeme))))
+            }
+
+            TokenType::BLOB => {
+                self.current += 1;
+
+                Ok(Expr::Literal(parse_blob(token)))
+            }
+
+            TokenType::NULL => {
+                self.current += 1;
+
+                Ok(Expr::Literal(Value::Null))
+            }
+
+            TokenType::LeftParen => {
+                self.current += 1;
+
+                if matches!(self.peek().token_type, TokenType::SELECT | TokenType::VALUES | TokenType::WITH) {
+                    let select = Box::new(self.select()?);
+
+                    self.expect(TokenType::RightParen)?;
+
+                    return Ok(Expr::Subquery(select));
+                }
+
+                let mut exprs = self.expr_list()?;
+
+                self.expect(TokenType::RightParen)?;
+
+                if exprs.len() > 1 {
+                    return Ok(Expr::Row(exprs));
+                }
+
+                Ok(exprs.remove(0))
+            }
+
+            TokenType::CAST if self.peek_at(1).token_type == TokenType::LeftParen => {
+                self.current += 2;
+
+                let expr = self.expr()?;
+
+                self.expect(TokenType::AS)?;
+
+                let type_name = self.type_name()?.ok_or_else(|| self.error())?;
+
+                self.expect(TokenType::RightParen)?;
+
+                Ok(Expr::Cast {
+                    expr: Box::new(expr),
+                    type_name,
+                })
+            }
+
+            TokenType::CASE => {
+                self.current += 1;
+
+                self.case_expr()
+            }
+
+            TokenType::EXISTS => {
+                self.current += 1;
+
+                self.expect(TokenType::LeftParen)?;
+
+                let select = Box::new(self.select()?);
+
+                self.expect(TokenType::RightParen)?;
+
+                Ok(Expr::Exists(select))
+            }
+
+            _ if self.is_name(token) => {
+                let name = self.name()?;
+
+                if self.matches(TokenType::LeftParen) {
+                    return self.function(name);
+                }
+
+                if !self.matches(TokenType::DOT) {
+                    return Ok(Expr::Column { table: None, name });
+                }
+
+                let mut table = name;
+
+                let mut name = self.name()?;
+
+                // schema.table.column, the schema has no meaning for a single database
+                if self.matches(TokenType::DOT) {
+                    table = std::mem::replace(&mut name, self.name()?);
+                }
+
+                Ok(Expr::Column {
+                    table: Some(table),
+                    name,
+                })
+            }
+
+            _ => Err(syntax_error(token)),
+        }
+    }
+
+    // Arguments of a function call, after its opening parenthesis
+    fn function(&mut self, name: String) -> Result<Expr> {
+        let mut args = vec![];
+        let mut distinct = false;
+
+        let star = self.matches(TokenType::STAR);
+
+        if !star && !self.check(TokenType::RightParen) {
+            distinct = self.matches(TokenType::DISTINCT);
+
+            if !distinct {
+                self.matches(TokenType::ALL);
+            }
+
+            args = self.expr_list()?;
+        }
+
+        self.expect(TokenType::RightParen)?;
+
+        Ok(Expr::Function {
+            name,
+            args,
+            distinct,
+            star,
+        })
+    }
+
+    // `CASE [operand] WHEN ... THEN ... [ELSE ...] END`, after CASE
+    fn case_expr(&mut self) -> Result<Expr> {
+        let operand = if self.check(TokenType::WHEN) {
+            None
+        } else {
+            Some(Box::new(self.expr()?))
+        };
+
+        let mut branches = vec![];
+
+        while self.matches(TokenType::WHEN) {
+            let condition = self.expr()?;
+
+            self.expect(TokenType::THEN)?;
+
+            branches.push((condition, self.expr()?));
+        }
+
+        if branches.is_empty() {
+            return Err(self.error());
+        }
+
+        let otherwise = if self.matches(TokenType::ELSE) {
+            Some(Box::new(self.expr()?))
+        } else {
+            None
+        };
+
+        self.expect(TokenType::END)?;
+
+        Ok(Expr::Case {
+            operand,
+            branches,
+            otherwise,
+        })
+    }
+
+    // A type name is one or more words and up to two signed numbers in parentheses, like
+    // `UNSIGNED BIG INT` or `DECIMAL(10, 5)`. None when there is no type.
+    fn type_name(&mut self) -> Result<Option<String>> {
+        let mut words = vec![];
+
+        while self.is_type_word() {
+            words.push(self.advance().lexeme.clone());
+        }
+
+        if words.is_empty() {
+            return Ok(None);
+        }
+
+        let mut type_name = words.join(" ");
+
+        if self.matches(TokenType::LeftParen) {
+            let mut sizes = vec![];
+
+            loop {
+                let mut size = String::new();
+
+                if matches!(self.peek().token_type, TokenType::PLUS | TokenType::MINUS) {
+                    size.push_str(&self.advance().lexeme);
+                }
+
+                size.push_str(&self.expect(TokenType::NUMBER)?.lexeme);
+
+                sizes.push(size);
+
+                if sizes.len() == 2 || !self.matches(TokenType::COMMA) {
+                    break;
+                }
+            }
+
+            self.expect(TokenType::RightParen)?;
+
+            type_name.push_str(&format!("({})", sizes.join(", ")));
+        }
+
+        Ok(Some(type_name))
+    }
+
+    // GENERATED ALWAYS AS starts a constraint, not a type word
+    fn is_type_word(&self) -> bool {
+        let token = self.peek();
+
+        let is_word = match token.token_type {
+            TokenType::IDENTIFIER => true,
+            token_type => token_type.is_fallback_keyword(),
+        };
+
+        is_word && !(token.is_word("GENERATED") && self.peek_at(1).is_word("ALWAYS"))
+    }
+
+    fn insert(&mut self, with: Option<WithClause>) -> Result<InsertStmt> {
+        let conflict = if self.matches(TokenType::REPLACE) {
+            Some(ConflictResolution::Replace)
+        } else {
+            self.expect(TokenType::INSERT)?;
+
+            if self.matches(TokenType::OR) {
+                Some(self.conflict_resolution()?)
+            } else {
+                None
+            }
+        };
+
+        self.expect(TokenType::INTO)?;
+
+        let (schema, table) = self.qualified_name()?;
+
+        let alias = if self.matches(TokenType::AS) {
+            Some(self.name()?)
+        } else {
+            None
+        };
+
+        let columns = if self.check(TokenType::LeftParen) {
+            self.name_list()?
+        } else {
+            vec![]
+        };
+
+        let source = if self.matches(TokenType::DEFAULT) {
+            self.expect(TokenType::VALUES)?;
+
+            None
+        } else {
+            Some(Box::new(self.select()?))
+        };
+
+        Ok(InsertStmt {
+            with,
+            conflict,
+            schema,
+            table,
+            alias,
+            columns,
+            source,
+        })
+    }
+
+    // `ROLLBACK`, `ABORT`, `FAIL`, `IGNORE` or `REPLACE`, after `OR` or `ON CONFLICT`
+    fn conflict_resolution(&mut self) -> Result<ConflictResolution> {
+        let token = self.advance();
+
+        let resolution = match token.lexeme.to_uppercase().as_str() {
+            "ROLLBACK" => ConflictResolution::Rollback,
+            "ABORT" => ConflictResolution::Abort,
+            "FAIL" => ConflictResolution::Fail,
+            "IGNORE" => ConflictResolution::Ignore,
+            "REPLACE" => ConflictResolution::Replace,
+            _ => return Err(syntax_error(token)),
+        };
+
+        Ok(resolution)
+    }
+
+    fn update(&mut self, with: Option<WithClause>) -> Result<UpdateStmt> {
+        self.expect(TokenType::UPDATE)?;
+
+        let conflict = if self.matches(TokenType::OR) {
+            Some(self.conflict_resolution()?)
+        } else {
+            None
+        };
+
+        let (schema, table) = self.qualified_name()?;
+
+        let alias = if self.matches(TokenType::AS) {
+            Some(self.name()?)
+        } else {
+            None
+        };
+
+        self.expect(TokenType::SET)?;
+
+        let mut assignments = vec![];
+
+        loop {
+            let columns = if self.check(TokenType::LeftParen) {
+                self.name_list()?
+            } else {
+                vec![self.name()?]
+            };
+
+            self.expect(TokenType::EQUAL)?;
+
+            assignments.push((columns, self.expr()?));
+
+            if !self.matches(TokenType::COMMA) {
+                break;
+            }
+        }
+
+        let from = if self.matches(TokenType::FROM) {
+            Some(self.table_ref()?)
+        } else {
+            None
+        };
+
+        let where_clause = if self.matches(TokenType::WHERE) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(UpdateStmt {
+            with,
+            conflict,
+            schema,
+            table,
+            alias,
+            assignments,
+            from,
+            where_clause,
+        })
+    }
+
+    fn delete(&mut self, with: Option<WithClause>) -> Result<DeleteStmt> {
+        self.expect(TokenType::DELETE)?;
+
+        self.expect(TokenType::FROM)?;
+
+        let (schema, table) = self.qualified_name()?;
+
+        let alias = if self.matches(TokenType::AS) {
+            Some(self.name()?)
+        } else {
+            None
+        };
+
+        let where_clause = if self.matches(TokenType::WHERE) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(DeleteStmt {
+            with,
+            schema,
+            table,
+            alias,
+            where_clause,
+        })
+    }
+
+    fn create(&mut self) -> Result<Statement> {
+        self.expect(TokenType::CREATE)?;
+
+        let temporary = self.matches(TokenType::TEMP);
+
+        let token = self.advance();
+
+        let statement = match token.token_type {
+            TokenType::TABLE => Statement::CreateTable(self.create_table(temporary)?),
+            TokenType::VIEW => Statement::CreateView(self.create_view(temporary)?),
+            TokenType::TRIGGER => Statement::CreateTrigger(self.create_trigger(temporary)?),
+            TokenType::INDEX if !temporary => Statement::CreateIndex(self.create_index(false)?),
+            TokenType::UNIQUE if !temporary => {
+                self.expect(TokenType::INDEX)?;
+
+                Statement::CreateIndex(self.create_index(true)?)
+            }
+            _ => return Err(syntax_error(token)),
+        };
+
+        Ok(statement)
+    }
+
+    fn if_not_exists(&mut self) -> Result<bool> {
+        if !self.matches(TokenType::IF) {
+            return Ok(false);
+        }
+
+        self.expect(TokenType::NOT)?;
+        self.expect(TokenType::EXISTS)?;
+
+        Ok(true)
+    }
+
+    fn create_table(&mut self, temporary: bool) -> Result<CreateTableStmt> {
+        let if_not_exists = self.if_not_exists()?;
+
+        let (schema, name) = self.qualified_name()?;
+
+        let mut table = CreateTableStmt {
+            temporary,
+            if_not_exists,
+            schema,
+            name,
+            columns: vec![],
+            constraints: vec![],
+            without_rowid: false,
+            strict: false,
+            select: None,
+        };
+
+        if self.matches(TokenType::AS) {
+            table.select = Some(Box::new(self.select()?));
+
+            return Ok(table);
+        }
+
+        self.expect(TokenType::LeftParen)?;
+
+        loop {
+            table.columns.push(self.column_def()?);
+
+            if !self.matches(TokenType::COMMA) {
+                break;
+            }
+
+            if self.is_table_constraint() {
+                break;
+            }
+        }
+
+        // table constraints may be separated by commas or only by spaces
+        while self.is_table_constraint() {
+            table.constraints.push(self.table_constraint()?);
+
+            self.matches(TokenType::COMMA);
+        }
+
+        self.expect(TokenType::RightParen)?;
+
+        // table options, `WITHOUT ROWID` and `STRICT`, separated by commas
+        if matches!(self.peek().token_type, TokenType::WITHOUT) || self.peek().is_word("STRICT") {
+            loop {
+                if self.matches(TokenType::WITHOUT) {
+                    if !self.matches_word("ROWID") {
+                        return Err(self.error());
+                    }
+
+                    table.without_rowid = true;
+                } else if self.matches_word("STRICT") {
+                    table.strict = true;
+                } else {
+                    return Err(self.error());
+                }
+
+                if !self.matches(TokenType::COMMA) {
+                    break;
+                }
+            }
+        }
+
+        Ok(table)
+    }
+
+    fn is_table_constraint(&self) -> bool {
+        matches!(
+            self.peek().token_type,
+            TokenType::CONSTRAINT | TokenType::PRIMARY | TokenType::UNIQUE | TokenType::CHECK | TokenType::FOREIGN
+        )
+    }
+
+    fn column_def(&mut self) -> Result<ColumnDef> {
+        let name = self.name()?;
+
+        let declared_type = self.type_name()?;
+
+        let mut constraints = vec![];
+
+        loop {
+            if self.matches(TokenType::CONSTRAINT) {
+                self.name()?;
+            }
+
+            let token = self.peek();
+
+            let constraint = match token.token_type {
+                TokenType::PRIMARY => {
+                    self.current += 1;
+
+                    self.expect(TokenType::KEY)?;
+
+                    let descending = self.matches(TokenType::DESC);
+
+                    if !descending {
+                        self.matches(TokenType::ASC);
+                    }
+
+                    self.conflict_clause()?;
+
+                    let autoincrement = self.matches(TokenType::AUTOINCREMENT);
+
+                    ColumnConstraint::PrimaryKey {
+                        descending,
+                        autoincrement,
+                    }
+                }
+
+                TokenType::NOT => {
+                    self.current += 1;
+
+                    self.expect(TokenType::NULL)?;
+
+                    self.conflict_clause()?;
+
+                    ColumnConstraint::NotNull
+                }
+
+                TokenType::NULL => {
+                    self.current += 1;
+
+                    self.conflict_clause()?;
+
+                    ColumnConstraint::Null
+                }
+
+                TokenType::UNIQUE => {
+                    self.current += 1;
+
+                    self.conflict_clause()?;
+
+                    ColumnConstraint::Unique
+                }
+
+                TokenType::CHECK => {
+                    self.current += 1;
+
+                    ColumnConstraint::Check(self.parenthesized_expr()?)
+                }
+
+                // a default is a literal, a signed number, a name or an expression in parentheses
+                TokenType::DEFAULT => {
+                    self.current += 1;
+
+                    let expr = if self.check(TokenType::LeftParen) {
+                        self.parenthesized_expr()?
+                    } else {
+                        self.unary()?
+                    };
+
+                    ColumnConstraint::Default(expr)
+                }
+
+                TokenType::COLLATE => {
+                    self.current += 1;
+
+                    ColumnConstraint::Collate(self.name()?)
+                }
+
+                TokenType::REFERENCES => ColumnConstraint::References(self.foreign_key_clause()?),
+
+                // `[GENERATED ALWAYS] AS (expr) [STORED | VIRTUAL]`
+                _ if token.is_word("GENERATED") || token.token_type == TokenType::AS => {
+                    if self.matches_word("GENERATED") && !self.matches_word("ALWAYS") {
+                        return Err(self.error());
+                    }
+
+                    self.expect(TokenType::AS)?;
+
+                    let expr = self.parenthesized_expr()?;
+
+                    let stored = self.matches_word("STORED");
+
+                    if !stored {
+                        self.matches_word("VIRTUAL");
+                    }
+
+                    ColumnConstraint::Generated { expr, stored }
+                }
+
+                _ => break,
+            };
+
+            constraints.push(constraint);
+        }
+
+        Ok(ColumnDef {
+            name,
+            declared_type,
+            constraints,
+        })
+    }
+
+    fn table_constraint(&mut self) -> Result<TableConstraint> {
+        if self.matches(TokenType::CONSTRAINT) {
+            self.name()?;
+        }
+
+        let token = self.advance();
+
+        let constraint = match token.token_type {
+            TokenType::PRIMARY => {
+                self.expect(TokenType::KEY)?;
+
+                let columns = self.indexed_columns()?;
+
+                self.conflict_clause()?;
+
+                TableConstraint::PrimaryKey(columns)
+            }
+
+            TokenType::UNIQUE => {
+                let columns = self.indexed_columns()?;
+
+                self.conflict_clause()?;
+
+                TableConstraint::Unique(columns)
+            }
+
+            TokenType::CHECK => {
+                let expr = self.parenthesized_expr()?;
+
+                self.conflict_clause()?;
+
+                TableConstraint::Check(expr)
+            }
+
+            TokenType::FOREIGN => {
+                self.expect(TokenType::KEY)?;
+
+                let columns = self.name_list()?;
+
+                let clause = self.foreign_key_clause()?;
+
+                TableConstraint::ForeignKey { columns, clause }
+            }
+
+            _ => return Err(syntax_error(token)),
+        };
+
+        Ok(constraint)
+    }
+
+    // `ON CONFLICT resolution`, which only matters when writing and is left out of the tree
+    fn conflict_clause(&mut self) -> Result<()> {
+        if self.check(TokenType::ON) && self.peek_at(1).is_word("CONFLICT") {
+            self.current += 2;
+
+            self.conflict_resolution()?;
+        }
+
+        Ok(())
+    }
+
+    // `REFERENCES table [(columns)]` followed by any number of ON DELETE, ON UPDATE, MATCH
+    // and [NOT] DEFERRABLE clauses
+    fn foreign_key_clause(&mut self) -> Result<ForeignKeyClause> {
+        self.expect(TokenType::REFERENCES)?;
+
+        let table = self.name()?;
+
+        let columns = if self.check(TokenType::LeftParen) {
+            self.name_list()?
+        } else {
+            vec![]
+        };
+
+        loop {
+            if self.matches(TokenType::ON) {
+                let event = self.advance();
+
+                if !matches!(event.token_type, TokenType::DELETE | TokenType::UPDATE) {
+                    return Err(syntax_error(event));
+                }
+
+                if self.matches(TokenType::SET) {
+                    let action = self.advance();
+
+                    if !matches!(action.token_type, TokenType::NULL | TokenType::DEFAULT) {
+                        return Err(syntax_error(action));
+                    }
+                } else if self.matches_word("NO") {
+                    if !self.matches_word("ACTION") {
+                        return Err(self.error());
+                    }
+                } else if !self.matches_word("CASCADE") && !self.matches_word("RESTRICT") {
+                    return Err(self.error());
+                }
+            } else if self.matches(TokenType::MATCH) {
+                self.name()?;
+            } else if self.peek().is_word("DEFERRABLE")
+                || (self.check(TokenType::NOT) && self.peek_at(1).is_word("DEFERRABLE"))
+            {
+                self.matches(TokenType::NOT);
+
+                self.current += 1;
+
+                if self.matches_word("INITIALLY") && !self.matches_word("DEFERRED") && !self.matches_word("IMMEDIATE") {
+                    return Err(self.error());
+                }
+            } else {
+                break;
+            }
+        }
+
+        Ok(ForeignKeyClause { table, columns })
+    }
+
+    // `(expr [COLLATE name] [ASC | DESC], ...)`
+    fn indexed_columns(&mut self) -> Result<Vec<IndexedColumn>> {
+        self.expect(TokenType::LeftParen)?;
+
+        let mut columns = vec![];
+
+        loop {
+            let mut expr = self.expr()?;
+
+            // the expression parser takes the COLLATE as part of the expression
+            let mut collation = None;
+
+            if let Expr::Collate { expr: inner, collation: name } = expr {
+                expr = *inner;
+                collation = Some(name);
+            }
+
+            let descending = self.matches(TokenType::DESC);
+
+            if !descending {
+                self.matches(TokenType::ASC);
+            }
+
+            columns.push(IndexedColumn {
+                expr,
+                collation,
+                descending,
+            });
+
+            if !self.matches(TokenType::COMMA) {
+                break;
+            }
+        }
+
+        self.expect(TokenType::RightParen)?;
+
+        Ok(columns)
+    }
+
+    fn create_index(&mut self, unique: bool) -> Result<CreateIndexStmt> {
+        let if_not_exists = self.if_not_exists()?;
+
+        let (schema, name) = self.qualified_name()?;
+
+        self.expect(TokenType::ON)?;
+
+        let table = self.name()?;
+
+        let columns = self.indexed_columns()?;
+
+        let where_clause = if self.matches(TokenType::WHERE) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(CreateIndexStmt {
+            unique,
+            if_not_exists,
+            schema,
+            name,
+            table,
+            columns,
+            where_clause,
+        })
+    }
+
+    fn create_view(&mut self, temporary: bool) -> Result<CreateViewStmt> {
+        let if_not_exists = self.if_not_exists()?;
+
+        let (schema, name) = self.qualified_name()?;
+
+        let columns = if self.check(TokenType::LeftParen) {
+            self.name_list()?
+        } else {
+            vec![]
+        };
+
+        self.expect(TokenType::AS)?;
+
+        let select = Box::new(self.select()?);
+
+        Ok(CreateViewStmt {
+            temporary,
+            if_not_exists,
+            schema,
+            name,
+            columns,
+            select,
+        })
+    }
+
+    // `name [BEFORE | AFTER | INSTEAD OF] DELETE | INSERT | UPDATE [OF columns] ON table
+    // [FOR EACH ROW] [WHEN expr] BEGIN statement; ... END`
+    fn create_trigger(&mut self, temporary: bool) -> Result<CreateTriggerStmt> {
+        let if_not_exists = self.if_not_exists()?;
+
+        let (schema, name) = self.qualified_name()?;
+
+        let timing = if self.matches_word("BEFORE") {
+            Some("BEFORE".to_string())
+        } else if self.matches_word("AFTER") {
+            Some("AFTER".to_string())
+        } else if self.matches_word("INSTEAD") {
+            if !self.matches_word("OF") {
+                return Err(self.error());
+            }
+
+            Some("INSTEAD OF".to_string())
+        } else {
+            None
+        };
+
+        let token = self.advance();
+
+        let event = match token.token_type {
+            TokenType::DELETE => "DELETE".to_string(),
+            TokenType::INSERT => "INSERT".to_string(),
+            TokenType::UPDATE if self.matches_word("OF") => {
+                let mut columns = vec![self.name()?];
+
+                while self.matches(TokenType::COMMA) {
+                    columns.push(self.name()?);
+                }
+
+                format!("UPDATE OF {}", columns.join(", "))
+            }
+            TokenType::UPDATE => "UPDATE".to_string(),
+            _ => return Err(syntax_error(token)),
+        };
+
+        self.expect(TokenType::ON)?;
+
+        let table = self.name()?;
+
+        if self.matches_word("FOR") && (!self.matches_word("EACH") || !self.matches_word("ROW")) {
+            return Err(self.error());
+        }
+
+        let when = if self.matches(TokenType::WHEN) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        if !self.matches_word("BEGIN") {
+            return Err(self.error());
+        }
+
+        let mut body = vec![];
+
+        while !self.check(TokenType::END) {
+            let statement = match self.peek().token_type {
+                TokenType::SELECT
+                | TokenType::VALUES
+                | TokenType::WITH
+                | TokenType::INSERT
+                | TokenType::REPLACE
+                | TokenType::UPDATE
+                | TokenType::DELETE => self.statement()?,
+                _ => return Err(self.error()),
+            };
+
+            body.push(statement);
+
+            self.expect(TokenType::SEMICOLON)?;
+        }
+
+        if body.is_empty() {
+            return Err(self.error());
+        }
+
+        self.current += 1;
+
+        Ok(CreateTriggerStmt {
+            temporary,
+            if_not_exists,
+            schema,
+            name,
+            timing,
+            event,
+            table,
+            when,
+            body,
+        })
+    }
+
+    fn drop(&mut self) -> Result<DropStmt> {
+        self.expect(TokenType::DROP)?;
+
+        let token = self.advance();
+
+        let object_type = match token.token_type {
+            TokenType::TABLE => ObjectType::Table,
+            TokenType::INDEX => ObjectType::Index,
+            TokenType::VIEW => ObjectType::View,
+            TokenType::TRIGGER => ObjectType::Trigger,
+            _ => return Err(syntax_error(token)),
+        };
+
+        let if_exists = if self.matches(TokenType::IF) {
+            self.expect(TokenType::EXISTS)?;
+
+            true
+        } else {
+            false
+        };
+
+        let (schema, name) = self.qualified_name()?;
+
+        Ok(DropStmt {
+            object_type,
+            if_exists,
+            schema,
+            name,
+        })
+    }
+
+    fn alter_table(&mut self) -> Result<AlterTableStmt> {
+        self.expect(TokenType::ALTER)?;
+        self.expect(TokenType::TABLE)?;
+
+        let (schema, table) = self.qualified_name()?;
+
+        let action = if self.matches_word("RENAME") {
+            if self.matches_word("TO") {
+                AlterTableAction::RenameTable(self.name()?)
+            } else {
+                self.matches_word("COLUMN");
+
+                let from = self.name()?;
+
+                if !self.matches_word("TO") {
+                    return Err(self.error());
+                }
+
+                AlterTableAction::RenameColumn { from, to: self.name()? }
+            }
+        } else if self.matches_word("ADD") {
+            self.matches_word("COLUMN");
+
+            AlterTableAction::AddColumn(self.column_def()?)
+        } else if self.matches(TokenType::DROP) {
+            self.matches_word("COLUMN");
+
+            AlterTableAction::DropColumn(self.name()?)
+        } else {
+            return Err(self.error());
+        };
+
+        Ok(AlterTableStmt { schema, table, action })
+    }
+
+    // `PRAGMA [schema.]name [= value | (value)]`, where the value is a signed number, a
+    // string, a name or any keyword, like ON, FULL or DELETE
+    fn pragma(&mut self) -> Result<Pragma> {
+        self.expect(TokenType::PRAGMA)?;
+
+        let (_, name) = self.qualified_name()?;
+
+        let mut argument = None;
+
+        let open = self.peek().token_type;
+
+        if matches!(open, TokenType::EQUAL | TokenType::LeftParen) {
+            self.current += 1;
+
+            let negative = self.matches(TokenType::MINUS);
+
+            if !negative {
+                self.matches(TokenType::PLUS);
+            }
+
+            let token = self.advance();
+
+            argument = Some(match token.token_type {
+                TokenType::NUMBER => parse_number(token, negative)?,
+                _ if negative => return Err(syntax_error(token)),
+                TokenType::STRING => Value::Text(unquote_identifier(&token.lexeme)),
+                _ if self.is_name(token) => Value::Text(unquote_identifier(&token.lexeme)),
+                _ if token.is_keyword() => Value::Text(token.lexeme.clone()),
+                _ => return Err(syntax_error(token)),
+            });
+
+            if open == TokenType::LeftParen {
+                self.expect(TokenType::RightParen)?;
+            }
+        }
+
+        Ok(Pragma {
+            name: name.to_lowercase(),
+            argument,
+        })
+    }
+
+    // `VACUUM [schema] [INTO 'file']`
+    fn vacuum(&mut self) -> Result<Vacuum> {
+        self.expect(TokenType::VACUUM)?;
+
+        let schema = if self.is_name(self.peek()) {
+            Some(self.name()?)
+        } else {
+            None
+        };
+
+        let into = if self.matches(TokenType::INTO) {
+            let file = self.expect(TokenType::STRING)?;
+
+            Some(unquote_identifier(&file.lexeme))
+        } else {
+            None
+        };
+
+        Ok(Vacuum { schema, into })
+    }
+
+    fn parenthesized_expr(&mut self) -> Result<Expr> {
+        self.expect(TokenType::LeftParen)?;
+
+        let expr = self.expr()?;
+
+        self.expect(TokenType::RightParen)?;
+
+        Ok(expr)
+    }
+
+    // `(name, ...)`
+    fn name_list(&mut self) -> Result<Vec<String>> {
+        self.expect(TokenType::LeftParen)?;
+
+        let mut names = vec![self.name()?];
+
+        while self.matches(TokenType::COMMA) {
+            names.push(self.name()?);
+        }
+
+        self.expect(TokenType::RightParen)?;
+
+        Ok(names)
+    }
+
+    // `name` or `schema.name`
+    fn qualified_name(&mut self) -> Result<(Option<String>, String)> {
+        let name = self.name()?;
+
+        if !self.matches(TokenType::DOT) {
+            return Ok((None, name));
+        }
+
+        Ok((Some(name), self.name()?))
+    }
+
+    // Names are identifiers, quoted or not, keywords sqlite lets double as names, and for
+    // compatibility strings in 'single quotes'
+    fn is_name(&self, token: &Token) -> bool {
+        matches!(token.token_type, TokenType::IDENTIFIER | TokenType::STRING) || token.token_type.is_fallback_keyword()
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let token = self.peek();
+
+        if !self.is_name(token) {
+            return Err(syntax_error(token));
+        }
+
+        self.current += 1;
+
+        Ok(unquote_identifier(&token.lexeme))
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.deepen()?;
+
+        let result = parse(self);
+
+        self.depth -= 1;
+
+        result
+    }
+
+    fn deepen(&mut self) -> Result<()> {
+        if self.depth >= MAX_EXPR_DEPTH {
+            let token = self.peek();
+
+            return Err(Error::Syntax {
+                line: token.line,
+                column: token.column,
+                msg: format!("Expression tree is too large (maximum depth {MAX_EXPR_DEPTH})"),
+            });
+        }
+
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    // The source text from a byte offset to the end of the last token consumed
+    fn span_from(&self, start: usize) -> String {
+        let end = match self.current.checked_sub(1).map(|i| &self.tokens[i]) {
+            Some(last) => last.offset + last.lexeme.len(),
+            None => start,
+        };
+
+        self.source.get(start..end).unwrap_or_default().to_string()
+    }
+
+    fn peek(&self) -> &'a Token {
+        self.peek_at(0)
+    }
+
+    // Looking past the end gives the EOF token
+    fn peek_at(&self, offset: usize) -> &'a Token {
+        let tokens: &'a [Token] = self.tokens;
+
+        &tokens[(self.current + offset).min(tokens.len() - 1)]
+    }
+
+    fn check(&self, token_type: TokenType) -> bool {
+        self.peek().token_type == token_type
+    }
+
+    fn advance(&mut self) -> &'a Token {
+        let token = self.peek();
+
+        if token.token_type != TokenType::EOF {
+            self.current += 1;
+        }
+
+        token
+    }
+
+    fn matches(&mut self, token_type: TokenType) -> bool {
+        if !self.check(token_type) {
+            return false;
+        }
+
+        self.current += 1;
+
+        true
+    }
+
+    fn matches_word(&mut self, word: &str) -> bool {
+        if !self.peek().is_word(word) {
+            return false;
+        }
+
+        self.current += 1;
+
+        true
+    }
+
+    fn expect(&mut self, token_type: TokenType) -> Result<&'a Token> {
+        if !self.check(token_type) {
+            return Err(self.error());
+        }
+
+        Ok(self.advance())
+    }
+
+    fn error(&self) -> Error {
+        syntax_error(self.peek())
+    }
+}
+
+fn binary(left: Expr, operator: BinaryOperator, right: Expr) -> Expr {
+    Expr::Binary {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+// Integers that do not fit in 64 bits become reals, except hexadecimal ones, which are
+// taken as the bits of a 64-bit integer and may not be longer than that
+fn parse_number(token: &Token, negative: bool) -> Result<Value> {
+    let lexeme = token.lexeme.as_str();
+
+    if let Some(digits) = lexeme.strip_prefix("0x").or_else(|| lexeme.strip_prefix("0X")) {
+        let bits = u64::from_str_radix(digits, 16).map_err(|_| Error::Syntax {
+            line: token.line,
+            column: token.column,
+            msg: format!("hex literal too big: {}{}", if negative { "-" } else { "" }, lexeme),
+        })?;
+
+        let value = bits as i64;
+
+        return Ok(Value::Integer(if negative { value.wrapping_neg() } else { value }));
+    }
+
+    let sign = if negative { "-" } else { "" };
+
+    if !lexeme.contains(['.', 'e', 'E']) {
+        if let Ok(value) = format!("{sign}{lexeme}").parse::<i64>() {
+            return Ok(Value::Integer(value));
+        }
+    }
+
+    format!("{sign}{lexeme}")
+        .parse::<f64>()
+        .map(Value::Real)
+        .map_err(|_| syntax_error(token))
+}
+
+// The scanner only makes BLOB tokens of an even number of hex digits in x'...'
+fn parse_blob(token: &Token) -> Value {
+    let digits = &token.lexeme[2..token.lexeme.len() - 1];
+
+    let bytes = (0..digits.len())
+        .step_by(2)
+        .filter_map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect();
+
+    Value::Blob(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_sql;
+    use crate::core::value::Value;
+    use crate::error::Error;
+    use crate::parser::ast::*;
+
+    fn select(sql: &str) -> SelectStmt {
+        match parse_sql(sql).unwrap() {
+            Statement::Select(select) => select,
+            other => panic!("{sql} is not a SELECT: {other:?}"),
+        }
+    }
+
+    // The expression of `SELECT expr`, written with every operation in parentheses
+    fn grouped(expr: &str) -> String {
+        let SelectCore::Select { columns, .. } = select(&format!("SELECT {expr}")).body.first else {
+            panic!("{expr} is not in a SELECT");
+        };
+
+        match &columns[..] {
+            [ResultColumn::Expr { expr, .. }] => render(expr),
+            _ => panic!("{expr} is not one result column"),
+        }
+    }
+
+    fn render(expr: &Expr) -> String {
+        match expr {
+            Expr::Literal(value) => value.to_sql_literal(),
+            Expr::Column { name, .. } => name.clone(),
+            Expr::Unary { operator, expr } => format!("({operator:?} {})", render(expr)),
+            Expr::Binary { left, operator, right } => format!("({} {operator:?} {})", render(left), render(right)),
+            Expr::Collate { expr, collation } => format!("({} COLLATE {collation})", render(expr)),
+            Expr::Like { expr, operator, pattern, negated, .. } => {
+                format!("({} {}{operator:?} {})", render(expr), if *negated { "NOT " } else { "" }, render(pattern))
+            }
+            Expr::Between { expr, low, high, .. } => {
+                format!("({} BETWEEN {} AND {})", render(expr), render(low), render(high))
+            }
+            other => panic!("no rendering for {other:?}"),
+        }
+    }
+
+    fn syntax_error(sql: &str) -> (u64, u64, String) {
+        match parse_sql(sql) {
+            Err(Error::Syntax { line, column, msg }) => (line, column, msg),
+            other => panic!("{sql} parsed to {other:?}"),
+        }
+    }
+
+    #[test]
+    fn select_clauses() {
+        let sql = "WITH c AS (SELECT 1) SELECT DISTINCT a AS x, t.* FROM t LEFT JOIN u USING (id) WHERE a > 1 \
+                   GROUP BY a HAVING count(*) > 1 ORDER BY x DESC NULLS FIRST LIMIT 5 OFFSET 2";
+
+        let statement = select(sql);
+
+        assert!(statement.with.is_some_and(|with| !with.recursive && with.tables[0].name == "c"));
+        assert!(statement.body.compounds.is_empty());
+
+        let SelectCore::Select { distinct, columns, from, where_clause, group_by, having } = statement.body.first else {
+            panic!("not a SELECT core");
+        };
+
+        assert!(distinct);
+        assert!(matches!(&columns[..], [ResultColumn::Expr { alias: Some(x), span, .. }, ResultColumn::AllFrom(t)]
+            if x == "x" && span == "a" && t == "t"));
+        assert!(matches!(from, Some(TableRef::Join { operator: JoinOperator { natural: false, kind: JoinKind::Left },
+            constraint: Some(JoinConstraint::Using(columns)), .. }) if columns == ["id"]));
+        assert!(where_clause.is_some() && having.is_some());
+        assert_eq!(group_by.len(), 1);
+
+        assert!(matches!(&statement.order_by[..], [OrderingTerm { descending: true, nulls_first: Some(true), .. }]));
+        assert!(matches!(statement.limit, Some(Limit { count: Expr::Literal(Value::Integer(5)),
+            offset: Some(Expr::Literal(Value::Integer(2))) })));
+    }
+
+    #[test]
+    fn compounds_and_values() {
+        let statement = select("SELECT 1 UNION SELECT 2 EXCEPT VALUES (3), (4) ORDER BY 1");
+
+        let operators = statement.body.compounds.iter().map(|(operator, _)| *operator).collect::<Vec<_>>();
+
+        assert_eq!(operators, [CompoundOperator::Union, CompoundOperator::Except]);
+        assert!(matches!(&statement.body.compounds[1].1, SelectCore::Values(rows) if rows.len() == 2));
+        assert_eq!(statement.order_by.len(), 1);
+
+        // LIMIT skip, count
+        let limit = select("SELECT 1 LIMIT 2, 3").limit.unwrap();
+
+        assert!(matches!((limit.count, limit.offset), (Expr::Literal(Value::Integer(3)),
+            Some(Expr::Literal(Value::Integer(2))))));
+    }
+
+    #[test]
+    fn other_statements() {
+        let sql = "CREATE TABLE IF NOT EXISTS t (id INTEGER PRIMARY KEY AUTOINCREMENT, a TEXT NOT NULL COLLATE NOCASE, \
+                   b AS (a || 'x') STORED, UNIQUE (a, b)) WITHOUT ROWID";
+
+        let Statement::CreateTable(table) = parse_sql(sql).unwrap() else { panic!("not a CREATE TABLE") };
+
+        assert!(table.if_not_exists && table.without_rowid && !table.strict);
+        assert_eq!(table.columns.iter().map(|column| column.name.as_str()).collect::<Vec<_>>(), ["id", "a", "b"]);
+        assert_eq!(table.columns[0].declared_type.as_deref(), Some("INTEGER"));
+        assert!(matches!(table.columns[0].constraints[..], [ColumnConstraint::PrimaryKey { autoincrement: true, .. }]));
+        assert!(matches!(table.columns[2].constraints[..], [ColumnConstraint::Generated { stored: true, .. }]));
+        assert!(matches!(&table.constraints[..], [TableConstraint::Unique(columns)] if columns.len() == 2));
+
+        let sql = "CREATE UNIQUE INDEX i ON t (a COLLATE NOCASE DESC, b) WHERE a IS NOT NULL";
+
+        let Statement::CreateIndex(index) = parse_sql(sql).unwrap() else { panic!("not a CREATE INDEX") };
+
+        assert!(index.unique && index.where_clause.is_some());
+        assert_eq!(index.table, "t");
+        assert!(index.columns[0].descending && index.columns[0].collation.as_deref() == Some("NOCASE"));
+
+        let sql = "INSERT OR REPLACE INTO t (a, b) VALUES (1, 2);;";
+
+        let Statement::Insert(insert) = parse_sql(sql).unwrap() else { panic!("not an INSERT") };
+
+        assert_eq!(insert.conflict, Some(ConflictResolution::Replace));
+        assert_eq!(insert.columns, ["a", "b"]);
+
+        let Statement::Drop(drop) = parse_sql("DROP INDEX IF EXISTS main.i").unwrap() else { panic!("not a DROP") };
+
+        assert!(drop.object_type == ObjectType::Index && drop.if_exists && drop.schema.as_deref() == Some("main"));
+
+        let Statement::Pragma(pragma) = parse_sql("PRAGMA page_size = 512").unwrap() else { panic!("not a PRAGMA") };
+
+        assert!(pragma.name == "page_size" && pragma.argument == Some(Value::Integer(512)));
+
+        // keywords are values too
+        for (sql, value) in [("PRAGMA case_sensitive_like = ON", "ON"), ("PRAGMA journal_mode(delete)", "delete")] {
+            let Statement::Pragma(pragma) = parse_sql(sql).unwrap() else { panic!("not a PRAGMA") };
+
+            assert_eq!(pragma.argument, Some(Value::Text(value.to_string())));
+        }
+
+        assert!(parse_sql("PRAGMA secure_delete = DEFAULT").is_ok());
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(grouped("1 + 2 * 3 - 4"), "((1 Add (2 Multiply 3)) Subtract 4)");
+        assert_eq!(grouped("a OR b AND NOT c = d"), "(a Or (b And (Not (c Equal d))))");
+        assert_eq!(grouped("a < b = c > d"), "((a Less b) Equal (c Greater d))");
+        // the bitwise operators share one level and group from the left
+        assert_eq!(grouped("-a || b & c << 1"), "((((Negate a) Concat b) BitAnd c) ShiftLeft 1)");
+        assert_eq!(grouped("a BETWEEN 1 AND 2 AND b"), "((a BETWEEN 1 AND 2) And b)");
+        assert_eq!(grouped("a NOT LIKE 'x' || 'y'"), "(a NOT Like ('x' Concat 'y'))");
+        assert_eq!(grouped("a || b COLLATE nocase"), "(a Concat (b COLLATE nocase))");
+        assert_eq!(grouped("a IS NOT NULL = 1"), "((a IsNot NULL) Equal 1)");
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_token() {
+        assert_eq!(syntax_error("SELEC 1"), (1, 1, "near \"SELEC\": syntax error".to_string()));
+        assert_eq!(syntax_error("SELECT a\nFROM t WHERE x = = 1"), (2, 18, "near \"=\": syntax error".to_string()));
+        assert_eq!(syntax_error("SELECT 1 FROM"), (1, 14, "incomplete input".to_string()));
+        assert_eq!(syntax_error("SELECT 1; SELECT 2").2, "near \"SELECT\": syntax error");
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{bail, Result};
+use crate::error::{Error, Result};
 
 use crate::parser::token::{Token, TokenType};
 
@@ -11,6 +11,10 @@ pub struct Scanner {
     column: u32,
     current_index: usize,
     start_index: usize,
+
+    // where the token being scanned starts, which is where errors about it point
+    start_line: u32,
+    start_column: u32,
 }
 
 impl From<String> for Scanner {
@@ -31,16 +35,20 @@ impl Scanner {
             tokens: vec![],
             start_index: 0,
             current_index: 0,
+            start_line: 1,
+            start_column: 1,
             source: String::new(),
         }
     }
 
-    pub fn scan(&mut self, input: &String) -> Result<()> {
-        self.source = input.clone();
+    pub fn scan(&mut self, input: &str) -> Result<()> {
+        self.source = input.to_string();
 
         while !self.at_end() {
             // We are at the beginning of the next lexeme.
             self.start_index = self.current_index;
+            self.start_line = self.line;
+            self.start_column = self.column + 1;
 
             self.scan_tokens()?;
         }
@@ -49,16 +57,17 @@ impl Scanner {
             TokenType::EOF,
             "\0".to_string(),
             self.line as u64,
-            self.column as u64,
+            self.column as u64 + 1,
+            self.current_index,
         ));
 
         Ok(())
     }
 
     fn scan_tokens(&mut self) -> Result<()> {
-        let c = self.advance();
+        let c = self.advance().to_string();
 
-        match c {
+        match c.as_str() {
             "(" => {
                 self.add_token(TokenType::LeftParen);
             }
@@ -79,12 +88,23 @@ impl Scanner {
                 self.add_token(TokenType::COMMA);
             }
 
+            "." if Scanner::is_digit(self.peek()) => {
+                self.parse_number()?;
+            }
+
             "." => {
                 self.add_token(TokenType::DOT);
             }
 
             "-" => {
-                self.add_token(TokenType::MINUS);
+                // a comment goes until the end of the line
+                if self.match_char("-") {
+                    while self.peek() != "\n" && !self.at_end() {
+                        self.advance();
+                    }
+                } else {
+                    self.add_token(TokenType::MINUS);
+                }
             }
 
             "+" => {
@@ -99,6 +119,28 @@ impl Scanner {
                 self.add_token(TokenType::STAR);
             }
 
+            "%" => {
+                self.add_token(TokenType::PERCENT);
+            }
+
+            "&" => {
+                self.add_token(TokenType::AMPERSAND);
+            }
+
+            "~" => {
+                self.add_token(TokenType::TILDE);
+            }
+
+            "|" => {
+                let token_type = if self.match_char("|") {
+                    TokenType::CONCAT
+                } else {
+                    TokenType::PIPE
+                };
+
+                self.add_token(token_type);
+            }
+
             // for these s (!, =, <, >) it can be a single char, or it can be followed by = (!=, ==, <=, >=)
             "!" => {
                 let token_type = if self.match_char("=") {
@@ -111,12 +153,18 @@ impl Scanner {
             }
 
             "=" => {
+                self.match_char("=");
+
                 self.add_token(TokenType::EQUAL);
             }
 
             "<" => {
                 let token_type = if self.match_char("=") {
                     TokenType::LessEqual
+                } else if self.match_char(">") {
+                    TokenType::BangEqual
+                } else if self.match_char("<") {
+                    TokenType::LeftShift
                 } else {
                     TokenType::LESS
                 };
@@ -127,6 +175,8 @@ impl Scanner {
             ">" => {
                 let token_type = if self.match_char("=") {
                     TokenType::GreaterEqual
+                } else if self.match_char(">") {
+                    TokenType::RightShift
                 } else {
                     TokenType::GREATER
                 };
@@ -135,7 +185,11 @@ impl Scanner {
             }
 
             "/" => {
-                self.add_token(TokenType::SLASH);
+                if self.match_char("*") {
+                    self.skip_block_comment()?;
+                } else {
+                    self.add_token(TokenType::SLASH);
+                }
             }
 
             "#" => {
@@ -145,34 +199,59 @@ impl Scanner {
                 }
             }
 
+            "'" => {
+                self.parse_quoted("'", TokenType::STRING)?;
+            }
+
             "\"" => {
-                self.parse_string("\"");
+                self.parse_quoted("\"", TokenType::IDENTIFIER)?;
             }
 
-            "'" => {
-                self.parse_string("'");
+            "`" => {
+                self.parse_quoted("`", TokenType::IDENTIFIER)?;
             }
 
-            " " | "\r" | "\t" => {
+            "[" => {
+                self.parse_quoted("]", TokenType::IDENTIFIER)?;
+            }
+
+            " " | "\r" | "\t" | "\n" => {
 
                 // Ignore whitespace.
             }
 
-            "\n" => {
-                self.increment_line();
+            "x" | "X" if self.peek() == "'" => {
+                self.advance();
+
+                self.parse_quoted("'", TokenType::BLOB)?;
+
+                // a blob needs an even number of hex digits, x'abc' is not one
+                let lexeme = &self.source[self.start_index..self.current_index];
+
+                let digits = &lexeme[2..lexeme.len() - 1];
+
+                if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(Error::Syntax {
+                        line: self.start_line as u64,
+                        column: self.start_column as u64,
+                        msg: format!("unrecognized token: \"{}\"", lexeme),
+                    });
+                }
             }
 
             _ => {
-                if Scanner::is_digit(c) {
-                    self.parse_number();
-                } else if Scanner::is_alpha(c) {
+                if Scanner::is_digit(&c) {
+                    self.parse_number()?;
+                } else if Scanner::is_alpha(&c) {
                     self.parse_identifier();
                 } else {
-                    bail!(
-                        "SyntaxError: Unexpected character. at {}:{}",
-                        self.line,
-                        self.column
-                    )
+                    let msg = format!("unrecognized token: \"{}\"", c);
+
+                    return Err(Error::Syntax {
+                        msg,
+                        line: self.start_line as u64,
+                        column: self.start_column as u64,
+                    });
                 }
             }
         };
@@ -194,100 +273,174 @@ impl Scanner {
         self.tokens.push(Token::new(
             token_type,
             text.to_string(),
-            self.line as u64,
-            self.column as u64,
+            self.start_line as u64,
+            self.start_column as u64,
+            self.start_index,
         ));
     }
 
-    fn increment_line(&mut self) {
-        self.column = 1;
+    fn skip_block_comment(&mut self) -> Result<()> {
+        // like sqlite, a comment left open runs to the end of the input
+        while !self.at_end() {
+            if self.advance() == "*" && self.match_char("/") {
+                break;
+            }
+        }
 
-        self.line += 1;
+        Ok(())
     }
 
     fn match_char(&mut self, expected: &str) -> bool {
-        if self.at_end() {
-            return false;
-        };
-
-        if &self.source[self.current_index..self.current_index + 1] != expected {
+        if self.at_end() || self.peek() != expected {
             return false;
         };
 
-        self.column += 1;
-
-        self.current_index += 1;
+        self.advance();
 
         true
     }
 
-    fn peek(&self) -> &str {
-        if self.at_end() {
-            return "\0";
-        };
+    // The helpers below step over whole characters, so that multi-byte UTF-8 input
+    // never slices the source in the middle of a character
+    fn char_at(&self, index: usize) -> &str {
+        match self.source[index..].chars().next() {
+            Some(c) => &self.source[index..index + c.len_utf8()],
+            None => "\0",
+        }
+    }
 
-        &self.source[self.current_index..self.current_index + 1]
+    fn peek(&self) -> &str {
+        self.char_at(self.current_index)
     }
 
     fn peek_next(&self) -> &str {
-        if self.current_index + 1 >= self.source.len() {
+        if self.at_end() {
             return "\0";
         };
 
-        &self.source[self.current_index + 1..self.current_index + 2]
+        self.char_at(self.current_index + self.peek().len())
     }
 
     fn advance(&mut self) -> &str {
-        let character = &self.source[self.current_index..self.current_index + 1];
-
-        self.column += 1;
+        let start = self.current_index;
+        let len = self.peek().len();
+
+        if self.peek() == "\n" {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
 
-        self.current_index += 1;
+        self.current_index += len;
 
-        character
+        &self.source[start..self.current_index]
     }
 
-    fn parse_string(&mut self, quote_type: &str) {
-        self.advance();
-        while (self.peek() != quote_type) && !self.at_end() {
-            if self.peek() == "\n" {
-                self.increment_line()
-            };
+    // Strings, quoted identifiers and blobs run up to the closing quote, where a doubled
+    // quote stands for the quote itself
+    fn parse_quoted(&mut self, closing_quote: &str, token_type: TokenType) -> Result<()> {
+        loop {
+            if self.at_end() {
+                return Err(Error::Syntax {
+                    line: self.start_line as u64,
+                    column: self.start_column as u64,
+                    msg: "unrecognized token: unterminated string".to_string(),
+                });
+            }
 
-            self.advance();
-        }
+            if self.advance() == closing_quote {
+                // a doubled quote is an escaped quote inside the string
+                if self.peek() != closing_quote || closing_quote == "]" {
+                    break;
+                }
 
-        if self.at_end() {
-            return;
+                self.advance();
+            }
         }
 
-        // The closing ".
-        self.advance();
+        self.add_token(token_type);
 
-        self.add_token(TokenType::TEXT);
+        Ok(())
     }
 
-    fn parse_number(&mut self) {
-        while Scanner::is_digit(self.peek()) {
-            self.advance();
-        }
+    // Integers, reals with a fraction and an exponent, and 0x hexadecimal integers
+    fn parse_number(&mut self) -> Result<()> {
+        let lexeme = &self.source[self.start_index..self.current_index];
+
+        let is_hex_digit = |c: &str| c.starts_with(|c: char| c.is_ascii_hexdigit());
 
-        // Look for a fractional part.
-        if self.peek() == "." && Scanner::is_digit(self.peek_next()) {
-            // Consume the "."
+        if lexeme == "0" && matches!(self.peek(), "x" | "X") && is_hex_digit(self.peek_next()) {
             self.advance();
 
-            //noinspection WhileCanBeDoWhile
+            while is_hex_digit(self.peek()) {
+                self.advance();
+            }
+        } else {
+            let has_fraction = lexeme == ".";
+
             while Scanner::is_digit(self.peek()) {
                 self.advance();
             }
+
+            // Look for a fractional part.
+            if self.peek() == "." && !has_fraction {
+                // Consume the "."
+                self.advance();
+
+                //noinspection WhileCanBeDoWhile
+                while Scanner::is_digit(self.peek()) {
+                    self.advance();
+                }
+            }
+
+            let has_exponent = matches!(self.peek(), "e" | "E")
+                && (Scanner::is_digit(self.peek_next())
+                    || (matches!(self.peek_next(), "+" | "-") && Scanner::is_digit(self.char_after_next())));
+
+            if has_exponent {
+                self.advance();
+
+                if matches!(self.peek(), "+" | "-") {
+                    self.advance();
+                }
+
+                while Scanner::is_digit(self.peek()) {
+                    self.advance();
+                }
+            }
+        }
+
+        // 12abc is one bad token, not a number followed by a name
+        if Scanner::is_alpha(self.peek()) {
+            while Scanner::is_alpha_numeric(self.peek()) {
+                self.advance();
+            }
+
+            return Err(Error::Syntax {
+                line: self.start_line as u64,
+                column: self.start_column as u64,
+                msg: format!("unrecognized token: \"{}\"", &self.source[self.start_index..self.current_index]),
+            });
         }
 
         self.add_token(TokenType::NUMBER);
+
+        Ok(())
+    }
+
+    fn char_after_next(&self) -> &str {
+        let next = self.current_index + self.peek().len();
+
+        if next >= self.source.len() {
+            return "\0";
+        }
+
+        self.char_at(next + self.char_at(next).len())
     }
 
     pub fn is_digit(st: &str) -> bool {
-        st.chars().all(|c| c >= '0' && c <= '9')
+        st.chars().all(|c| c.is_ascii_digit())
     }
 
     fn parse_identifier(&mut self) {
@@ -302,9 +455,10 @@ impl Scanner {
         self.add_token(token_type);
     }
 
+    // Letters outside ASCII may be part of names too
     pub fn is_alpha(st: &str) -> bool {
         st.chars()
-            .all(|c| (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_')
+            .all(|c| c.is_ascii_alphabetic() || c == '_' || (!c.is_ascii() && c != '\0'))
     }
 
     pub fn is_alpha_numeric(c: &str) -> bool {
@@ -1,6 +1,6 @@
-use crate::utils::convert_u32_to_bytes;
-
-#[derive(Debug, PartialEq, Eq, Clone)]
+// keywords are named the way they are written in SQL
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TokenType {
     CREATE,
     INSERT,
@@ -8,6 +8,7 @@ pub enum TokenType {
     UPDATE,
     DELETE,
     ALTER,
+    DROP,
     FROM,
     WHERE,
     ORDER,
@@ -18,34 +19,77 @@ pub enum TokenType {
     VALUES,
     JOIN,
     NATURAL,
+    LEFT,
+    RIGHT,
     INNER,
     OUTER,
+    CROSS,
     FULL,
     ON,
+    USING,
     AND,
     OR,
     BETWEEN,
     AS,
     LIMIT,
+    OFFSET,
+    DISTINCT,
+    ALL,
+    UNION,
+    INTERSECT,
+    EXCEPT,
+    WITH,
+    RECURSIVE,
+    ASC,
+    DESC,
 
     TABLE,
+    INDEX,
+    VIEW,
+    TRIGGER,
+    TEMP,
+    IF,
     SET,
     PRIMARY,
     KEY,
     AUTOINCREMENT,
     UNIQUE,
     FOREIGN,
-    ALLOW,
+    REFERENCES,
+    CONSTRAINT,
+    CHECK,
+    DEFAULT,
+    COLLATE,
+    WITHOUT,
+    REPLACE,
+
     NOT,
     NULL,
-    INTEGER,
-    TEXT,
-    BOOLEAN,
-    BLOB,
+    IS,
+    ISNULL,
+    NOTNULL,
+    IN,
+    LIKE,
+    GLOB,
+    REGEXP,
+    MATCH,
+    ESCAPE,
+    EXISTS,
+    CASE,
+    WHEN,
+    THEN,
+    ELSE,
+    END,
+    CAST,
 
+    // literals: 'text', 42 or 1.5e3, x'0a0b'
     STRING,
     NUMBER,
+    BLOB,
+
+    // a bare word, or a name in "double quotes", `backticks` or [brackets]
     IDENTIFIER,
+
     LeftParen,
     RightParen,
     LeftBrace,
@@ -56,6 +100,14 @@ pub enum TokenType {
     PLUS,
     SEMICOLON,
     STAR,
+    SLASH,
+    PERCENT,
+    CONCAT,
+    AMPERSAND,
+    PIPE,
+    TILDE,
+    LeftShift,
+    RightShift,
     BangEqual,
     BANG,
     EQUAL,
@@ -63,96 +115,174 @@ pub enum TokenType {
     LESS,
     GreaterEqual,
     GREATER,
-    SLASH,
-    HASH,
 
-    COUNT,
+    PRAGMA,
+    VACUUM,
     EOF,
 }
 
+impl TokenType {
+    // Keywords sqlite lets double as names, so a column can be called `key` or `left`
+    pub fn is_fallback_keyword(&self) -> bool {
+        matches!(
+            self,
+            TokenType::KEY
+                | TokenType::BY
+                | TokenType::ASC
+                | TokenType::DESC
+                | TokenType::END
+                | TokenType::IF
+                | TokenType::TEMP
+                | TokenType::VIEW
+                | TokenType::TRIGGER
+                | TokenType::WITH
+                | TokenType::RECURSIVE
+                | TokenType::WITHOUT
+                | TokenType::REPLACE
+                | TokenType::OFFSET
+                | TokenType::LIKE
+                | TokenType::GLOB
+                | TokenType::REGEXP
+                | TokenType::MATCH
+                | TokenType::CAST
+                | TokenType::PRAGMA
+                | TokenType::VACUUM
+                | TokenType::NATURAL
+                | TokenType::LEFT
+                | TokenType::RIGHT
+                | TokenType::INNER
+                | TokenType::OUTER
+                | TokenType::CROSS
+                | TokenType::FULL
+        )
+    }
+
+    pub fn is_join_keyword(&self) -> bool {
+        matches!(
+            self,
+            TokenType::NATURAL | TokenType::LEFT | TokenType::RIGHT | TokenType::INNER | TokenType::OUTER | TokenType::CROSS | TokenType::FULL
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub lexeme: String,
     pub token_type: TokenType,
     pub line: u64,
     pub column: u64,
+
+    // where the lexeme starts in the source, in bytes
+    pub offset: usize,
 }
 
 impl From<&str> for TokenType {
     fn from(value: &str) -> Self {
-        TokenType::from(value.to_string())
-    }
-}
-
-impl From<String> for TokenType {
-    fn from(value: String) -> Self {
         match value.to_uppercase().as_str() {
             "CREATE" => TokenType::CREATE,
+            "INSERT" => TokenType::INSERT,
             "SELECT" => TokenType::SELECT,
             "UPDATE" => TokenType::UPDATE,
             "DELETE" => TokenType::DELETE,
             "ALTER" => TokenType::ALTER,
+            "DROP" => TokenType::DROP,
             "FROM" => TokenType::FROM,
             "WHERE" => TokenType::WHERE,
             "ORDER" => TokenType::ORDER,
             "GROUP" => TokenType::GROUP,
             "BY" => TokenType::BY,
             "HAVING" => TokenType::HAVING,
+            "INTO" => TokenType::INTO,
+            "VALUES" => TokenType::VALUES,
             "JOIN" => TokenType::JOIN,
             "NATURAL" => TokenType::NATURAL,
+            "LEFT" => TokenType::LEFT,
+            "RIGHT" => TokenType::RIGHT,
             "INNER" => TokenType::INNER,
             "OUTER" => TokenType::OUTER,
+            "CROSS" => TokenType::CROSS,
             "FULL" => TokenType::FULL,
             "ON" => TokenType::ON,
+            "USING" => TokenType::USING,
             "AND" => TokenType::AND,
             "OR" => TokenType::OR,
             "BETWEEN" => TokenType::BETWEEN,
             "AS" => TokenType::AS,
-            "STRING" => TokenType::STRING,
-            "NUMBER" => TokenType::NUMBER,
             "LIMIT" => TokenType::LIMIT,
+            "OFFSET" => TokenType::OFFSET,
+            "DISTINCT" => TokenType::DISTINCT,
+            "ALL" => TokenType::ALL,
+            "UNION" => TokenType::UNION,
+            "INTERSECT" => TokenType::INTERSECT,
+            "EXCEPT" => TokenType::EXCEPT,
+            "WITH" => TokenType::WITH,
+            "RECURSIVE" => TokenType::RECURSIVE,
+            "ASC" => TokenType::ASC,
+            "DESC" => TokenType::DESC,
             "TABLE" => TokenType::TABLE,
+            "INDEX" => TokenType::INDEX,
+            "VIEW" => TokenType::VIEW,
+            "TRIGGER" => TokenType::TRIGGER,
+            "TEMP" | "TEMPORARY" => TokenType::TEMP,
+            "IF" => TokenType::IF,
             "SET" => TokenType::SET,
             "PRIMARY" => TokenType::PRIMARY,
             "KEY" => TokenType::KEY,
             "AUTOINCREMENT" => TokenType::AUTOINCREMENT,
             "UNIQUE" => TokenType::UNIQUE,
             "FOREIGN" => TokenType::FOREIGN,
-            "NULL" => TokenType::NULL,
-            "INTEGER" => TokenType::INTEGER,
-            "TEXT" => TokenType::TEXT,
-            "BOOLEAN" => TokenType::BOOLEAN,
-            "BLOB" => TokenType::BLOB,
-            "ALLOW" => TokenType::ALLOW,
-            "COUNT" => TokenType::COUNT,
+            "REFERENCES" => TokenType::REFERENCES,
+            "CONSTRAINT" => TokenType::CONSTRAINT,
+            "CHECK" => TokenType::CHECK,
+            "DEFAULT" => TokenType::DEFAULT,
+            "COLLATE" => TokenType::COLLATE,
+            "WITHOUT" => TokenType::WITHOUT,
+            "REPLACE" => TokenType::REPLACE,
             "NOT" => TokenType::NOT,
+            "NULL" => TokenType::NULL,
+            "IS" => TokenType::IS,
+            "ISNULL" => TokenType::ISNULL,
+            "NOTNULL" => TokenType::NOTNULL,
+            "IN" => TokenType::IN,
+            "LIKE" => TokenType::LIKE,
+            "GLOB" => TokenType::GLOB,
+            "REGEXP" => TokenType::REGEXP,
+            "MATCH" => TokenType::MATCH,
+            "ESCAPE" => TokenType::ESCAPE,
+            "EXISTS" => TokenType::EXISTS,
+            "CASE" => TokenType::CASE,
+            "WHEN" => TokenType::WHEN,
+            "THEN" => TokenType::THEN,
+            "ELSE" => TokenType::ELSE,
+            "END" => TokenType::END,
+            "CAST" => TokenType::CAST,
+            "PRAGMA" => TokenType::PRAGMA,
+            "VACUUM" => TokenType::VACUUM,
             _ => TokenType::IDENTIFIER,
         }
     }
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, line: u64, column: u64) -> Self {
+    pub fn new(token_type: TokenType, lexeme: String, line: u64, column: u64, offset: usize) -> Self {
         Self {
             line,
             column,
+            offset,
             lexeme,
             token_type,
         }
     }
 
-    pub fn get_lexeme_bytes(&self) -> Vec<u8> {
-        match self.token_type {
-            TokenType::TEXT => self.lexeme.as_str()[1..self.lexeme.len() - 1].as_bytes().to_vec(),
-
-            TokenType::NUMBER => convert_u32_to_bytes(
-                u32::from_str_radix(&self.lexeme, 10)
-                    .expect(format!("Could not convert {} to bytes", &self.lexeme)
-                        .as_str()
-                    )
-            ).to_vec(),
+    // Whether the lexeme is this word, for the words that only mean something in one spot
+    // of the grammar, like ROWID in WITHOUT ROWID, and are left as identifiers by the scanner
+    pub fn is_word(&self, word: &str) -> bool {
+        self.token_type == TokenType::IDENTIFIER && self.lexeme.eq_ignore_ascii_case(word)
+    }
 
-            _ => self.lexeme.as_bytes().to_vec(),
-        }
+    // Whether the token is a keyword as written, like ON or DELETE, rather than a name, a
+    // literal or an operator
+    pub fn is_keyword(&self) -> bool {
+        self.token_type != TokenType::IDENTIFIER && TokenType::from(self.lexeme.as_str()) == self.token_type
     }
 }
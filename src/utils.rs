@@ -1,26 +1,25 @@
-use anyhow::{anyhow, Result};
+use crate::parser::token::TokenType;
+use crate::error::{Error, Result};
 
 pub fn parse_varint(bytes: &[u8]) -> Result<(u64, &[u8], usize)> {
     let mut result = 0;
-    let mut shift = 0;
     let mut bytes_read = 0;
     let mut bs = bytes.iter().copied();
 
     loop {
         let byte = bs
             .next()
-            .ok_or_else(|| anyhow!("Unexpected end of bytes"))?;
+            .ok_or_else(|| Error::corrupt(0, "varint runs past the end of the buffer"))?;
 
         bytes_read += 1;
 
+        // the ninth byte contributes all of its 8 bits
         if bytes_read == 9 {
-            result = (result << shift) | u64::from(byte);
+            result = (result << 8) | u64::from(byte);
             break;
         }
 
-        result = (result << shift) | u64::from(byte & 0b0111_1111);
-
-        shift += 7;
+        result = (result << 7) | u64::from(byte & 0b0111_1111);
 
         if byte & 0b1000_0000 == 0 {
             break;
@@ -28,14 +27,161 @@ pub fn parse_varint(bytes: &[u8]) -> Result<(u64, &[u8], usize)> {
     }
 
     Ok((result, &bytes[bytes_read..], bytes_read))
-    // u32::from_be_bytes([num_vec[6], num_vec[7], num_vec[8], num_vec[9]])
 }
 
-pub fn convert_u32_to_bytes(x: u32) -> [u8; 4] {
-    let b1: u8 = ((x >> 24) & 0xff) as u8;
-    let b2: u8 = ((x >> 16) & 0xff) as u8;
-    let b3: u8 = ((x >> 8) & 0xff) as u8;
-    let b4: u8 = (x & 0xff) as u8;
+pub fn encode_varint(value: u64) -> Vec<u8> {
+    // values that need more than 56 bits use the 9 byte form, where the last byte is stored whole
+    if value > 0x00ff_ffff_ffff_ffff {
+        let mut bytes = vec![0u8; 9];
+
+        bytes[8] = value as u8;
+
+        let mut rest = value >> 8;
+
+        for i in (0..8).rev() {
+            bytes[i] = (rest & 0b0111_1111) as u8 | 0b1000_0000;
+            rest >>= 7;
+        }
+
+        return bytes;
+    }
+
+    let mut bytes = vec![];
+    let mut rest = value;
+
+    loop {
+        bytes.push((rest & 0b0111_1111) as u8);
+        rest >>= 7;
+
+        if rest == 0 {
+            break;
+        }
+    }
+
+    bytes.reverse();
+
+    let last = bytes.len() - 1;
+
+    for byte in &mut bytes[..last] {
+        *byte |= 0b1000_0000;
+    }
+
+    bytes
+}
+
+pub fn varint_len(value: u64) -> usize {
+    encode_varint(value).len()
+}
+
+// Wraps an identifier in double quotes unless it is a plain word that is not a keyword
+pub fn quote_identifier(name: &str) -> String {
+    let is_plain = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && TokenType::from(name) == TokenType::IDENTIFIER;
+
+    if is_plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+// Strips the quotes around an identifier written as "name", `name`, [name] or 'name'
+pub fn unquote_identifier(name: &str) -> String {
+    let quotes = [('"', '"'), ('`', '`'), ('[', ']'), ('\'', '\'')];
+
+    for (open, close) in quotes {
+        if name.len() >= 2 && name.starts_with(open) && name.ends_with(close) {
+            let inner = &name[1..name.len() - 1];
+
+            return match open {
+                '"' | '`' | '\'' => inner.replace(&format!("{open}{open}"), &open.to_string()),
+                _ => inner.to_string(),
+            };
+        }
+    }
+
+    name.to_string()
+}
+
+// Splits the arguments of a dot-command on whitespace. Arguments can be wrapped in single
+// or double quotes, and backslash escapes (\t, \n, \\, ...) are resolved inside double quotes
+// and bare words, like the sqlite3 shell does.
+pub fn split_command_args(line: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let Some(first) = chars.next() else {
+            break;
+        };
+
+        let mut arg = String::new();
+
+        if first == '\'' {
+            for c in chars.by_ref() {
+                if c == '\'' {
+                    break;
+                }
+
+                arg.push(c);
+            }
+        } else {
+            let quote = if first == '"' { Some('"') } else { None };
+
+            let mut next = if quote.is_some() { chars.next() } else { Some(first) };
+
+            while let Some(c) = next {
+                if Some(c) == quote || (quote.is_none() && c.is_whitespace()) {
+                    break;
+                }
+
+                if c == '\\' {
+                    match chars.next() {
+                        Some('t') => arg.push('\t'),
+                        Some('n') => arg.push('\n'),
+                        Some('r') => arg.push('\r'),
+                        Some(other) => arg.push(other),
+                        None => arg.push('\\'),
+                    }
+                } else {
+                    arg.push(c);
+                }
+
+                next = chars.next();
+            }
+        }
+
+        args.push(arg);
+    }
+
+    args
+}
+
+// Matches text against an SQL LIKE pattern: % matches any sequence, _ any single character,
+// and ASCII letters compare case-insensitively
+pub fn like_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern.first() {
+            None => text.is_empty(),
+
+            Some('%') => (0..=text.len()).any(|skip| matches(&pattern[1..], &text[skip..])),
+
+            Some('_') => !text.is_empty() && matches(&pattern[1..], &text[1..]),
+
+            Some(c) => {
+                !text.is_empty() && c.eq_ignore_ascii_case(&text[0]) && matches(&pattern[1..], &text[1..])
+            }
+        }
+    }
 
-    [b1, b2, b3, b4].to_owned()
+    matches(&pattern, &text)
 }
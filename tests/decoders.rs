@@ -0,0 +1,131 @@
+use codecrafters_sqlite::fuzzing::{self, PAGE_SIZE};
+use codecrafters_sqlite::Value;
+use proptest::prelude::*;
+
+// A real database to damage, so the mutations start from pages that decode
+const SAMPLE: &[u8] = include_bytes!("../sample.db");
+
+// Flag bytes of the four b-tree page kinds
+const BTREE_PAGE_TYPES: [u8; 4] = [0x02, 0x05, 0x0a, 0x0d];
+
+fn value() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(Value::Null),
+        any::<i64>().prop_map(Value::Integer),
+        // NaN is stored as NULL and would not compare equal anyway
+        any::<f64>().prop_filter("NaN", |f| !f.is_nan()).prop_map(Value::Real),
+        ".{0,40}".prop_map(Value::Text),
+        prop::collection::vec(any::<u8>(), 0..40).prop_map(Value::Blob),
+    ]
+}
+
+// A b-tree page header with a plausible cell count in front of random bytes, which gets
+// the decoders past the first checks far more often than random bytes alone
+fn btree_page() -> impl Strategy<Value = Vec<u8>> {
+    (
+        prop::sample::select(&BTREE_PAGE_TYPES[..]),
+        0u16..40,
+        prop::collection::vec(any::<u8>(), PAGE_SIZE),
+    )
+        .prop_map(|(page_type, cells, mut page)| {
+            page[0] = page_type;
+            page[3..5].copy_from_slice(&cells.to_be_bytes());
+
+            page
+        })
+}
+
+proptest! {
+    #[test]
+    fn varints_round_trip(value in any::<u64>()) {
+        let encoded = fuzzing::encode_varint(value);
+
+        prop_assert!(encoded.len() <= 9);
+        prop_assert_eq!(fuzzing::parse_varint(&encoded).unwrap(), (value, encoded.len()));
+    }
+
+    #[test]
+    fn varints_ignore_trailing_bytes(value in any::<u64>(), rest in prop::collection::vec(any::<u8>(), 0..16)) {
+        let mut encoded = fuzzing::encode_varint(value);
+
+        let len = encoded.len();
+
+        encoded.extend(rest);
+
+        prop_assert_eq!(fuzzing::parse_varint(&encoded).unwrap(), (value, len));
+    }
+
+    #[test]
+    fn varints_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..12)) {
+        if let Ok((_, len)) = fuzzing::parse_varint(&bytes) {
+            prop_assert!(len <= bytes.len().min(9));
+        }
+    }
+
+    #[test]
+    fn records_round_trip(values in prop::collection::vec(value(), 0..20)) {
+        let record = fuzzing::encode_record(&values);
+
+        prop_assert_eq!(fuzzing::decode_record(&record).unwrap(), values);
+    }
+
+    #[test]
+    fn records_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..64)) {
+        let _ = fuzzing::decode_record(&bytes);
+    }
+
+    #[test]
+    fn truncated_records_never_panic(values in prop::collection::vec(value(), 1..10), cut in any::<prop::sample::Index>()) {
+        let record = fuzzing::encode_record(&values);
+
+        let _ = fuzzing::decode_record(&record[..cut.index(record.len())]);
+    }
+
+    #[test]
+    fn headers_never_panic(mut bytes in prop::collection::vec(any::<u8>(), 0..120), magic in any::<bool>()) {
+        if magic && bytes.len() >= 16 {
+            bytes[..16].copy_from_slice(b"SQLite format 3\0");
+        }
+
+        let _ = fuzzing::decode_header(&bytes);
+    }
+
+    #[test]
+    fn cells_never_panic(page_type in prop::sample::select(&BTREE_PAGE_TYPES[..]), bytes in prop::collection::vec(any::<u8>(), 0..2 * PAGE_SIZE)) {
+        let _ = fuzzing::decode_cell(page_type, &bytes);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn random_pages_never_panic(pages in prop::collection::vec(any::<u8>(), 0..3 * PAGE_SIZE)) {
+        fuzzing::exercise_database(fuzzing::database_with_pages(&pages));
+    }
+
+    #[test]
+    fn random_btree_pages_never_panic(table in btree_page(), index in btree_page(), rest in prop::collection::vec(any::<u8>(), 0..2 * PAGE_SIZE)) {
+        let pages = [table, index, rest].concat();
+
+        fuzzing::exercise_database(fuzzing::database_with_pages(&pages));
+    }
+
+    #[test]
+    fn damaged_databases_never_panic(changes in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..16)) {
+        let mut image = SAMPLE.to_vec();
+
+        for (offset, byte) in changes {
+            let offset = offset.index(image.len());
+
+            image[offset] = byte;
+        }
+
+        fuzzing::exercise_database(image);
+    }
+
+    #[test]
+    fn truncated_databases_never_panic(cut in any::<prop::sample::Index>()) {
+        fuzzing::exercise_database(SAMPLE[..cut.index(SAMPLE.len())].to_vec());
+    }
+}
@@ -0,0 +1,151 @@
+use codecrafters_sqlite::Database;
+use std::path::PathBuf;
+
+// t(id INTEGER PRIMARY KEY, a TEXT) with the index ti on t(a), rows 1, 500 and 2000, on
+// 512 byte pages so that imports split pages at every level
+const INDEXED: &[u8] = include_bytes!("indexed.db");
+
+// On 1024 byte pages with auto_vacuum=incremental, t(id INTEGER PRIMARY KEY, a TEXT) with
+// the index ta on t(a), 150 rows, and 13 of its 75 pages free
+const INCREMENTAL: &[u8] = include_bytes!("vacuum.db");
+
+// A CSV file of the test's own, removed when dropped
+struct Csv(PathBuf);
+
+impl Csv {
+    fn new(name: &str, content: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("import-{}-{name}.csv", std::process::id()));
+
+        std::fs::write(&path, content).unwrap();
+
+        Csv(path)
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for Csv {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn check(db: &Database) {
+    assert_eq!(db.integrity_check(100, false, None).unwrap(), vec!["ok".to_string()]);
+}
+
+// The page count and the number of free pages in the header, which must agree with the size
+// of the file
+fn page_counts(db: &Database) -> (u32, u32) {
+    let image = db.serialize().unwrap();
+
+    let field = |offset: usize| u32::from_be_bytes(image[offset..offset + 4].try_into().unwrap());
+
+    let page_count = field(28);
+
+    assert_eq!(image.len(), page_count as usize * usize::from(u16::from_be_bytes([image[16], image[17]])));
+
+    (page_count, field(36))
+}
+
+// Rows 1000 and up, with text from empty to spilling over two overflow pages
+fn spilling_rows(count: usize) -> String {
+    let mut content = "id,a\n".to_string();
+
+    for i in 0..count {
+        content.push_str(&format!("{},{}{i}\n", 1000 + i, "y".repeat(i % 5 * 600)));
+    }
+
+    content
+}
+
+#[test]
+fn import_into_an_indexed_table_keeps_the_index() {
+    let mut db = Database::deserialize(INDEXED.to_vec()).unwrap();
+
+    // rowids in no particular order, some between the existing ones, and text long enough
+    // to spill into overflow pages
+    let mut content = "id,a\n".to_string();
+
+    for i in 0..600 {
+        let id = (i * 389) % 600 + 1000;
+
+        content.push_str(&format!("{id},{}\n", "x".repeat(id % 7 * 60) + &id.to_string()));
+    }
+
+    let csv = Csv::new("indexed", &content);
+
+    assert_eq!(db.import(csv.path(), "t", ',', 1).unwrap(), 600);
+
+    check(&db);
+
+    assert_eq!(db.count_records("t").unwrap(), 603);
+}
+
+#[test]
+fn import_leaves_out_rows_whose_rowid_is_taken() {
+    let mut db = Database::deserialize(INDEXED.to_vec()).unwrap();
+
+    let csv = Csv::new("duplicates", "id,a\n7,seven\n500,taken\n3,three\n7,again\n,next\n");
+
+    assert_eq!(db.import(csv.path(), "t", ',', 1).unwrap(), 3);
+
+    check(&db);
+
+    assert_eq!(db.count_records("t").unwrap(), 6);
+}
+
+// With auto_vacuum=full every commit gives the free pages back, so the import leaves none
+// and the pointer map keeps up with the pages it moved
+#[test]
+fn import_with_full_auto_vacuum_leaves_no_free_pages() {
+    let mut image = INCREMENTAL.to_vec();
+
+    // the same database with auto_vacuum=full
+    image[64..68].fill(0);
+
+    let mut db = Database::deserialize(image).unwrap();
+
+    assert_eq!(page_counts(&db), (75, 13));
+
+    let csv = Csv::new("full", &spilling_rows(40));
+
+    assert_eq!(db.import(csv.path(), "t", ',', 1).unwrap(), 40);
+
+    check(&db);
+
+    assert_eq!(page_counts(&db).1, 0);
+    assert_eq!(db.count_records("t").unwrap(), 190);
+}
+
+// With auto_vacuum=incremental the import reuses free pages, and what is left of them goes
+// only when incremental_vacuum asks for it
+#[test]
+fn import_with_incremental_auto_vacuum_then_incremental_vacuum() {
+    let mut db = Database::deserialize(INCREMENTAL.to_vec()).unwrap();
+
+    let csv = Csv::new("incremental", &spilling_rows(4));
+
+    assert_eq!(db.import(csv.path(), "t", ',', 1).unwrap(), 4);
+
+    check(&db);
+
+    let (page_count, free_pages) = page_counts(&db);
+
+    assert!((1..13).contains(&free_pages), "{free_pages} free pages");
+
+    assert_eq!(db.incremental_vacuum(Some(1)).unwrap(), 1);
+
+    check(&db);
+
+    assert_eq!(page_counts(&db), (page_count - 1, free_pages - 1));
+
+    assert_eq!(db.incremental_vacuum(None).unwrap(), free_pages - 1);
+
+    check(&db);
+
+    assert_eq!(page_counts(&db), (page_count - free_pages, 0));
+    assert_eq!(db.count_records("t").unwrap(), 154);
+}
@@ -0,0 +1,101 @@
+use codecrafters_sqlite::Database;
+
+// On 512 byte pages with auto_vacuum=incremental, t(a INTEGER NOT NULL, b TEXT) on page 3
+// with the rows (1,'apple'), (1,'Apple'), (1,'banana') and (1,'cherry'), the index
+// tb ON t(b COLLATE NOCASE) on page 4, an empty table on page 5 and two free pages, 6 and 7
+const FIXTURE: &[u8] = include_bytes!("integrity.db");
+
+const TABLE: usize = 2 * 512;
+
+const INDEX: usize = 3 * 512;
+
+// where the cells of two rows and of an index entry start
+const BANANA_ROW: usize = TABLE + 0x1e1;
+const CHERRY_ROW: usize = TABLE + 0x1d6;
+const CHERRY_ENTRY: usize = INDEX + 0x1d7;
+
+// What integrity_check says of the fixture with `patches` written over it, its messages one
+// per line. Every expected result is what sqlite3 reports for the same file.
+fn check(patches: &[(usize, &[u8])]) -> String {
+    let mut image = FIXTURE.to_vec();
+
+    for (offset, bytes) in patches {
+        image[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    let db = Database::deserialize(image).unwrap();
+
+    db.integrity_check(100, false, None).unwrap().join("\n")
+}
+
+// The fixture with the text `from` in it replaced by `to`, of the same length
+fn replace(from: &str, to: &str) -> (usize, Vec<u8>) {
+    let offset = FIXTURE.windows(from.len()).position(|window| window == from.as_bytes()).unwrap();
+
+    (offset, to.as_bytes().to_vec())
+}
+
+#[test]
+fn the_fixture_is_ok() {
+    assert_eq!(check(&[]), "ok");
+}
+
+#[test]
+fn rowids_out_of_order() {
+    assert_eq!(
+        check(&[(BANANA_ROW + 1, &[9])]),
+        "*** in database main ***\nTree 3 page 3 cell 2: Rowid 9 out of order\nrow 3 missing from index tb"
+    );
+}
+
+#[test]
+fn pages_never_used() {
+    // the header forgets the freelist
+    assert_eq!(check(&[(32, &[0; 8])]), "*** in database main ***\nPage 6: never used\nPage 7: never used");
+}
+
+#[test]
+fn bad_pointer_map_entries() {
+    // the entry of the root page 3 gets a parent
+    assert_eq!(
+        check(&[(512 + 4, &[1])]),
+        "*** in database main ***\nBad ptr map entry key=3 expected=(1,0) got=(1,1)"
+    );
+}
+
+#[test]
+fn wrong_number_of_index_entries() {
+    // the index page loses its last cell, which is left as fragmented space
+    assert_eq!(
+        check(&[(INDEX + 4, &[3])]),
+        "*** in database main ***\nFragmentation of 11 bytes reported as 0 on page 4\n\
+         wrong # of entries in index tb\nrow 4 missing from index tb"
+    );
+}
+
+#[test]
+fn rows_missing_from_an_index() {
+    assert_eq!(check(&[(BANANA_ROW + 5, b"banane")]), "row 3 missing from index tb");
+}
+
+// Entries that cannot be decoded count as missing, the rest are still checked
+#[test]
+fn damaged_index_entries_are_missing() {
+    assert_eq!(
+        check(&[(CHERRY_ENTRY + 3, &[0x7f]), (BANANA_ROW + 5, b"banane")]),
+        "row 3 missing from index tb\nrow 4 missing from index tb"
+    );
+}
+
+#[test]
+fn null_values_in_not_null_columns() {
+    assert_eq!(check(&[(CHERRY_ROW + 3, &[0])]), "NULL value in t.a");
+}
+
+// 'apple' and 'Apple' are the same under the index's collation
+#[test]
+fn non_unique_entries() {
+    let (offset, sql) = replace("CREATE INDEX tb ON t(b        COLLATE", "CREATE UNIQUE INDEX tb ON t(b COLLATE");
+
+    assert_eq!(check(&[(offset, &sql)]), "non-unique entry in index tb");
+}
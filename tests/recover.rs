@@ -0,0 +1,74 @@
+use codecrafters_sqlite::Database;
+
+// On 512 byte pages, t(a INTEGER, b TEXT) with rows 1 to 20 under the interior page 2,
+// rows 1 to 10 on the leaf page 3 and 11 to 20 on page 4, and u(x) with the row 'kept' on
+// page 5
+const TREE: &[u8] = include_bytes!("tree.db");
+
+const PAGE_SIZE: usize = 512;
+
+// The script .recover writes for the fixture with some of its bytes zeroed
+fn recover(zeroed: std::ops::Range<usize>) -> String {
+    let mut image = TREE.to_vec();
+
+    image[zeroed].fill(0);
+
+    let db = Database::deserialize(image).unwrap();
+
+    let mut out = vec![];
+
+    db.recover(&mut out).unwrap();
+
+    String::from_utf8(out).unwrap()
+}
+
+// The rows put in lost_and_found from a page, under the root page they are taken to belong to
+fn lost(script: &str, root_page: u32, page: u32) -> usize {
+    let prefix = format!("INSERT INTO lost_and_found VALUES({root_page},{page},");
+
+    script.lines().filter(|line| line.starts_with(&prefix)).count()
+}
+
+// Without a readable schema no table is known, and every row goes to lost_and_found under
+// the root of the b-tree it was found in
+#[test]
+fn rows_are_lost_and_found_without_the_schema() {
+    let script = recover(100..PAGE_SIZE);
+
+    assert!(!script.contains("CREATE TABLE t("), "{script}");
+    let table = "CREATE TABLE lost_and_found(rootpgno INTEGER, pgno INTEGER, nfield INTEGER, id INTEGER, c0, c1);";
+
+    assert!(script.contains(table), "{script}");
+
+    assert_eq!(lost(&script, 2, 3), 10);
+    assert_eq!(lost(&script, 2, 4), 10);
+    assert!(script.contains("INSERT INTO lost_and_found VALUES(5,5,1,1,'kept',NULL);"), "{script}");
+    assert!(script.contains(&format!("INSERT INTO lost_and_found VALUES(2,4,2,20,20,'{}');", "t".repeat(40))));
+}
+
+// Leaves cut off from their table by a damaged interior page are each their own root, and
+// the tables the schema still names are rebuilt with what can be found of them
+#[test]
+fn orphaned_leaves_are_lost_and_found() {
+    let script = recover(PAGE_SIZE..2 * PAGE_SIZE);
+
+    assert!(script.contains("CREATE TABLE t(a INTEGER, b TEXT);"), "{script}");
+    assert!(!script.contains("INSERT OR IGNORE INTO t("), "{script}");
+    assert!(script.contains("INSERT OR IGNORE INTO u(_rowid_,x) VALUES(1,'kept');"), "{script}");
+
+    assert_eq!(lost(&script, 3, 3), 10);
+    assert_eq!(lost(&script, 4, 4), 10);
+    assert_eq!(script.matches("INSERT INTO lost_and_found").count(), 20);
+}
+
+// A damaged first page leaves the other commands with an error rather than failing to open
+#[test]
+fn a_damaged_schema_page_still_opens() {
+    let mut image = TREE.to_vec();
+
+    image[100..PAGE_SIZE].fill(0);
+
+    let mut db = Database::deserialize(image).unwrap();
+
+    assert!(db.execute_command(".tables").is_err());
+}
@@ -0,0 +1,119 @@
+use codecrafters_sqlite::{Connection, Value};
+
+// t(id INTEGER PRIMARY KEY, a TEXT) with the rows (1, 'one'), (500, 'five hundred') and
+// (2000, 'two thousand')
+const INDEXED: &[u8] = include_bytes!("indexed.db");
+
+fn rows(sql: &str) -> Vec<Vec<Value>> {
+    let connection = Connection::deserialize(INDEXED.to_vec()).unwrap();
+
+    let mut statement = connection.prepare(sql).unwrap();
+
+    let rows = statement.query().unwrap();
+
+    rows.map(|row| row.into_values()).collect()
+}
+
+fn integers(rows: &[&[i64]]) -> Vec<Vec<Value>> {
+    rows.iter().map(|row| row.iter().map(|value| Value::Integer(*value)).collect()).collect()
+}
+
+// A SELECT stops reading rows once it has those its LIMIT keeps, which must not change
+// which rows those are
+#[test]
+fn limit_keeps_the_same_rows() {
+    assert_eq!(rows("SELECT id FROM t WHERE a > 'o' LIMIT 1 OFFSET 1"), integers(&[&[2000]]));
+    assert_eq!(rows("SELECT DISTINCT id > 1 FROM t LIMIT 2"), integers(&[&[0], &[1]]));
+    assert_eq!(rows("SELECT id FROM t ORDER BY a LIMIT 1"), integers(&[&[500]]));
+    assert_eq!(rows("SELECT id FROM t WHERE id > 1 UNION SELECT 1 LIMIT 2"), integers(&[&[1], &[500]]));
+    assert_eq!(rows("SELECT count(*) FROM t WHERE id > 1 LIMIT 1"), integers(&[&[2]]));
+    assert_eq!(rows("SELECT id FROM t LIMIT 0"), integers(&[]));
+}
+
+// EXISTS and a subquery used as a value read one row of the subquery, IN all of them
+#[test]
+fn subqueries_read_the_rows_they_need() {
+    let sql = "SELECT id, (SELECT t2.id FROM t t2 WHERE t2.id > t.id) FROM t LIMIT 2";
+
+    assert_eq!(rows(sql), integers(&[&[1, 500], &[500, 2000]]));
+
+    let sql = "SELECT id FROM t WHERE NOT EXISTS (SELECT 1 FROM t t2 WHERE t2.id > t.id)";
+
+    assert_eq!(rows(sql), integers(&[&[2000]]));
+
+    let sql = "SELECT id FROM t WHERE id IN (SELECT t2.id FROM t t2 WHERE t2.id > 1 LIMIT 5)";
+
+    assert_eq!(rows(sql), integers(&[&[500], &[2000]]));
+}
+
+// A recursive common table expression makes its rows as they are read, so whatever stops
+// reading them ends a recursion that would go on forever
+#[test]
+fn limit_ends_a_recursion() {
+    let sql = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT x FROM c LIMIT 3";
+
+    assert_eq!(rows(sql), integers(&[&[1], &[2], &[3]]));
+
+    let sql = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 5 OFFSET 2) \
+               SELECT x * 2 FROM c LIMIT 2 OFFSET 1";
+
+    assert_eq!(rows(sql), integers(&[&[8], &[10]]));
+
+    // rows cut short by one query are not kept for the others
+    let sql = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 4) \
+               SELECT (SELECT count(*) FROM c), x FROM c LIMIT 2";
+
+    assert_eq!(rows(sql), integers(&[&[4, 1], &[4, 2]]));
+
+    let recursive = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c)";
+
+    let sql = format!("{recursive} SELECT x FROM c WHERE x % 2 = 0 LIMIT 3");
+
+    assert_eq!(rows(&sql), integers(&[&[2], &[4], &[6]]));
+
+    let sql = format!("{recursive} SELECT EXISTS (SELECT 1 FROM c WHERE x = 10)");
+
+    assert_eq!(rows(&sql), integers(&[&[1]]));
+
+    let sql = format!("{recursive} SELECT (SELECT x FROM c WHERE x > 3 LIMIT 1)");
+
+    assert_eq!(rows(&sql), integers(&[&[4]]));
+
+    // through the queries that read it
+    let sql = format!("{recursive}, d AS (SELECT x * 2 y FROM c) SELECT * FROM (SELECT y FROM d) WHERE y > 10 LIMIT 2");
+
+    assert_eq!(rows(&sql), integers(&[&[12], &[14]]));
+}
+
+// With ORDER BY a recursive common table expression takes the row that sorts first, and of
+// those that tie the one added first, so ORDER BY depth DESC walks a tree depth first
+#[test]
+fn order_by_picks_the_next_row_of_a_recursion() {
+    let sql = "WITH RECURSIVE c(x, d) AS (SELECT 1, 0 \
+               UNION ALL SELECT x * 3, d + 1 FROM c WHERE d < 2 \
+               UNION ALL SELECT x * 3 + 1, d + 1 FROM c WHERE d < 2 ORDER BY 2 DESC) SELECT x FROM c";
+
+    assert_eq!(rows(sql), integers(&[&[1], &[3], &[9], &[10], &[4], &[12], &[13]]));
+
+    let sql = sql.replace("DESC", "");
+
+    assert_eq!(rows(&sql), integers(&[&[1], &[3], &[4], &[9], &[10], &[12], &[13]]));
+}
+
+// PRAGMA case_sensitive_like takes ON and OFF as well as numbers, and LIKE follows it
+#[test]
+fn case_sensitive_like_takes_on_and_off() {
+    let connection = Connection::deserialize(INDEXED.to_vec()).unwrap();
+
+    for (argument, matches) in [("ON", 0), ("off", 1), ("1", 0), ("0", 1), ("on", 0), ("OFF", 1)] {
+        let mut pragma = connection.prepare(&format!("PRAGMA case_sensitive_like = {argument}")).unwrap();
+
+        pragma.query().unwrap().for_each(drop);
+
+        let mut statement = connection.prepare("SELECT 'a' LIKE 'A'").unwrap();
+
+        let values = statement.query().unwrap().map(|row| row.into_values()).collect::<Vec<_>>();
+
+        assert_eq!(values, integers(&[&[matches]]), "after case_sensitive_like = {argument}");
+    }
+}
@@ -0,0 +1,109 @@
+use codecrafters_sqlite::Database;
+use std::path::{Path, PathBuf};
+
+// On 1024 byte pages with auto_vacuum=incremental, t(id INTEGER PRIMARY KEY, a TEXT) with
+// the index ta on t(a), of whose 300 rows the even ones were deleted, which left 13 of its
+// 75 pages free
+const FRAGMENTED: &[u8] = include_bytes!("vacuum.db");
+
+// A database file of the test's own, removed with whatever was left next to it when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, content: Option<&[u8]>) -> Self {
+        let path = std::env::temp_dir().join(format!("vacuum-{}-{name}.db", std::process::id()));
+
+        if let Some(content) = content {
+            std::fs::write(&path, content).unwrap();
+        }
+
+        TempFile(path)
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        PathBuf::from(format!("{}{suffix}", self.0.display()))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        for path in [self.0.clone(), self.sibling("-journal"), self.sibling("-vacuum")] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// The page size, page count, free page count and auto_vacuum mode in a database header
+fn layout(path: &Path) -> (u32, u32, u32, &'static str) {
+    let header = std::fs::read(path).unwrap();
+
+    let field = |offset: usize| u32::from_be_bytes(header[offset..offset + 4].try_into().unwrap());
+
+    let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+        1 => 65536,
+        size => u32::from(size),
+    };
+
+    let auto_vacuum = match (field(52), field(64)) {
+        (0, _) => "none",
+        (_, 0) => "full",
+        _ => "incremental",
+    };
+
+    (page_size, field(28), field(36), auto_vacuum)
+}
+
+fn check(path: &Path) {
+    let db = Database::open(path).unwrap();
+
+    assert_eq!(db.integrity_check(100, false, None).unwrap(), vec!["ok".to_string()]);
+    assert_eq!(db.count_records("t").unwrap(), 150);
+}
+
+#[test]
+fn vacuum_rebuilds_the_file_in_place() {
+    let file = TempFile::new("in-place", Some(FRAGMENTED));
+
+    assert_eq!(layout(&file.0), (1024, 75, 13, "incremental"));
+
+    let mut db = Database::open(&file.0).unwrap();
+
+    db.execute_command("PRAGMA page_size = 4096").unwrap();
+    db.execute_command("VACUUM").unwrap();
+
+    drop(db);
+
+    let (page_size, page_count, free_pages, auto_vacuum) = layout(&file.0);
+
+    assert_eq!((page_size, free_pages, auto_vacuum), (4096, 0, "incremental"));
+    assert_eq!(std::fs::metadata(&file.0).unwrap().len(), u64::from(page_count) * 4096);
+
+    check(&file.0);
+
+    // the journal and the rebuilt copy are gone once the file is replaced
+    assert!(!file.sibling("-journal").exists());
+    assert!(!file.sibling("-vacuum").exists());
+}
+
+#[test]
+fn vacuum_into_writes_a_compact_copy() {
+    let file = TempFile::new("source", Some(FRAGMENTED));
+    let copy = TempFile::new("copy", None);
+
+    let mut db = Database::open(&file.0).unwrap();
+
+    db.execute_command(&format!("VACUUM INTO '{}'", copy.0.display())).unwrap();
+
+    let (page_size, page_count, free_pages, auto_vacuum) = layout(&copy.0);
+
+    assert_eq!((page_size, free_pages, auto_vacuum), (1024, 0, "incremental"));
+    assert!(page_count < 75 - 13, "{page_count} pages");
+
+    check(&copy.0);
+
+    // the source is left alone, and an existing file is never overwritten
+    assert_eq!(layout(&file.0), (1024, 75, 13, "incremental"));
+    assert!(db.execute_command(&format!("VACUUM INTO '{}'", copy.0.display())).is_err());
+
+    check(&copy.0);
+}